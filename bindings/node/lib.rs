@@ -0,0 +1,4 @@
+//! Node.js addon of rs-opw-kinematics: the classes of the `node` module, linked into the
+//! cdylib the napi CLI needs.
+
+pub use rs_opw_kinematics::node::*;
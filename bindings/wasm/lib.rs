@@ -0,0 +1,4 @@
+//! JavaScript bindings of rs-opw-kinematics: the classes of the `wasm` module, linked into the
+//! cdylib wasm-pack needs.
+
+pub use rs_opw_kinematics::wasm::*;
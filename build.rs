@@ -0,0 +1,41 @@
+// Generates the C header for the C interface (feature `ffi`) from src/ffi.rs into OUT_DIR. The
+// copy in include/ is checked against it by the tests of the ffi module.
+//
+// Compiles the C interface to the original C++ opw_kinematics when the differential tests
+// are enabled (feature `cpp_reference`). Header locations are taken from OPW_KINEMATICS_INCLUDE
+// and EIGEN3_INCLUDE_DIR (the latter defaults to /usr/include/eigen3).
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+            .expect("cbindgen.toml is not valid");
+        cbindgen::Builder::new()
+            .with_src(format!("{}/src/ffi.rs", crate_dir))
+            .with_config(config)
+            .generate()
+            .expect("Unable to generate C header")
+            .write_to_file(format!("{}/rs_opw_kinematics.h", std::env::var("OUT_DIR").unwrap()));
+    }
+
+    #[cfg(feature = "cpp_reference")]
+    {
+        println!("cargo:rerun-if-changed=cpp/opw_reference.cpp");
+        println!("cargo:rerun-if-env-changed=OPW_KINEMATICS_INCLUDE");
+        println!("cargo:rerun-if-env-changed=EIGEN3_INCLUDE_DIR");
+        let opw = std::env::var("OPW_KINEMATICS_INCLUDE")
+            .expect("Set OPW_KINEMATICS_INCLUDE to the include folder of C++ opw_kinematics");
+        let eigen = std::env::var("EIGEN3_INCLUDE_DIR")
+            .unwrap_or_else(|_| "/usr/include/eigen3".to_string());
+        cc::Build::new()
+            .cpp(true)
+            .flag_if_supported("-std=c++14")
+            .include(opw)
+            .include(eigen)
+            .file("cpp/opw_reference.cpp")
+            .compile("opw_reference");
+    }
+}
@@ -0,0 +1,46 @@
+// C interface to the original C++ opw_kinematics (https://github.com/Jmeyer1292/opw_kinematics),
+// used only by the differential tests (feature `cpp_reference`). Parameters are passed as
+// a1, a2, b, c1, c2, c3, c4; matrices are 4x4 column major.
+
+#include <opw_kinematics/opw_kinematics.h>
+
+static opw_kinematics::Parameters<double> make_parameters(const double* p, const double* offsets,
+                                                          const signed char* signs) {
+  opw_kinematics::Parameters<double> params;
+  params.a1 = p[0];
+  params.a2 = p[1];
+  params.b = p[2];
+  params.c1 = p[3];
+  params.c2 = p[4];
+  params.c3 = p[5];
+  params.c4 = p[6];
+  for (int i = 0; i < 6; ++i) {
+    params.offsets[i] = offsets[i];
+    params.sign_corrections[i] = signs[i];
+  }
+  return params;
+}
+
+extern "C" void opw_cpp_forward(const double* p, const double* offsets, const signed char* signs,
+                                const double* joints, double* matrix) {
+  const auto params = make_parameters(p, offsets, signs);
+  const auto pose = opw_kinematics::forward(params, joints);
+  const Eigen::Matrix4d m = pose.matrix();
+  for (int i = 0; i < 16; ++i) {
+    matrix[i] = m.data()[i];
+  }
+}
+
+// Writes 8 solutions, 6 joints each. Invalid solutions contain NaN.
+extern "C" void opw_cpp_inverse(const double* p, const double* offsets, const signed char* signs,
+                                const double* matrix, double* solutions) {
+  const auto params = make_parameters(p, offsets, signs);
+  Eigen::Isometry3d pose;
+  pose.matrix() = Eigen::Map<const Eigen::Matrix4d>(matrix);
+  const auto sols = opw_kinematics::inverse(params, pose);
+  for (int s = 0; s < 8; ++s) {
+    for (int j = 0; j < 6; ++j) {
+      solutions[s * 6 + j] = sols[s][j];
+    }
+  }
+}
@@ -0,0 +1,126 @@
+#ifndef RS_OPW_KINEMATICS_H
+#define RS_OPW_KINEMATICS_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Maximal number of solutions returned by the inverse kinematics. The solutions buffer
+// must hold OPW_MAX_SOLUTIONS * 6 doubles.
+#define OPW_MAX_SOLUTIONS 9
+
+// Success, as `moveit_msgs::MoveItErrorCodes::SUCCESS`.
+#define OPW_SUCCESS 1
+
+// Timeout expired before the callback accepted any solution, as `TIMED_OUT`.
+#define OPW_TIMED_OUT -6
+
+// The pose is out of reach or the callback rejected all solutions, as `NO_IK_SOLUTION`.
+#define OPW_NO_IK_SOLUTION -31
+
+// Opaque handle of the robot.
+typedef struct OpwRobot OpwRobot;
+
+// Robot parameters, see the documentation of the Rust `Parameters`.
+typedef struct OpwParameters {
+  double a1;
+  double a2;
+  double b;
+  double c1;
+  double c2;
+  double c3;
+  double c4;
+  double offsets[6];
+  int8_t sign_corrections[6];
+} OpwParameters;
+
+// Callback checking the IK solution (like MoveIt `IKCallbackFn`): receives the user data, the
+// pose (7 doubles) and the candidate joints (6 doubles), returns `OPW_SUCCESS` to accept
+// the solution or any other MoveIt error code to reject it.
+typedef int32_t (*OpwSolutionCallback)(void *user_data, const double *pose, const double *joints);
+
+// Creates the robot with the given parameters. Returns NULL if the parameters are not valid
+// (non-finite values, sign corrections other than 1 or -1, zero arm lengths). Free with
+// `opw_robot_free`.
+//
+// # Safety
+// `parameters` must point to the valid `OpwParameters`.
+struct OpwRobot *opw_robot_new(const struct OpwParameters *parameters);
+
+// Creates the robot from the built-in preset (like "irb2400_10"). Returns NULL if there is
+// no such preset. Free with `opw_robot_free`.
+//
+// # Safety
+// `name` must be the valid null terminated string.
+struct OpwRobot *opw_robot_preset(const char *name);
+
+// Releases the robot. NULL is ignored.
+//
+// # Safety
+// `robot` must be NULL or returned by `opw_robot_new` or `opw_robot_preset`, and not used
+// after this call.
+void opw_robot_free(struct OpwRobot *robot);
+
+// Forward kinematics: writes the pose of the flange (7 doubles) for the joints (6 doubles).
+//
+// # Safety
+// All pointers must be valid, with the sizes given above.
+void opw_forward(const struct OpwRobot *robot, const double *joints, double *pose);
+
+// Inverse kinematics: writes all solutions for the pose (7 doubles) into the buffer of
+// OPW_MAX_SOLUTIONS * 6 doubles and returns their number. The pose with non-finite values or
+// the quaternion far from unit has no solutions.
+//
+// # Safety
+// All pointers must be valid, with the sizes given above.
+size_t opw_inverse(const struct OpwRobot *robot, const double *pose, double *solutions);
+
+// Inverse kinematics for the trajectory: as `opw_inverse`, but handles the wrist singularity
+// using the previous joint position (6 doubles) and returns the closest solutions first.
+//
+// # Safety
+// All pointers must be valid, with the sizes given above.
+size_t opw_inverse_continuing(const struct OpwRobot *robot,
+                              const double *pose,
+                              const double *previous,
+                              double *solutions);
+
+// The solution closest to the seed state (6 doubles), written into `solution` (6 doubles).
+// The wrist singularity is resolved keeping J4 and J6 near the seed. Returns `OPW_SUCCESS`
+// or `OPW_NO_IK_SOLUTION`.
+//
+// # Safety
+// All pointers must be valid, with the sizes given above.
+int32_t opw_get_position_ik(const struct OpwRobot *robot,
+                            const double *pose,
+                            const double *seed,
+                            double *solution);
+
+// Searches for the solution accepted by the callback, trying the solutions in the order of
+// the distance from the seed state (6 doubles). Without the callback, the closest solution is
+// accepted. The accepted solution is written into `solution` (6 doubles). The timeout
+// (seconds; 0, negative or not finite for no limit) is checked between the callback calls.
+// Returns `OPW_SUCCESS`, `OPW_TIMED_OUT` or `OPW_NO_IK_SOLUTION` (also for the invalid pose,
+// as `opw_inverse`).
+//
+// # Safety
+// All pointers must be valid, with the sizes given above. `user_data` is passed to the
+// callback as is.
+int32_t opw_search_position_ik(const struct OpwRobot *robot,
+                               const double *pose,
+                               const double *seed,
+                               double timeout,
+                               OpwSolutionCallback callback,
+                               void *user_data,
+                               double *solution);
+
+// Poses of the links 1 to 6 (6 * 7 doubles) for the joints (6 doubles), for the forward
+// kinematics of the intermediate links. The last link is at the wrist center, use
+// `opw_forward` for the flange.
+//
+// # Safety
+// All pointers must be valid, with the sizes given above.
+void opw_link_poses(const struct OpwRobot *robot, const double *joints, double *poses);
+
+#endif  /* RS_OPW_KINEMATICS_H */
@@ -0,0 +1,196 @@
+//! ROS 2 node offering the MoveIt-style kinematics services backed by rs-opw-kinematics:
+//! - `compute_ik` (moveit_msgs/srv/GetPositionIK)
+//! - `compute_fk` (moveit_msgs/srv/GetPositionFK)
+//!
+//! ROS parameters:
+//! - `robot`: name of the built-in preset (like "irb2400_10"), used if `parameters_file` is empty,
+//! - `parameters_file`: OPW parameters YAML as in ros-industrial support packages,
+//! - `joint_names`: names of the 6 joints in the joint states,
+//! - `link_names`: names of the 6 links, for the forward kinematics of intermediate links,
+//! - `base_frame` and `tip_frame`: robot base and flange frames.
+//!
+//! Poses must be given in the base frame. The joint state in the IK request, if present,
+//! is used as the seed: the closest solution is returned and the wrist singularity is
+//! resolved by keeping J4 and J6 near their seed values.
+
+use std::sync::Arc;
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use geometry_msgs::msg::{Point, PoseStamped, Pose as PoseMsg, Quaternion as QuaternionMsg};
+use moveit_msgs::msg::{MoveItErrorCodes, RobotState};
+use moveit_msgs::srv::{GetPositionFK, GetPositionFK_Request, GetPositionFK_Response,
+                       GetPositionIK, GetPositionIK_Request, GetPositionIK_Response};
+use rs_opw_kinematics::kinematic_traits::{Joints, Kinematics, Pose, JOINTS_AT_ZERO};
+use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+use sensor_msgs::msg::JointState;
+use std_msgs::msg::Header;
+
+struct KinematicsServer {
+    robot: OPWKinematics,
+    joint_names: Vec<String>,
+    link_names: Vec<String>,
+    base_frame: String,
+    tip_frame: String,
+}
+
+fn error_code(val: i32) -> MoveItErrorCodes {
+    MoveItErrorCodes { val }
+}
+
+fn to_pose(msg: &PoseMsg) -> Pose {
+    let (p, q) = (&msg.position, &msg.orientation);
+    Pose::from_parts(Translation3::new(p.x, p.y, p.z),
+                     UnitQuaternion::from_quaternion(Quaternion::new(q.w, q.x, q.y, q.z)))
+}
+
+fn to_pose_msg(pose: &Pose) -> PoseMsg {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    PoseMsg {
+        position: Point { x: t.x, y: t.y, z: t.z },
+        orientation: QuaternionMsg { x: q.i, y: q.j, z: q.k, w: q.w },
+    }
+}
+
+impl KinematicsServer {
+    /// Joint values from the joint state, by name. None if some joint is missing.
+    fn joints_from_state(&self, state: &JointState) -> Option<Joints> {
+        let mut joints = JOINTS_AT_ZERO;
+        for (i, name) in self.joint_names.iter().enumerate() {
+            let index = state.name.iter().position(|n| n == name)?;
+            joints[i] = *state.position.get(index)?;
+        }
+        Some(joints)
+    }
+
+    fn robot_state(&self, joints: &Joints) -> RobotState {
+        RobotState {
+            joint_state: JointState {
+                name: self.joint_names.clone(),
+                position: joints.to_vec(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn in_base_frame(&self, header: &Header) -> bool {
+        header.frame_id.is_empty() || header.frame_id == self.base_frame
+    }
+
+    fn compute_ik(&self, request: GetPositionIK_Request) -> GetPositionIK_Response {
+        let ik = request.ik_request;
+        if !ik.ik_link_name.is_empty() && ik.ik_link_name != self.tip_frame {
+            return GetPositionIK_Response {
+                error_code: error_code(MoveItErrorCodes::INVALID_LINK_NAME),
+                ..Default::default()
+            };
+        }
+        if !self.in_base_frame(&ik.pose_stamped.header) {
+            return GetPositionIK_Response {
+                error_code: error_code(MoveItErrorCodes::FRAME_TRANSFORM_FAILURE),
+                ..Default::default()
+            };
+        }
+
+        let pose = to_pose(&ik.pose_stamped.pose);
+        let solutions = match self.joints_from_state(&ik.robot_state.joint_state) {
+            Some(seed) => self.robot.inverse_continuing(&pose, &seed),
+            None => self.robot.inverse_continuing(&pose, &JOINTS_AT_ZERO),
+        };
+        match solutions.first() {
+            Some(joints) => GetPositionIK_Response {
+                solution: self.robot_state(joints),
+                error_code: error_code(MoveItErrorCodes::SUCCESS),
+            },
+            None => GetPositionIK_Response {
+                error_code: error_code(MoveItErrorCodes::NO_IK_SOLUTION),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn compute_fk(&self, request: GetPositionFK_Request) -> GetPositionFK_Response {
+        let Some(joints) = self.joints_from_state(&request.robot_state.joint_state) else {
+            return GetPositionFK_Response {
+                error_code: error_code(MoveItErrorCodes::INVALID_ROBOT_STATE),
+                ..Default::default()
+            };
+        };
+        let links = self.robot.link_poses(&joints);
+        let tip = self.robot.forward(&joints);
+        let header = Header { frame_id: self.base_frame.clone(), ..request.header };
+
+        let mut poses = Vec::with_capacity(request.fk_link_names.len());
+        for name in &request.fk_link_names {
+            let pose = if *name == self.tip_frame {
+                tip
+            } else if let Some(i) = self.link_names.iter().position(|n| n == name) {
+                links[i]
+            } else {
+                return GetPositionFK_Response {
+                    error_code: error_code(MoveItErrorCodes::INVALID_LINK_NAME),
+                    ..Default::default()
+                };
+            };
+            poses.push(PoseStamped { header: header.clone(), pose: to_pose_msg(&pose) });
+        }
+        GetPositionFK_Response {
+            pose_stamped: poses,
+            fk_link_names: request.fk_link_names,
+            error_code: error_code(MoveItErrorCodes::SUCCESS),
+        }
+    }
+}
+
+fn string_list(values: Arc<[Arc<str>]>) -> Vec<String> {
+    values.iter().map(|s| s.to_string()).collect()
+}
+
+fn default_names(prefix: &str) -> Arc<[Arc<str>]> {
+    (1..=6).map(|i| Arc::from(format!("{}_{}", prefix, i))).collect()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let context = rclrs::Context::new(std::env::args())?;
+    let node = rclrs::create_node(&context, "opw_kinematics")?;
+
+    let robot = node.declare_parameter::<Arc<str>>("robot")
+        .default("irb2400_10".into()).mandatory()?.get();
+    let parameters_file = node.declare_parameter::<Arc<str>>("parameters_file")
+        .default("".into()).mandatory()?.get();
+    let joint_names = node.declare_parameter::<Arc<[Arc<str>]>>("joint_names")
+        .default(default_names("joint")).mandatory()?.get();
+    let link_names = node.declare_parameter::<Arc<[Arc<str>]>>("link_names")
+        .default(default_names("link")).mandatory()?.get();
+    let base_frame = node.declare_parameter::<Arc<str>>("base_frame")
+        .default("base_link".into()).mandatory()?.get();
+    let tip_frame = node.declare_parameter::<Arc<str>>("tip_frame")
+        .default("tool0".into()).mandatory()?.get();
+
+    let parameters = if parameters_file.is_empty() {
+        Parameters::preset(&robot).ok_or(format!("Unknown robot preset {}", robot))?
+    } else {
+        Parameters::from_yaml_file(&*parameters_file)?
+    };
+    if joint_names.len() != 6 || link_names.len() != 6 {
+        return Err("joint_names and link_names must have 6 entries".into());
+    }
+
+    let server = Arc::new(KinematicsServer {
+        robot: OPWKinematics::new(parameters),
+        joint_names: string_list(joint_names),
+        link_names: string_list(link_names),
+        base_frame: base_frame.to_string(),
+        tip_frame: tip_frame.to_string(),
+    });
+
+    let ik_server = Arc::clone(&server);
+    let _ik = node.create_service::<GetPositionIK, _>(
+        "compute_ik", move |_id, request| ik_server.compute_ik(request))?;
+    let fk_server = Arc::clone(&server);
+    let _fk = node.create_service::<GetPositionFK, _>(
+        "compute_fk", move |_id, request| fk_server.compute_fk(request))?;
+
+    rclrs::spin(node).map_err(|e| e.into())
+}
@@ -0,0 +1,64 @@
+//! Generates the test cases (as in src/tests/cases.yaml) for the robot: samples the joint space,
+//! computes the pose with the forward kinematics and all solutions with the inverse kinematics.
+//! Needed when contributing the new robot preset; the cases can be checked with
+//! `conformance::ConformanceSuite` once the robot is added as the preset of the same name
+//! (see `conformance::case_parameters`).
+
+use std::process::ExitCode;
+use rs_opw_kinematics::conformance::ConformanceSuite;
+use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+
+const USAGE: &str = "\
+Usage: opw-cases <robot> [--name <name>] [--count <n>] [--seed <n>] [--first-id <n>] [--append]
+
+Writes the cases YAML to the standard output. The robot is the preset name or the robot file
+(URDF, DH table or parameters YAML). The cases refer to the robot by --name (the robot argument
+by default). Joints are sampled as whole degrees within [-180, 180], 2048 cases by default,
+skipping the wrist and elbow singularities. With --append, the cases: header is omitted so
+the output can be appended to the existing file.";
+
+fn main() -> ExitCode {
+    match generate(&std::env::args().skip(1).collect::<Vec<_>>()) {
+        Ok(yaml) => {
+            print!("{}", yaml);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("opw-cases: {}\n\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn generate(args: &[String]) -> Result<String, String> {
+    let spec = args.first().filter(|a| !a.starts_with("--")).ok_or("robot not given")?;
+    let parameters = match Parameters::preset(spec) {
+        Some(parameters) => parameters,
+        None => Parameters::from_file(spec).map_err(|e| format!("{}: {}", spec, e))?,
+    };
+
+    let (mut name, mut count, mut seed, mut first_id, mut append) = (spec.clone(), 2048, 1, 0, false);
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        if option == "--append" {
+            append = true;
+            continue;
+        }
+        let value = options.next().ok_or(format!("{} needs the value", option))?;
+        let number = || value.parse::<u64>().map_err(|_| format!("invalid {} {}", option, value));
+        match option.as_str() {
+            "--name" => name = value.clone(),
+            "--count" => count = number()?,
+            "--seed" => seed = number()?,
+            "--first-id" => first_id = number()?,
+            _ => return Err(format!("unknown option {}", option)),
+        }
+    }
+
+    let suite = ConformanceSuite::generate(&parameters, &name, count as usize, seed, first_id as i32);
+    let yaml = suite.to_compact_yaml();
+    Ok(match append {
+        true => yaml["cases:\n".len()..].to_string(),
+        false => yaml,
+    })
+}
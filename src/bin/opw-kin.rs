@@ -0,0 +1,314 @@
+//! Command line tool for quick checks of the robot parameters without writing a program.
+//! Joint values are in degrees, positions in meters.
+
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+use rs_opw_kinematics::joints::JointVector;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use rs_opw_kinematics::constraints::JointLimits;
+use rs_opw_kinematics::jog::{JogError, JogFrame, JogKinematics, JogSettings};
+use rs_opw_kinematics::kinematic_traits::{Joints, Kinematics, Pose, JOINTS_AT_ZERO};
+use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+use rs_opw_kinematics::utils::SolutionsTable;
+use rs_opw_kinematics::validation::{pose_from_raw, validate_parameters};
+
+const USAGE: &str = "\
+Usage: opw-kin <command> <robot> [arguments]
+
+Commands:
+  fk <robot> <j1> ... <j6>                       Pose of the flange
+  ik <robot> <x> <y> <z> <qx> <qy> <qz> <qw> [--near <j1> ... <j6>]
+                                                 Joint solutions for the pose
+  check <robot>                                  Validates the parameters and the FK/IK round trip
+  convert <robot>                                Prints the parameters as YAML
+  jog <robot> [<j1> ... <j6>] [--limits <min1> <max1> ... <min6> <max6>]
+                                                 Steps the flange from the keyboard, starting
+                                                 from the given joints (zero by default)
+
+The robot is the preset name (like irb2400_10) or the file: URDF (.urdf, .xml), the
+Denavit-Hartenberg table (YAML list of six rows with a, alpha, d, theta) or the parameters
+(YAML, ROS-Industrial opw_kinematics format or as written by convert).";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("fk") => fk(&args[1..]),
+        Some("ik") => ik(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("jog") => jog(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("opw-kin: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn load_robot(spec: Option<&String>) -> Result<Parameters> {
+    let spec = spec.ok_or("robot not given")?;
+    match Parameters::preset(spec) {
+        Some(parameters) => Ok(parameters),
+        None => Parameters::from_file(spec)
+            .map_err(|e| format!("{} is neither a preset nor a readable robot file: {}", spec, e).into()),
+    }
+}
+
+fn numbers<const N: usize>(args: &[String], what: &str) -> Result<[f64; N]> {
+    if args.len() != N {
+        return Err(format!("{} expects {} numbers, got {}", what, N, args.len()).into());
+    }
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| format!("invalid number {}", arg))?;
+    }
+    Ok(values)
+}
+
+fn degrees_to_joints(degrees: [f64; 6]) -> Joints {
+    degrees.map(f64::to_radians)
+}
+
+fn fk(args: &[String]) -> Result<()> {
+    let robot = OPWKinematics::new(load_robot(args.first())?);
+    let joints = degrees_to_joints(numbers(args.get(1..).unwrap_or_default(), "joints")?);
+    let pose = robot.forward(&joints);
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    let (roll, pitch, yaw) = pose.rotation.euler_angles();
+    println!("translation: {:.6} {:.6} {:.6}", t.x, t.y, t.z);
+    println!("quaternion (x y z w): {:.6} {:.6} {:.6} {:.6}", q.i, q.j, q.k, q.w);
+    println!("roll pitch yaw (deg): {:.4} {:.4} {:.4}",
+             roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees());
+    Ok(())
+}
+
+fn ik(args: &[String]) -> Result<()> {
+    let robot = OPWKinematics::new(load_robot(args.first())?);
+    let args = args.get(1..).unwrap_or_default();
+    let (pose_args, near) = match args.iter().position(|a| a == "--near") {
+        Some(i) => (&args[..i], Some(degrees_to_joints(numbers(&args[i + 1..], "--near")?))),
+        None => (args, None),
+    };
+    let [x, y, z, qx, qy, qz, qw] = numbers(pose_args, "pose")?;
+    let pose = pose_from_raw([x, y, z], [qx, qy, qz, qw])?;
+    let solutions = robot.inverse_continuing(&pose, &near.unwrap_or(JOINTS_AT_ZERO));
+    if solutions.is_empty() {
+        return Err("pose is not reachable".into());
+    }
+    println!("{}", SolutionsTable::new(&solutions));
+    Ok(())
+}
+
+fn check(args: &[String]) -> Result<()> {
+    let parameters = load_robot(args.first())?;
+    validate_parameters(&parameters)?;
+    let robot = OPWKinematics::new(parameters);
+
+    // Round trip over the grid of joint values, away from the wrist singularity and from the
+    // stretched or folded arm
+    let steps = [-150.0, -60.0, -20.0, 20.0, 60.0, 150.0];
+    let (mut checked, mut failed, mut max_error) = (0, 0, 0.0_f64);
+    for (i, j1) in steps.iter().enumerate() {
+        for j2 in &steps[1..5] {
+            for j3 in &steps[1..5] {
+                let j5 = if (i + checked) % 2 == 0 { -60.0 } else { 45.0 };
+                let joints = degrees_to_joints([*j1, *j2, *j3, steps[5 - i], j5, steps[(i + 2) % 6]]);
+                let pose = robot.forward(&joints);
+                let error = robot.inverse(&pose).iter()
+                    .map(|s| JointVector(*s).difference(&JointVector(joints)).max_abs())
+                    .fold(f64::INFINITY, f64::min);
+                checked += 1;
+                if error > 1E-6 {
+                    failed += 1;
+                } else {
+                    max_error = max_error.max(error);
+                }
+            }
+        }
+    }
+    println!("{:?}", robot.parameters());
+    println!("round trip: {} of {} joint sets recovered, max error {:.3e} rad",
+             checked - failed, checked, max_error);
+    if failed > 0 {
+        return Err(format!("{} joint sets not recovered by the inverse kinematics", failed).into());
+    }
+    Ok(())
+}
+
+fn convert(args: &[String]) -> Result<()> {
+    print!("{}", load_robot(args.first())?.to_yaml()?);
+    Ok(())
+}
+
+const JOG_HELP: &str = "\
+Jog commands, one per line (empty line repeats the last move):
+  x+ x- y+ y- z+ z-          move the flange by the linear step
+  rx+ rx- ry+ ry- rz+ rz-    rotate the flange about its origin by the angular step
+  base | tool                axes of the moves: base frame (default) or flange frame
+  step <mm> | angle <deg>    set the linear (default 10 mm) or angular (default 5 deg) step
+  help | quit";
+
+/// Distance to the singularities and joint limits that triggers the warning.
+const JOG_WARNING_ANGLE: f64 = 5.0;
+const JOG_WARNING_DISTANCE: f64 = 0.05;
+/// Larger joint change in one step is the configuration flip and the step is rejected.
+const JOG_MAX_JOINT_STEP: f64 = 30.0;
+
+fn jog(args: &[String]) -> Result<()> {
+    let robot = OPWKinematics::new(load_robot(args.first())?);
+    let args = args.get(1..).unwrap_or_default();
+    let (joint_args, limits) = match args.iter().position(|a| a == "--limits") {
+        Some(i) => {
+            let limits = numbers::<12>(&args[i + 1..], "--limits")?;
+            let ranges = std::array::from_fn(|j| (limits[2 * j], limits[2 * j + 1]));
+            (&args[..i], Some(JointLimits::from_degrees(ranges)))
+        }
+        None => (args, None),
+    };
+    let start = if joint_args.is_empty() {
+        JOINTS_AT_ZERO
+    } else {
+        degrees_to_joints(numbers(joint_args, "joints")?)
+    };
+    let stdin = std::io::stdin();
+    jog_session(&robot, start, limits, &mut stdin.lock(), &mut std::io::stdout())
+}
+
+fn jog_session(robot: &OPWKinematics, start: Joints, limits: Option<JointLimits>,
+               input: &mut impl BufRead, output: &mut impl Write) -> Result<()> {
+    let mut settings = JogSettings::default()
+        .with_max_joint_step(JOG_MAX_JOINT_STEP.to_radians());
+    if let Some(limits) = limits {
+        settings = settings.with_limits(limits);
+    }
+    let mut joints = start;
+    let (mut step, mut angle) = (0.010, 5.0_f64.to_radians());
+    let mut last_move = String::new();
+    writeln!(output, "{}", JOG_HELP)?;
+    report(robot, &joints, limits.as_ref(), output)?;
+
+    let mut line = String::new();
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command.to_string(),
+            None if !last_move.is_empty() => last_move.clone(),
+            None => continue,
+        };
+        let value = words.next().and_then(|v| v.parse::<f64>().ok());
+        match (command.as_str(), value) {
+            ("quit" | "q", _) => return Ok(()),
+            ("help" | "h", _) => writeln!(output, "{}", JOG_HELP)?,
+            ("base", _) => settings = settings.with_frame(JogFrame::Base),
+            ("tool", _) => settings = settings.with_frame(JogFrame::Tool),
+            ("step", Some(mm)) => step = mm / 1000.0,
+            ("angle", Some(degrees)) => angle = degrees.to_radians(),
+            _ => {
+                let Some(delta) = jog_delta(&command, step, angle) else {
+                    writeln!(output, "unknown command {}, type help", command.trim())?;
+                    continue;
+                };
+                last_move = command;
+                match robot.jog(&joints, &delta, &settings) {
+                    Err(JogError::Unreachable) => {
+                        writeln!(output, "not reachable, step rejected")?
+                    }
+                    Err(JogError::JointLimits) => {
+                        writeln!(output, "out of the joint limits, step rejected")?
+                    }
+                    Err(JogError::Discontinuity { .. }) => {
+                        writeln!(output, "configuration flip, step rejected")?
+                    }
+                    Ok(next) => {
+                        joints = next;
+                        report(robot, &joints, limits.as_ref(), output)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Motion of the jog command as the pose delta, None if the command is not the move.
+fn jog_delta(command: &str, step: f64, angle: f64) -> Option<Pose> {
+    let (axis, sign) = command.split_at(command.len().checked_sub(1)?);
+    let sign = match sign {
+        "+" => 1.0,
+        "-" => -1.0,
+        _ => return None,
+    };
+    let (rotation, index) = match axis {
+        "x" => (false, 0),
+        "y" => (false, 1),
+        "z" => (false, 2),
+        "rx" => (true, 0),
+        "ry" => (true, 1),
+        "rz" => (true, 2),
+        _ => return None,
+    };
+    let mut direction = Vector3::zeros();
+    direction[index] = sign;
+    Some(if rotation {
+        Pose::from_parts(Translation3::identity(), UnitQuaternion::from_scaled_axis(direction * angle))
+    } else {
+        Pose::from_parts(Translation3::from(direction * step), UnitQuaternion::identity())
+    })
+}
+
+fn report(robot: &OPWKinematics, joints: &Joints, limits: Option<&JointLimits>,
+          output: &mut impl Write) -> Result<()> {
+    let pose = robot.forward(joints);
+    let t = pose.translation.vector;
+    let (roll, pitch, yaw) = pose.rotation.euler_angles();
+    writeln!(output, "{}", SolutionsTable::new(std::slice::from_ref(joints)))?;
+    writeln!(output, "flange {:.4} {:.4} {:.4} m, rpy {:.2} {:.2} {:.2} deg", t.x, t.y, t.z,
+             roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees())?;
+    for warning in jog_warnings(robot, joints, limits) {
+        writeln!(output, "warning: {}", warning)?;
+    }
+    Ok(())
+}
+
+fn jog_warnings(robot: &OPWKinematics, joints: &Joints, limits: Option<&JointLimits>)
+                -> Vec<String> {
+    let proximity = robot.singularity_proximity(joints);
+    let threshold = JOG_WARNING_ANGLE.to_radians().sin();
+    let mut warnings = Vec::new();
+    if proximity.wrist < threshold {
+        warnings.push("near the wrist singularity (J5 close to 0)".to_string());
+    }
+    if proximity.elbow < threshold {
+        warnings.push("near the elbow singularity (arm stretched or folded)".to_string());
+    }
+    if proximity.alignment - robot.parameters().b.abs() < JOG_WARNING_DISTANCE {
+        warnings.push("near the shoulder singularity (wrist center over J1 axis)".to_string());
+    }
+    if let Some(limits) = limits {
+        for (i, q) in joints.iter().enumerate() {
+            let (min, max) = (limits.min[i].to_degrees(), limits.max[i].to_degrees());
+            let q = q.to_degrees();
+            if q < min + JOG_WARNING_ANGLE || q > max - JOG_WARNING_ANGLE {
+                warnings.push(format!("J{} at {:.1} deg is close to its limits [{:.1}, {:.1}]",
+                                      i + 1, q, min, max));
+            }
+        }
+    }
+    warnings
+}
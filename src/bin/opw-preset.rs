@@ -0,0 +1,198 @@
+//! Turns the robot description of the ROS-Industrial support package (config/opw_parameters_*.yaml
+//! or the URDF) into the preset: prints the constructor function for parameters_robots.rs with
+//! the entry for `Parameters::preset`, and writes the golden cases to append to
+//! src/tests/cases.yaml (they name the robot as the preset, see `conformance::case_parameters`).
+
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use rs_opw_kinematics::conformance::{ConformanceSuite, CASES_FILE};
+use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+use rs_opw_kinematics::validation::validate_parameters;
+
+const USAGE: &str = "\
+Usage: opw-preset <package or file>... [--name <name>] [--cases <n>] [--output <dir>]
+
+Reads the support package directory (config/opw_parameters_*.yaml, or urdf/*.urdf if there are
+none) or the robot files. The preset is named after the vendor (the package name before _) and
+the file name, unless --name is given for the single robot. Prints the preset entries and
+writes <name>.cases.yaml (64 cases by default, numbered after the cases in cases.yaml) to the
+output directory (current by default), to append to src/tests/cases.yaml.";
+
+fn main() -> ExitCode {
+    match ingest(&std::env::args().skip(1).collect::<Vec<_>>()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("opw-preset: {}\n\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn ingest(args: &[String]) -> Result<(), String> {
+    let (mut inputs, mut name, mut count, mut output) = (Vec::new(), None, 64, PathBuf::from("."));
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        if !option.starts_with("--") {
+            inputs.push(PathBuf::from(option));
+            continue;
+        }
+        let value = options.next().ok_or(format!("{} needs the value", option))?;
+        match option.as_str() {
+            "--name" => name = Some(identifier(value)),
+            "--cases" => count = value.parse().map_err(|_| format!("invalid --cases {}", value))?,
+            "--output" => output = PathBuf::from(value),
+            _ => return Err(format!("unknown option {}", option)),
+        }
+    }
+
+    let mut files = Vec::new();
+    for input in &inputs {
+        match input.is_dir() {
+            true => files.extend(robot_files(input)?),
+            false => files.push(input.clone()),
+        }
+    }
+    match files.len() {
+        0 => return Err("no robot files given".into()),
+        1 => {}
+        _ if name.is_some() => return Err("--name needs the single robot".into()),
+        _ => {}
+    }
+
+    let mut next_id = ConformanceSuite::builtin()
+        .map(|suite| suite.cases.iter().map(|c| c.id + 1).max().unwrap_or(0))
+        .unwrap_or(0);
+    let (mut functions, mut presets) = (String::new(), String::new());
+    for file in &files {
+        let parameters = Parameters::from_file(file)
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+        validate_parameters(&parameters).map_err(|e| format!("{}: {}", file.display(), e))?;
+        let name = name.clone().unwrap_or_else(|| preset_name(file));
+        if Parameters::preset(&name).is_some() {
+            eprintln!("opw-preset: warning: preset {} already exists", name);
+        }
+        let case_name = capitalize(&name);
+
+        functions.push_str(&constructor(&name, &parameters, file));
+        presets.push_str(&format!("                \"{0}\" => Self::{0}(),\n", name));
+
+        let suite = ConformanceSuite::generate(&parameters, &case_name, count, 1, next_id);
+        next_id += count as i32;
+        let yaml = suite.to_compact_yaml();
+        let path = output.join(format!("{}.cases.yaml", name));
+        std::fs::write(&path, &yaml["cases:\n".len()..])
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        eprintln!("opw-preset: wrote {} cases of {} to {}", count, name, path.display());
+    }
+
+    println!("// src/parameters_robots.rs, Parameters:\n{}\n", functions.trim_end());
+    println!("// src/parameters_robots.rs, Parameters::preset:\n{}", presets);
+    println!("// Append the cases to {}", CASES_FILE);
+    Ok(())
+}
+
+/// OPW parameter files of the support package, or its URDF files if there are none.
+fn robot_files(package: &Path) -> Result<Vec<PathBuf>, String> {
+    let list = |dir: &str, prefix: &str, extension: &str| -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(package.join(dir)).into_iter().flatten()
+            .flatten().map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == extension)
+                && path.file_name().is_some_and(|n| n.to_string_lossy().starts_with(prefix)))
+            .collect();
+        files.sort();
+        files
+    };
+    let files = list("config", "opw_parameters", "yaml");
+    if !files.is_empty() {
+        return Ok(files);
+    }
+    let files = list("urdf", "", "urdf");
+    if !files.is_empty() {
+        return Ok(files);
+    }
+    match list("urdf", "", "xacro").is_empty() {
+        true => Err(format!("{}: no opw_parameters YAML or URDF found", package.display())),
+        false => Err(format!("{}: only xacro found, expand it into the URDF with xacro first",
+                             package.display())),
+    }
+}
+
+/// Vendor (from the support package name like fanuc_m16ib_support) and model (from the file
+/// name like opw_parameters_m16ib20.yaml), as fanuc_m16ib20.
+fn preset_name(file: &Path) -> String {
+    let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let model = identifier(stem.strip_prefix("opw_parameters_").unwrap_or(&stem));
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let package = match dir.file_name().and_then(|n| n.to_str()) {
+        Some("config") | Some("urdf") => dir.parent().and_then(|p| p.file_name()),
+        _ => dir.file_name(),
+    };
+    let vendor = package.map(|p| p.to_string_lossy().into_owned())
+        .filter(|p| p.ends_with("_support"))
+        .and_then(|p| p.split('_').next().map(identifier));
+    match vendor {
+        Some(vendor) if !model.starts_with(&vendor) => format!("{}_{}", vendor, model),
+        _ => model,
+    }
+}
+
+fn constructor(name: &str, p: &Parameters, file: &Path) -> String {
+    let mut code = format!("        // From {}\n        pub const fn {}() -> Self {{\n            \
+                            Parameters {{\n", file.display(), name);
+    for (field, value) in [("a1", p.a1), ("a2", p.a2), ("b", p.b), ("c1", p.c1), ("c2", p.c2),
+                           ("c3", p.c3), ("c4", p.c4)] {
+        code.push_str(&format!("                {}: {},\n", field, length(value)));
+    }
+    let defaults = Parameters::new();
+    if p.offsets != defaults.offsets {
+        let offsets: Vec<String> = p.offsets.iter().map(|v| angle(*v)).collect();
+        code.push_str(&format!("                offsets: [{}],\n", offsets.join(", ")));
+    }
+    if p.sign_corrections != defaults.sign_corrections {
+        code.push_str(&format!("                sign_corrections: {:?},\n", p.sign_corrections));
+    }
+    if p.j3_coupling != defaults.j3_coupling {
+        code.push_str(&format!("                j3_coupling: {:?},\n", p.j3_coupling));
+    }
+    if p.offsets == defaults.offsets || p.sign_corrections == defaults.sign_corrections
+        || p.j3_coupling == defaults.j3_coupling {
+        code.push_str("                ..Self::new()\n");
+    }
+    code.push_str("            }\n        }\n\n");
+    code
+}
+
+/// Length with 3 decimal places as in the existing presets, unless more are needed.
+fn length(value: f64) -> String {
+    let short = format!("{:.3}", value);
+    match short.parse::<f64>() == Ok(value) {
+        true => short,
+        false => format!("{:?}", value),
+    }
+}
+
+/// Offset as the multiple of PI / 2 where it is one.
+fn angle(value: f64) -> String {
+    let quarters = (value / (PI / 2.0)).round();
+    if (value - quarters * PI / 2.0).abs() > 1E-12 {
+        return format!("{:?}", value);
+    }
+    match quarters as i32 {
+        0 => "0.0".into(),
+        1 => "PI / 2.0".into(),
+        -1 => "-PI / 2.0".into(),
+        2 => "PI".into(),
+        -2 => "-PI".into(),
+        _ => format!("{:?}", value),
+    }
+}
+
+fn identifier(text: &str) -> String {
+    text.to_lowercase().chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
@@ -0,0 +1,215 @@
+//! Labels of the analytic solution branches. The OPW solution has up to 8 branches: two for
+//! J1, two for the elbow (J2, J3) in each of them and two for the wrist (J4, J5, J6). The label
+//! and its index are stable, so a branch choice can be persisted and reproduced later with
+//! `inverse_branch`, even if the order of returned solutions changes.
+//!
+//! The labels follow the analytic formulas and not the physical appearance of the robot, use
+//! `OPWKinematics::configuration` for the latter.
+
+use std::f64::consts::PI;
+use crate::kinematic_traits::{Joints, Pose};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Branch of the J1 solution: the wrist center is reached with the arm in front of the base
+/// (theta1 = atan2(y, x) - atan2(b, ...)) or over the head (the second solution, rotated by pi).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShoulderBranch {
+    First,
+    Second,
+}
+
+/// Sign of the acos term in theta3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElbowBranch {
+    Positive,
+    Negative,
+}
+
+/// Wrist branch: theta5 from the first formula, or the flipped wrist (theta5 negated,
+/// theta4 and theta6 rotated by pi).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WristBranch {
+    NotFlipped,
+    Flipped,
+}
+
+/// Analytic branch of the solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Branch {
+    pub shoulder: ShoulderBranch,
+    pub elbow: ElbowBranch,
+    pub wrist: WristBranch,
+}
+
+impl Branch {
+    /// All branches, ordered by index.
+    pub const ALL: [Branch; 8] = [
+        Branch::from_index(0), Branch::from_index(1), Branch::from_index(2), Branch::from_index(3),
+        Branch::from_index(4), Branch::from_index(5), Branch::from_index(6), Branch::from_index(7),
+    ];
+
+    /// Stable index of the branch in 0..8, suitable for persisting.
+    pub const fn index(&self) -> usize {
+        (matches!(self.wrist, WristBranch::Flipped) as usize) << 2 |
+            (matches!(self.shoulder, ShoulderBranch::Second) as usize) << 1 |
+            matches!(self.elbow, ElbowBranch::Negative) as usize
+    }
+
+    /// Branch from its stable index (only the lower 3 bits are used).
+    pub const fn from_index(index: usize) -> Self {
+        Branch {
+            shoulder: if index & 2 == 0 { ShoulderBranch::First } else { ShoulderBranch::Second },
+            elbow: if index & 1 == 0 { ElbowBranch::Positive } else { ElbowBranch::Negative },
+            wrist: if index & 4 == 0 { WristBranch::NotFlipped } else { WristBranch::Flipped },
+        }
+    }
+}
+
+/// Inverse kinematics solution with its analytic branch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution {
+    pub joints: Joints,
+    pub branch: Branch,
+    /// The pose is marginally out of reach and this is the boundary solution computed with
+    /// clamped acos or sqrt arguments, see `OPWKinematics::with_domain_epsilon`. Its position
+    /// differs slightly from the requested one.
+    pub clamped: bool,
+    /// Condition number of the flange Jacobian in this solution (see
+    /// `OPWKinematics::condition_number`), to prefer the solutions away from the singularities.
+    pub condition: f64,
+    /// Distance between the requested position and the forward kinematics of the solution,
+    /// meters, for applying own acceptance thresholds.
+    pub translation_residual: f64,
+    /// Angle between the requested orientation and the forward kinematics of the solution,
+    /// radians.
+    pub rotation_residual: f64,
+    /// Full turns the wrap into [-pi, pi] takes from the raw branch angles: the wrapped joint
+    /// is the raw one minus 2 pi turns. Reported also if the wrap is disabled with
+    /// `OPWKinematics::with_wrap` and the joints are raw.
+    pub turns: [i32; 6],
+}
+
+impl OPWKinematics {
+    /// Same solutions as `inverse`, in the same order, labeled with their branches, condition
+    /// numbers, residuals and wraps. If the domain epsilon is set, this also returns the
+    /// clamped solutions for the poses that are marginally out of reach.
+    pub fn inverse_detailed(&self, pose: &Pose) -> Vec<Solution> {
+        let (arm, clamped) = self.arm_branches_clamped(pose);
+        let theta = self.wrist_branches(pose, &arm);
+        theta.iter().enumerate()
+            .filter_map(|(i, theta)| {
+                let joints = self.normalized_joints(theta)?;
+                let raw = self.joints_wrapped(theta, false)?;
+                let wrapped = self.joints_wrapped(theta, true)?;
+                let clamped = clamped[i % 4];
+                let residual = self.residual(pose, &joints);
+                self.accepts(residual, clamped).then(|| Solution {
+                    joints,
+                    branch: Branch::from_index(i),
+                    clamped,
+                    condition: self.condition_number(&joints),
+                    translation_residual: residual.0,
+                    rotation_residual: residual.1,
+                    turns: std::array::from_fn(|j| {
+                        ((raw[j] - wrapped[j]) / (2.0 * PI)).round() as i32
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    /// Solution of the given branch only, if it exists for this pose.
+    pub fn inverse_branch(&self, pose: &Pose, branch: Branch) -> Option<Joints> {
+        let theta = self.wrist_branches(pose, &self.arm_branches(pose));
+        let joints = self.normalized_joints(&theta[branch.index()])?;
+        self.verify(pose, &joints, false).then_some(joints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::Kinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_branch_index_round_trip() {
+        for (i, branch) in Branch::ALL.iter().enumerate() {
+            assert_eq!(branch.index(), i);
+        }
+        assert_eq!(Branch::from_index(5), Branch {
+            shoulder: ShoulderBranch::First,
+            elbow: ElbowBranch::Negative,
+            wrist: WristBranch::Flipped,
+        });
+    }
+
+    #[test]
+    fn test_branch_reproduced() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let detailed = robot.inverse_detailed(&pose);
+        let plain = robot.inverse(&pose);
+        assert_eq!(detailed.iter().map(|s| s.joints).collect::<Vec<_>>(), plain);
+        assert!(detailed.iter().all(|s| s.condition == robot.condition_number(&s.joints)));
+        for solution in &detailed {
+            let check = robot.forward(&solution.joints);
+            let translation = (check.translation.vector - pose.translation.vector).norm();
+            assert_eq!(solution.translation_residual, translation);
+            assert!(solution.translation_residual < 1E-9 && solution.rotation_residual < 1E-9);
+        }
+
+        // A nearby pose solved in the persisted branch stays close to the previous solution
+        let chosen = detailed[3];
+        let near = robot.forward(&[0.11, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let joints = robot.inverse_branch(&near, chosen.branch).expect("branch must exist");
+        assert!(joints.iter().zip(chosen.joints.iter()).all(|(a, b)| (a - b).abs() < 0.1));
+    }
+
+    #[test]
+    fn test_clamped_solution() {
+        let parameters = Parameters::irb2400_10();
+        // Arm fully stretched upwards (q3 = -psi3), wrist center at the full reach
+        let psi3 = parameters.a2.atan2(parameters.c3);
+        let joints = [0.0, 0.0, -psi3 + parameters.offsets[2], 0.0, 0.3, 0.0];
+        let robot = OPWKinematics::new(parameters.clone());
+        let mut pose = robot.forward(&joints);
+        pose.translation.z += 5E-6;
+        assert!(robot.inverse(&pose).is_empty());
+
+        let robot = OPWKinematics::new(parameters).with_domain_epsilon(1E-4);
+        let solutions = robot.inverse_detailed(&pose);
+        assert!(!solutions.is_empty());
+        assert!(solutions.iter().all(|s| s.clamped));
+        // The residual tells how far it misses
+        assert!(solutions.iter().all(|s| (s.translation_residual - 5E-6).abs() < 1E-6));
+        // The boundary solution is fully stretched as the original one
+        assert!(solutions.iter().any(|s| (s.joints[2] - joints[2]).abs() < 1E-3));
+    }
+
+    #[test]
+    fn test_unwrapped() {
+        let robot = OPWKinematics::new(Parameters::kuka_kr6_r700_sixx());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, -2.8, 0.5, 2.9]);
+        let unwrapped = robot.clone().with_wrap(false);
+        assert_eq!(unwrapped.inverse(&pose).len(), robot.inverse(&pose).len());
+
+        let detailed = robot.inverse_detailed(&pose);
+        let raw = unwrapped.inverse_detailed(&pose);
+        assert!(raw.iter().any(|s| s.joints.iter().any(|j| j.abs() > PI)));
+        for (wrapped, raw) in detailed.iter().zip(&raw) {
+            assert_eq!(wrapped.turns, raw.turns);
+            for j in 0..6 {
+                let turn = 2.0 * PI * raw.turns[j] as f64;
+                assert!((raw.joints[j] - turn - wrapped.joints[j]).abs() < 1E-12);
+            }
+            assert!(compare_poses(&unwrapped.forward(&raw.joints), &pose, 1E-9, 1E-9).is_ok());
+        }
+    }
+}
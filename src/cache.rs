@@ -0,0 +1,182 @@
+//! Cache of inverse kinematics solutions for repeated queries, as in pick-and-place where the
+//! same few poses are requested thousands of times. Poses are quantized with the configurable
+//! resolution, so all poses within the same quantization cell share the cached solutions:
+//! the resolution must be chosen well below the required accuracy of the application.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions};
+
+/// Quantized pose, translation and quaternion components in units of the resolution.
+type PoseKey = [i64; 7];
+
+struct Entry {
+    solutions: Solutions,
+    last_used: u64,
+}
+
+struct CacheState {
+    entries: HashMap<PoseKey, Entry>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+/// Kinematics with the least recently used cache in front of `inverse`. Other methods are
+/// passed to the wrapped kinematics unchanged (`inverse_continuing` depends on the previous
+/// joint position and is not cached).
+pub struct CachedKinematics<K: Kinematics> {
+    pub robot: K,
+    capacity: usize,
+    /// Quantization step for the translation, meters.
+    distance_resolution: f64,
+    /// Quantization step for the quaternion components.
+    angular_resolution: f64,
+    state: Mutex<CacheState>,
+}
+
+impl<K: Kinematics> CachedKinematics<K> {
+    /// Creates the cache holding up to `capacity` poses, quantizing translations to 1 micrometer
+    /// and orientations to about 1 microradian.
+    pub fn new(robot: K, capacity: usize) -> Self {
+        Self::with_resolution(robot, capacity, 1E-6, 1E-6)
+    }
+
+    /// Creates the cache with the given quantization steps for translation (meters) and for
+    /// the quaternion components (roughly radians for small differences).
+    pub fn with_resolution(robot: K, capacity: usize, distance_resolution: f64,
+                           angular_resolution: f64) -> Self {
+        CachedKinematics {
+            robot,
+            capacity,
+            distance_resolution,
+            angular_resolution,
+            state: Mutex::new(CacheState {
+                entries: HashMap::with_capacity(capacity),
+                clock: 0,
+                hits: 0,
+                misses: 0,
+            }),
+        }
+    }
+
+    /// Number of queries served from the cache and computed, respectively.
+    pub fn statistics(&self) -> (u64, u64) {
+        let state = self.state();
+        (state.hits, state.misses)
+    }
+
+    /// Removes all cached entries (for instance, after the tool has changed).
+    pub fn clear(&self) {
+        self.state().entries.clear();
+    }
+
+    /// The cache state. The state stays consistent if the other thread panicked holding the
+    /// lock (the robot is never called under it), so the poisoning is ignored.
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn key(&self, pose: &Pose) -> PoseKey {
+        let t = pose.translation.vector;
+        let q = pose.rotation.quaternion();
+        // q and -q are the same rotation, use the one with non-negative w
+        let s = if q.w < 0.0 { -1.0 } else { 1.0 };
+        let d = |v: f64| (v / self.distance_resolution).round() as i64;
+        let a = |v: f64| (s * v / self.angular_resolution).round() as i64;
+        [d(t.x), d(t.y), d(t.z), a(q.i), a(q.j), a(q.k), a(q.w)]
+    }
+}
+
+impl<K: Kinematics> Kinematics for CachedKinematics<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        if self.capacity == 0 {
+            return self.robot.inverse(pose);
+        }
+        let key = self.key(pose);
+        {
+            let mut state = self.state();
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some(entry) = state.entries.get_mut(&key) {
+                entry.last_used = clock;
+                let solutions = entry.solutions.clone();
+                state.hits += 1;
+                return solutions;
+            }
+            state.misses += 1;
+        }
+
+        // Solved without the lock, so the misses do not block the other threads
+        let solutions = self.robot.inverse(pose);
+        let mut state = self.state();
+        state.clock += 1;
+        let clock = state.clock;
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            // Linear search is fine for the cache sizes this is intended for (hundreds of poses)
+            let oldest = state.entries.iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(key, Entry { solutions: solutions.clone(), last_used: clock });
+        solutions
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(pose, previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_cache_hits_and_eviction() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let cached = CachedKinematics::new(OPWKinematics::new(Parameters::irb2400_10()), 2);
+        let poses: Vec<Pose> = (0..3)
+            .map(|i| robot.forward(&[0.1 * i as f64, 0.2, 0.3, 0.4, 0.5, 0.6]))
+            .collect();
+
+        assert_eq!(cached.inverse(&poses[0]), robot.inverse(&poses[0]));
+        assert_eq!(cached.inverse(&poses[0]), robot.inverse(&poses[0]));
+        assert_eq!(cached.statistics(), (1, 1));
+
+        // Pose 1 evicts nothing, pose 2 evicts the least recently used pose 0
+        cached.inverse(&poses[1]);
+        cached.inverse(&poses[2]);
+        cached.inverse(&poses[1]);
+        assert_eq!(cached.statistics(), (2, 3));
+        cached.inverse(&poses[0]);
+        assert_eq!(cached.statistics(), (2, 4));
+    }
+
+    #[test]
+    fn test_poisoned_cache() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let cached = CachedKinematics::new(robot.clone(), 2);
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        cached.inverse(&pose);
+        let poisoned = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _state = cached.state.lock();
+            panic!("poisoning the cache");
+        }));
+        assert!(poisoned.is_err() && cached.state.is_poisoned());
+        assert_eq!(cached.inverse(&pose), robot.inverse(&pose));
+        assert_eq!(cached.statistics(), (1, 1));
+    }
+}
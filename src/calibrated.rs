@@ -0,0 +1,198 @@
+//! Calibrated kinematic model: the nominal OPW geometry extended with the small per-joint
+//! errors found by the calibration. The forward kinematics uses the full model. The inverse
+//! kinematics seeds with the nominal analytic solutions and refines them by a few Newton
+//! iterations, which is fast as the seeds are already close to the calibrated solutions.
+
+use nalgebra::{Matrix6, Translation3, UnitQuaternion, Vector3};
+use crate::jacobian::{pose_error, JOINT_AXES};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::{OPWKinematics, ANGULAR_TOLERANCE, DISTANCE_TOLERANCE};
+use crate::utils::compare_poses;
+
+/// Maximal number of the Newton iterations refining the nominal solution.
+const MAX_ITERATIONS: usize = 10;
+
+/// Residual (meters and radians combined) below which the refinement stops.
+const RESIDUAL_TOLERANCE: f64 = 1E-13;
+
+/// Calibrated error of one joint. The offset shifts the joint zero; the translation and the
+/// rotation (rotation vector) are the small transform added after the joint, in the frame of
+/// its link (see `OPWKinematics::link_poses`), like the DH error terms.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointError {
+    /// Error of the joint zero, radians, added to the joint value.
+    pub offset: f64,
+    /// Error of the link position, meters.
+    pub translation: [f64; 3],
+    /// Error of the link orientation, rotation vector, radians.
+    pub rotation: [f64; 3],
+}
+
+impl JointError {
+    fn transform(&self) -> Pose {
+        Pose::from_parts(Translation3::from(Vector3::from(self.translation)),
+                         UnitQuaternion::from_scaled_axis(Vector3::from(self.rotation)))
+    }
+}
+
+/// Robot with the calibrated errors of the joints. Joint values are as in the nominal robot.
+#[derive(Debug, Clone)]
+pub struct CalibratedKinematics {
+    /// Nominal robot, also used for the seeds.
+    pub robot: OPWKinematics,
+    pub errors: [JointError; 6],
+}
+
+impl CalibratedKinematics {
+    pub fn new(robot: OPWKinematics, errors: [JointError; 6]) -> Self {
+        CalibratedKinematics { robot, errors }
+    }
+
+    /// Frames of the joints 1 to 6 in the base frame (joint rotation applied, link error not
+    /// yet) and the flange pose.
+    fn frames(&self, joints: &Joints) -> ([Pose; 6], Pose) {
+        let shifted: Joints = std::array::from_fn(|i| joints[i] + self.errors[i].offset);
+        let links = self.robot.link_poses(&shifted);
+        let mut frames = [Pose::identity(); 6];
+        let mut previous = (Pose::identity(), Pose::identity());
+        for i in 0..6 {
+            // Nominal transform from the previous link to this one
+            let relative = previous.0.inverse() * links[i];
+            frames[i] = previous.1 * relative;
+            previous = (links[i], frames[i] * self.errors[i].transform());
+        }
+        let flange = previous.1 * Translation3::new(0.0, 0.0, self.robot.parameters().c4);
+        (frames, flange)
+    }
+
+    /// Jacobian of the flange in the calibrated model, as `OPWKinematics::jacobian`.
+    pub fn jacobian(&self, joints: &Joints) -> Matrix6<f64> {
+        let (frames, flange) = self.frames(joints);
+        let signs = &self.robot.parameters().sign_corrections;
+        let mut jacobian = Matrix6::zeros();
+        for (j, frame) in frames.iter().enumerate() {
+            let mut local = Vector3::zeros();
+            local[JOINT_AXES[j]] = signs[j] as f64;
+            let axis = frame.rotation * local;
+            let linear = axis.cross(&(flange.translation.vector - frame.translation.vector));
+            jacobian.fixed_view_mut::<3, 1>(0, j).copy_from(&linear);
+            jacobian.fixed_view_mut::<3, 1>(3, j).copy_from(&axis);
+        }
+        let j3 = jacobian.column(2) * self.robot.parameters().j3_coupling;
+        jacobian.column_mut(1).axpy(-1.0, &j3, 1.0);
+        jacobian
+    }
+
+    /// Nominal solution refined to the calibrated model, None if it does not converge.
+    fn refine(&self, pose: &Pose, seed: &Joints) -> Option<Joints> {
+        let mut joints = *seed;
+        let mut residual = pose_error(pose, &self.forward(&joints));
+        for _ in 0..MAX_ITERATIONS {
+            if residual.norm() < RESIDUAL_TOLERANCE {
+                break;
+            }
+            let Some(step) = self.jacobian(&joints).lu().solve(&residual) else { break };
+            let mut next = joints;
+            for (joint, delta) in next.iter_mut().zip(step.iter()) {
+                *joint += delta;
+            }
+            let next_residual = pose_error(pose, &self.forward(&next));
+            if next_residual.norm().is_nan() || next_residual.norm() >= residual.norm() {
+                break;
+            }
+            joints = next;
+            residual = next_residual;
+        }
+        compare_poses(&self.forward(&joints), pose, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE).ok()
+            .map(|_| joints)
+    }
+
+    fn refine_all(&self, pose: &Pose, seeds: Solutions) -> Solutions {
+        seeds.iter().filter_map(|seed| self.refine(pose, seed)).collect()
+    }
+}
+
+impl Stateless for CalibratedKinematics {}
+
+impl Kinematics for CalibratedKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.refine_all(pose, self.robot.inverse(pose))
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.refine_all(pose, self.robot.inverse_continuing(pose, previous))
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.frames(qs).1
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector6;
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    fn calibrated() -> CalibratedKinematics {
+        let error = |k: f64| JointError {
+            offset: 1E-3 * k,
+            translation: [2E-4 * k, -1E-4, 3E-4],
+            rotation: [1E-3, -5E-4 * k, 2E-4],
+        };
+        CalibratedKinematics::new(OPWKinematics::new(Parameters::irb2400_10()),
+                                  std::array::from_fn(|i| error(i as f64 - 2.5)))
+    }
+
+    #[test]
+    fn test_nominal_model() {
+        let nominal = CalibratedKinematics::new(OPWKinematics::new(Parameters::irb2400_10()),
+                                                [JointError::default(); 6]);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = nominal.robot.forward(&joints);
+        assert!(compare_poses(&nominal.forward(&joints), &pose, 1E-12, 1E-12).is_ok());
+        assert!((nominal.jacobian(&joints) - nominal.robot.jacobian(&joints)).norm() < 1E-12);
+    }
+
+    #[test]
+    fn test_calibrated_inverse() {
+        let robot = calibrated();
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        // Errors are large enough for the nominal solution to miss
+        let nominal = robot.robot.inverse_continuing(&pose, &joints);
+        assert!(pose_error(&pose, &robot.forward(&nominal[0])).norm() > 1E-4);
+
+        let solutions = robot.inverse_continuing(&pose, &joints);
+        assert!(solutions[0].iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-9));
+        let all = robot.inverse(&pose);
+        assert_eq!(all.len(), robot.robot.inverse(&pose).len());
+        for solution in &all {
+            assert!(pose_error(&pose, &robot.forward(solution)).norm() < 1E-12);
+        }
+    }
+
+    #[test]
+    fn test_calibrated_jacobian() {
+        let robot = calibrated();
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let jacobian = robot.jacobian(&joints);
+        let pose = robot.forward(&joints);
+        let h = 1E-7;
+        for j in 0..6 {
+            let mut moved = joints;
+            moved[j] += h;
+            let moved = robot.forward(&moved);
+            let linear = (moved.translation.vector - pose.translation.vector) / h;
+            let angular = (moved.rotation * pose.rotation.inverse()).scaled_axis() / h;
+            let numeric = Vector6::new(linear.x, linear.y, linear.z,
+                                       angular.x, angular.y, angular.z);
+            assert!((jacobian.column(j) - numeric).norm() < 1E-5);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Output side of the motion: the joint commands, each to be reached at the given time, go to
+//! a `JointCommandSink`. The simulator, the robot driver or the recorder implements the trait
+//! once, and the generated trajectories (or the streaming session output) are sent to any of
+//! them the same way. The adapters here record the commands, check them against the limits and
+//! forward them by joint name, as the driver interfaces address the joints.
+
+use thiserror::Error;
+use crate::constraints::JointLimits;
+use crate::joint_names::JointMapping;
+use crate::kinematic_traits::Joints;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CommandError {
+    #[error("J{} = {value} rad at {t} s is out of the limits", joint + 1)]
+    OutOfLimits { t: f64, joint: usize, value: f64 },
+    #[error("the command at {t} s is not after the previous one")]
+    NotInOrder { t: f64 },
+    #[error("the receiver refused the command: {0}")]
+    Refused(String),
+}
+
+/// Receiver of the joint commands.
+pub trait JointCommandSink {
+    /// Commands the joints to be reached at the time t, seconds from the start of the motion.
+    fn send(&mut self, t: f64, joints: &Joints) -> Result<(), CommandError>;
+
+    /// Limits of the receiver (as the axis limits of the driver), if known.
+    fn limits(&self) -> Option<JointLimits> {
+        None
+    }
+
+    /// Sends the trajectory points `time_step` seconds apart, the first at `start`. Stops at the
+    /// first refused command.
+    fn send_trajectory(&mut self, start: f64, trajectory: &[Joints], time_step: f64)
+                       -> Result<(), CommandError> {
+        trajectory.iter().enumerate()
+            .try_for_each(|(i, joints)| self.send(start + i as f64 * time_step, joints))
+    }
+}
+
+/// Records the commands, as for the offline simulation or the tests.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandRecorder {
+    pub commands: Vec<(f64, Joints)>,
+}
+
+impl CommandRecorder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Recorded joints without the times.
+    pub fn trajectory(&self) -> Vec<Joints> {
+        self.commands.iter().map(|(_, joints)| *joints).collect()
+    }
+}
+
+impl JointCommandSink for CommandRecorder {
+    fn send(&mut self, t: f64, joints: &Joints) -> Result<(), CommandError> {
+        if self.commands.last().is_some_and(|(last, _)| t <= *last) {
+            return Err(CommandError::NotInOrder { t });
+        }
+        self.commands.push((t, *joints));
+        Ok(())
+    }
+}
+
+/// Passes on only the commands within the limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitChecked<S: JointCommandSink> {
+    pub sink: S,
+    limits: JointLimits,
+}
+
+impl<S: JointCommandSink> LimitChecked<S> {
+    pub fn new(sink: S, limits: JointLimits) -> Self {
+        LimitChecked { sink, limits }
+    }
+
+    /// Checked against the limits the sink reports, None if it has none.
+    pub fn from_sink(sink: S) -> Option<Self> {
+        let limits = sink.limits()?;
+        Some(Self::new(sink, limits))
+    }
+}
+
+impl<S: JointCommandSink> JointCommandSink for LimitChecked<S> {
+    fn send(&mut self, t: f64, joints: &Joints) -> Result<(), CommandError> {
+        let (min, max) = (&self.limits.min, &self.limits.max);
+        if let Some(joint) = (0..6).find(|&i| joints[i] < min[i] || joints[i] > max[i]) {
+            return Err(CommandError::OutOfLimits { t, joint, value: joints[joint] });
+        }
+        self.sink.send(t, joints)
+    }
+
+    fn limits(&self) -> Option<JointLimits> {
+        Some(self.limits)
+    }
+}
+
+/// Forwards the commands as the joint names and values (in the order of J1 to J6) to the
+/// function, as for the drivers and messages addressing the joints by name.
+pub struct NamedCommands<F: FnMut(f64, &[String], &[f64]) -> Result<(), CommandError>> {
+    mapping: JointMapping,
+    limits: Option<JointLimits>,
+    forward: F,
+}
+
+impl<F: FnMut(f64, &[String], &[f64]) -> Result<(), CommandError>> NamedCommands<F> {
+    pub fn new(mapping: JointMapping, forward: F) -> Self {
+        NamedCommands { mapping, limits: None, forward }
+    }
+
+    /// Limits to report, as read from the driver.
+    pub fn with_limits(self, limits: JointLimits) -> Self {
+        NamedCommands { limits: Some(limits), ..self }
+    }
+}
+
+impl<F: FnMut(f64, &[String], &[f64]) -> Result<(), CommandError>> JointCommandSink
+for NamedCommands<F> {
+    fn send(&mut self, t: f64, joints: &Joints) -> Result<(), CommandError> {
+        (self.forward)(t, self.mapping.names(), joints)
+    }
+
+    fn limits(&self) -> Option<JointLimits> {
+        self.limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_sinks() {
+        let trajectory = [[0.0; 6], [0.1; 6], [0.2; 6], [0.3; 6]];
+        let mut recorder = CommandRecorder::new();
+        recorder.send_trajectory(1.0, &trajectory, 0.5).unwrap();
+        assert_eq!(recorder.trajectory(), trajectory);
+        assert_eq!(recorder.commands[3].0, 2.5);
+        assert_eq!(recorder.send(2.0, &[0.4; 6]), Err(CommandError::NotInOrder { t: 2.0 }));
+        assert_eq!(recorder.limits(), None);
+        assert!(LimitChecked::from_sink(CommandRecorder::new()).is_none());
+
+        // The trajectory stops at the first point out of the limits
+        let limits = JointLimits::new([-1.0; 6], [1.0, 1.0, 0.25, 1.0, 1.0, 1.0]);
+        let mut checked = LimitChecked::new(CommandRecorder::new(), limits);
+        assert_eq!(checked.send_trajectory(0.0, &trajectory, 0.1),
+                   Err(CommandError::OutOfLimits { t: 3.0 * 0.1, joint: 2, value: 0.3 }));
+        assert_eq!(checked.sink.trajectory(), trajectory[..3]);
+        assert_eq!(checked.limits(), Some(limits));
+
+        let mut sent = Vec::new();
+        let mapping = JointMapping::new(["a1", "a2", "a3", "a4", "a5", "a6"]);
+        let mut named = NamedCommands::new(mapping, |t, names: &[String], values: &[f64]| {
+            sent.push((t, names[5].clone(), values[5]));
+            Ok(())
+        }).with_limits(limits);
+        assert_eq!(named.limits(), Some(limits));
+        named.send(0.5, &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]).unwrap();
+        assert_eq!(sent, vec![(0.5, "a6".to_string(), 0.6)]);
+    }
+}
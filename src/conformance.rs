@@ -0,0 +1,288 @@
+//! Conformance suite for implementations of the `Kinematics` trait. The cases (cases.yaml, the
+//! same corpus the solver of this crate is tested against) give the joints, the pose they
+//! produce and all inverse kinematics solutions for the built-in robots. Any solver can be run
+//! against them with the same tolerances:
+//! ```no_run
+//! use rs_opw_kinematics::conformance::{ConformanceSuite, Tolerances};
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//!
+//! let suite = ConformanceSuite::builtin().unwrap();
+//! let report = suite.run(|parameters| OPWKinematics::new(parameters.clone()), &Tolerances::default());
+//! assert!(report.is_ok(), "{}", report);
+//! ```
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::path::Path;
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use crate::utils::compare_poses;
+
+/// Location of the built-in cases in the source tree, where the cases of the new robots are
+/// appended. `ConformanceSuite::builtin` embeds the file at build time and does not read it.
+pub const CASES_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/cases.yaml");
+
+/// The built-in cases.
+const CASES: &str = include_str!("tests/cases.yaml");
+
+/// Sine of the distance to the singularity below which the generated joints are sampled again.
+const SINGULARITY_MARGIN: f64 = 1E-3;
+
+/// Defines the conformance suite loading error
+#[derive(Error, Debug)]
+pub enum ConformanceError {
+    #[error("failed to read the cases file")]
+    FileReadError(#[from] std::io::Error),
+    #[error("failed to parse YAML in the cases file")]
+    YamlParseError(#[from] serde_yaml::Error),
+}
+
+/// Pose of the case: translation and quaternion x, y, z, w.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CasePose {
+    pub translation: [f64; 3],
+    pub quaternion: [f64; 4],
+}
+
+impl CasePose {
+    pub fn to_pose(&self) -> Pose {
+        let [x, y, z, w] = self.quaternion;
+        Pose::from_parts(Translation3::from(self.translation),
+                         UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)))
+    }
+
+    pub fn from_pose(pose: &Pose) -> Self {
+        let q = pose.rotation.quaternion();
+        CasePose { translation: pose.translation.vector.into(), quaternion: [q.i, q.j, q.k, q.w] }
+    }
+}
+
+/// Single case. Joints and solutions are in degrees, the robot is named as in `case_parameters`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Case {
+    pub id: i32,
+    pub parameters: String,
+    pub joints: [f64; 6],
+    pub solutions: Vec<[f64; 6]>,
+    pub pose: CasePose,
+}
+
+impl Case {
+    pub fn joints_in_radians(&self) -> Joints {
+        self.joints.map(f64::to_radians)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConformanceSuite {
+    pub cases: Vec<Case>,
+}
+
+/// Parameters of the robot as named in the cases: the `Parameters::preset` name, in any
+/// letter case (like "Irb2400_10"). "KukaKR6_R700_sixx" of the original cases is also known.
+pub fn case_parameters(name: &str) -> Option<Parameters> {
+    match name {
+        "KukaKR6_R700_sixx" => Parameters::preset("kuka_kr6_r700_sixx"),
+        _ => Parameters::preset(&name.to_lowercase()),
+    }
+}
+
+/// Tolerances of the checks, as used by the tests of this crate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// Translation of the forward kinematics pose, meters.
+    pub translation: f64,
+    /// Rotation angle of the forward kinematics pose, radians.
+    pub rotation: f64,
+    /// Each joint of the inverse kinematics solution, radians.
+    pub joints: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances { translation: 1E-5, rotation: 1E-5, joints: 0.001_f64.to_radians() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// Forward kinematics of the case joints must produce the case pose.
+    Forward,
+    /// Inverse kinematics of the pose must include the case joints (not checked in the
+    /// singularity as reported by the solver).
+    Inverse,
+    /// Inverse kinematics continuing from the case joints must return them first.
+    InverseContinuing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub id: i32,
+    pub robot: String,
+    pub check: Check,
+}
+
+/// Outcome of the suite run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConformanceReport {
+    /// Cases checked.
+    pub checked: usize,
+    /// Cases for the robots the solver factory does not know.
+    pub skipped: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl ConformanceReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} cases checked, {} skipped, {} failures", self.checked, self.skipped,
+               self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "\n  case {} on {}: {:?}", failure.id, failure.robot, failure.check)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConformanceSuite {
+    /// Cases of this crate (cases.yaml, embedded in the library).
+    pub fn builtin() -> Result<Self, ConformanceError> {
+        Ok(Self::from_yaml(CASES)?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConformanceError> {
+        Ok(Self::from_yaml(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn from_yaml(contents: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(contents)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    /// Generates `count` cases for the robot, referring to it by `name`. Joints are sampled as
+    /// whole degrees within [-180, 180] (the same seed gives the same joints on every platform),
+    /// skipping the wrist and elbow singularities where the case would not be checkable.
+    pub fn generate(parameters: &Parameters, name: &str, count: usize, seed: u64,
+                    first_id: i32) -> Self {
+        let robot = OPWKinematics::new(parameters.clone());
+        let mut random = XorShift(seed.max(1));
+        let cases = (0..count).map(|i| {
+            let joints = loop {
+                let joints: [f64; 6] = std::array::from_fn(|_| (random.next() % 361) as f64 - 180.0);
+                if !singular(parameters, &joints) {
+                    break joints;
+                }
+            };
+            let pose = robot.forward(&joints.map(f64::to_radians));
+            Case {
+                id: first_id + i as i32,
+                parameters: name.to_string(),
+                joints,
+                solutions: robot.inverse(&pose).iter().map(|s| s.map(f64::to_degrees)).collect(),
+                pose: CasePose::from_pose(&pose),
+            }
+        }).collect();
+        ConformanceSuite { cases }
+    }
+
+    /// Cases in the compact layout of cases.yaml (one line per field, joints with 6 decimal
+    /// places), starting with the `cases:` header.
+    pub fn to_compact_yaml(&self) -> String {
+        let degrees = |joints: &[f64; 6]| format!("[{}]", joints.iter()
+            .map(|v| format!("{:.6}", v)).collect::<Vec<_>>().join(", "));
+        let numbers = |values: &[f64]| format!("[{}]", values.iter()
+            .map(|v| format!("{:?}", v)).collect::<Vec<_>>().join(", "));
+        let mut yaml = String::from("cases:\n");
+        for case in &self.cases {
+            let solutions: Vec<String> = case.solutions.iter().map(degrees).collect();
+            yaml.push_str(&format!("  - id: {}\n    parameters: {}\n    joints: {}\n", case.id,
+                                   case.parameters, degrees(&case.joints)));
+            yaml.push_str(&format!("    pose: {{translation: {}, quaternion: {}}}\n",
+                                   numbers(&case.pose.translation), numbers(&case.pose.quaternion)));
+            yaml.push_str(&format!("    solutions: [{}]\n", solutions.join(", ")));
+        }
+        yaml
+    }
+
+    /// Runs all cases with the solver built by the factory from the robot parameters
+    /// (once per robot).
+    pub fn run<K: Kinematics>(&self, factory: impl Fn(&Parameters) -> K,
+                              tolerances: &Tolerances) -> ConformanceReport {
+        self.run_with(|name| case_parameters(name).map(|p| factory(&p)), tolerances)
+    }
+
+    /// Runs all cases with the solver built by the factory from the robot name; cases of the
+    /// robots for which the factory returns None are skipped.
+    pub fn run_with<K: Kinematics>(&self, factory: impl Fn(&str) -> Option<K>,
+                                   tolerances: &Tolerances) -> ConformanceReport {
+        let mut solvers: HashMap<&str, Option<K>> = HashMap::new();
+        let mut report = ConformanceReport::default();
+        for case in &self.cases {
+            let solver = solvers.entry(&case.parameters).or_insert_with(|| factory(&case.parameters));
+            let Some(solver) = solver else {
+                report.skipped += 1;
+                continue;
+            };
+            report.checked += 1;
+            let joints = case.joints_in_radians();
+            let pose = case.pose.to_pose();
+            let mut fail = |check| report.failures.push(
+                Failure { id: case.id, robot: case.parameters.clone(), check });
+
+            if compare_poses(&solver.forward(&joints), &pose, tolerances.translation,
+                             tolerances.rotation).is_err() {
+                fail(Check::Forward);
+            }
+            if solver.kinematic_singularity(&joints).is_none()
+                && find_joints(&solver.inverse(&pose), &joints, tolerances.joints).is_none() {
+                fail(Check::Inverse);
+            }
+            if find_joints(&solver.inverse_continuing(&pose, &joints), &joints, tolerances.joints)
+                != Some(0) {
+                fail(Check::InverseContinuing);
+            }
+        }
+        report
+    }
+}
+
+/// Wrist (J5 at 0 or 180) or elbow (arm stretched or folded) singularity, joints in degrees.
+fn singular(parameters: &Parameters, degrees: &[f64; 6]) -> bool {
+    let joints = parameters.decoupled(&degrees.map(f64::to_radians));
+    let q3 = joints[2] * parameters.sign_corrections[2] as f64 - parameters.offsets[2];
+    joints[4].sin().abs() < SINGULARITY_MARGIN
+        || (q3 + parameters.a2.atan2(parameters.c3)).sin().abs() < SINGULARITY_MARGIN
+}
+
+/// Small reproducible generator, so the same seed gives the same cases on every platform.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Index of the solution matching the expected joints (360 degrees apart counts as equal).
+fn find_joints(solutions: &[Joints], expected: &Joints, tolerance: f64) -> Option<usize> {
+    solutions.iter().position(|solution| solution.iter().zip(expected).all(|(a, b)| {
+        let diff = (a - b).abs();
+        diff < tolerance || (diff - 2.0 * PI).abs() <= tolerance
+    }))
+}
@@ -0,0 +1,307 @@
+//! Joint limits given at the call time, like the axes temporarily restricted by the cabling or
+//! fixtures of the current job. The solutions are shifted by full turns into the limits where
+//! possible (for the joints rotating beyond ±180 degrees) and dropped otherwise.
+//!
+//! The limits can have the soft margin inside: the solutions within it are still returned,
+//! but after the others and flagged, helping the programs to stay away from the limit stops.
+//!
+//! The turn counter tracks the cumulative rotation of the joints (J1, J4 and J6 are the usual
+//! ones with the dress pack) along the trajectory and rejects the motion that would wind the
+//! cables beyond the budget, even though each pose is valid on its own.
+
+use std::f64::consts::PI;
+use thiserror::Error;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::utils::angle_difference;
+
+/// Position limits of the joints, radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointLimits {
+    pub min: Joints,
+    pub max: Joints,
+    /// Width of the soft margin inside the limits of each joint, radians.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub soft_margin: [f64; 6],
+}
+
+impl JointLimits {
+    pub fn new(min: Joints, max: Joints) -> Self {
+        JointLimits { min, max, soft_margin: [0.0; 6] }
+    }
+
+    /// Limits from the (min, max) range of each joint in degrees.
+    pub fn from_degrees(ranges: [(f64, f64); 6]) -> Self {
+        Self::new(ranges.map(|(min, _)| min.to_radians()), ranges.map(|(_, max)| max.to_radians()))
+    }
+
+    pub fn with_soft_margin(self, soft_margin: [f64; 6]) -> Self {
+        JointLimits { soft_margin, ..self }
+    }
+
+    /// Penalty of the joints inside the soft margin: for each joint, how deep it is in the
+    /// margin (0 at its inner edge, 1 at the limit), summed over the joints. 0 if clear of it.
+    pub fn soft_penalty(&self, joints: &Joints) -> f64 {
+        (0..6).filter(|&i| self.soft_margin[i] > 0.0).map(|i| {
+            let clearance = (joints[i] - self.min[i]).min(self.max[i] - joints[i]);
+            (1.0 - clearance / self.soft_margin[i]).clamp(0.0, 1.0)
+        }).sum()
+    }
+
+    pub fn contains(&self, joints: &Joints) -> bool {
+        (0..6).all(|i| joints[i] >= self.min[i] && joints[i] <= self.max[i])
+    }
+
+    /// The joints shifted by the full turns into the limits, each joint by the fewest turns.
+    /// None if some joint does not fit.
+    pub fn fit(&self, joints: &Joints) -> Option<Joints> {
+        let mut fitted = *joints;
+        for (i, value) in fitted.iter_mut().enumerate() {
+            if *value < self.min[i] {
+                *value += 2.0 * PI * ((self.min[i] - *value) / (2.0 * PI)).ceil();
+            } else if *value > self.max[i] {
+                *value -= 2.0 * PI * ((*value - self.max[i]) / (2.0 * PI)).ceil();
+            }
+            if !(self.min[i]..=self.max[i]).contains(value) {
+                return None;
+            }
+        }
+        Some(fitted)
+    }
+}
+
+/// Solution within the limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimitedSolution {
+    pub joints: Joints,
+    /// Some joint is inside the soft margin.
+    pub in_soft_margin: bool,
+    /// See `JointLimits::soft_penalty`.
+    pub penalty: f64,
+}
+
+/// Failure to follow the trajectory within the winding budget.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum WindingError {
+    #[error("pose {0} is not reachable")]
+    Unreachable(usize),
+    #[error("pose {index} would wind joint {joint} by {winding} rad beyond the budget")]
+    OverBudget { index: usize, joint: usize, winding: f64 },
+}
+
+/// Cumulative rotation of the joints from the start of the trajectory, against the budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TurnCounter {
+    start: Joints,
+    current: Joints,
+    /// Largest allowed winding of each joint either way from the start, radians (infinite for
+    /// the joints not counted).
+    pub budget: [f64; 6],
+}
+
+impl TurnCounter {
+    pub fn new(start: Joints, budget: [f64; 6]) -> Self {
+        TurnCounter { start, current: start, budget }
+    }
+
+    /// Counter for J1, J4 and J6 with the budget of the given number of turns either way.
+    pub fn with_turns(start: Joints, j1: f64, j4: f64, j6: f64) -> Self {
+        let turn = 2.0 * PI;
+        Self::new(start, [j1 * turn, f64::INFINITY, f64::INFINITY, j4 * turn, f64::INFINITY,
+            j6 * turn])
+    }
+
+    /// Current joints, unwrapped: accumulating the full turns.
+    pub fn current(&self) -> Joints {
+        self.current
+    }
+
+    /// Rotation of each joint since the start, radians.
+    pub fn winding(&self) -> [f64; 6] {
+        std::array::from_fn(|i| self.current[i] - self.start[i])
+    }
+
+    /// Rotation of each joint since the start, turns.
+    pub fn turns(&self) -> [f64; 6] {
+        self.winding().map(|w| w / (2.0 * PI))
+    }
+
+    /// Unwrapped joints after moving to `joints` (each joint the shorter way) or the first
+    /// joint whose winding would exceed the budget, with that winding.
+    pub fn check(&self, joints: &Joints) -> Result<Joints, (usize, f64)> {
+        let next: Joints = std::array::from_fn(|i| {
+            self.current[i] + angle_difference(joints[i], self.current[i])
+        });
+        match (0..6).find(|&i| (next[i] - self.start[i]).abs() > self.budget[i]) {
+            Some(joint) => Err((joint, next[joint] - self.start[joint])),
+            None => Ok(next),
+        }
+    }
+
+    /// Moves to the joints if within the budget, returning them unwrapped.
+    pub fn advance(&mut self, joints: &Joints) -> Result<Joints, (usize, f64)> {
+        self.current = self.check(joints)?;
+        Ok(self.current)
+    }
+}
+
+/// Inverse kinematics within the joint limits given at the call. Implemented for all solvers.
+/// The solutions inside the soft margin come after the others, the least penalized first,
+/// otherwise the order of the underlying call is kept.
+pub trait ConstrainedKinematics: Kinematics {
+    /// Solutions of `inverse` fitted into the limits, see `JointLimits::fit`.
+    fn inverse_within(&self, pose: &Pose, limits: &JointLimits) -> Solutions {
+        fit_all(&self.inverse(pose), limits).into_iter().map(|s| s.joints).collect()
+    }
+
+    /// Solutions of `inverse_continuing` fitted into the limits, closest to the previous first.
+    fn inverse_continuing_within(&self, pose: &Pose, previous: &Joints, limits: &JointLimits)
+                                 -> Solutions {
+        self.inverse_continuing_flagged(pose, previous, limits).into_iter()
+            .map(|s| s.joints).collect()
+    }
+
+    /// Follows the trajectory from the current joints of the counter, taking for each pose the
+    /// closest solution within the winding budget. Returns the unwrapped joints of the poses.
+    fn follow_within_winding(&self, poses: &[Pose], counter: &mut TurnCounter)
+                             -> Result<Solutions, WindingError> {
+        poses.iter().enumerate().map(|(index, pose)| {
+            let solutions = self.inverse_continuing(pose, &counter.current());
+            let mut over = None;
+            for solution in &solutions {
+                match counter.advance(solution) {
+                    Ok(joints) => return Ok(joints),
+                    Err(exceeded) => { over.get_or_insert(exceeded); }
+                }
+            }
+            Err(match over {
+                Some((joint, winding)) => WindingError::OverBudget { index, joint, winding },
+                None => WindingError::Unreachable(index),
+            })
+        }).collect()
+    }
+
+    /// As `inverse_continuing_within`, with the solutions inside the soft margin flagged.
+    fn inverse_continuing_flagged(&self, pose: &Pose, previous: &Joints, limits: &JointLimits)
+                                  -> Vec<LimitedSolution> {
+        fit_all(&self.inverse_continuing(pose, previous), limits)
+    }
+}
+
+fn fit_all(solutions: &[Joints], limits: &JointLimits) -> Vec<LimitedSolution> {
+    let mut fitted: Vec<LimitedSolution> = solutions.iter().filter_map(|s| limits.fit(s))
+        .map(|joints| {
+            let penalty = limits.soft_penalty(&joints);
+            LimitedSolution { joints, in_soft_margin: penalty > 0.0, penalty }
+        })
+        .collect();
+    fitted.sort_by(|a, b| a.in_soft_margin.cmp(&b.in_soft_margin)
+        .then(a.penalty.total_cmp(&b.penalty)));
+    fitted
+}
+
+impl<K: Kinematics + ?Sized> ConstrainedKinematics for K {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_fit() {
+        let limits = JointLimits::from_degrees([(-170.0, 170.0), (-90.0, 150.0), (-90.0, 180.0),
+            (-200.0, 200.0), (-120.0, 120.0), (-400.0, 400.0)]);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        assert!(limits.contains(&joints));
+        assert_eq!(limits.fit(&joints), Some(joints));
+        let fitted = limits.fit(&[0.1, 0.2, 0.3, 0.4, 0.5, 6.9]).unwrap();
+        assert!((fitted[5] - 6.9).abs() < 1E-12);
+        let fitted = limits.fit(&[0.1, 0.2, 0.3, 0.4, 0.5, 8.0]).unwrap();
+        assert!((fitted[5] - (8.0 - 2.0 * PI)).abs() < 1E-12);
+        let fitted = limits.fit(&[0.1, 0.2, 0.3, -3.6, 0.5, 0.6]).unwrap();
+        assert!((fitted[3] - (-3.6 + 2.0 * PI)).abs() < 1E-12);
+        assert_eq!(limits.fit(&[0.1, 0.2, 0.3, 0.4, 2.5, 0.6]), None);
+    }
+
+    #[test]
+    fn test_inverse_within() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let all = robot.inverse(&pose);
+
+        // J5 restricted to positive, J1 to the front
+        let limits = JointLimits::from_degrees([(-90.0, 90.0), (-180.0, 180.0), (-180.0, 180.0),
+            (-180.0, 180.0), (0.0, 180.0), (-180.0, 180.0)]);
+        let within = robot.inverse_within(&pose, &limits);
+        assert!(!within.is_empty() && within.len() < all.len());
+        assert!(within.iter().all(|s| limits.contains(s)));
+        let continuing = robot.inverse_continuing_within(&pose, &joints, &limits);
+        assert!((continuing[0][0] - joints[0]).abs() < 1E-9);
+    }
+
+    #[test]
+    fn test_soft_margin() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let limits = JointLimits::from_degrees([(-180.0, 180.0); 6]);
+        let plain = robot.inverse_continuing_within(&pose, &joints, &limits);
+
+        // The closest solution has J4 within 10 degrees of the limit at 0.4 rad
+        let soft = JointLimits { max: [PI, PI, PI, 0.45, PI, PI], ..limits }
+            .with_soft_margin([0.0, 0.0, 0.0, 10f64.to_radians(), 0.0, 0.0]);
+        let penalty = soft.soft_penalty(&joints);
+        assert!(penalty > 0.5 && penalty < 1.0);
+        assert_eq!(soft.soft_penalty(&[0.0; 6]), 0.0);
+
+        let flagged = robot.inverse_continuing_flagged(&pose, &joints, &soft);
+        assert!(flagged.windows(2).all(|w| w[0].in_soft_margin <= w[1].in_soft_margin));
+        let kept = flagged.iter().find(|s| s.joints == plain[0]).unwrap();
+        assert!(kept.in_soft_margin && (kept.penalty - penalty).abs() < 1E-6);
+        assert_ne!(flagged[0].joints, plain[0]);
+    }
+
+    #[test]
+    fn test_soft_margin_order() {
+        let limits = JointLimits::from_degrees([(-180.0, 180.0); 6])
+            .with_soft_margin([0.5; 6]);
+        let deep = [0.1, 0.2, 0.3, 3.0, 0.5, 0.6];
+        let shallow = [0.1, 0.2, 0.3, 2.8, 0.5, 0.6];
+        let clear = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let fitted = fit_all(&[deep, shallow, clear], &limits);
+        assert_eq!(fitted.iter().map(|s| s.joints).collect::<Vec<_>>(), [clear, shallow, deep]);
+        assert!(fitted[1].in_soft_margin && fitted[1].penalty < fitted[2].penalty);
+    }
+
+    #[test]
+    fn test_turn_counter() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.0, 0.2, 0.3, 0.0, 0.5, 0.0];
+        // J6 spinning by 1.5 turns in 10 degree steps
+        let poses: Vec<Pose> = (1..=54).map(|i| {
+            robot.forward(&[0.0, 0.2, 0.3, 0.0, 0.5, (10.0 * i as f64).to_radians()])
+        }).collect();
+
+        let mut counter = TurnCounter::with_turns(start, 1.0, 1.0, 2.0);
+        let path = robot.follow_within_winding(&poses, &mut counter).unwrap();
+        assert!((path[53][5] - 3.0 * PI).abs() < 1E-6);
+        assert!((counter.turns()[5] - 1.5).abs() < 1E-6);
+
+        // With one turn of J6, the flipped wrist is taken to unwind it
+        let mut counter = TurnCounter::with_turns(start, 1.0, 1.0, 1.0);
+        let path = robot.follow_within_winding(&poses, &mut counter).unwrap();
+        assert!(path[35][4] > 0.0 && path[36][4] < 0.0);
+
+        let mut counter = TurnCounter::with_turns(start, 0.25, 0.25, 1.0);
+        match robot.follow_within_winding(&poses, &mut counter) {
+            Err(WindingError::OverBudget { index, joint, .. }) => {
+                assert_eq!((index, joint), (36, 5));
+            }
+            other => panic!("over budget expected, got {:?}", other),
+        }
+    }
+}
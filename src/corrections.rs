@@ -0,0 +1,266 @@
+//! Per-joint corrections, mirroring the compensations of the controller (gear coupling,
+//! temperature or compensation tables) in the offline computation. The commanded joint values
+//! are what the application and the controller use; the correction gives the actual joint
+//! position from them, which the kinematic model sees. The same correction is applied in
+//! `forward` and undone in `inverse`, so both stay consistent.
+//!
+//! The linear coupling of the drives, like J6 turning with J5 on some wrists, is declared
+//! with `MotorCoupling` instead: it maps between the motor and the kinematic joints exactly.
+
+use std::sync::Arc;
+use nalgebra::{Matrix6, Vector6};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+
+/// Maximal number of the fixed point iterations removing the corrections.
+const MAX_ITERATIONS: usize = 50;
+
+/// Change of the joint values, radians, at which the iterations stop.
+const CONVERGENCE_TOLERANCE: f64 = 1E-14;
+
+/// Correction of one joint: the difference between the actual and the commanded position,
+/// radians, from the commanded values of all joints (so couplings can be expressed).
+pub type Correction = Arc<dyn Fn(&Joints) -> f64 + Send + Sync>;
+
+/// Robot with the joint corrections. The joint values accepted by `forward` and returned by
+/// `inverse` are commanded values. The corrections must be small (a fraction of a radian)
+/// and smooth, as they are removed by the fixed point iteration; the solutions for which it
+/// does not converge are dropped.
+#[derive(Clone)]
+pub struct Corrected<K: Kinematics> {
+    pub robot: K,
+    corrections: Vec<(usize, Correction)>,
+}
+
+impl<K: Kinematics> Corrected<K> {
+    pub fn new(robot: K) -> Self {
+        Corrected { robot, corrections: Vec::new() }
+    }
+
+    /// Adds the correction of the joint (0 for J1). The corrections of the same joint add up.
+    /// Panics if the joint is not 0 to 5.
+    pub fn with_correction<F>(mut self, joint: usize, correction: F) -> Self
+    where
+        F: Fn(&Joints) -> f64 + Send + Sync + 'static,
+    {
+        assert!(joint < 6, "joint {} out of range, 0 (J1) to 5 (J6) expected", joint);
+        self.corrections.push((joint, Arc::new(correction)));
+        self
+    }
+
+    /// Adds the correction of the joint from the compensation table of (commanded position,
+    /// correction) points, interpolated linearly and held constant beyond the ends. Panics
+    /// if the joint is not 0 to 5, or if a position is not finite or given twice (where the
+    /// correction would be ambiguous).
+    pub fn with_table(self, joint: usize, table: &[(f64, f64)]) -> Self {
+        let mut table = table.to_vec();
+        table.sort_by(|a, b| a.0.total_cmp(&b.0));
+        assert!(table.iter().all(|(at, _)| at.is_finite()), "table position is not finite");
+        if let Some(pair) = table.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            panic!("table position {} is given twice", pair[0].0);
+        }
+        self.with_correction(joint, move |joints| interpolate(&table, joints[joint]))
+    }
+
+    /// Actual joint positions for the commanded values.
+    pub fn actual(&self, commanded: &Joints) -> Joints {
+        let mut actual = *commanded;
+        for (joint, correction) in &self.corrections {
+            actual[*joint] += correction(commanded);
+        }
+        actual
+    }
+
+    /// Commanded values giving the actual joint positions, None if the fixed point iteration
+    /// does not converge (the correction is too large or too steep).
+    pub fn commanded(&self, actual: &Joints) -> Option<Joints> {
+        let mut commanded = *actual;
+        for _ in 0..MAX_ITERATIONS {
+            let corrected = self.actual(&commanded);
+            let mut change: f64 = 0.0;
+            for i in 0..6 {
+                let next = commanded[i] + actual[i] - corrected[i];
+                change = change.max((next - commanded[i]).abs());
+                commanded[i] = next;
+            }
+            if change < CONVERGENCE_TOLERANCE {
+                return Some(commanded);
+            }
+        }
+        None
+    }
+}
+
+/// Linear interpolation in the sorted table, constant beyond the ends, zero if empty.
+fn interpolate(table: &[(f64, f64)], x: f64) -> f64 {
+    match table.iter().position(|(at, _)| *at > x) {
+        None => table.last().map_or(0.0, |(_, value)| *value),
+        Some(0) => table[0].1,
+        Some(i) => {
+            let ((x0, y0), (x1, y1)) = (table[i - 1], table[i]);
+            y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+        }
+    }
+}
+
+impl<K: Stateless> Stateless for Corrected<K> {}
+
+impl<K: Kinematics> Kinematics for Corrected<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.robot.inverse(pose).iter().filter_map(|joints| self.commanded(joints)).collect()
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(pose, &self.actual(previous)).iter()
+            .filter_map(|joints| self.commanded(joints)).collect()
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(&self.actual(qs))
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(&self.actual(qs))
+    }
+}
+
+/// Robot with the linearly coupled drives. The joint values accepted by `forward` and
+/// returned by `inverse` are motor joints, related to the kinematic joints as
+/// `motor = matrix * kinematic`.
+#[derive(Debug, Clone)]
+pub struct MotorCoupling<K: Kinematics> {
+    pub robot: K,
+    matrix: Matrix6<f64>,
+    inverse: Matrix6<f64>,
+}
+
+impl<K: Kinematics> MotorCoupling<K> {
+    /// Robot with the given coupling matrix, None if it is not invertible.
+    pub fn new(robot: K, matrix: Matrix6<f64>) -> Option<Self> {
+        let inverse = matrix.try_inverse()?;
+        Some(MotorCoupling { robot, matrix, inverse })
+    }
+
+    /// Robot with J6 coupled to J5: the motor J6 is J6 + `ratio` * J5.
+    pub fn wrist(robot: K, ratio: f64) -> Self {
+        let mut matrix = Matrix6::identity();
+        matrix[(5, 4)] = ratio;
+        Self::new(robot, matrix).expect("triangular matrix with the unit diagonal")
+    }
+
+    pub fn matrix(&self) -> &Matrix6<f64> {
+        &self.matrix
+    }
+
+    /// Motor joints for the kinematic joints.
+    pub fn motor(&self, kinematic: &Joints) -> Joints {
+        (self.matrix * Vector6::from(*kinematic)).into()
+    }
+
+    /// Kinematic joints for the motor joints.
+    pub fn kinematic(&self, motor: &Joints) -> Joints {
+        (self.inverse * Vector6::from(*motor)).into()
+    }
+}
+
+impl<K: Stateless> Stateless for MotorCoupling<K> {}
+
+impl<K: Kinematics> Kinematics for MotorCoupling<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.robot.inverse(pose).iter().map(|joints| self.motor(joints)).collect()
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(pose, &self.kinematic(previous)).iter()
+            .map(|joints| self.motor(joints)).collect()
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(&self.kinematic(qs))
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(&self.kinematic(qs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_corrections() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        // J6 coupled to J5 by the gear, J2 compensated by the table
+        let corrected = Corrected::new(robot.clone())
+            .with_correction(5, |joints| 0.01 * joints[4])
+            .with_table(1, &[(0.5, 0.002), (-0.5, -0.001)]);
+        let commanded = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let actual = corrected.actual(&commanded);
+        assert!((actual[5] - 0.605).abs() < 1E-12);
+        assert!((actual[1] - 0.2 - 0.0011).abs() < 1E-12);
+        assert_eq!(actual[0], 0.1);
+        assert!(corrected.commanded(&actual).unwrap().iter().zip(&commanded)
+            .all(|(a, b)| (a - b).abs() < 1E-12));
+
+        let pose = corrected.forward(&commanded);
+        assert!(compare_poses(&pose, &robot.forward(&actual), 1E-12, 1E-12).is_ok());
+        let solutions = corrected.inverse_continuing(&pose, &commanded);
+        assert!(solutions[0].iter().zip(&commanded).all(|(a, b)| (a - b).abs() < 1E-9));
+        for solution in corrected.inverse(&pose) {
+            assert!(compare_poses(&corrected.forward(&solution), &pose, 1E-9, 1E-9).is_ok());
+        }
+
+        // Steeper than the commanded motion itself, the iteration diverges
+        let diverging = Corrected::new(robot).with_correction(0, |joints| 2.0 * joints[0]);
+        assert_eq!(diverging.commanded(&actual), None);
+        assert!(diverging.inverse(&pose).is_empty());
+        assert!(diverging.inverse_continuing(&pose, &commanded).is_empty());
+    }
+
+    #[test]
+    fn test_motor_coupling() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let coupled = MotorCoupling::wrist(robot.clone(), 0.5);
+        let kinematic = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let motor = coupled.motor(&kinematic);
+        assert!((motor[5] - 0.85).abs() < 1E-15);
+        assert_eq!(&motor[..5], &kinematic[..5]);
+        assert!(coupled.kinematic(&motor).iter().zip(&kinematic)
+            .all(|(a, b)| (a - b).abs() < 1E-15));
+
+        let pose = coupled.forward(&motor);
+        assert!(compare_poses(&pose, &robot.forward(&kinematic), 1E-12, 1E-12).is_ok());
+        let solutions = coupled.inverse_continuing(&pose, &motor);
+        assert!(solutions[0].iter().zip(&motor).all(|(a, b)| (a - b).abs() < 1E-9));
+        assert_eq!(coupled.inverse(&pose).len(), robot.inverse(&pose).len());
+
+        assert!(MotorCoupling::new(robot, Matrix6::zeros()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "joint 6 out of range")]
+    fn test_joint_out_of_range() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let _ = Corrected::new(robot).with_correction(6, |_| 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "table position 0.5 is given twice")]
+    fn test_duplicate_table_position() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let _ = Corrected::new(robot).with_table(1, &[(0.5, 0.002), (-0.5, 0.0), (0.5, 0.001)]);
+    }
+
+    #[test]
+    fn test_table() {
+        let table = [(-1.0, 0.0), (0.0, 0.01), (1.0, 0.0)];
+        assert_eq!(interpolate(&table, -2.0), 0.0);
+        assert!((interpolate(&table, -0.5) - 0.005).abs() < 1E-15);
+        assert_eq!(interpolate(&table, 0.0), 0.01);
+        assert_eq!(interpolate(&table, 3.0), 0.0);
+        assert_eq!(interpolate(&[], 3.0), 0.0);
+    }
+}
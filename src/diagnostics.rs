@@ -0,0 +1,216 @@
+//! Detailed report of the inverse kinematics, branch by branch. Useful when onboarding a new
+//! robot: if the parameters are slightly off, the solutions fail the forward kinematics
+//! cross-check and `inverse` silently returns fewer solutions (or none). The report shows
+//! which branches were rejected, why, and by how much they missed.
+//!
+//! For the pose without the solution, `unreachable_cause` tells what the user has to change:
+//! the position, the orientation or the joint limits.
+
+use nalgebra::Vector3;
+use crate::constraints::JointLimits;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::{OPWKinematics, ANGULAR_TOLERANCE, DISTANCE_TOLERANCE};
+use std::f64::consts::PI;
+
+/// Why the branch did not produce a valid solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rejection {
+    /// The argument of acos for J2 or J3 is outside [-1, 1]: the wrist center is out of
+    /// reach in this arm configuration.
+    AcosDomain,
+    /// Some angle is not a number for another reason (for instance, the wrist center is
+    /// closer to the J1 axis than the b offset).
+    NotFinite,
+    /// The solution exists but its forward kinematics does not match the requested pose
+    /// within the tolerance.
+    ForwardMismatch,
+}
+
+/// Outcome of one of the 8 analytic branches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BranchDiagnostics {
+    /// Joint values of the branch, None if not finite.
+    pub joints: Option<Joints>,
+    /// None if the branch produced the valid solution.
+    pub rejection: Option<Rejection>,
+    /// Distance between the requested position and the forward kinematics of the solution,
+    /// meters. NaN if there are no joint values.
+    pub translation_error: f64,
+    /// Angle between the requested orientation and the forward kinematics of the solution,
+    /// radians. NaN if there are no joint values.
+    pub angular_error: f64,
+    /// Distance of J5 to the nearest wrist singularity (J5 = 0 or ±180 degrees), radians.
+    pub wrist_singularity_distance: f64,
+}
+
+/// Why the pose has no solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unreachable {
+    /// The position is farther from the shoulder than the stretched arm reaches, with any
+    /// orientation.
+    OutOfReach,
+    /// The position is too close to the shoulder (or the J1 axis) for the folded arm, with any
+    /// orientation.
+    InnerVoid,
+    /// The position is reachable, but not with this orientation: the wrist center (c4 behind
+    /// the flange) is out of reach.
+    Orientation,
+    /// There are solutions, but none within the joint limits.
+    JointLimits,
+}
+
+impl BranchDiagnostics {
+    pub fn is_valid(&self) -> bool {
+        self.rejection.is_none()
+    }
+}
+
+impl OPWKinematics {
+    /// Inverse kinematics reporting the outcome of every analytic branch, in the order of
+    /// the branches (the valid ones are returned by `inverse` in the same order).
+    pub fn inverse_diagnose(&self, pose: &Pose) -> [BranchDiagnostics; 8] {
+        let arm = self.arm_branches(pose);
+        let theta = self.wrist_branches(pose, &arm);
+        std::array::from_fn(|i| {
+            let joints = self.normalized_joints(&theta[i]);
+            let Some(joints) = joints else {
+                let [theta1, theta2, theta3] = arm[i % 4];
+                let rejection = if theta1.is_finite() && !(theta2.is_finite() && theta3.is_finite()) {
+                    Rejection::AcosDomain
+                } else {
+                    Rejection::NotFinite
+                };
+                return BranchDiagnostics {
+                    joints: None,
+                    rejection: Some(rejection),
+                    translation_error: f64::NAN,
+                    angular_error: f64::NAN,
+                    wrist_singularity_distance: f64::NAN,
+                };
+            };
+
+            let check = self.forward(&joints);
+            let translation_error = (check.translation.vector - pose.translation.vector).norm();
+            let angular_error = check.rotation.angle_to(&pose.rotation);
+            let valid = translation_error <= DISTANCE_TOLERANCE && angular_error <= ANGULAR_TOLERANCE;
+            let theta5 = theta[i][4].rem_euclid(PI);
+            BranchDiagnostics {
+                joints: Some(joints),
+                rejection: if valid { None } else { Some(Rejection::ForwardMismatch) },
+                translation_error,
+                angular_error,
+                wrist_singularity_distance: theta5.min(PI - theta5),
+            }
+        })
+    }
+}
+
+impl OPWKinematics {
+    /// Why the pose has no solution (within the limits, if given), None if it has. The reach
+    /// with some other orientation is estimated by extending the arm reach by c4.
+    pub fn unreachable_cause(&self, pose: &Pose, limits: Option<&JointLimits>)
+                             -> Option<Unreachable> {
+        let solutions = self.inverse(pose);
+        if !solutions.is_empty() {
+            return match limits {
+                Some(limits) if solutions.iter().all(|s| limits.fit(s).is_none()) => {
+                    Some(Unreachable::JointLimits)
+                }
+                _ => None,
+            };
+        }
+        // If the flange position is within the reach extended by c4, some other orientation
+        // would bring the wrist center into the reach
+        self.position_cause(&pose.translation.vector, self.parameters().c4)
+            .or(Some(Unreachable::Orientation))
+    }
+
+    /// Whether the point is out of the reach of the wrist center, extended by the margin.
+    fn position_cause(&self, point: &Vector3<f64>, margin: f64) -> Option<Unreachable> {
+        let p = self.parameters();
+        let k = p.a2.hypot(p.c3);
+        let (inner, outer) = ((p.c2 - k).abs() - margin, p.c2 + k + margin);
+        let radial = point.x.hypot(point.y);
+        if radial < p.b.abs() - margin {
+            // Inside the cylinder of the b offset around the J1 axis
+            return Some(Unreachable::InnerVoid);
+        }
+        // Distance from the shoulder in front and over the head, as in the arm solution
+        let nx1 = (radial * radial - p.b * p.b).max(0.0).sqrt() - p.a1;
+        let height = point.z - p.c1;
+        let distances = [nx1.hypot(height), (nx1 + 2.0 * p.a1).hypot(height)];
+        let tolerance = DISTANCE_TOLERANCE;
+        if distances.iter().any(|s| *s >= inner - tolerance && *s <= outer + tolerance) {
+            None
+        } else if distances.iter().all(|s| *s > outer) {
+            Some(Unreachable::OutOfReach)
+        } else {
+            Some(Unreachable::InnerVoid)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_diagnose_matches_inverse() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let report = robot.inverse_diagnose(&pose);
+        let valid: Vec<Joints> = report.iter()
+            .filter(|b| b.is_valid())
+            .map(|b| b.joints.unwrap())
+            .collect();
+        assert_eq!(valid, robot.inverse(&pose));
+        // The original joint position has J5 = 0.5
+        assert!(report.iter().filter(|b| b.is_valid())
+            .any(|b| (b.wrist_singularity_distance - 0.5).abs() < 1E-6));
+    }
+
+    #[test]
+    fn test_diagnose_unreachable() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+
+        // Far out of reach: the arm branches fail in acos
+        let mut far = pose;
+        far.translation.x += 10.0;
+        assert!(robot.inverse_diagnose(&far).iter()
+            .all(|b| b.rejection == Some(Rejection::AcosDomain)));
+    }
+
+    #[test]
+    fn test_unreachable_cause() {
+        let parameters = Parameters::irb2400_10();
+        let robot = OPWKinematics::new(parameters.clone());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        assert_eq!(robot.unreachable_cause(&pose, None), None);
+
+        let mut far = pose;
+        far.translation.x += 10.0;
+        assert_eq!(robot.unreachable_cause(&far, None), Some(Unreachable::OutOfReach));
+
+        // Stretched arm with the flange turned back: the wrist center moves out of reach
+        let psi3 = parameters.a2.atan2(parameters.c3);
+        let stretched = robot.forward(&[0.0, 0.3, -psi3 + parameters.offsets[2], 0.0, 0.0, 0.0]);
+        let turned = stretched * nalgebra::UnitQuaternion::from_euler_angles(PI, 0.0, 0.0);
+        assert!(robot.inverse(&turned).is_empty());
+        assert_eq!(robot.unreachable_cause(&turned, None), Some(Unreachable::Orientation));
+
+        let limits = JointLimits::new([2.0, -PI, -PI, -PI, -PI, -PI], [2.1, PI, PI, PI, PI, PI]);
+        assert_eq!(robot.unreachable_cause(&pose, Some(&limits)), Some(Unreachable::JointLimits));
+
+        // Upper arm much longer than the forearm leaves the void around the shoulder
+        let robot = OPWKinematics::new(Parameters {
+            c1: 0.5, c2: 1.0, c3: 0.4, c4: 0.05, ..Parameters::new()
+        });
+        let void = Pose::translation(0.1, 0.0, 0.5);
+        assert_eq!(robot.unreachable_cause(&void, None), Some(Unreachable::InnerVoid));
+    }
+}
@@ -0,0 +1,204 @@
+//! Poses from position and Euler angles, and the decomposition of poses back into Euler angles.
+//! All twelve axis sequences are supported, both intrinsic (rotations about the axes of the
+//! rotating frame, like ZY'X'') and extrinsic (rotations about the fixed axes). Angles are
+//! in radians, in the order of the sequence.
+
+use nalgebra::{Rotation3, Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::Pose;
+
+/// Axis sequence of the Euler angles. Tait-Bryan sequences use all three axes, proper Euler
+/// sequences repeat the first axis.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EulerSequence {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+    XYX,
+    XZX,
+    YXY,
+    YZY,
+    ZXZ,
+    ZYZ,
+}
+
+/// If the rotations are about the axes of the rotating frame or about the fixed axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EulerFrame {
+    Intrinsic,
+    Extrinsic,
+}
+
+/// Below this sine (proper Euler) or cosine (Tait-Bryan) of the middle angle the decomposition
+/// is treated as gimbal lock: the first and third rotations are then about the same axis and
+/// the third angle is set to zero.
+const GIMBAL_LOCK_THRESHOLD: f64 = 1E-9;
+
+impl EulerSequence {
+    fn axes(self) -> [usize; 3] {
+        use EulerSequence::*;
+        match self {
+            XYZ => [0, 1, 2],
+            XZY => [0, 2, 1],
+            YXZ => [1, 0, 2],
+            YZX => [1, 2, 0],
+            ZXY => [2, 0, 1],
+            ZYX => [2, 1, 0],
+            XYX => [0, 1, 0],
+            XZX => [0, 2, 0],
+            YXY => [1, 0, 1],
+            YZY => [1, 2, 1],
+            ZXZ => [2, 0, 2],
+            ZYZ => [2, 1, 2],
+        }
+    }
+}
+
+fn axis_rotation(axis: usize, angle: f64) -> UnitQuaternion<f64> {
+    let mut v = Vector3::zeros();
+    v[axis] = 1.0;
+    UnitQuaternion::from_scaled_axis(v * angle)
+}
+
+/// Rotation from the Euler angles.
+pub fn rotation_from_euler(angles: [f64; 3], sequence: EulerSequence, frame: EulerFrame)
+                           -> UnitQuaternion<f64> {
+    let [a, b, c] = sequence.axes();
+    let r = [axis_rotation(a, angles[0]), axis_rotation(b, angles[1]), axis_rotation(c, angles[2])];
+    match frame {
+        EulerFrame::Intrinsic => r[0] * r[1] * r[2],
+        EulerFrame::Extrinsic => r[2] * r[1] * r[0],
+    }
+}
+
+/// Pose from the position and Euler angles.
+pub fn pose_from_euler(translation: [f64; 3], angles: [f64; 3], sequence: EulerSequence,
+                       frame: EulerFrame) -> Pose {
+    let [x, y, z] = translation;
+    Pose::from_parts(Translation3::new(x, y, z), rotation_from_euler(angles, sequence, frame))
+}
+
+/// Position and Euler angles, as most user interfaces show the pose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EulerPose {
+    pub translation: [f64; 3],
+    pub angles: [f64; 3],
+    pub sequence: EulerSequence,
+    pub frame: EulerFrame,
+}
+
+impl EulerPose {
+    pub fn to_pose(&self) -> Pose {
+        pose_from_euler(self.translation, self.angles, self.sequence, self.frame)
+    }
+
+    pub fn from_pose(pose: &Pose, sequence: EulerSequence, frame: EulerFrame) -> Self {
+        let (translation, angles) = euler_from_pose(pose, sequence, frame);
+        EulerPose { translation, angles, sequence, frame }
+    }
+}
+
+/// Euler angles of the rotation. The middle angle is in [0, pi] for proper Euler sequences
+/// and in [-pi/2, pi/2] for Tait-Bryan sequences, the other two in (-pi, pi].
+pub fn euler_from_rotation(rotation: &UnitQuaternion<f64>, sequence: EulerSequence,
+                           frame: EulerFrame) -> [f64; 3] {
+    let m = rotation.to_rotation_matrix();
+    match frame {
+        EulerFrame::Intrinsic => intrinsic_angles(&m, sequence.axes()),
+        EulerFrame::Extrinsic => {
+            // Extrinsic a-b-c is the same rotation as intrinsic c-b-a with the angles reversed
+            let [a, b, c] = sequence.axes();
+            let [x, y, z] = intrinsic_angles(&m, [c, b, a]);
+            [z, y, x]
+        }
+    }
+}
+
+/// Position and Euler angles of the pose.
+pub fn euler_from_pose(pose: &Pose, sequence: EulerSequence, frame: EulerFrame)
+                       -> ([f64; 3], [f64; 3]) {
+    let t = pose.translation.vector;
+    ([t.x, t.y, t.z], euler_from_rotation(&pose.rotation, sequence, frame))
+}
+
+fn intrinsic_angles(m: &Rotation3<f64>, axes: [usize; 3]) -> [f64; 3] {
+    let [a0, a1, a2] = axes;
+    let r = |i: usize, j: usize| m[(i, j)];
+    // The axis not used by the first two rotations, and the handedness of the sequence
+    let c = 3 - a0 - a1;
+    let s = if (a1 + 3 - a0) % 3 == 1 { 1.0 } else { -1.0 };
+
+    if a0 == a2 {
+        // Proper Euler: R = Ra0(alpha) Ra1(beta) Ra0(gamma)
+        let sin_beta = r(a0, a1).hypot(r(a0, c));
+        let beta = sin_beta.atan2(r(a0, a0));
+        if sin_beta < GIMBAL_LOCK_THRESHOLD {
+            [(s * r(c, a1)).atan2(r(a1, a1)), beta, 0.0]
+        } else {
+            [r(a1, a0).atan2(-s * r(c, a0)), beta, r(a0, a1).atan2(s * r(a0, c))]
+        }
+    } else {
+        // Tait-Bryan: R = Ra0(alpha) Ra1(beta) Ra2(gamma)
+        // atan2 rather than asin keeps beta accurate near +-pi/2
+        let cos_beta = r(a0, a0).hypot(r(a0, a1));
+        let beta = (s * r(a0, a2)).atan2(cos_beta);
+        if cos_beta < GIMBAL_LOCK_THRESHOLD {
+            [(s * r(a2, a1)).atan2(r(a1, a1)), beta, 0.0]
+        } else {
+            [(-s * r(a1, a2)).atan2(r(a2, a2)), beta, (-s * r(a0, a1)).atan2(r(a0, a0))]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+    use EulerSequence::*;
+
+    const SEQUENCES: [EulerSequence; 12] = [XYZ, XZY, YXZ, YZX, ZXY, ZYX, XYX, XZX, YXY, YZY, ZXZ, ZYZ];
+
+    #[test]
+    fn test_euler_round_trip() {
+        for sequence in SEQUENCES {
+            for frame in [EulerFrame::Intrinsic, EulerFrame::Extrinsic] {
+                for angles in [[0.3, 0.4, -0.5], [-2.0, 1.2, 2.5], [0.7, 0.0, 0.0],
+                    [0.7, FRAC_PI_2, 0.2], [0.7, -FRAC_PI_2, 0.2], [0.7, 3.0, -1.0]] {
+                    let rotation = rotation_from_euler(angles, sequence, frame);
+                    let restored = euler_from_rotation(&rotation, sequence, frame);
+                    let again = rotation_from_euler(restored, sequence, frame);
+                    assert!(rotation.angle_to(&again) < 1E-9, "{:?} {:?} {:?}",
+                            sequence, frame, angles);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_euler_conventions() {
+        let (a, b, c) = (0.3, -0.4, 0.5);
+        let rpy = UnitQuaternion::from_euler_angles(a, b, c);
+        // nalgebra roll-pitch-yaw is extrinsic XYZ, that is intrinsic ZYX with angles reversed
+        assert!(rpy.angle_to(&rotation_from_euler([a, b, c], XYZ, EulerFrame::Extrinsic)) < 1E-12);
+        assert!(rpy.angle_to(&rotation_from_euler([c, b, a], ZYX, EulerFrame::Intrinsic)) < 1E-12);
+
+        let angles = [a, b, c];
+        let unique = euler_from_rotation(&rotation_from_euler(angles, ZYX, EulerFrame::Intrinsic),
+                                         ZYX, EulerFrame::Intrinsic);
+        for (x, y) in angles.iter().zip(unique.iter()) {
+            assert!((x - y).abs() < 1E-12);
+        }
+
+        let pose = pose_from_euler([1.0, 2.0, 3.0], angles, ZYZ, EulerFrame::Intrinsic);
+        let (translation, restored) = euler_from_pose(&pose, ZYZ, EulerFrame::Intrinsic);
+        assert_eq!(translation, [1.0, 2.0, 3.0]);
+        assert!(pose.rotation.angle_to(&rotation_from_euler(restored, ZYZ, EulerFrame::Intrinsic))
+            < 1E-12);
+    }
+}
@@ -0,0 +1,301 @@
+//! Forward kinematics cross-check in extended (double-double, about 106 bit) precision.
+//!
+//! The regular solver verifies its solutions with the f64 forward kinematics, so the reported
+//! accuracy includes its own rounding errors. For metrology-grade applications, the check can be
+//! repeated here in double-double arithmetic: this gives the true error of the f64 solution
+//! (to about 1E-30) and the size of the rounding error of the f64 forward kinematics itself.
+
+use std::ops::{Add, Mul, Neg, Sub};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Unevaluated sum of two f64 values, hi + lo with |lo| <= ulp(hi) / 2.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+/// pi / 2 in double-double precision.
+const FRAC_PI_2: DoubleDouble = DoubleDouble { hi: std::f64::consts::FRAC_PI_2, lo: 6.123233995736766e-17 };
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+impl DoubleDouble {
+    pub const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+    pub const ONE: DoubleDouble = DoubleDouble { hi: 1.0, lo: 0.0 };
+
+    pub const fn from_f64(value: f64) -> Self {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    /// Rounds to the nearest f64.
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    fn normalized(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi, lo }
+    }
+
+    /// Division by f64.
+    fn div_f64(self, b: f64) -> Self {
+        let q1 = self.hi / b;
+        let p = q1 * b;
+        let e = q1.mul_add(b, -p);
+        let (s, e2) = two_sum(self.hi, -p);
+        let q2 = (s + (e2 - e + self.lo)) / b;
+        Self::normalized(q1, q2)
+    }
+
+    /// Sine and cosine. The argument is reduced by multiples of pi/2 and the Taylor series
+    /// is summed until the terms fall below the precision.
+    pub fn sin_cos(self) -> (Self, Self) {
+        let quadrant = (self.to_f64() / FRAC_PI_2.hi).round();
+        let r = self - FRAC_PI_2 * DoubleDouble::from_f64(quadrant);
+        let r2 = r * r;
+
+        let mut sin = r;
+        let mut cos = DoubleDouble::ONE;
+        let mut sin_term = r;
+        let mut cos_term = DoubleDouble::ONE;
+        let mut n = 1.0;
+        while sin_term.hi.abs() > 1E-34 || cos_term.hi.abs() > 1E-34 {
+            cos_term = -(cos_term * r2).div_f64(n * (n + 1.0));
+            sin_term = -(sin_term * r2).div_f64((n + 1.0) * (n + 2.0));
+            cos = cos + cos_term;
+            sin = sin + sin_term;
+            n += 2.0;
+        }
+
+        match (quadrant as i64).rem_euclid(4) {
+            0 => (sin, cos),
+            1 => (cos, -sin),
+            2 => (-sin, -cos),
+            _ => (-cos, sin),
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, b: Self) -> Self {
+        let (s, e) = two_sum(self.hi, b.hi);
+        let (t, f) = two_sum(self.lo, b.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        Self::normalized(s, e + f)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, b: Self) -> Self {
+        self + (-b)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        DoubleDouble { hi: -self.hi, lo: -self.lo }
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, b: Self) -> Self {
+        let p = self.hi * b.hi;
+        let e = self.hi.mul_add(b.hi, -p) + (self.hi * b.lo + self.lo * b.hi);
+        Self::normalized(p, e)
+    }
+}
+
+type Matrix = [[DoubleDouble; 3]; 3];
+
+/// Pose in double-double precision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtendedPose {
+    pub translation: [DoubleDouble; 3],
+    /// Rotation matrix, row major.
+    pub rotation: Matrix,
+}
+
+impl ExtendedPose {
+    /// Exact conversion of the f64 pose (the rotation matrix is computed from the quaternion
+    /// in extended precision).
+    pub fn from_pose(pose: &Pose) -> Self {
+        let d = DoubleDouble::from_f64;
+        let t = pose.translation.vector;
+        let q = pose.rotation.quaternion();
+        let (w, x, y, z) = (d(q.w), d(q.i), d(q.j), d(q.k));
+        let one = DoubleDouble::ONE;
+        let two = d(2.0);
+        ExtendedPose {
+            translation: [d(t.x), d(t.y), d(t.z)],
+            rotation: [
+                [one - two * (y * y + z * z), two * (x * y - z * w), two * (x * z + y * w)],
+                [two * (x * y + z * w), one - two * (x * x + z * z), two * (y * z - x * w)],
+                [two * (x * z - y * w), two * (y * z + x * w), one - two * (x * x + y * y)],
+            ],
+        }
+    }
+
+    /// Distance between translations and angle between rotations, computed in extended
+    /// precision and then rounded to f64.
+    pub fn difference(&self, other: &ExtendedPose) -> (f64, f64) {
+        let distance = (0..3)
+            .map(|i| (self.translation[i] - other.translation[i]).to_f64().powi(2))
+            .sum::<f64>()
+            .sqrt();
+
+        // E = A^T * B, the angle follows from its skew symmetric part and the trace.
+        let e: Matrix = std::array::from_fn(|r| std::array::from_fn(|c| {
+            (0..3).fold(DoubleDouble::ZERO, |acc, k| acc + self.rotation[k][r] * other.rotation[k][c])
+        }));
+        let half = DoubleDouble::from_f64(0.5);
+        let v = [(e[2][1] - e[1][2]) * half, (e[0][2] - e[2][0]) * half, (e[1][0] - e[0][1]) * half];
+        let sin = v.iter().map(|x| x.to_f64().powi(2)).sum::<f64>().sqrt();
+        let cos = ((e[0][0] + e[1][1] + e[2][2] - DoubleDouble::ONE) * half).to_f64();
+        (distance, sin.atan2(cos))
+    }
+}
+
+/// Result of the extended precision cross-check of one solution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtendedCheck {
+    /// Distance between the requested position and the exact forward kinematics, meters.
+    pub translation_error: f64,
+    /// Angle between the requested orientation and the exact forward kinematics, radians.
+    pub angular_error: f64,
+    /// Rounding error of the f64 forward kinematics position for these joints, meters.
+    pub f64_translation_rounding: f64,
+    /// Rounding error of the f64 forward kinematics orientation for these joints, radians.
+    pub f64_angular_rounding: f64,
+}
+
+impl OPWKinematics {
+    /// Forward kinematics in double-double precision. Joint values, offsets and parameters
+    /// are taken as exact.
+    pub fn forward_extended(&self, joints: &Joints) -> ExtendedPose {
+        let p = self.parameters();
+        let d = DoubleDouble::from_f64;
+        let mut decoupled = joints.map(d);
+        decoupled[2] = decoupled[2] - d(p.j3_coupling) * decoupled[1];
+        let q: [DoubleDouble; 6] = std::array::from_fn(|i| {
+            decoupled[i] * d(p.sign_corrections[i] as f64) - d(p.offsets[i])
+        });
+        let (s1, c1) = q[0].sin_cos();
+        let (s2, c2) = q[1].sin_cos();
+        let (s23, c23) = (q[1] + q[2]).sin_cos();
+        let (s4, c4) = q[3].sin_cos();
+        let (s5, c5) = q[4].sin_cos();
+        let (s6, c6) = q[5].sin_cos();
+
+        // k * sin(q2 + q3 + psi3) and k * cos(q2 + q3 + psi3), expanded to avoid atan2 and sqrt
+        let (a2, c3) = (d(p.a2), d(p.c3));
+        let cx1 = d(p.c2) * s2 + c3 * s23 + a2 * c23 + d(p.a1);
+        let cy1 = d(p.b);
+        let cz1 = d(p.c2) * c2 + c3 * c23 - a2 * s23;
+
+        let zero = DoubleDouble::ZERO;
+        let r_0c: Matrix = [
+            [c1 * c23, -s1, c1 * s23],
+            [s1 * c23, c1, s1 * s23],
+            [-s23, zero, c23],
+        ];
+        let r_ce: Matrix = [
+            [c4 * c5 * c6 - s4 * s6, -(c4 * c5 * s6) - s4 * c6, c4 * s5],
+            [s4 * c5 * c6 + c4 * s6, -(s4 * c5 * s6) + c4 * c6, s4 * s5],
+            [-(s5 * c6), s5 * s6, c5],
+        ];
+        let rotation: Matrix = std::array::from_fn(|r| std::array::from_fn(|c| {
+            (0..3).fold(zero, |acc, k| acc + r_0c[r][k] * r_ce[k][c])
+        }));
+
+        let c4_length = d(p.c4);
+        ExtendedPose {
+            translation: [
+                cx1 * c1 - cy1 * s1 + c4_length * rotation[0][2],
+                cx1 * s1 + cy1 * c1 + c4_length * rotation[1][2],
+                cz1 + d(p.c1) + c4_length * rotation[2][2],
+            ],
+            rotation,
+        }
+    }
+
+    /// Cross-checks the solution in extended precision against the requested pose, also
+    /// reporting how much the f64 forward kinematics deviates for these joints.
+    pub fn check_extended(&self, pose: &Pose, joints: &Joints) -> ExtendedCheck {
+        let exact = self.forward_extended(joints);
+        let (translation_error, angular_error) =
+            exact.difference(&ExtendedPose::from_pose(pose));
+        let (f64_translation_rounding, f64_angular_rounding) =
+            exact.difference(&ExtendedPose::from_pose(&self.forward(joints)));
+        ExtendedCheck { translation_error, angular_error, f64_translation_rounding, f64_angular_rounding }
+    }
+
+    /// Inverse kinematics keeping only the solutions whose exact forward kinematics is
+    /// within the given tolerances (meters and radians) from the requested pose.
+    pub fn inverse_extended(&self, pose: &Pose, distance_tolerance: f64,
+                            angular_tolerance: f64) -> Solutions {
+        self.inverse(pose).into_iter()
+            .filter(|joints| {
+                let check = self.check_extended(pose, joints);
+                check.translation_error <= distance_tolerance &&
+                    check.angular_error <= angular_tolerance
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_double_double_sin_cos() {
+        for x in [-7.0, -1.0, 0.0, 0.3, 1.2, 2.5, 4.0, 10.0] {
+            let (s, c) = DoubleDouble::from_f64(x).sin_cos();
+            assert!((s.to_f64() - x.sin()).abs() < 1E-15);
+            assert!((c.to_f64() - x.cos()).abs() < 1E-15);
+            // sin² + cos² = 1 well beyond f64 precision
+            let one = s * s + c * c - DoubleDouble::ONE;
+            assert!(one.to_f64().abs() < 1E-28, "{:?}", one);
+        }
+    }
+
+    #[test]
+    fn test_extended_check() {
+        for parameters in [Parameters::irb2400_10(), Parameters::kuka_kr6_r700_sixx()] {
+            let robot = OPWKinematics::new(parameters);
+            let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+            let pose = robot.forward(&joints);
+            let check = robot.check_extended(&pose, &joints);
+            // f64 forward kinematics is accurate to a few ulps
+            assert!(check.translation_error < 1E-14, "{:?}", check);
+            assert!(check.angular_error < 1E-14, "{:?}", check);
+            assert!((check.translation_error - check.f64_translation_rounding).abs() < 1E-20);
+
+            let solutions = robot.inverse_extended(&pose, 1E-9, 1E-9);
+            assert_eq!(solutions, robot.inverse(&pose));
+        }
+    }
+}
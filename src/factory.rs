@@ -0,0 +1,70 @@
+//! Creation of the solver at runtime, from the robot parameters and the description of the
+//! cell (mounting, keep-out zones, caching). The result is the shared trait object that can
+//! be passed between threads.
+
+use std::sync::Arc;
+use crate::cache::CachedKinematics;
+use crate::keep_out::{KeepOutKinematics, KeepOutZones};
+use crate::kinematic_traits::{Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::mounting::Base;
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Optional layers around the plain OPW solver.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KinematicsConfig {
+    /// Pose of the robot base in the world frame. If set, the solver accepts and returns
+    /// poses in the world frame.
+    pub base: Option<Pose>,
+    /// Keep-out zones. Solutions entering them are not returned.
+    pub keep_out: Option<KeepOutZones>,
+    /// Capacity of the inverse kinematics cache, see [`CachedKinematics`].
+    pub cache_capacity: Option<usize>,
+}
+
+/// Creates the solver for the robot with the given parameters and configuration.
+pub fn create_kinematics(parameters: Parameters, config: KinematicsConfig)
+                         -> Arc<dyn Kinematics + Send + Sync> {
+    let robot = OPWKinematics::new(parameters);
+    let robot: Box<dyn Kinematics + Send + Sync> = match config.keep_out {
+        Some(zones) => Box::new(KeepOutKinematics { robot, zones }),
+        None => Box::new(robot),
+    };
+    let robot: Box<dyn Kinematics + Send + Sync> = match config.base {
+        Some(base) => Box::new(Base { robot, base }),
+        None => robot,
+    };
+    match config.cache_capacity {
+        Some(capacity) => Arc::new(CachedKinematics::new(robot, capacity)),
+        None => Arc::from(robot),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mounting::Mounting;
+
+    #[test]
+    fn test_factory_shared_across_threads() {
+        let config = KinematicsConfig {
+            base: Some(Mounting::Ceiling.base_pose(0.0, 0.0, 3.0)),
+            cache_capacity: Some(16),
+            ..Default::default()
+        };
+        let robot = create_kinematics(Parameters::irb2400_10(), config);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        assert!((pose.translation.z - 3.0).abs() > 0.1);
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let robot = Arc::clone(&robot);
+            std::thread::spawn(move || robot.inverse_continuing(&pose, &joints)[0])
+        }).collect();
+        for handle in handles {
+            let solution = handle.join().unwrap();
+            assert!(solution.iter().zip(joints.iter()).all(|(a, b)| (a - b).abs() < 1E-6));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! C interface (feature `ffi`). The header include/rs_opw_kinematics.h is generated by
+//! cbindgen when building with this feature. Poses are 7 doubles: translation x, y, z and
+//! quaternion x, y, z, w. Joints are 6 doubles in radians.
+//!
+//! `opw_get_position_ik`, `opw_search_position_ik` and `opw_link_poses` follow the semantics of
+//! the MoveIt `kinematics::KinematicsBase` methods (seed state, timeout, solution callback,
+//! MoveIt error codes), so a thin C++ plugin can forward to them directly.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::time::{Duration, Instant};
+use crate::kinematic_traits::{FixedSolutions, Joints, Kinematics, Pose, MAX_SOLUTIONS};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use crate::validation::{pose_from_raw, validate_parameters};
+
+/// Maximal number of solutions returned by the inverse kinematics. The solutions buffer
+/// must hold OPW_MAX_SOLUTIONS * 6 doubles.
+pub const OPW_MAX_SOLUTIONS: usize = 9;
+const _: () = assert!(OPW_MAX_SOLUTIONS == MAX_SOLUTIONS);
+
+/// Success, as `moveit_msgs::MoveItErrorCodes::SUCCESS`.
+pub const OPW_SUCCESS: i32 = 1;
+/// Timeout expired before the callback accepted any solution, as `TIMED_OUT`.
+pub const OPW_TIMED_OUT: i32 = -6;
+/// The pose is out of reach or the callback rejected all solutions, as `NO_IK_SOLUTION`.
+pub const OPW_NO_IK_SOLUTION: i32 = -31;
+
+/// Callback checking the IK solution (like MoveIt `IKCallbackFn`): receives the user data, the
+/// pose (7 doubles) and the candidate joints (6 doubles), returns `OPW_SUCCESS` to accept
+/// the solution or any other MoveIt error code to reject it.
+pub type OpwSolutionCallback =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, pose: *const f64, joints: *const f64) -> i32>;
+
+/// Robot parameters, see the documentation of the Rust `Parameters`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct OpwParameters {
+    pub a1: f64,
+    pub a2: f64,
+    pub b: f64,
+    pub c1: f64,
+    pub c2: f64,
+    pub c3: f64,
+    pub c4: f64,
+    pub offsets: [f64; 6],
+    pub sign_corrections: [i8; 6],
+}
+
+/// Opaque handle of the robot.
+pub struct OpwRobot {
+    robot: OPWKinematics,
+}
+
+fn new_robot(parameters: Parameters) -> *mut OpwRobot {
+    Box::into_raw(Box::new(OpwRobot { robot: OPWKinematics::new(parameters) }))
+}
+
+/// Creates the robot with the given parameters. Returns NULL if the parameters are not valid
+/// (non-finite values, sign corrections other than 1 or -1, zero arm lengths). Free with
+/// `opw_robot_free`.
+///
+/// # Safety
+/// `parameters` must point to the valid `OpwParameters`.
+#[no_mangle]
+pub unsafe extern "C" fn opw_robot_new(parameters: *const OpwParameters) -> *mut OpwRobot {
+    let p = &*parameters;
+    let parameters = Parameters {
+        a1: p.a1,
+        a2: p.a2,
+        b: p.b,
+        c1: p.c1,
+        c2: p.c2,
+        c3: p.c3,
+        c4: p.c4,
+        offsets: p.offsets,
+        sign_corrections: p.sign_corrections,
+        ..Parameters::new()
+    };
+    match validate_parameters(&parameters) {
+        Ok(()) => new_robot(parameters),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Creates the robot from the built-in preset (like "irb2400_10"). Returns NULL if there is
+/// no such preset. Free with `opw_robot_free`.
+///
+/// # Safety
+/// `name` must be the valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn opw_robot_preset(name: *const c_char) -> *mut OpwRobot {
+    match CStr::from_ptr(name).to_str().ok().and_then(Parameters::preset) {
+        Some(parameters) => new_robot(parameters),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Releases the robot. NULL is ignored.
+///
+/// # Safety
+/// `robot` must be NULL or returned by `opw_robot_new` or `opw_robot_preset`, and not used
+/// after this call.
+#[no_mangle]
+pub unsafe extern "C" fn opw_robot_free(robot: *mut OpwRobot) {
+    if !robot.is_null() {
+        drop(Box::from_raw(robot));
+    }
+}
+
+/// Forward kinematics: writes the pose of the flange (7 doubles) for the joints (6 doubles).
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above.
+#[no_mangle]
+pub unsafe extern "C" fn opw_forward(robot: *const OpwRobot, joints: *const f64, pose: *mut f64) {
+    let joints: Joints = *(joints as *const Joints);
+    let result = (*robot).robot.forward(&joints);
+    let t = result.translation.vector;
+    let q = result.rotation.quaternion();
+    *(pose as *mut [f64; 7]) = [t.x, t.y, t.z, q.i, q.j, q.k, q.w];
+}
+
+/// Inverse kinematics: writes all solutions for the pose (7 doubles) into the buffer of
+/// OPW_MAX_SOLUTIONS * 6 doubles and returns their number. The pose with non-finite values or
+/// the quaternion far from unit has no solutions.
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above.
+#[no_mangle]
+pub unsafe extern "C" fn opw_inverse(robot: *const OpwRobot, pose: *const f64,
+                                     solutions: *mut f64) -> usize {
+    let Some(pose) = read_pose(pose) else { return 0 };
+    let result = (*robot).robot.inverse_fixed(&pose);
+    write_solutions(&result, solutions)
+}
+
+/// Inverse kinematics for the trajectory: as `opw_inverse`, but handles the wrist singularity
+/// using the previous joint position (6 doubles) and returns the closest solutions first.
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above.
+#[no_mangle]
+pub unsafe extern "C" fn opw_inverse_continuing(robot: *const OpwRobot, pose: *const f64,
+                                                previous: *const f64, solutions: *mut f64) -> usize {
+    let previous: Joints = *(previous as *const Joints);
+    let Some(pose) = read_pose(pose) else { return 0 };
+    let result = (*robot).robot.inverse_continuing_fixed(&pose, &previous);
+    write_solutions(&result, solutions)
+}
+
+/// The solution closest to the seed state (6 doubles), written into `solution` (6 doubles).
+/// The wrist singularity is resolved keeping J4 and J6 near the seed. Returns `OPW_SUCCESS`
+/// or `OPW_NO_IK_SOLUTION`.
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above.
+#[no_mangle]
+pub unsafe extern "C" fn opw_get_position_ik(robot: *const OpwRobot, pose: *const f64,
+                                             seed: *const f64, solution: *mut f64) -> i32 {
+    opw_search_position_ik(robot, pose, seed, 0.0, None, std::ptr::null_mut(), solution)
+}
+
+/// Searches for the solution accepted by the callback, trying the solutions in the order of
+/// the distance from the seed state (6 doubles). Without the callback, the closest solution is
+/// accepted. The accepted solution is written into `solution` (6 doubles). The timeout
+/// (seconds; 0, negative or not finite for no limit) is checked between the callback calls.
+/// Returns `OPW_SUCCESS`, `OPW_TIMED_OUT` or `OPW_NO_IK_SOLUTION` (also for the invalid pose,
+/// as `opw_inverse`).
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above. `user_data` is passed to the
+/// callback as is.
+#[no_mangle]
+pub unsafe extern "C" fn opw_search_position_ik(robot: *const OpwRobot, pose: *const f64,
+                                                seed: *const f64, timeout: f64,
+                                                callback: OpwSolutionCallback,
+                                                user_data: *mut c_void,
+                                                solution: *mut f64) -> i32 {
+    let deadline = Duration::try_from_secs_f64(timeout).ok().filter(|t| !t.is_zero())
+        .and_then(|timeout| Instant::now().checked_add(timeout));
+    let seed: Joints = *(seed as *const Joints);
+    let Some(target) = read_pose(pose) else { return OPW_NO_IK_SOLUTION };
+    let candidates = (*robot).robot.inverse_continuing_fixed(&target, &seed);
+    for candidate in &candidates {
+        if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return OPW_TIMED_OUT;
+        }
+        let accepted = match callback {
+            Some(callback) => callback(user_data, pose, candidate.as_ptr()) == OPW_SUCCESS,
+            None => true,
+        };
+        if accepted {
+            *(solution as *mut Joints) = *candidate;
+            return OPW_SUCCESS;
+        }
+    }
+    OPW_NO_IK_SOLUTION
+}
+
+/// Poses of the links 1 to 6 (6 * 7 doubles) for the joints (6 doubles), for the forward
+/// kinematics of the intermediate links. The last link is at the wrist center, use
+/// `opw_forward` for the flange.
+///
+/// # Safety
+/// All pointers must be valid, with the sizes given above.
+#[no_mangle]
+pub unsafe extern "C" fn opw_link_poses(robot: *const OpwRobot, joints: *const f64, poses: *mut f64) {
+    let joints: Joints = *(joints as *const Joints);
+    let out = std::slice::from_raw_parts_mut(poses as *mut [f64; 7], 6);
+    for (pose, link) in out.iter_mut().zip((*robot).robot.link_poses(&joints)) {
+        let t = link.translation.vector;
+        let q = link.rotation.quaternion();
+        *pose = [t.x, t.y, t.z, q.i, q.j, q.k, q.w];
+    }
+}
+
+/// The pose validated by `pose_from_raw`, None if it is not valid.
+unsafe fn read_pose(pose: *const f64) -> Option<Pose> {
+    let [x, y, z, qx, qy, qz, qw] = *(pose as *const [f64; 7]);
+    pose_from_raw([x, y, z], [qx, qy, qz, qw]).ok()
+}
+
+unsafe fn write_solutions(result: &FixedSolutions, solutions: *mut f64) -> usize {
+    let out = std::slice::from_raw_parts_mut(solutions as *mut Joints, MAX_SOLUTIONS);
+    out[..result.len()].copy_from_slice(result);
+    result.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_interface() {
+        unsafe {
+            let robot = opw_robot_preset(c"irb2400_10".as_ptr());
+            assert!(!robot.is_null());
+            let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+            let mut pose = [0.0; 7];
+            opw_forward(robot, joints.as_ptr(), pose.as_mut_ptr());
+
+            let mut solutions = [0.0; OPW_MAX_SOLUTIONS * 6];
+            let n = opw_inverse_continuing(robot, pose.as_ptr(), joints.as_ptr(),
+                                           solutions.as_mut_ptr());
+            assert!(n > 0);
+            for (a, b) in solutions[..6].iter().zip(joints.iter()) {
+                assert!((a - b).abs() < 1E-6);
+            }
+            assert_eq!(opw_inverse(robot, pose.as_ptr(), solutions.as_mut_ptr()), n);
+
+            // The zero quaternion is not a rotation
+            let invalid = [1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+            assert_eq!(opw_inverse(robot, invalid.as_ptr(), solutions.as_mut_ptr()), 0);
+            assert_eq!(opw_inverse_continuing(robot, invalid.as_ptr(), joints.as_ptr(),
+                                              solutions.as_mut_ptr()), 0);
+            assert_eq!(opw_get_position_ik(robot, invalid.as_ptr(), joints.as_ptr(),
+                                           solutions.as_mut_ptr()), OPW_NO_IK_SOLUTION);
+            opw_robot_free(robot);
+
+            assert!(opw_robot_preset(c"unknown".as_ptr()).is_null());
+            let mut parameters = OpwParameters {
+                a1: 0.1, a2: -0.135, b: 0.0, c1: 0.615, c2: 0.705, c3: 0.755, c4: 0.085,
+                offsets: [0.0; 6], sign_corrections: [1; 6],
+            };
+            let robot = opw_robot_new(&parameters);
+            assert!(!robot.is_null());
+            opw_robot_free(robot);
+            parameters.sign_corrections[2] = 0;
+            assert!(opw_robot_new(&parameters).is_null());
+        }
+    }
+
+    unsafe extern "C" fn reject_first(user_data: *mut c_void, _pose: *const f64,
+                                      _joints: *const f64) -> i32 {
+        let calls = &mut *(user_data as *mut usize);
+        *calls += 1;
+        if *calls == 1 { OPW_NO_IK_SOLUTION } else { OPW_SUCCESS }
+    }
+
+    #[test]
+    fn test_moveit_semantics() {
+        unsafe {
+            let robot = opw_robot_preset(c"irb2400_10".as_ptr());
+            let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+            let mut pose = [0.0; 7];
+            opw_forward(robot, joints.as_ptr(), pose.as_mut_ptr());
+
+            let mut closest = [0.0; 6];
+            assert_eq!(opw_get_position_ik(robot, pose.as_ptr(), joints.as_ptr(),
+                                           closest.as_mut_ptr()), OPW_SUCCESS);
+            assert!(closest.iter().zip(joints.iter()).all(|(a, b)| (a - b).abs() < 1E-6));
+
+            // The callback rejects the closest solution, so the next one is returned
+            let mut calls = 0usize;
+            let mut second = [0.0; 6];
+            assert_eq!(opw_search_position_ik(robot, pose.as_ptr(), joints.as_ptr(), 1.0,
+                                              Some(reject_first),
+                                              &mut calls as *mut usize as *mut c_void,
+                                              second.as_mut_ptr()), OPW_SUCCESS);
+            assert_eq!(calls, 2);
+            assert_ne!(second, closest);
+
+            let far = [10.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+            assert_eq!(opw_get_position_ik(robot, far.as_ptr(), joints.as_ptr(),
+                                           second.as_mut_ptr()), OPW_NO_IK_SOLUTION);
+
+            let mut links = [0.0; 42];
+            opw_link_poses(robot, joints.as_ptr(), links.as_mut_ptr());
+            let wrist = (*robot).robot.link_poses(&joints)[5].translation.vector;
+            assert_eq!(links[35..38], [wrist.x, wrist.y, wrist.z]);
+            opw_robot_free(robot);
+        }
+    }
+
+    #[test]
+    fn test_header_is_current() {
+        // Copy the header from OUT_DIR to include/ after changing the interface
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/rs_opw_kinematics.h"));
+        assert!(generated == include_str!("../include/rs_opw_kinematics.h"),
+                "include/rs_opw_kinematics.h is not the header generated from src/ffi.rs");
+    }
+}
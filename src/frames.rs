@@ -0,0 +1,169 @@
+//! Named coordinate frames, as the user and object frames of the robot controllers. Each frame
+//! is given by its pose in the parent frame; the tree is rooted in the world frame and the
+//! robot base is the frame `base` in it. Moving a frame (a user frame recalibrated, a conveyor
+//! object advanced) moves all frames defined on it, and the IK targets can be given in any
+//! of the frames.
+
+use std::collections::HashMap;
+use thiserror::Error;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+
+/// Root frame.
+pub const WORLD: &str = "world";
+/// Frame of the robot base, the frame the kinematics work in.
+pub const BASE: &str = "base";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("unknown frame {0}")]
+    UnknownFrame(String),
+    #[error("frame {0} already exists")]
+    DuplicateFrame(String),
+    #[error("the world frame cannot be moved")]
+    World,
+}
+
+/// Tree of the named frames.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frames {
+    /// Parent and the pose in it of each frame but the world.
+    frames: HashMap<String, (String, Pose)>,
+}
+
+impl Frames {
+    /// Frames with the robot base at the given pose in the world.
+    pub fn new(base: Pose) -> Self {
+        let frames = HashMap::from([(BASE.to_string(), (WORLD.to_string(), base))]);
+        Frames { frames }
+    }
+
+    /// Adds the frame with the given pose in the (existing) parent frame.
+    pub fn add(&mut self, name: &str, parent: &str, pose: Pose) -> Result<(), FrameError> {
+        if self.contains(name) {
+            return Err(FrameError::DuplicateFrame(name.to_string()));
+        }
+        if !self.contains(parent) {
+            return Err(FrameError::UnknownFrame(parent.to_string()));
+        }
+        self.frames.insert(name.to_string(), (parent.to_string(), pose));
+        Ok(())
+    }
+
+    /// Sets the pose of the frame in its parent, the frames defined on it move along.
+    pub fn set_pose(&mut self, name: &str, pose: Pose) -> Result<(), FrameError> {
+        if name == WORLD {
+            return Err(FrameError::World);
+        }
+        let frame = self.frames.get_mut(name)
+            .ok_or_else(|| FrameError::UnknownFrame(name.to_string()))?;
+        frame.1 = pose;
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name == WORLD || self.frames.contains_key(name)
+    }
+
+    /// Parent of the frame, None for the world.
+    pub fn parent(&self, name: &str) -> Result<Option<&str>, FrameError> {
+        if name == WORLD {
+            return Ok(None);
+        }
+        self.frames.get(name).map(|(parent, _)| Some(parent.as_str()))
+            .ok_or_else(|| FrameError::UnknownFrame(name.to_string()))
+    }
+
+    /// Pose of the frame in the world.
+    pub fn in_world(&self, name: &str) -> Result<Pose, FrameError> {
+        let mut pose = Pose::identity();
+        let mut current = name;
+        while current != WORLD {
+            let (parent, local) = self.frames.get(current)
+                .ok_or_else(|| FrameError::UnknownFrame(current.to_string()))?;
+            pose = local * pose;
+            current = parent;
+        }
+        Ok(pose)
+    }
+
+    /// Pose of the frame `from` in the frame `to`.
+    pub fn transform(&self, from: &str, to: &str) -> Result<Pose, FrameError> {
+        Ok(self.in_world(to)?.inverse() * self.in_world(from)?)
+    }
+
+    /// The pose given in the frame `from`, expressed in the frame `to`.
+    pub fn express(&self, pose: &Pose, from: &str, to: &str) -> Result<Pose, FrameError> {
+        Ok(self.transform(from, to)? * pose)
+    }
+
+    /// Inverse kinematics of the robot at the base frame, for the pose given in the frame.
+    pub fn inverse_in(&self, robot: &impl Kinematics, pose: &Pose, frame: &str)
+                      -> Result<Solutions, FrameError> {
+        Ok(robot.inverse(&self.express(pose, frame, BASE)?))
+    }
+
+    /// Inverse kinematics continuing from the previous joints, for the pose given in the frame.
+    pub fn inverse_continuing_in(&self, robot: &impl Kinematics, pose: &Pose, previous: &Joints,
+                                 frame: &str) -> Result<Solutions, FrameError> {
+        Ok(robot.inverse_continuing(&self.express(pose, frame, BASE)?, previous))
+    }
+
+    /// Forward kinematics of the robot at the base frame, expressed in the frame.
+    pub fn forward_in(&self, robot: &impl Kinematics, joints: &Joints, frame: &str)
+                      -> Result<Pose, FrameError> {
+        self.express(&robot.forward(joints), BASE, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Translation3, UnitQuaternion};
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_frames() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let base = Pose::from_parts(Translation3::new(1.0, 2.0, 0.5),
+                                    UnitQuaternion::from_euler_angles(0.0, 0.0, 0.3));
+        let table = Pose::from_parts(Translation3::new(2.0, 2.0, 0.8),
+                                     UnitQuaternion::from_euler_angles(0.0, 0.0, -0.2));
+        let part = Pose::from_parts(Translation3::new(0.1, 0.05, 0.02),
+                                    UnitQuaternion::from_euler_angles(0.0, 0.0, 1.0));
+        let mut frames = Frames::new(base);
+        frames.add("table", WORLD, table).unwrap();
+        frames.add("part", "table", part).unwrap();
+        assert_eq!(frames.parent("part"), Ok(Some("table")));
+        assert_eq!(frames.parent(WORLD), Ok(None));
+
+        let in_world = frames.in_world("part").unwrap();
+        assert!(compare_poses(&in_world, &(table * part), 1E-12, 1E-12).is_ok());
+        let in_base = frames.transform("part", BASE).unwrap();
+        assert!(compare_poses(&in_base, &(base.inverse() * table * part), 1E-12, 1E-12).is_ok());
+
+        // Target above the part, pointing down
+        let target = Pose::from_parts(Translation3::new(0.0, 0.0, 0.1),
+                                      UnitQuaternion::from_euler_angles(std::f64::consts::PI,
+                                                                        0.0, 0.0));
+        let solutions = frames.inverse_in(&robot, &target, "part").unwrap();
+        assert!(!solutions.is_empty());
+        for joints in &solutions {
+            let reached = frames.forward_in(&robot, joints, "part").unwrap();
+            assert!(compare_poses(&reached, &target, 1E-9, 1E-9).is_ok());
+        }
+
+        // Moving the table moves the part
+        frames.set_pose("table", Pose::translation(0.0, 0.1, 0.0) * table).unwrap();
+        let moved = frames.in_world("part").unwrap();
+        assert!((moved.translation.vector - in_world.translation.vector
+            - nalgebra::Vector3::y() * 0.1).norm() < 1E-12);
+
+        assert_eq!(frames.add("part", BASE, part), Err(FrameError::DuplicateFrame("part".into())));
+        assert_eq!(frames.add("tray", "shelf", part), Err(FrameError::UnknownFrame("shelf".into())));
+        assert_eq!(frames.set_pose(WORLD, part), Err(FrameError::World));
+        assert_eq!(frames.in_world("tray"), Err(FrameError::UnknownFrame("tray".into())));
+    }
+}
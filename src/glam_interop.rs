@@ -0,0 +1,58 @@
+//! Conversions of poses from and to [glam](https://docs.rs/glam) types (feature `glam`), for game
+//! engines and visualization tools that do not use nalgebra. glam `Mat4`, `Quat` and `Vec3`
+//! are single precision, so the converted poses are accurate to about 1e-7 relative.
+
+use glam::{Mat4, Quat, Vec3};
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use crate::kinematic_traits::Pose;
+
+/// Pose as the translation vector and rotation quaternion.
+pub fn pose_to_glam_parts(pose: &Pose) -> (Vec3, Quat) {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    (Vec3::new(t.x as f32, t.y as f32, t.z as f32),
+     Quat::from_xyzw(q.i as f32, q.j as f32, q.k as f32, q.w as f32))
+}
+
+/// Pose from the translation vector and rotation quaternion. The quaternion is normalized.
+pub fn pose_from_glam_parts(translation: Vec3, rotation: Quat) -> Pose {
+    Pose::from_parts(
+        Translation3::new(translation.x as f64, translation.y as f64, translation.z as f64),
+        UnitQuaternion::from_quaternion(Quaternion::new(
+            rotation.w as f64, rotation.x as f64, rotation.y as f64, rotation.z as f64)))
+}
+
+/// Pose as the homogeneous transformation matrix.
+pub fn pose_to_glam(pose: &Pose) -> Mat4 {
+    let (translation, rotation) = pose_to_glam_parts(pose);
+    Mat4::from_rotation_translation(rotation, translation)
+}
+
+/// Pose from the homogeneous transformation matrix without scaling.
+pub fn pose_from_glam(matrix: &Mat4) -> Pose {
+    let (_, rotation, translation) = matrix.to_scale_rotation_translation();
+    pose_from_glam_parts(translation, rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::Kinematics;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_glam_round_trip() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+
+        let (translation, rotation) = pose_to_glam_parts(&pose);
+        let from_parts = pose_from_glam_parts(translation, rotation);
+        let from_matrix = pose_from_glam(&pose_to_glam(&pose));
+
+        for restored in [from_parts, from_matrix] {
+            assert!((restored.translation.vector - pose.translation.vector).norm() < 1E-5);
+            assert!(restored.rotation.angle_to(&pose.rotation) < 1E-5);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Hand-eye calibration of the camera mounted on the flange: from the flange poses (as given
+//! by the forward kinematics) and the poses of the calibration target seen by the camera at
+//! the same moments, finds the camera pose in the flange frame. The target stays fixed, so
+//! for any two stations i and j, A X = X B with A = F_j⁻¹ F_i the flange motion and
+//! B = C_j C_i⁻¹ the camera motion. The rotation is the quaternion least squares solution
+//! over all pairs, the translation the linear least squares solution given the rotation.
+//!
+//! For the camera fixed in the cell looking at the target on the flange, pass the inverted
+//! flange poses; the result is then the camera pose in the base frame, inverted.
+
+use nalgebra::{Matrix3, Matrix4, Quaternion, SymmetricEigen, Translation3, UnitQuaternion,
+               Vector3, Vector4};
+use thiserror::Error;
+use crate::kinematic_traits::Pose;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandEyeError {
+    #[error("{flange} flange poses do not pair with {camera} camera poses")]
+    LengthMismatch { flange: usize, camera: usize },
+    #[error("at least 3 stations are needed, {0} given")]
+    TooFewStations(usize),
+    #[error("the flange rotations between the stations do not span two different axes")]
+    Degenerate,
+}
+
+/// Result of the calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HandEye {
+    /// Camera pose in the flange frame.
+    pub camera: Pose,
+    /// Root mean square of the translation mismatch of A X and X B over the station pairs,
+    /// meters.
+    pub translation_residual: f64,
+    /// Root mean square of the rotation mismatch of A X and X B over the station pairs,
+    /// radians.
+    pub rotation_residual: f64,
+}
+
+/// Camera pose in the flange frame from the flange poses in the base frame and the target
+/// poses in the camera frame, taken at the same stations. The stations should rotate the
+/// flange about at least two different axes, by large angles for the best accuracy.
+pub fn calibrate_hand_eye(flange: &[Pose], target_in_camera: &[Pose])
+                          -> Result<HandEye, HandEyeError> {
+    if flange.len() != target_in_camera.len() {
+        return Err(HandEyeError::LengthMismatch {
+            flange: flange.len(),
+            camera: target_in_camera.len(),
+        });
+    }
+    if flange.len() < 3 {
+        return Err(HandEyeError::TooFewStations(flange.len()));
+    }
+    let motions: Vec<(Pose, Pose)> = (0..flange.len())
+        .flat_map(|i| (i + 1..flange.len()).map(move |j| (i, j)))
+        .map(|(i, j)| (flange[j].inverse() * flange[i],
+                       target_in_camera[j] * target_in_camera[i].inverse()))
+        .collect();
+
+    // Rotation: q_A q_X - q_X q_B = (L(q_A) - R(q_B)) q_X = 0 over all pairs
+    let mut normal = Matrix4::zeros();
+    for (a, b) in &motions {
+        let m = left(&positive(&a.rotation)) - right(&positive(&b.rotation));
+        normal += m.transpose() * m;
+    }
+    let eigen = SymmetricEigen::new(normal);
+    let mut order = [0, 1, 2, 3];
+    order.sort_by(|&i, &j| eigen.eigenvalues[i].total_cmp(&eigen.eigenvalues[j]));
+    // A single rotation axis leaves the rotation about it undetermined (two zero eigenvalues)
+    if eigen.eigenvalues[order[1]] <= 1E-10 * normal.trace() {
+        return Err(HandEyeError::Degenerate);
+    }
+    let q: Vector4<f64> = eigen.eigenvectors.column(order[0]).into();
+    let rotation = UnitQuaternion::from_quaternion(Quaternion::new(q[0], q[1], q[2], q[3]));
+
+    // Translation: (R_A - I) t_X = R_X t_B - t_A
+    let (mut lhs, mut rhs) = (Matrix3::zeros(), Vector3::zeros());
+    for (a, b) in &motions {
+        let m = a.rotation.to_rotation_matrix().into_inner() - Matrix3::identity();
+        lhs += m.transpose() * m;
+        rhs += m.transpose() * (rotation * b.translation.vector - a.translation.vector);
+    }
+    let translation = lhs.try_inverse().ok_or(HandEyeError::Degenerate)? * rhs;
+    let camera = Pose::from_parts(Translation3::from(translation), rotation);
+
+    let (mut translation_sq, mut rotation_sq) = (0.0, 0.0);
+    for (a, b) in &motions {
+        let (ax, xb) = (a * camera, camera * b);
+        translation_sq += (ax.translation.vector - xb.translation.vector).norm_squared();
+        rotation_sq += ax.rotation.angle_to(&xb.rotation).powi(2);
+    }
+    let n = motions.len() as f64;
+    Ok(HandEye {
+        camera,
+        translation_residual: (translation_sq / n).sqrt(),
+        rotation_residual: (rotation_sq / n).sqrt(),
+    })
+}
+
+/// Quaternion (w, x, y, z) with the non-negative w, so the quaternions of the similar
+/// rotations A and B have the same sign.
+fn positive(q: &UnitQuaternion<f64>) -> Vector4<f64> {
+    let v = Vector4::new(q.w, q.i, q.j, q.k);
+    if q.w < 0.0 { -v } else { v }
+}
+
+/// Matrix of the left multiplication by q: q p = L(q) p.
+fn left(q: &Vector4<f64>) -> Matrix4<f64> {
+    let (w, x, y, z) = (q[0], q[1], q[2], q[3]);
+    Matrix4::new(w, -x, -y, -z,
+                 x, w, -z, y,
+                 y, z, w, -x,
+                 z, -y, x, w)
+}
+
+/// Matrix of the right multiplication by q: p q = R(q) p.
+fn right(q: &Vector4<f64>) -> Matrix4<f64> {
+    let (w, x, y, z) = (q[0], q[1], q[2], q[3]);
+    Matrix4::new(w, -x, -y, -z,
+                 x, w, z, -y,
+                 y, -z, w, x,
+                 z, y, -x, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::{Joints, Kinematics};
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_hand_eye() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let camera = Pose::from_parts(Translation3::new(0.05, -0.02, 0.08),
+                                      UnitQuaternion::from_euler_angles(0.1, -0.3, 1.2));
+        let target = Pose::from_parts(Translation3::new(1.2, 0.1, 0.0),
+                                      UnitQuaternion::from_euler_angles(3.1, 0.0, 0.2));
+        let stations: [Joints; 5] = [
+            [0.0, 0.2, 0.1, 0.0, 0.6, 0.0],
+            [0.2, 0.1, 0.2, 0.3, 0.5, -0.4],
+            [-0.2, 0.3, 0.0, -0.4, 0.8, 0.5],
+            [0.1, 0.0, 0.3, 0.6, 0.4, 1.0],
+            [-0.1, 0.2, 0.2, -0.2, 1.0, -0.8],
+        ];
+        let flange: Vec<Pose> = stations.iter().map(|j| robot.forward(j)).collect();
+        let seen: Vec<Pose> = flange.iter().map(|f| (f * camera).inverse() * target).collect();
+
+        let result = calibrate_hand_eye(&flange, &seen).unwrap();
+        assert!(compare_poses(&result.camera, &camera, 1E-9, 1E-9).is_ok());
+        assert!(result.translation_residual < 1E-9 && result.rotation_residual < 1E-9);
+
+        // The noise shows in the residuals and slightly in the result
+        let noisy: Vec<Pose> = seen.iter().enumerate()
+            .map(|(i, c)| Pose::translation(0.0, 0.0, 1E-4 * (i as f64 - 2.0)) * c).collect();
+        let result = calibrate_hand_eye(&flange, &noisy).unwrap();
+        assert!(result.translation_residual > 1E-5);
+        assert!(compare_poses(&result.camera, &camera, 1E-3, 1E-2).is_ok());
+
+        // Turning J6 only rotates about one axis
+        let flange: Vec<Pose> = (0..4).map(|i| robot.forward(&[0.0, 0.2, 0.1, 0.0, 0.6,
+            0.5 * i as f64])).collect();
+        let seen: Vec<Pose> = flange.iter().map(|f| (f * camera).inverse() * target).collect();
+        assert_eq!(calibrate_hand_eye(&flange, &seen), Err(HandEyeError::Degenerate));
+        assert_eq!(calibrate_hand_eye(&flange[..2], &seen[..2]),
+                   Err(HandEyeError::TooFewStations(2)));
+        assert_eq!(calibrate_hand_eye(&flange, &seen[..3]),
+                   Err(HandEyeError::LengthMismatch { flange: 4, camera: 3 }));
+    }
+}
@@ -0,0 +1,77 @@
+//! Interpolation between poses: the translation is interpolated linearly and the rotation
+//! with the spherical linear interpolation (slerp) along the shorter arc. The quaternions of
+//! the results stay on the hemisphere of the start pose, so the quaternion signs of the
+//! consecutive samples are continuous (no jumps between q and -q when exported).
+
+use nalgebra::{Quaternion, UnitQuaternion};
+use crate::kinematic_traits::Pose;
+
+/// Pose at `t` between `a` (t = 0) and `b` (t = 1).
+pub fn interpolate(a: &Pose, b: &Pose, t: f64) -> Pose {
+    let translation = a.translation.vector.lerp(&b.translation.vector, t);
+    Pose::from_parts(translation.into(), slerp(&a.rotation, &b.rotation, t))
+}
+
+/// `n` poses evenly spaced between `a` and `b`, neither of them included.
+pub fn intermediate_poses(a: &Pose, b: &Pose, n: usize) -> Vec<Pose> {
+    (1..=n).map(|i| interpolate(a, b, i as f64 / (n + 1) as f64)).collect()
+}
+
+/// Slerp that, unlike `UnitQuaternion::slerp`, does not panic on the rotations 180 degrees
+/// apart and keeps the sign of `a`.
+fn slerp(a: &UnitQuaternion<f64>, b: &UnitQuaternion<f64>, t: f64) -> UnitQuaternion<f64> {
+    let qa = a.coords;
+    let mut qb = b.coords;
+    let mut dot = qa.dot(&qb);
+    if dot < 0.0 {
+        qb = -qb;
+        dot = -dot;
+    }
+    if dot > 1.0 - 1E-9 {
+        // Nearly the same rotation, where sin(theta) below vanishes
+        return UnitQuaternion::new_normalize(Quaternion::from(qa.lerp(&qb, t)));
+    }
+    let theta = dot.acos();
+    let q = (qa * ((1.0 - t) * theta).sin() + qb * (t * theta).sin()) / theta.sin();
+    UnitQuaternion::new_normalize(Quaternion::from(q))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use nalgebra::{Translation3, Vector3};
+    use super::*;
+
+    fn pose(x: f64, angle: f64) -> Pose {
+        Pose::from_parts(Translation3::new(x, 0.0, 1.0),
+                         UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle))
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let (a, b) = (pose(0.0, 0.0), pose(1.0, PI / 2.0));
+        let middle = interpolate(&a, &b, 0.5);
+        assert!((middle.translation.x - 0.5).abs() < 1E-12);
+        assert!((middle.rotation.angle() - PI / 4.0).abs() < 1E-12);
+        assert!(interpolate(&a, &b, 1.0).rotation.angle_to(&b.rotation) < 1E-9);
+
+        // Shorter arc across +-PI, 180 degrees apart does not panic
+        let across = interpolate(&pose(0.0, 0.9 * PI), &pose(0.0, -0.9 * PI), 0.5);
+        assert!(across.rotation.angle_to(&pose(0.0, PI).rotation) < 1E-9);
+        let half_turn = interpolate(&a, &pose(0.0, PI), 0.5);
+        assert!((half_turn.rotation.angle() - PI / 2.0).abs() < 1E-9);
+    }
+
+    #[test]
+    fn test_intermediate_poses() {
+        let (a, b) = (pose(0.0, 0.0), pose(0.0, 1.9 * PI));
+        let poses = intermediate_poses(&a, &b, 9);
+        assert_eq!(poses.len(), 9);
+        let mut previous = a.rotation;
+        for pose in poses.iter().chain([&b]) {
+            assert!(previous.coords.dot(&pose.rotation.coords) > 0.0 || pose == &b);
+            assert!(previous.angle_to(&pose.rotation) < 0.02 * PI);
+            previous = pose.rotation;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! `IntoPose` conversion for the pose representations the callers usually have at hand, and
+//! the inverse kinematics methods accepting any of them:
+//! ```
+//! use nalgebra::{UnitQuaternion, Vector3};
+//! use rs_opw_kinematics::euler::{EulerFrame, EulerPose, EulerSequence};
+//! use rs_opw_kinematics::into_pose::PoseKinematics;
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//! use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+//!
+//! let robot = OPWKinematics::new(Parameters::irb2400_10());
+//! let down = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f64::consts::PI);
+//! let a = robot.inverse_from((Vector3::new(0.8, 0.0, 0.6), down)).unwrap();
+//! let b = robot.inverse_from(EulerPose {
+//!     translation: [0.8, 0.0, 0.6],
+//!     angles: [0.0, std::f64::consts::PI, 0.0],
+//!     sequence: EulerSequence::XYZ,
+//!     frame: EulerFrame::Intrinsic,
+//! }).unwrap();
+//! assert_eq!(a.len(), b.len());
+//! ```
+//! The poses are validated as by [`crate::validation::validate_pose`].
+//!
+//! The methods of `Kinematics` deliberately keep taking `&Pose`: generic methods would make
+//! the trait unusable as `dyn Kinematics`, so the conversions are in this extension trait.
+
+use nalgebra::{Matrix4, Translation3, UnitQuaternion, Vector3};
+use crate::euler::EulerPose;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::pose_formats::{pose_from_matrix, MatrixLayout};
+use crate::validation::{validate_pose, PoseError, QUATERNION_NORM_TOLERANCE};
+
+/// Conversion into the pose. Fails if the value does not describe the valid pose.
+pub trait IntoPose {
+    fn into_pose(self) -> Result<Pose, PoseError>;
+}
+
+impl IntoPose for Pose {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(self)
+    }
+}
+
+impl IntoPose for &Pose {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(*self)
+    }
+}
+
+impl IntoPose for (Translation3<f64>, UnitQuaternion<f64>) {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(Pose::from_parts(self.0, self.1))
+    }
+}
+
+impl IntoPose for (Vector3<f64>, UnitQuaternion<f64>) {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(Pose::from_parts(Translation3::from(self.0), self.1))
+    }
+}
+
+impl IntoPose for ([f64; 3], UnitQuaternion<f64>) {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(Pose::from_parts(Translation3::from(Vector3::from(self.0)), self.1))
+    }
+}
+
+/// Homogeneous transformation matrix.
+impl IntoPose for Matrix4<f64> {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        let rows: [[f64; 4]; 4] = std::array::from_fn(|r| std::array::from_fn(|c| self[(r, c)]));
+        pose_from_matrix(&rows, MatrixLayout::RowMajor)
+    }
+}
+
+/// Homogeneous transformation matrix with the explicit layout.
+impl IntoPose for ([[f64; 4]; 4], MatrixLayout) {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        pose_from_matrix(&self.0, self.1)
+    }
+}
+
+impl IntoPose for EulerPose {
+    fn into_pose(self) -> Result<Pose, PoseError> {
+        Ok(self.to_pose())
+    }
+}
+
+/// Inverse kinematics accepting anything that converts into the pose.
+/// Implemented for all solvers.
+pub trait PoseKinematics: Kinematics {
+    fn inverse_from(&self, pose: impl IntoPose) -> Result<Solutions, PoseError> {
+        let pose = validate_pose(&pose.into_pose()?, QUATERNION_NORM_TOLERANCE)?;
+        Ok(self.inverse(&pose))
+    }
+
+    fn inverse_continuing_from(&self, pose: impl IntoPose, previous: &Joints)
+                               -> Result<Solutions, PoseError> {
+        let pose = validate_pose(&pose.into_pose()?, QUATERNION_NORM_TOLERANCE)?;
+        Ok(self.inverse_continuing(&pose, previous))
+    }
+}
+
+impl<K: Kinematics + ?Sized> PoseKinematics for K {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_pose_inputs() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let t = pose.translation.vector;
+        let expected = robot.inverse_continuing(&pose, &joints);
+
+        let inputs = [
+            pose.into_pose(),
+            (&pose).into_pose(),
+            (pose.translation, pose.rotation).into_pose(),
+            (t, pose.rotation).into_pose(),
+            ([t.x, t.y, t.z], pose.rotation).into_pose(),
+            pose.to_homogeneous().into_pose(),
+            (crate::pose_formats::pose_to_matrix(&pose, MatrixLayout::ColumnMajor),
+             MatrixLayout::ColumnMajor).into_pose(),
+        ];
+        for input in inputs {
+            let solutions = robot.inverse_continuing_from(input.unwrap(), &joints).unwrap();
+            assert_eq!(solutions.len(), expected.len());
+            for (a, b) in solutions[0].iter().zip(joints.iter()) {
+                assert!((a - b).abs() < 1E-6);
+            }
+        }
+
+        let mut broken = pose.to_homogeneous();
+        broken[(0, 3)] = f64::NAN;
+        assert_eq!(robot.inverse_from(broken), Err(PoseError::NotFinite));
+    }
+}
@@ -0,0 +1,305 @@
+//! Geometric Jacobian of the robot. Rows are the linear (vx, vy, vz) and angular (wx, wy, wz)
+//! velocities in the base frame, columns the joints J1 to J6, with the sign corrections
+//! applied (so the Jacobian is with respect to the joint values the solver uses).
+//!
+//! The Jacobian also propagates the joint errors (encoder noise or resolution) into the
+//! uncertainty of the TCP, for comparing the repeatability of the candidate solutions.
+//!
+//! Newton iterations with the Jacobian polish the analytic solutions against the exact target,
+//! reducing the residual well below the verification tolerance for metrology or laser work.
+
+use nalgebra::{Matrix3, Matrix6, Point3, Vector3, Vector6};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Uncertainty of the TCP pose caused by the independent random errors of the joints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TcpUncertainty {
+    /// Covariance of the TCP displacement, rows and columns as in the Jacobian (m² for the
+    /// translation, rad² for the rotation).
+    pub covariance: Matrix6<f64>,
+}
+
+impl TcpUncertainty {
+    /// Root mean square of the position error, meters.
+    pub fn position_rms(&self) -> f64 {
+        self.covariance.fixed_view::<3, 3>(0, 0).trace().sqrt()
+    }
+
+    /// Root mean square of the rotation error, radians.
+    pub fn orientation_rms(&self) -> f64 {
+        self.covariance.fixed_view::<3, 3>(3, 3).trace().sqrt()
+    }
+
+    /// Standard deviation of the position along its worst direction, meters.
+    pub fn worst_position_std_dev(&self) -> f64 {
+        let position: Matrix3<f64> = self.covariance.fixed_view::<3, 3>(0, 0).into();
+        position.symmetric_eigenvalues().max().max(0.0).sqrt()
+    }
+}
+
+/// Standard deviation of the error of the encoder with the given resolution (radians per
+/// count): the quantization error is uniform within one count, giving resolution / sqrt(12).
+pub fn resolution_std_dev(resolution: f64) -> f64 {
+    resolution / 12f64.sqrt()
+}
+
+/// Rotation axes of the joints in their link frames: J1, J4 and J6 rotate about z,
+/// J2, J3 and J5 about y (see `link_poses`).
+pub(crate) const JOINT_AXES: [usize; 6] = [2, 1, 1, 2, 1, 2];
+
+impl OPWKinematics {
+    /// Jacobian of the flange.
+    pub fn jacobian(&self, joints: &Joints) -> Matrix6<f64> {
+        self.point_jacobian(joints, 6, &self.forward(joints).translation.vector)
+    }
+
+    /// Jacobian of the point given in the flange frame, like the TCP or the contact point on
+    /// the tool. The angular rows are the same as for the flange, the linear ones differ.
+    pub fn jacobian_at(&self, joints: &Joints, point: &Vector3<f64>) -> Matrix6<f64> {
+        let point = self.forward(joints) * Point3::from(*point);
+        self.point_jacobian(joints, 6, &point.coords)
+    }
+
+    /// Jacobian of the origin of the frame given in the flange frame, with the rows in the
+    /// axes of this frame rather than the base frame, for mapping the velocities and forces
+    /// measured at the tool (like by the force sensor).
+    pub fn jacobian_in(&self, joints: &Joints, frame: &Pose) -> Matrix6<f64> {
+        let jacobian = self.jacobian_at(joints, &frame.translation.vector);
+        let rotation = (self.forward(joints).rotation * frame.rotation).to_rotation_matrix();
+        let mut rotate = Matrix6::zeros();
+        rotate.fixed_view_mut::<3, 3>(0, 0).copy_from(&rotation.transpose().into_inner());
+        rotate.fixed_view_mut::<3, 3>(3, 3).copy_from(&rotation.transpose().into_inner());
+        rotate * jacobian
+    }
+
+    /// Manipulability measure of Yoshikawa, |det J| of the flange Jacobian: zero in the
+    /// singularities, larger where the arm moves the flange more freely.
+    pub fn manipulability(&self, joints: &Joints) -> f64 {
+        self.jacobian(joints).determinant().abs()
+    }
+
+    /// Smallest singular value of the flange Jacobian: zero in the singularities, the speed
+    /// (meters or radians per radian) of the flange in its least mobile direction.
+    pub fn min_singular_value(&self, joints: &Joints) -> f64 {
+        self.jacobian(joints).singular_values().min()
+    }
+
+    /// Condition number of the flange Jacobian, the ratio of its largest and smallest
+    /// singular values: infinite in the singularities, large near them. The rows mix meters
+    /// and radians, so the value only compares configurations of the same robot, such as the
+    /// solutions of one pose.
+    pub fn condition_number(&self, joints: &Joints) -> f64 {
+        let singular_values = self.jacobian(joints).singular_values();
+        let min = singular_values.min();
+        if min > 0.0 {
+            singular_values.max() / min
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Uncertainty of the TCP (given in the flange frame) for the standard deviations of the
+    /// joint errors, radians: the covariance J diag(std_devs²) Jᵀ.
+    pub fn tcp_uncertainty(&self, joints: &Joints, tcp: &Vector3<f64>, std_devs: &[f64; 6])
+                           -> TcpUncertainty {
+        let point = self.forward(joints) * Point3::from(*tcp);
+        let jacobian = self.point_jacobian(joints, 6, &point.coords);
+        let variances = Matrix6::from_diagonal(&Vector6::from(std_devs.map(|s| s * s)));
+        TcpUncertainty { covariance: jacobian * variances * jacobian.transpose() }
+    }
+
+    /// Joints refined by up to `iterations` Newton steps towards the pose (one or two are
+    /// usually enough). The step is only taken if it reduces the residual, so the joints are
+    /// never made worse, including near the singularities.
+    pub fn polish(&self, pose: &Pose, joints: &Joints, iterations: usize) -> Joints {
+        let mut joints = *joints;
+        let mut residual = pose_error(pose, &self.forward(&joints));
+        for _ in 0..iterations {
+            let Some(step) = self.jacobian(&joints).lu().solve(&residual) else { break };
+            let mut next = joints;
+            for (joint, delta) in next.iter_mut().zip(step.iter()) {
+                *joint += delta;
+            }
+            let next_residual = pose_error(pose, &self.forward(&next));
+            if next_residual.norm().is_nan() || next_residual.norm() >= residual.norm() {
+                break;
+            }
+            joints = next;
+            residual = next_residual;
+        }
+        joints
+    }
+
+    /// Solutions of `inverse` polished with `polish`.
+    pub fn inverse_polished(&self, pose: &Pose, iterations: usize) -> Solutions {
+        self.inverse(pose).iter().map(|joints| self.polish(pose, joints, iterations)).collect()
+    }
+
+    /// Solutions of `inverse_continuing` polished with `polish`.
+    pub fn inverse_continuing_polished(&self, pose: &Pose, previous: &Joints, iterations: usize)
+                                       -> Solutions {
+        self.inverse_continuing(pose, previous).iter()
+            .map(|joints| self.polish(pose, joints, iterations)).collect()
+    }
+
+    /// Jacobian of the given point (base frame) attached to the link `link` (0 to 5 for the
+    /// links 1 to 6, 6 for the flange). Columns of the joints after that link are zero.
+    pub(crate) fn point_jacobian(&self, joints: &Joints, link: usize, point: &Vector3<f64>)
+                                 -> Matrix6<f64> {
+        let links = self.link_poses(joints);
+        let signs = &self.parameters().sign_corrections;
+        let mut jacobian = Matrix6::zeros();
+        for j in 0..(link + 1).min(6) {
+            let mut local = Vector3::zeros();
+            local[JOINT_AXES[j]] = signs[j] as f64;
+            let axis = links[j].rotation * local;
+            let linear = axis.cross(&(point - links[j].translation.vector));
+            jacobian.fixed_view_mut::<3, 1>(0, j).copy_from(&linear);
+            jacobian.fixed_view_mut::<3, 1>(3, j).copy_from(&axis);
+        }
+        let coupling = self.parameters().j3_coupling;
+        if link >= 2 && coupling != 0.0 {
+            // J2 also turns the coupled J3
+            let j3 = jacobian.column(2) * coupling;
+            jacobian.column_mut(1).axpy(-1.0, &j3, 1.0);
+        }
+        jacobian
+    }
+}
+
+/// Displacement from the actual to the target flange pose, rows as in the Jacobian.
+pub(crate) fn pose_error(target: &Pose, actual: &Pose) -> Vector6<f64> {
+    let linear = target.translation.vector - actual.translation.vector;
+    let angular = (target.rotation * actual.rotation.inverse()).scaled_axis();
+    Vector6::new(linear.x, linear.y, linear.z, angular.x, angular.y, angular.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector6;
+    use crate::parameters::opw_kinematics::Parameters;
+    use super::*;
+
+    #[test]
+    fn test_jacobian_matches_finite_differences() {
+        for parameters in [Parameters::irb2400_10(), Parameters::staubli_tx40(),
+            Parameters::fanuc_r2000ib_200r()] {
+            let robot = OPWKinematics::new(parameters);
+            let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+            let jacobian = robot.jacobian(&joints);
+            let pose = robot.forward(&joints);
+            let h = 1E-7;
+            for j in 0..6 {
+                let mut moved = joints;
+                moved[j] += h;
+                let moved = robot.forward(&moved);
+                let linear = (moved.translation.vector - pose.translation.vector) / h;
+                let angular = (moved.rotation * pose.rotation.inverse()).scaled_axis() / h;
+                let numeric = Vector6::new(linear.x, linear.y, linear.z,
+                                           angular.x, angular.y, angular.z);
+                assert!((jacobian.column(j) - numeric).norm() < 1E-5,
+                        "column {}: {} vs {}", j, jacobian.column(j), numeric);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_at_point() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        assert_eq!(robot.jacobian_at(&joints, &Vector3::zeros()), robot.jacobian(&joints));
+
+        let point = Vector3::new(0.05, -0.02, 0.2);
+        let jacobian = robot.jacobian_at(&joints, &point);
+        let at = |joints: &Joints| robot.forward(joints) * Point3::from(point);
+        let h = 1E-7;
+        for j in 0..6 {
+            let mut moved = joints;
+            moved[j] += h;
+            let numeric = (at(&moved) - at(&joints)) / h;
+            assert!((jacobian.fixed_view::<3, 1>(0, j) - numeric).norm() < 1E-5);
+        }
+        assert_eq!(jacobian.fixed_rows::<3>(3), robot.jacobian(&joints).fixed_rows::<3>(3));
+
+        // In the frame of the tool, the joint velocity moves the frame as seen from it
+        let frame = Pose::from_parts(point.into(),
+                                     nalgebra::UnitQuaternion::from_euler_angles(0.3, 0.2, 0.1));
+        let local = robot.jacobian_in(&joints, &frame);
+        let world = robot.forward(&joints) * frame;
+        for j in 0..6 {
+            let linear = world.rotation * local.fixed_view::<3, 1>(0, j).into_owned();
+            let angular = world.rotation * local.fixed_view::<3, 1>(3, j).into_owned();
+            assert!((linear - jacobian.fixed_view::<3, 1>(0, j)).norm() < 1E-12);
+            assert!((angular - jacobian.fixed_view::<3, 1>(3, j)).norm() < 1E-12);
+        }
+    }
+
+    #[test]
+    fn test_manipulability() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        assert!(robot.manipulability(&[0.1, 0.2, 0.3, 0.4, 0.0, 0.6]) < 1E-12);
+        assert!(robot.manipulability(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]) > 1E-3);
+    }
+
+    #[test]
+    fn test_condition_number() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let regular = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        assert!(robot.condition_number(&regular) >= 1.0);
+        assert!(robot.condition_number(&regular) < 100.0);
+        assert!(robot.min_singular_value(&regular) > 1E-3);
+
+        // Approaching the wrist singularity
+        let near = [0.1, 0.2, 0.3, 0.4, 0.01, 0.6];
+        assert!(robot.condition_number(&near) > 10.0 * robot.condition_number(&regular));
+        assert!(robot.min_singular_value(&near) < 0.01);
+        assert!(robot.condition_number(&[0.1, 0.2, 0.3, 0.4, 0.0, 0.6]) > 1E12);
+    }
+
+    #[test]
+    fn test_tcp_uncertainty() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let std_devs = [resolution_std_dev(1E-4); 6];
+        let tcp = Vector3::new(0.0, 0.0, 0.2);
+        let stretched = robot.tcp_uncertainty(&[0.0, 0.5, -0.1, 0.0, 0.5, 0.0], &tcp, &std_devs);
+        let folded = robot.tcp_uncertainty(&[0.0, -0.5, 1.0, 0.0, 0.5, 0.0], &tcp, &std_devs);
+
+        // Rotation errors of all joints add up the same way in any configuration
+        assert!((stretched.orientation_rms() - folded.orientation_rms()).abs() < 1E-9);
+        // Joint errors move the TCP further when the arm is stretched out
+        assert!(stretched.position_rms() > folded.position_rms());
+        assert!(stretched.worst_position_std_dev() <= stretched.position_rms());
+        assert!(stretched.worst_position_std_dev() > stretched.position_rms() / 3f64.sqrt());
+
+        // Single joint error moves the TCP along one direction only
+        let j1 = robot.tcp_uncertainty(&[0.0; 6], &tcp, &[0.001, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!((j1.worst_position_std_dev() - j1.position_rms()).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_polish() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let rough = joints.map(|j| j + 1E-4);
+        assert!(pose_error(&pose, &robot.forward(&rough)).norm() > 1E-5);
+        let polished = robot.polish(&pose, &rough, 2);
+        assert!(pose_error(&pose, &robot.forward(&polished)).norm() < 1E-12);
+        assert_eq!(robot.polish(&pose, &rough, 0), rough);
+
+        let solutions = robot.inverse_polished(&pose, 2);
+        assert_eq!(solutions.len(), robot.inverse(&pose).len());
+        for solution in &solutions {
+            assert!(pose_error(&pose, &robot.forward(solution)).norm() < 1E-12);
+        }
+        let continuing = robot.inverse_continuing_polished(&pose, &joints, 1);
+        assert!(continuing[0].iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-12));
+
+        // In the wrist singularity, the step is not taken rather than diverging
+        let singular = [0.1, 0.2, 0.3, 0.4, 0.0, 0.6];
+        let pose = robot.forward(&singular);
+        let polished = robot.polish(&pose, &singular, 2);
+        assert!(pose_error(&pose, &robot.forward(&polished)).norm() < 1E-12);
+    }
+}
@@ -0,0 +1,157 @@
+//! Cartesian jogging: the small step of the TCP (or flange) is composed on the current pose
+//! and solved continuing from the current joints. The step is refused, rather than taken with
+//! the sudden joint motion, if the robot would leave the joint limits or jump to the other
+//! configuration (as it happens passing through the singularities). This is the primitive of
+//! the hand guiding and teach pendant like tools.
+
+use thiserror::Error;
+use crate::constraints::JointLimits;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+
+/// Axes the jog step is given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JogFrame {
+    /// Translation along the base axes, rotation about the base axes through the TCP.
+    #[default]
+    Base,
+    /// Translation and rotation in the TCP frame.
+    Tool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JogSettings {
+    pub frame: JogFrame,
+    /// Limits the jogged joints must stay within, if any.
+    pub limits: Option<JointLimits>,
+    /// Largest change of any joint in one step, radians. Larger change means the step
+    /// crosses the singularity or the configuration boundary.
+    pub max_joint_step: f64,
+}
+
+impl Default for JogSettings {
+    fn default() -> Self {
+        JogSettings { frame: JogFrame::Base, limits: None, max_joint_step: 0.1 }
+    }
+}
+
+impl JogSettings {
+    pub fn with_frame(self, frame: JogFrame) -> Self {
+        JogSettings { frame, ..self }
+    }
+
+    pub fn with_limits(self, limits: JointLimits) -> Self {
+        JogSettings { limits: Some(limits), ..self }
+    }
+
+    pub fn with_max_joint_step(self, max_joint_step: f64) -> Self {
+        JogSettings { max_joint_step, ..self }
+    }
+}
+
+/// Why the jog step was refused. The robot should stay where it is.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum JogError {
+    #[error("the jogged pose is out of reach")]
+    Unreachable,
+    #[error("the jogged pose is out of the joint limits")]
+    JointLimits,
+    #[error("the step requires J{} to move by {change} rad", joint + 1)]
+    Discontinuity { joint: usize, change: f64 },
+}
+
+/// Cartesian jogging, implemented for all solvers.
+pub trait JogKinematics: Kinematics {
+    /// Joints after the Cartesian step from the current joints: the closest solution that
+    /// passes `check_step`. Otherwise the error of the closest one within the limits.
+    fn jog(&self, current: &Joints, step: &Pose, settings: &JogSettings)
+           -> Result<Joints, JogError> {
+        let pose = self.forward(current);
+        let target = match settings.frame {
+            JogFrame::Base => Pose::from_parts(pose.translation * step.translation,
+                                               step.rotation * pose.rotation),
+            JogFrame::Tool => pose * step,
+        };
+        let mut error = JogError::Unreachable;
+        for joints in self.inverse_continuing(&target, current) {
+            match check_step(current, &joints, settings.limits.as_ref(), settings.max_joint_step) {
+                Ok(()) => return Ok(joints),
+                Err(e) => if !matches!(error, JogError::Discontinuity { .. }) {
+                    error = e;
+                },
+            }
+        }
+        Err(error)
+    }
+}
+
+impl<K: Kinematics + ?Sized> JogKinematics for K {}
+
+/// Checks the step from the previous joints to the next ones, as for jogging and streaming:
+/// the next joints must be within the limits (if any) and no joint may move by more than
+/// `max_joint_step`. The discontinuity is reported for the joint moving the most.
+pub(crate) fn check_step(previous: &Joints, joints: &Joints, limits: Option<&JointLimits>,
+                         max_joint_step: f64) -> Result<(), JogError> {
+    if limits.is_some_and(|limits| !limits.contains(joints)) {
+        return Err(JogError::JointLimits);
+    }
+    let (joint, change) = joints.iter().zip(previous).map(|(a, b)| (a - b).abs()).enumerate()
+        .fold((0, 0.0), |worst, (joint, change)| if change > worst.1 {
+            (joint, change)
+        } else {
+            worst
+        });
+    if change > max_joint_step {
+        return Err(JogError::Discontinuity { joint, change });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Translation3, UnitQuaternion, Vector3};
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_jog() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let current = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&current);
+        let settings = JogSettings::default();
+
+        // 1 mm along the base X, the orientation is kept
+        let step = Pose::translation(0.001, 0.0, 0.0);
+        let joints = robot.jog(&current, &step, &settings).unwrap();
+        let moved = robot.forward(&joints);
+        assert!((moved.translation.vector - pose.translation.vector - Vector3::x() * 0.001)
+            .norm() < 1E-9);
+        assert!(moved.rotation.angle_to(&pose.rotation) < 1E-9);
+
+        // Rotation about the base Z through the TCP does not move the TCP
+        let step = Pose::from_parts(Translation3::identity(),
+                                    UnitQuaternion::from_euler_angles(0.0, 0.0, 0.01));
+        let joints = robot.jog(&current, &step, &settings).unwrap();
+        let moved = robot.forward(&joints);
+        assert!((moved.translation.vector - pose.translation.vector).norm() < 1E-9);
+
+        // Tool frame step
+        let step = Pose::translation(0.0, 0.0, 0.002);
+        let settings = settings.with_frame(JogFrame::Tool);
+        let joints = robot.jog(&current, &step, &settings).unwrap();
+        assert!(compare_poses(&robot.forward(&joints), &(pose * step), 1E-9, 1E-9).is_ok());
+
+        let far = Pose::translation(10.0, 0.0, 0.0);
+        assert_eq!(robot.jog(&current, &far, &settings), Err(JogError::Unreachable));
+        // Limits leave no room around the current joints
+        let limits = JointLimits::new(current.map(|j| j - 1E-6), current.map(|j| j + 1E-6));
+        assert_eq!(robot.jog(&current, &step, &settings.with_limits(limits)),
+                   Err(JogError::JointLimits));
+        let large = Pose::translation(0.0, 0.2, 0.0);
+        assert!(matches!(robot.jog(&current, &large, &settings),
+                         Err(JogError::Discontinuity { .. })));
+    }
+}
@@ -0,0 +1,325 @@
+//! Mapping of the joint names used in URDF and ROS messages to the joints J1 to J6. The order
+//! of the joints in the URDF or in the joint state rarely matches J1 to J6 (joints are often
+//! sorted alphabetically, and grippers or external axes are mixed in), so the values are always
+//! picked by name. The mapping is given explicitly or detected:
+//! - from the numbering in the names (joint_1 ... joint_6, joint_a1 ... joint_a6,
+//!   joint_1_s ... joint_6_t, J0 ... J5 and similar),
+//! - from the URDF, following the kinematic chain from the root link if the names are not
+//!   numbered.
+
+use std::collections::HashMap;
+#[cfg(feature = "files")]
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use thiserror::Error;
+use crate::kinematic_traits::Joints;
+#[cfg(feature = "files")]
+use crate::kinematic_traits::Pose;
+
+/// Defines the joint mapping error
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum JointMappingError {
+    #[error("no joint name given for J{0}")]
+    MissingJoint(usize),
+    #[error("joint index {0} out of range 0 to 5")]
+    InvalidIndex(usize),
+    #[error("J{0} is mapped more than once")]
+    DuplicateJoint(usize),
+    #[error("cannot detect J1 to J6 from the joint names")]
+    NotDetected,
+    #[error("joint names are ambiguous, several numbered groups: {0:?}")]
+    Ambiguous(Vec<String>),
+    #[error("URDF parse error: {0}")]
+    Urdf(String),
+}
+
+/// Names of the joints J1 to J6.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JointMapping {
+    names: [String; 6],
+}
+
+impl JointMapping {
+    /// Mapping with the given names of J1 to J6.
+    pub fn new(names: [&str; 6]) -> Self {
+        JointMapping { names: names.map(str::to_string) }
+    }
+
+    /// Mapping from the explicit joint name to index (0 for J1 to 5 for J6) map.
+    pub fn from_indices(indices: &HashMap<String, usize>) -> Result<Self, JointMappingError> {
+        let mut names: [Option<String>; 6] = Default::default();
+        for (name, &index) in indices {
+            let slot = names.get_mut(index).ok_or(JointMappingError::InvalidIndex(index))?;
+            if slot.replace(name.clone()).is_some() {
+                return Err(JointMappingError::DuplicateJoint(index + 1));
+            }
+        }
+        let mut result: [String; 6] = Default::default();
+        for (i, name) in names.into_iter().enumerate() {
+            result[i] = name.ok_or(JointMappingError::MissingJoint(i + 1))?;
+        }
+        Ok(JointMapping { names: result })
+    }
+
+    /// Detects the mapping from the numbering in the joint names. Names are split at the last
+    /// group of digits; among the names sharing the same text before it, exactly one group must
+    /// be numbered 1 to 6 (or 0 to 5). Other joints (grippers, external axes) are ignored.
+    pub fn detect<S: AsRef<str>>(names: &[S]) -> Result<Self, JointMappingError> {
+        let mut groups: HashMap<&str, Vec<(u32, &str)>> = HashMap::new();
+        for name in names {
+            let name = name.as_ref();
+            if let Some((prefix, number)) = split_number(name) {
+                groups.entry(prefix).or_default().push((number, name));
+            }
+        }
+
+        let mut found: Vec<(&str, [String; 6])> = groups.into_iter().filter_map(|(prefix, mut group)| {
+            group.sort();
+            let numbers: Vec<u32> = group.iter().map(|(n, _)| *n).collect();
+            if numbers == [1, 2, 3, 4, 5, 6] || numbers == [0, 1, 2, 3, 4, 5] {
+                Some((prefix, std::array::from_fn(|i| group[i].1.to_string())))
+            } else {
+                None
+            }
+        }).collect();
+
+        match found.len() {
+            0 => Err(JointMappingError::NotDetected),
+            1 => Ok(JointMapping { names: found.pop().unwrap().1 }),
+            _ => {
+                let mut prefixes: Vec<String> = found.iter().map(|(p, _)| p.to_string()).collect();
+                prefixes.sort();
+                Err(JointMappingError::Ambiguous(prefixes))
+            }
+        }
+    }
+
+    /// Detects the mapping from the URDF: by the numbering of the movable joint names as in
+    /// `detect`, or else the first six revolute joints along the longest chain from the root.
+    #[cfg(feature = "files")]
+    pub fn from_urdf(urdf: &str) -> Result<Self, JointMappingError> {
+        let joints = urdf_joints(urdf)?;
+        let movable: Vec<&str> = joints.iter().filter(|j| j.movable).map(|j| j.name.as_str()).collect();
+        match Self::detect(&movable) {
+            Err(JointMappingError::NotDetected) => chain_order(&joints),
+            result => result,
+        }
+    }
+
+    pub fn names(&self) -> &[String; 6] {
+        &self.names
+    }
+
+    /// Index (0 for J1 to 5 for J6) of the named joint.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Picks the values of J1 to J6 from the named values (as in the joint state or trajectory
+    /// messages). Other joints are ignored. None if any of J1 to J6 is missing.
+    pub fn joints_from_named<S: AsRef<str>>(&self, names: &[S], values: &[f64]) -> Option<Joints> {
+        let mut joints = [0.0; 6];
+        for (joint, name) in joints.iter_mut().zip(&self.names) {
+            let index = names.iter().position(|n| n.as_ref() == name)?;
+            *joint = *values.get(index)?;
+        }
+        Some(joints)
+    }
+}
+
+fn split_number(name: &str) -> Option<(&str, u32)> {
+    let end = name.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = name[..end].rfind(|c: char| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+    Some((&name[..start], name[start..end].parse().ok()?))
+}
+
+/// Joint of the URDF, as needed to follow the kinematic chain.
+#[cfg(feature = "files")]
+pub(crate) struct UrdfJoint {
+    pub name: String,
+    pub parent: String,
+    pub child: String,
+    pub movable: bool,
+    /// Transform from the parent link to the joint frame.
+    pub origin: Pose,
+    /// Rotation axis in the joint frame.
+    pub axis: Vector3<f64>,
+}
+
+#[cfg(feature = "files")]
+pub(crate) fn urdf_joints(urdf: &str) -> Result<Vec<UrdfJoint>, JointMappingError> {
+    let document = roxmltree::Document::parse(urdf)
+        .map_err(|e| JointMappingError::Urdf(e.to_string()))?;
+    let link = |joint: roxmltree::Node, tag: &str| -> Result<String, JointMappingError> {
+        child_attribute(joint, tag, "link").ok_or_else(|| JointMappingError::Urdf(format!("joint without {}", tag)))
+    };
+    document.root_element().children().filter(|n| n.has_tag_name("joint")).map(|joint| {
+        let kind = joint.attribute("type").unwrap_or("fixed");
+        let mimic = joint.children().any(|n| n.has_tag_name("mimic"));
+        Ok(UrdfJoint {
+            name: joint.attribute("name").unwrap_or_default().to_string(),
+            parent: link(joint, "parent")?,
+            child: link(joint, "child")?,
+            movable: (kind == "revolute" || kind == "continuous") && !mimic,
+            origin: origin(joint)?,
+            axis: vector(joint, "axis", "xyz", [1.0, 0.0, 0.0])?.normalize(),
+        })
+    }).collect()
+}
+
+/// Visual of the URDF link: the mesh file and its pose in the link frame.
+#[cfg(feature = "files")]
+pub(crate) struct UrdfVisual {
+    pub mesh: String,
+    pub origin: Pose,
+}
+
+/// Visuals of the links (link name to the first visual with a mesh).
+#[cfg(feature = "files")]
+pub(crate) fn urdf_visuals(urdf: &str) -> Result<HashMap<String, UrdfVisual>, JointMappingError> {
+    let document = roxmltree::Document::parse(urdf)
+        .map_err(|e| JointMappingError::Urdf(e.to_string()))?;
+    let mut visuals = HashMap::new();
+    for link in document.root_element().children().filter(|n| n.has_tag_name("link")) {
+        let name = link.attribute("name").unwrap_or_default();
+        for visual in link.children().filter(|n| n.has_tag_name("visual")) {
+            let Some(geometry) = visual.children().find(|n| n.has_tag_name("geometry")) else {
+                continue;
+            };
+            if let Some(mesh) = child_attribute(geometry, "mesh", "filename") {
+                visuals.insert(name.to_string(), UrdfVisual { mesh, origin: origin(visual)? });
+                break;
+            }
+        }
+    }
+    Ok(visuals)
+}
+
+/// Pose given by the origin element of the node (identity if missing).
+#[cfg(feature = "files")]
+fn origin(node: roxmltree::Node) -> Result<Pose, JointMappingError> {
+    let rpy = vector(node, "origin", "rpy", [0.0; 3])?;
+    Ok(Pose::from_parts(Translation3::from(vector(node, "origin", "xyz", [0.0; 3])?),
+                        UnitQuaternion::from_euler_angles(rpy.x, rpy.y, rpy.z)))
+}
+
+#[cfg(feature = "files")]
+fn vector(node: roxmltree::Node, tag: &str, attribute: &str, default: [f64; 3])
+          -> Result<Vector3<f64>, JointMappingError> {
+    let Some(text) = child_attribute(node, tag, attribute) else {
+        return Ok(Vector3::from(default));
+    };
+    let values: Vec<f64> = text.split_whitespace().map(str::parse).collect::<Result<_, _>>()
+        .map_err(|_| JointMappingError::Urdf(format!("invalid {} {}: {}", tag, attribute, text)))?;
+    match values[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(JointMappingError::Urdf(format!("invalid {} {}: {}", tag, attribute, text))),
+    }
+}
+
+#[cfg(feature = "files")]
+fn child_attribute(node: roxmltree::Node, tag: &str, attribute: &str) -> Option<String> {
+    node.children().find(|n| n.has_tag_name(tag))?.attribute(attribute).map(str::to_string)
+}
+
+/// All joints from the root to the given joint (inclusive).
+#[cfg(feature = "files")]
+pub(crate) fn urdf_chain<'a>(joints: &'a [UrdfJoint], mut joint: &'a UrdfJoint) -> Vec<&'a UrdfJoint> {
+    let mut chain = vec![joint];
+    while let Some(parent) = joints.iter().find(|j| j.child == joint.parent) {
+        if chain.len() > joints.len() {
+            break; // Cycle, not a valid URDF
+        }
+        joint = parent;
+        chain.push(joint);
+    }
+    chain.reverse();
+    chain
+}
+
+/// Movable joints from the root to the given joint (inclusive).
+#[cfg(feature = "files")]
+fn movable_chain<'a>(joints: &'a [UrdfJoint], joint: &'a UrdfJoint) -> Vec<&'a str> {
+    urdf_chain(joints, joint).into_iter().filter(|j| j.movable).map(|j| j.name.as_str()).collect()
+}
+
+/// The first six movable joints on the chain from the root with the most movable joints.
+#[cfg(feature = "files")]
+fn chain_order(joints: &[UrdfJoint]) -> Result<JointMapping, JointMappingError> {
+    let longest = joints.iter().map(|joint| movable_chain(joints, joint))
+        .max_by_key(|chain| chain.len()).unwrap_or_default();
+    if longest.len() < 6 {
+        return Err(JointMappingError::NotDetected);
+    }
+    Ok(JointMapping::new(std::array::from_fn(|i| longest[i])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_from_names() {
+        let ros_industrial = ["joint_6", "joint_1", "gripper_joint", "joint_2", "joint_3",
+            "joint_4", "joint_5"];
+        assert_eq!(JointMapping::detect(&ros_industrial).unwrap(), JointMapping::new(
+            ["joint_1", "joint_2", "joint_3", "joint_4", "joint_5", "joint_6"]));
+
+        let kuka = ["joint_a1", "joint_a2", "joint_a3", "joint_a4", "joint_a5", "joint_a6"];
+        assert_eq!(JointMapping::detect(&kuka).unwrap().names()[0], "joint_a1");
+
+        let yaskawa = ["joint_2_l", "joint_1_s", "joint_3_u", "joint_4_r", "joint_5_b", "joint_6_t"];
+        assert_eq!(JointMapping::detect(&yaskawa).unwrap().index_of("joint_5_b"), Some(4));
+
+        let zero_based = ["J0", "J1", "J2", "J3", "J4", "J5", "finger_1"];
+        assert_eq!(JointMapping::detect(&zero_based).unwrap().names()[5], "J5");
+
+        let two_robots = ["left_joint_1", "left_joint_2", "left_joint_3", "left_joint_4",
+            "left_joint_5", "left_joint_6", "right_joint_1", "right_joint_2", "right_joint_3",
+            "right_joint_4", "right_joint_5", "right_joint_6"];
+        assert_eq!(JointMapping::detect(&two_robots), Err(JointMappingError::Ambiguous(
+            vec!["left_joint_".to_string(), "right_joint_".to_string()])));
+        assert_eq!(JointMapping::detect(&["shoulder", "elbow"]), Err(JointMappingError::NotDetected));
+    }
+
+    #[test]
+    fn test_explicit_mapping() {
+        let indices: HashMap<String, usize> = ["base", "shoulder", "elbow", "wrist1", "wrist2", "wrist3"]
+            .iter().enumerate().map(|(i, n)| (n.to_string(), i)).collect();
+        let mapping = JointMapping::from_indices(&indices).unwrap();
+        // Joint state sorted alphabetically, with the extra joint
+        let names = ["base", "elbow", "gripper", "shoulder", "wrist1", "wrist2", "wrist3"];
+        let values = [1.0, 3.0, 9.0, 2.0, 4.0, 5.0, 6.0];
+        assert_eq!(mapping.joints_from_named(&names, &values), Some([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+        assert_eq!(mapping.joints_from_named(&names[..3], &values), None);
+
+        let mut missing = indices.clone();
+        missing.remove("elbow");
+        assert_eq!(JointMapping::from_indices(&missing), Err(JointMappingError::MissingJoint(3)));
+        missing.insert("elbow".to_string(), 6);
+        assert_eq!(JointMapping::from_indices(&missing), Err(JointMappingError::InvalidIndex(6)));
+    }
+
+    #[cfg(feature = "files")]
+    #[test]
+    fn test_from_urdf_chain() {
+        let joint = |name: &str, kind: &str, parent: &str, child: &str| format!(
+            r#"<joint name="{}" type="{}"><parent link="{}"/><child link="{}"/></joint>"#,
+            name, kind, parent, child);
+        // Not numbered, listed out of order, with the fixed flange and the gripper finger
+        let joints = [
+            joint("wrist_roll", "revolute", "l5", "l6"),
+            joint("base_yaw", "revolute", "base", "l1"),
+            joint("shoulder", "revolute", "l1", "l2"),
+            joint("flange", "fixed", "l6", "tool0"),
+            joint("elbow", "revolute", "l2", "l3"),
+            joint("finger", "prismatic", "tool0", "finger"),
+            joint("forearm_roll", "continuous", "l3", "l4"),
+            joint("wrist_pitch", "revolute", "l4", "l5"),
+        ].concat();
+        let urdf = format!(r#"<robot name="r">{}</robot>"#, joints);
+        assert_eq!(JointMapping::from_urdf(&urdf).unwrap(), JointMapping::new(
+            ["base_yaw", "shoulder", "elbow", "forearm_roll", "wrist_pitch", "wrist_roll"]));
+        assert!(matches!(JointMapping::from_urdf("<robot"), Err(JointMappingError::Urdf(_))));
+    }
+}
@@ -0,0 +1,242 @@
+//! `JointVector` newtype with conversions from and to the common representations. The solvers
+//! take and return the plain array (`kinematic_traits::Joints`); this type converts to and
+//! from it with `From` / `Into` and dereferences to it, so `&joints` can be passed to the
+//! solvers:
+//! ```
+//! use rs_opw_kinematics::joints::{Joint, JointVector};
+//! use rs_opw_kinematics::kinematic_traits::Kinematics;
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//! use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+//!
+//! let robot = OPWKinematics::new(Parameters::irb2400_10());
+//! let joints = JointVector::from_degrees([0.0, 10.0, 20.0, 0.0, 45.0, 0.0]);
+//! let pose = robot.forward(&joints);
+//! let solutions: Vec<JointVector> =
+//!     robot.inverse(&pose).into_iter().map(JointVector::from).collect();
+//! assert!(solutions.iter().any(|s| s.difference(&joints).max_abs() < 1E-6));
+//! assert_eq!(joints[Joint::J5], 45f64.to_radians());
+//! ```
+
+use std::fmt;
+use std::ops::{Add, Deref, DerefMut, Index, IndexMut, Mul, Neg, Sub};
+use nalgebra::Vector6;
+use thiserror::Error;
+use crate::kinematic_traits;
+use crate::utils::{angle_difference, SolutionsTable};
+
+/// Joint of the robot, for indexing `JointVector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Joint {
+    J1,
+    J2,
+    J3,
+    J4,
+    J5,
+    J6,
+}
+
+impl Joint {
+    pub const ALL: [Joint; 6] = [Joint::J1, Joint::J2, Joint::J3, Joint::J4, Joint::J5, Joint::J6];
+}
+
+/// Error when converting slices or vectors of the wrong length.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("expected 6 joint values, got {0}")]
+pub struct JointsLengthError(pub usize);
+
+/// Six joint values in radians.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct JointVector(pub kinematic_traits::Joints);
+
+impl JointVector {
+    pub fn from_radians(values: [f64; 6]) -> Self {
+        JointVector(values)
+    }
+
+    pub fn from_degrees(values: [f64; 6]) -> Self {
+        JointVector(values.map(f64::to_radians))
+    }
+
+    pub fn to_degrees(&self) -> [f64; 6] {
+        self.0.map(f64::to_degrees)
+    }
+
+    /// Difference `self - other` with each value wrapped into [-pi, pi], so that the angles
+    /// differing by the full turn are treated as the same.
+    pub fn difference(&self, other: &JointVector) -> JointVector {
+        JointVector(std::array::from_fn(|i| angle_difference(self.0[i], other.0[i])))
+    }
+
+    /// The largest absolute value.
+    pub fn max_abs(&self) -> f64 {
+        self.0.iter().fold(0.0, |m, v| m.max(v.abs()))
+    }
+}
+
+impl fmt::Display for JointVector {
+    /// Values in degrees, as a row of `SolutionsTable`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SolutionsTable::new(std::slice::from_ref(&self.0)), f)
+    }
+}
+
+impl Deref for JointVector {
+    type Target = kinematic_traits::Joints;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for JointVector {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Index<Joint> for JointVector {
+    type Output = f64;
+
+    fn index(&self, joint: Joint) -> &f64 {
+        &self.0[joint as usize]
+    }
+}
+
+impl IndexMut<Joint> for JointVector {
+    fn index_mut(&mut self, joint: Joint) -> &mut f64 {
+        &mut self.0[joint as usize]
+    }
+}
+
+impl Index<usize> for JointVector {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &f64 {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for JointVector {
+    fn index_mut(&mut self, index: usize) -> &mut f64 {
+        &mut self.0[index]
+    }
+}
+
+impl From<[f64; 6]> for JointVector {
+    fn from(values: [f64; 6]) -> Self {
+        JointVector(values)
+    }
+}
+
+impl From<JointVector> for [f64; 6] {
+    fn from(joints: JointVector) -> Self {
+        joints.0
+    }
+}
+
+impl From<Vector6<f64>> for JointVector {
+    fn from(values: Vector6<f64>) -> Self {
+        JointVector(values.into())
+    }
+}
+
+impl From<JointVector> for Vector6<f64> {
+    fn from(joints: JointVector) -> Self {
+        Vector6::from(joints.0)
+    }
+}
+
+impl From<JointVector> for Vec<f64> {
+    fn from(joints: JointVector) -> Self {
+        joints.0.to_vec()
+    }
+}
+
+impl TryFrom<&[f64]> for JointVector {
+    type Error = JointsLengthError;
+
+    fn try_from(values: &[f64]) -> Result<Self, Self::Error> {
+        values.try_into().map(JointVector).map_err(|_| JointsLengthError(values.len()))
+    }
+}
+
+impl TryFrom<Vec<f64>> for JointVector {
+    type Error = JointsLengthError;
+
+    fn try_from(values: Vec<f64>) -> Result<Self, Self::Error> {
+        JointVector::try_from(values.as_slice())
+    }
+}
+
+impl Add for JointVector {
+    type Output = JointVector;
+
+    fn add(self, other: JointVector) -> JointVector {
+        JointVector(std::array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+}
+
+impl Sub for JointVector {
+    type Output = JointVector;
+
+    fn sub(self, other: JointVector) -> JointVector {
+        JointVector(std::array::from_fn(|i| self.0[i] - other.0[i]))
+    }
+}
+
+impl Neg for JointVector {
+    type Output = JointVector;
+
+    fn neg(self) -> JointVector {
+        JointVector(self.0.map(|v| -v))
+    }
+}
+
+impl Mul<f64> for JointVector {
+    type Output = JointVector;
+
+    fn mul(self, factor: f64) -> JointVector {
+        JointVector(self.0.map(|v| v * factor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+
+    #[test]
+    fn test_joints_conversions() {
+        let joints = JointVector::from_degrees([90.0, 0.0, 0.0, 0.0, 0.0, -180.0]);
+        assert!((joints[Joint::J1] - PI / 2.0).abs() < 1E-15);
+        assert!((joints[5] + PI).abs() < 1E-15);
+        assert!((joints.to_degrees()[0] - 90.0).abs() < 1E-12);
+
+        let array: [f64; 6] = joints.into();
+        let vector: Vector6<f64> = joints.into();
+        let vec: Vec<f64> = joints.into();
+        assert_eq!(JointVector::from(array), joints);
+        assert_eq!(JointVector::from(vector), joints);
+        assert_eq!(JointVector::try_from(vec.as_slice()), Ok(joints));
+        assert_eq!(JointVector::try_from(vec![1.0, 2.0]), Err(JointsLengthError(2)));
+
+        let mut sum = joints + joints * 2.0 - joints;
+        sum[Joint::J2] = 1.0;
+        assert_eq!(sum[1], 1.0);
+        assert!((-sum)[Joint::J1] < 0.0);
+        assert_eq!(format!("{:.1}", joints), "[  90.0    0.0    0.0    0.0    0.0 -180.0]");
+    }
+
+    #[test]
+    fn test_joints_difference() {
+        let a = JointVector::from_degrees([179.0, 0.0, 10.0, 0.0, 0.0, 0.0]);
+        let b = JointVector::from_degrees([-179.0, 0.0, 370.0, 0.0, 0.0, 0.0]);
+        let d = a.difference(&b).to_degrees();
+        assert!((d[0] + 2.0).abs() < 1E-9);
+        assert!(d[2].abs() < 1E-9);
+        assert!((a.difference(&b).max_abs() - 2f64.to_radians()).abs() < 1E-9);
+    }
+}
@@ -0,0 +1,167 @@
+//! Interoperability with the [k](https://docs.rs/k) kinematics crate (feature `k`). The robot
+//! can be converted into `k::Chain`, and `OPWIkSolver` implements `k::InverseKinematicsSolver`
+//! so that the analytic solver can replace the numerical `k::JacobianIkSolver`.
+//!
+//! The chain consists of the revolute joints "joint_1" to "joint_6" followed by the fixed
+//! "tool0" at the flange. k joint positions are angles of the kinematic model, without the
+//! offsets and sign corrections of the parameters; use `joints_to_k` and `joints_from_k`
+//! to map them. k uses its own version of nalgebra, so poses are converted with `pose_to_k`
+//! and `pose_from_k`.
+
+use k::nalgebra as kna;
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Names of the joints in the chain built by `k_chain`.
+pub const K_JOINT_NAMES: [&str; 6] = ["joint_1", "joint_2", "joint_3", "joint_4", "joint_5", "joint_6"];
+
+/// Name of the fixed flange node in the chain built by `k_chain`.
+pub const K_FLANGE_NAME: &str = "tool0";
+
+/// Builds the k chain with the same kinematics as the robot with these parameters.
+pub fn k_chain(parameters: &Parameters) -> k::Chain<f64> {
+    let p = parameters;
+    let z = kna::Vector3::z_axis();
+    let y = kna::Vector3::y_axis();
+    let origins = [(0.0, 0.0, 0.0), (p.a1, p.b, p.c1), (0.0, 0.0, p.c2), (p.a2, 0.0, 0.0),
+        (0.0, 0.0, p.c3), (0.0, 0.0, 0.0)];
+    let axes = [z, y, y, z, y, z];
+
+    let nodes: Vec<k::Node<f64>> = K_JOINT_NAMES.iter().zip(origins.iter().zip(axes))
+        .map(|(name, ((x, y, z), axis))| k::NodeBuilder::new()
+            .name(name)
+            .translation(kna::Translation3::new(*x, *y, *z))
+            .joint_type(k::JointType::Rotational { axis })
+            .into_node())
+        .chain(std::iter::once(k::NodeBuilder::new()
+            .name(K_FLANGE_NAME)
+            .translation(kna::Translation3::new(0.0, 0.0, p.c4))
+            .joint_type(k::JointType::Fixed)
+            .into_node()))
+        .collect();
+    for pair in nodes.windows(2) {
+        pair[1].set_parent(&pair[0]);
+    }
+    k::Chain::from_root(nodes[0].clone())
+}
+
+/// Converts the robot joints into the k joint positions.
+pub fn joints_to_k(parameters: &Parameters, joints: &Joints) -> [f64; 6] {
+    let joints = parameters.decoupled(joints);
+    std::array::from_fn(|i| {
+        joints[i] * parameters.sign_corrections[i] as f64 - parameters.offsets[i]
+    })
+}
+
+/// Converts the k joint positions into the robot joints. Returns None if there are
+/// not exactly 6 positions.
+pub fn joints_from_k(parameters: &Parameters, positions: &[f64]) -> Option<Joints> {
+    let positions: &[f64; 6] = positions.try_into().ok()?;
+    Some(parameters.coupled(&std::array::from_fn(|i| {
+        (positions[i] + parameters.offsets[i]) * parameters.sign_corrections[i] as f64
+    })))
+}
+
+/// Converts the pose into the k (nalgebra version used by k) isometry.
+pub fn pose_to_k(pose: &Pose) -> kna::Isometry3<f64> {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    kna::Isometry3::from_parts(
+        kna::Translation3::new(t.x, t.y, t.z),
+        kna::UnitQuaternion::new_unchecked(kna::Quaternion::new(q.w, q.i, q.j, q.k)))
+}
+
+/// Converts the k isometry into the pose.
+pub fn pose_from_k(pose: &kna::Isometry3<f64>) -> Pose {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    Pose::from_parts(Translation3::new(t.x, t.y, t.z),
+                     UnitQuaternion::new_unchecked(Quaternion::new(q.w, q.i, q.j, q.k)))
+}
+
+/// Analytic inverse kinematics solver for the chains built by `k_chain`. The target pose is
+/// the pose of the flange, and the origin of the chain is taken as the robot base. The
+/// solution closest to the current joint positions is applied. Constraints are ignored as
+/// the full pose is always solved.
+#[derive(Debug, Clone)]
+pub struct OPWIkSolver {
+    robot: OPWKinematics,
+}
+
+impl OPWIkSolver {
+    pub fn new(parameters: Parameters) -> Self {
+        OPWIkSolver { robot: OPWKinematics::new(parameters) }
+    }
+}
+
+impl k::InverseKinematicsSolver<f64> for OPWIkSolver {
+    fn solve_with_constraints(&self, arm: &k::SerialChain<f64>, target_pose: &kna::Isometry3<f64>,
+                              _constraints: &k::Constraints) -> Result<(), k::Error> {
+        let parameters = self.robot.parameters();
+        let current = arm.joint_positions();
+        let previous = joints_from_k(parameters, &current)
+            .ok_or(k::Error::PreconditionError { dof: current.len(), necessary_dof: 6 })?;
+
+        let target = pose_from_k(&(arm.origin().inverse() * target_pose));
+        match self.robot.inverse_continuing(&target, &previous).first() {
+            Some(joints) => arm.set_joint_positions(&joints_to_k(parameters, joints)),
+            None => {
+                let end = arm.end_transform();
+                Err(k::Error::NotConvergedError {
+                    num_tried: 1,
+                    position_diff: target_pose.translation.vector - end.translation.vector,
+                    rotation_diff: (end.rotation.inverse() * target_pose.rotation).scaled_axis(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k::InverseKinematicsSolver;
+
+    #[test]
+    fn test_k_chain() {
+        let parameters = Parameters::staubli_tx40();
+        let robot = OPWKinematics::new(parameters.clone());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+
+        let chain = k_chain(&parameters);
+        assert_eq!(chain.dof(), 6);
+        chain.set_joint_positions(&joints_to_k(&parameters, &joints)).unwrap();
+        chain.update_transforms();
+        let flange = chain.find(K_FLANGE_NAME).unwrap();
+        let flange = pose_from_k(&flange.world_transform().unwrap());
+        let expected = robot.forward(&joints);
+        assert!((flange.translation.vector - expected.translation.vector).norm() < 1E-9);
+        assert!(flange.rotation.angle_to(&expected.rotation) < 1E-9);
+
+        let restored = joints_from_k(&parameters, &chain.joint_positions()).unwrap();
+        for (a, b) in restored.iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1E-12);
+        }
+    }
+
+    #[test]
+    fn test_k_solver() {
+        let parameters = Parameters::irb2400_10();
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let target = pose_to_k(&OPWKinematics::new(parameters.clone()).forward(&joints));
+
+        let arm = k::SerialChain::new_unchecked(k_chain(&parameters));
+        arm.set_joint_positions(&joints_to_k(&parameters, &[0.0, 0.1, 0.2, 0.3, 0.4, 0.5]))
+            .unwrap();
+        OPWIkSolver::new(parameters.clone()).solve(&arm, &target).unwrap();
+        let solved = joints_from_k(&parameters, &arm.joint_positions()).unwrap();
+        for (a, b) in solved.iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1E-6);
+        }
+
+        let unreachable = kna::Isometry3::translation(10.0, 0.0, 0.0);
+        assert!(OPWIkSolver::new(parameters).solve(&arm, &unreachable).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Simple analytic keep-out volumes (boxes, half spaces, cylinders) used to reject inverse
+//! kinematics solutions that would bring the elbow, the wrist center or the TCP into them.
+//! This is not a collision check, but it covers most of the practical cell constraints
+//! (walls, tables, fences, columns) at almost no cost.
+
+use nalgebra::Vector3;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Keep-out volume in the world frame.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeepOutZone {
+    /// Axis aligned box between the two corners.
+    Box { min: Vector3<f64>, max: Vector3<f64> },
+    /// Everything on the side of the plane the normal points to.
+    HalfSpace { point: Vector3<f64>, normal: Vector3<f64> },
+    /// Cylinder starting at the base point and extending along the axis by its height.
+    Cylinder { base: Vector3<f64>, axis: Vector3<f64>, radius: f64, height: f64 },
+}
+
+impl KeepOutZone {
+    /// Checks if the point (world frame) is inside this zone.
+    pub fn contains(&self, p: &Vector3<f64>) -> bool {
+        match self {
+            KeepOutZone::Box { min, max } => {
+                (0..3).all(|i| p[i] >= min[i] && p[i] <= max[i])
+            }
+            KeepOutZone::HalfSpace { point, normal } => {
+                (p - point).dot(normal) > 0.0
+            }
+            KeepOutZone::Cylinder { base, axis, radius, height } => {
+                let axis = axis.normalize();
+                let d = p - base;
+                let along = d.dot(&axis);
+                along >= 0.0 && along <= *height && (d - along * axis).norm() <= *radius
+            }
+        }
+    }
+
+    /// Checks if the sphere (center in the world frame) reaches into this zone.
+    pub fn intersects_sphere(&self, center: &Vector3<f64>, radius: f64) -> bool {
+        match self {
+            KeepOutZone::Box { min, max } => {
+                let closest = Vector3::from_fn(|i, _| center[i].clamp(min[i], max[i]));
+                (center - closest).norm() <= radius
+            }
+            KeepOutZone::HalfSpace { point, normal } => {
+                (center - point).dot(&normal.normalize()) > -radius
+            }
+            KeepOutZone::Cylinder { base, axis, radius: r, height } => {
+                let axis = axis.normalize();
+                let d = center - base;
+                let along = d.dot(&axis);
+                let radial = ((d - along * axis).norm() - r).max(0.0);
+                let axial = (-along).max(along - height).max(0.0);
+                radial.hypot(axial) <= radius
+            }
+        }
+    }
+}
+
+/// Robot points that are checked against the keep-out zones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckedPoints {
+    pub elbow: bool,
+    pub wrist_center: bool,
+    pub tcp: bool,
+}
+
+impl Default for CheckedPoints {
+    fn default() -> Self {
+        CheckedPoints { elbow: true, wrist_center: true, tcp: true }
+    }
+}
+
+/// Set of keep-out zones for the given robot.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeepOutZones {
+    pub zones: Vec<KeepOutZone>,
+    /// Pose of the robot base in the world frame the zones are defined in.
+    pub base: Pose,
+    pub checked: CheckedPoints,
+}
+
+impl KeepOutZones {
+    pub fn new(zones: Vec<KeepOutZone>) -> Self {
+        KeepOutZones { zones, ..Default::default() }
+    }
+
+    /// Returns true if the robot in this joint position has any checked point inside any zone.
+    pub fn violated(&self, robot: &OPWKinematics, joints: &Joints) -> bool {
+        let links = robot.link_poses(joints);
+        let mut points: Vec<Vector3<f64>> = Vec::with_capacity(3);
+        if self.checked.elbow {
+            points.push(links[2].translation.vector);
+        }
+        if self.checked.wrist_center {
+            points.push(links[4].translation.vector);
+        }
+        if self.checked.tcp {
+            points.push(robot.forward(joints).translation.vector);
+        }
+        points.iter()
+            .map(|p| self.base.transform_point(&(*p).into()).coords)
+            .any(|p| self.zones.iter().any(|zone| zone.contains(&p)))
+    }
+
+    /// Returns only the solutions that do not enter any keep-out zone.
+    pub fn filter(&self, robot: &OPWKinematics, solutions: &Solutions) -> Solutions {
+        solutions.iter()
+            .filter(|joints| !self.violated(robot, joints))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Robot kinematics that never returns solutions entering keep-out zones. Poses are in the
+/// robot base frame, as for the wrapped robot; zones are in the world frame.
+#[derive(Debug, Clone)]
+pub struct KeepOutKinematics {
+    pub robot: OPWKinematics,
+    pub zones: KeepOutZones,
+}
+
+impl Stateless for KeepOutKinematics {}
+
+impl Kinematics for KeepOutKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.zones.filter(&self.robot, &self.robot.inverse(pose))
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.zones.filter(&self.robot, &self.robot.inverse_continuing(pose, previous))
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_zone_contains() {
+        let zone = KeepOutZone::Box { min: Vector3::new(0.0, 0.0, 0.0), max: Vector3::new(1.0, 1.0, 1.0) };
+        assert!(zone.contains(&Vector3::new(0.5, 0.5, 0.5)));
+        assert!(!zone.contains(&Vector3::new(1.5, 0.5, 0.5)));
+
+        let zone = KeepOutZone::HalfSpace { point: Vector3::zeros(), normal: -Vector3::z() };
+        assert!(zone.contains(&Vector3::new(3.0, 0.0, -0.1)));
+        assert!(!zone.contains(&Vector3::new(3.0, 0.0, 0.1)));
+
+        let zone = KeepOutZone::Cylinder {
+            base: Vector3::zeros(), axis: Vector3::z(), radius: 0.5, height: 2.0 };
+        assert!(zone.contains(&Vector3::new(0.3, 0.3, 1.0)));
+        assert!(!zone.contains(&Vector3::new(0.3, 0.3, 2.5)));
+        assert!(!zone.contains(&Vector3::new(0.4, 0.4, 1.0)));
+        assert!(zone.intersects_sphere(&Vector3::new(0.6, 0.0, 2.05), 0.15));
+        assert!(!zone.intersects_sphere(&Vector3::new(0.6, 0.0, 2.15), 0.15));
+
+        let zone = KeepOutZone::Box { min: Vector3::zeros(), max: Vector3::new(1.0, 1.0, 1.0) };
+        assert!(zone.intersects_sphere(&Vector3::new(1.1, 0.5, 0.5), 0.2));
+        assert!(!zone.intersects_sphere(&Vector3::new(1.1, 1.1, 1.1), 0.15));
+    }
+
+    #[test]
+    fn test_solutions_filtered() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.0, 0.3, 0.2, 0.0, 0.8, 0.0];
+        let pose = robot.forward(&joints);
+        let all = robot.inverse(&pose);
+
+        // Table top at 0.4 m removes solutions with the elbow down.
+        let zones = KeepOutZones::new(vec![KeepOutZone::HalfSpace {
+            point: Vector3::new(0.0, 0.0, 0.4), normal: -Vector3::z() }]);
+        let filtered = KeepOutKinematics { robot, zones }.inverse(&pose);
+        assert!(!filtered.is_empty());
+        assert!(filtered.len() < all.len());
+    }
+}
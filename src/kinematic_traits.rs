@@ -17,13 +17,65 @@ use na::{Isometry3};
 pub type Pose = Isometry3<f64>;
 
 /// Defines kinematic singularity. A is a singularity when J5 = 0 (this is possible with
-/// any robot). The structure is reserved for other possible singularies but these require
-/// b = 0 and a1 = a2 so not possible with most of the robots. 
+/// any robot). B is the overhead (alignment) singularity when the wrist center is on the J1
+/// axis, that requires b = 0. The structure is reserved for other possible singularies.
 /// Joints are counted from 1 to 6 in this comment.
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Singularity {
     /// Represents singularity when J5 = 0, possible with any robot.
     A,
+    /// Represents singularity when the wrist center is on the J1 axis: J1 does not move the
+    /// wrist center and can take any value, `inverse_continuing` keeps it from the previous
+    /// joints.
+    B,
+}
+
+/// Continuous distance of the robot from each kind of singularity, zero in the singularity,
+/// for slowing down gradually as the singularity approaches rather than on the binary flag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SingularityProximity {
+    /// |sin J5| in the kinematic model: zero in the wrist singularity (A), one with J5 at
+    /// ±90 degrees.
+    pub wrist: f64,
+    /// Distance of the wrist center from the J1 axis, meters: zero in the overhead
+    /// singularity (B).
+    pub alignment: f64,
+    /// |sin| of the angle between the upper arm and the forearm: zero with the arm fully
+    /// stretched or folded, at the boundary of the reach.
+    pub elbow: f64,
+}
+
+/// Arm configuration of the OPW robot (shoulder and elbow), as seen in robot programs.
+/// Within one configuration, the joint motion is continuous.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArmConfiguration {
+    /// Wrist center is in front of J1 axis (not reached over the head backwards).
+    pub front: bool,
+    /// Elbow is above the line from shoulder to wrist center.
+    pub elbow_up: bool,
+}
+
+impl ArmConfiguration {
+    /// All four arm configurations.
+    pub const ALL: [ArmConfiguration; 4] = [
+        ArmConfiguration { front: true, elbow_up: true },
+        ArmConfiguration { front: true, elbow_up: false },
+        ArmConfiguration { front: false, elbow_up: true },
+        ArmConfiguration { front: false, elbow_up: false },
+    ];
+}
+
+/// Full configuration of the OPW robot: arm configuration plus wrist flip.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Configuration {
+    pub arm: ArmConfiguration,
+    /// J5 is negative (in the kinematic model of the paper, after offsets and sign corrections).
+    pub wrist_flipped: bool,
 }
 
 /// Six rotary joints of the robot with angles in radians. 
@@ -40,6 +92,121 @@ pub const JOINTS_AT_ZERO: Joints = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
 /// given point).
 pub type Solutions = Vec<Joints>;
 
+/// Maximal number of solutions the solver can produce (8 analytic branches plus the
+/// singularity-specific solution added by `inverse_continuing`).
+pub const MAX_SOLUTIONS: usize = 9;
+
+/// Fixed capacity container of solutions that lives on the stack. This is what the
+/// allocation-free methods of the solver return, for use inside real time control loops.
+/// Dereferences into a slice of joints.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedSolutions {
+    joints: [Joints; MAX_SOLUTIONS],
+    len: usize,
+}
+
+impl FixedSolutions {
+    pub const fn new() -> Self {
+        FixedSolutions { joints: [[0.0; 6]; MAX_SOLUTIONS], len: 0 }
+    }
+
+    /// Appends the solution. Returns false (and discards the solution) if the container is full.
+    pub fn push(&mut self, joints: Joints) -> bool {
+        if self.len < MAX_SOLUTIONS {
+            self.joints[self.len] = joints;
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// The next free slot, to build the candidate solution in place. It becomes part of the
+    /// solutions only after `commit`. None if the container is full.
+    pub(crate) fn spare(&mut self) -> Option<&mut Joints> {
+        self.joints.get_mut(self.len)
+    }
+
+    /// Accepts the candidate built in the slot returned by `spare`.
+    pub(crate) fn commit(&mut self) {
+        debug_assert!(self.len < MAX_SOLUTIONS);
+        self.len += 1;
+    }
+
+    /// Copies the solutions into the vector (this allocates).
+    pub fn to_vec(&self) -> Solutions {
+        self.as_slice().to_vec()
+    }
+
+    pub fn as_slice(&self) -> &[Joints] {
+        &self.joints[..self.len]
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [Joints] {
+        &mut self.joints[..self.len]
+    }
+}
+
+impl Default for FixedSolutions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::Deref for FixedSolutions {
+    type Target = [Joints];
+
+    fn deref(&self) -> &[Joints] {
+        self.as_slice()
+    }
+}
+
+impl<'a> IntoIterator for &'a FixedSolutions {
+    type Item = &'a Joints;
+    type IntoIter = std::slice::Iter<'a, Joints>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl std::ops::DerefMut for FixedSolutions {
+    fn deref_mut(&mut self) -> &mut [Joints] {
+        self.as_mut_slice()
+    }
+}
+
+/// Serialized as the sequence of solutions, like `Solutions`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FixedSolutions {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.as_slice())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FixedSolutions {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let solutions = Solutions::deserialize(deserializer)?;
+        if solutions.len() > MAX_SOLUTIONS {
+            return Err(serde::de::Error::invalid_length(solutions.len(),
+                                                        &"at most MAX_SOLUTIONS solutions"));
+        }
+        let mut fixed = FixedSolutions::new();
+        for joints in solutions {
+            fixed.push(joints);
+        }
+        Ok(fixed)
+    }
+}
+
+/// Forward and inverse kinematics of the robot. The trait is object safe so the solver can be
+/// chosen at runtime and shared as `Arc<dyn Kinematics + Send + Sync>`, see
+/// [`crate::factory::create_kinematics`].
 pub trait Kinematics {
     /// Find inverse kinematics (joint position) for this pose
     /// This function is faster but does not handle the singularity J5 = 0 well.
@@ -61,3 +228,47 @@ pub trait Kinematics {
     fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity>;
 }
 
+
+/// Marker for solvers that keep no state between calls (the result only depends on the
+/// arguments), are cheap to clone and can be shared between threads without locking.
+/// Multithreaded planners can require this bound instead of wrapping the solver in a mutex.
+pub trait Stateless: Kinematics + Send + Sync + Clone {}
+
+/// Boxed solver (possibly a trait object) can be used wherever the solver is expected,
+/// for instance, wrapped into [`crate::mounting::Base`].
+impl<K: Kinematics + ?Sized> Kinematics for Box<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        (**self).inverse(pose)
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        (**self).inverse_continuing(pose, previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        (**self).forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        (**self).kinematic_singularity(qs)
+    }
+}
+
+/// Shared solver, as returned by [`crate::factory::create_kinematics`].
+impl<K: Kinematics + ?Sized> Kinematics for std::sync::Arc<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        (**self).inverse(pose)
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        (**self).inverse_continuing(pose, previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        (**self).forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        (**self).kinematic_singularity(qs)
+    }
+}
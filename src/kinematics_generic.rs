@@ -0,0 +1,359 @@
+//! OPW solver generic over the scalar type (`nalgebra::RealField`), for f32 builds on embedded
+//! targets and in GPU-friendly batch pipelines where f64 is costly. The analytic solution
+//! (`OpwCore`) is the one [`crate::kinematics_impl::OPWKinematics`] is built on, so both
+//! return the same branches; the singularity handling of `inverse_continuing` is only
+//! available in f64.
+
+use nalgebra::{convert, Isometry3, Matrix3, RealField, Rotation3, Translation3, UnitQuaternion,
+               Vector3};
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Joint values in the scalar type of the solver.
+pub type GenericJoints<T> = [T; 6];
+
+/// Analytic OPW solution in the scalar type: the parameters, the constants derived from them,
+/// and the forward and inverse kinematics in the angles of the kinematic model (paper
+/// convention, before offsets, sign corrections and J3 coupling).
+#[derive(Debug, Clone)]
+pub(crate) struct OpwCore<T: RealField + Copy> {
+    pub a1: T,
+    pub b: T,
+    pub c1: T,
+    pub c2: T,
+    pub c4: T,
+    pub offsets: [T; 6],
+    /// Sign corrections as floating point multipliers
+    pub signs: [T; 6],
+    pub j3_coupling: T,
+
+    /// Angle between the forearm line and the c3 segment, atan2(a2, c3)
+    pub psi3: T,
+    /// Forearm length including the a2 offset, sqrt(a2² + c3²)
+    pub k: T,
+    /// Squared forearm length, a2² + c3²
+    pub kappa_2: T,
+    /// Squared upper arm length, c2²
+    pub c2_2: T,
+}
+
+impl<T: RealField + Copy> OpwCore<T> {
+    pub fn new(parameters: &Parameters) -> Self {
+        let c = |v: f64| -> T { convert(v) };
+        let (a2, c3) = (c(parameters.a2), c(parameters.c3));
+        let kappa_2 = a2 * a2 + c3 * c3;
+        OpwCore {
+            a1: c(parameters.a1),
+            b: c(parameters.b),
+            c1: c(parameters.c1),
+            c2: c(parameters.c2),
+            c4: c(parameters.c4),
+            offsets: parameters.offsets.map(c),
+            signs: parameters.sign_corrections.map(|s| c(s as f64)),
+            j3_coupling: c(parameters.j3_coupling),
+            psi3: a2.atan2(c3),
+            k: kappa_2.sqrt(),
+            kappa_2,
+            c2_2: c(parameters.c2) * c(parameters.c2),
+        }
+    }
+
+    /// Converts joint values into the angles of the kinematic model, removing the J3 coupling
+    /// and applying sign corrections and offsets.
+    pub fn model_angles(&self, joints: &GenericJoints<T>) -> [T; 6] {
+        let mut joints = *joints;
+        joints[2] -= self.j3_coupling * joints[1];
+        std::array::from_fn(|i| joints[i] * self.signs[i] - self.offsets[i])
+    }
+
+    /// Distance of the wrist center from the J1 axis along the arm (J1 rotation removed,
+    /// without the b offset) and its height above the shoulder, for the model angles q2, q3.
+    pub fn wrist_center_in_arm(&self, q2: T, q3: T) -> (T, T) {
+        (self.c2 * q2.sin() + self.k * (q2 + q3 + self.psi3).sin() + self.a1,
+         self.c2 * q2.cos() + self.k * (q2 + q3 + self.psi3).cos())
+    }
+
+    /// Pose of the flange for the angles of the kinematic model.
+    pub fn forward_model(&self, q: &[T; 6]) -> Isometry3<T> {
+        let [q1, q2, q3, q4, q5, q6] = *q;
+        let (cx1, cz1) = self.wrist_center_in_arm(q2, q3);
+        let cy1 = self.b;
+
+        let (s1, c1) = q1.sin_cos();
+        let (s2, c2) = q2.sin_cos();
+        let (s3, c3) = q3.sin_cos();
+        let (s4, c4) = q4.sin_cos();
+        let (s5, c5) = q5.sin_cos();
+        let (s6, c6) = q6.sin_cos();
+
+        let r_0c = Matrix3::new(
+            c1 * c2 * c3 - c1 * s2 * s3, -s1, c1 * c2 * s3 + c1 * s2 * c3,
+            s1 * c2 * c3 - s1 * s2 * s3, c1, s1 * c2 * s3 + s1 * s2 * c3,
+            -s2 * c3 - c2 * s3, T::zero(), -s2 * s3 + c2 * c3,
+        );
+        let r_ce = Matrix3::new(
+            c4 * c5 * c6 - s4 * s6, -c4 * c5 * s6 - s4 * c6, c4 * s5,
+            s4 * c5 * c6 + c4 * s6, -s4 * c5 * s6 + c4 * c6, s4 * s5,
+            -s5 * c6, s5 * s6, c5,
+        );
+        let r_oe = r_0c * r_ce;
+
+        let wrist = Vector3::new(cx1 * c1 - cy1 * s1, cx1 * s1 + cy1 * c1, cz1 + self.c1);
+        let translation = wrist + r_oe.column(2) * self.c4;
+        Isometry3::from_parts(Translation3::from(translation),
+                              UnitQuaternion::from_rotation_matrix(
+                                  &Rotation3::from_matrix_unchecked(r_oe)))
+    }
+
+    /// Arm part of the inverse kinematics: theta1, theta2 and theta3 for each of the four arm
+    /// configurations, and whether acos or sqrt arguments were clamped for them. Arguments
+    /// exceeding the domain by less than `domain_epsilon` are clamped to the boundary,
+    /// unreachable configurations contain NaN. Theta1 of the first two configurations is
+    /// computed from the direction of the wrist center unless given (for the wrist center on
+    /// the J1 axis where this direction is undefined).
+    pub fn arm_branches(&self, pose: &Isometry3<T>, theta1: Option<T>, domain_epsilon: T)
+                        -> ([[T; 3]; 4], [bool; 4]) {
+        let two: T = convert(2.0);
+        let mut clamped_1 = false;
+        let mut clamped_2 = false;
+        let acos = |x: T, clamped: &mut bool| {
+            if x.abs() > T::one() && x.abs() <= T::one() + domain_epsilon {
+                *clamped = true;
+                x.signum().acos()
+            } else {
+                x.acos()
+            }
+        };
+
+        // Adjust to wrist center
+        let matrix = pose.rotation.to_rotation_matrix();
+        let c = pose.translation.vector - matrix.matrix().column(2) * self.c4;
+
+        let mut nx1_2 = c.x * c.x + c.y * c.y - self.b * self.b;
+        if nx1_2 < T::zero() && nx1_2 >= -domain_epsilon {
+            // Wrist center inside the b offset cylinder due to rounding, affects all branches
+            nx1_2 = T::zero();
+            clamped_1 = true;
+            clamped_2 = true;
+        }
+        let nx1 = nx1_2.sqrt() - self.a1;
+
+        let tmp2 = self.b.atan2(nx1 + self.a1);
+        let tmp1 = match theta1 {
+            Some(theta1) => theta1 + tmp2,
+            None => c.y.atan2(c.x),
+        };
+        let theta1_i = tmp1 - tmp2;
+        let theta1_ii = tmp1 + tmp2 - T::pi();
+
+        let tmp3 = c.z - self.c1;
+        let s1_2 = nx1 * nx1 + tmp3 * tmp3;
+        let tmp4 = nx1 + two * self.a1;
+        let s2_2 = tmp4 * tmp4 + tmp3 * tmp3;
+
+        let tmp13 = acos((s1_2 + self.c2_2 - self.kappa_2) / (two * s1_2.sqrt() * self.c2),
+                         &mut clamped_1);
+        let tmp14 = nx1.atan2(tmp3);
+        let tmp15 = acos((s2_2 + self.c2_2 - self.kappa_2) / (two * s2_2.sqrt() * self.c2),
+                         &mut clamped_2);
+        let tmp16 = tmp4.atan2(tmp3);
+
+        let tmp9 = two * self.c2 * self.k;
+        let tmp11 = acos((s1_2 - self.c2_2 - self.kappa_2) / tmp9, &mut clamped_1);
+        let tmp12 = acos((s2_2 - self.c2_2 - self.kappa_2) / tmp9, &mut clamped_2);
+
+        ([
+            [theta1_i, -tmp13 + tmp14, tmp11 - self.psi3],
+            [theta1_i, tmp13 + tmp14, -tmp11 - self.psi3],
+            [theta1_ii, -tmp15 - tmp16, tmp12 - self.psi3],
+            [theta1_ii, tmp15 - tmp16, -tmp12 - self.psi3],
+        ], [clamped_1, clamped_1, clamped_2, clamped_2])
+    }
+
+    /// Orientation part of the inverse kinematics. Each arm configuration has two wrist
+    /// solutions: the one computed here goes to the first half of the solution table, the
+    /// flipped one (theta5 negated, theta4 and theta6 rotated by 180 degrees) goes to the
+    /// second half.
+    pub fn wrist_branches(&self, pose: &Isometry3<T>, arm: &[[T; 3]; 4], domain_epsilon: T)
+                          -> [[T; 6]; 8] {
+        let pi = T::pi();
+        let matrix = pose.rotation.to_rotation_matrix();
+        let m = |r: usize, c: usize| matrix[(r, c)];
+        let zero_threshold: T = convert(1E-6);
+        let mut theta = [[T::zero(); 6]; 8];
+        for (i, &[theta1, theta2, theta3]) in arm.iter().enumerate() {
+            let (sin1, cos1) = theta1.sin_cos();
+            let (s23, c23) = (theta2 + theta3).sin_cos();
+
+            let mm = m(0, 2) * s23 * cos1 + m(1, 2) * s23 * sin1 + m(2, 2) * c23;
+            let mut sin5_2 = T::one() - mm * mm;
+            if sin5_2 < T::zero() && sin5_2 >= -domain_epsilon {
+                sin5_2 = T::zero();
+            }
+            let theta5 = sin5_2.sqrt().atan2(mm);
+
+            let (theta4, theta6) = if theta5.abs() < zero_threshold {
+                // Wrist singularity: J4 and J6 are aligned, put all rotation on J6
+                (T::zero(), singular_theta6(&matrix, sin1, cos1))
+            } else {
+                let theta4_y = m(1, 2) * cos1 - m(0, 2) * sin1;
+                let theta4_x = m(0, 2) * c23 * cos1 + m(1, 2) * c23 * sin1 - m(2, 2) * s23;
+                let theta6_y = m(0, 1) * s23 * cos1 + m(1, 1) * s23 * sin1 + m(2, 1) * c23;
+                let theta6_x = -m(0, 0) * s23 * cos1 - m(1, 0) * s23 * sin1 - m(2, 0) * c23;
+                (theta4_y.atan2(theta4_x), theta6_y.atan2(theta6_x))
+            };
+
+            theta[i] = [theta1, theta2, theta3, theta4, theta5, theta6];
+            theta[i + 4] = [theta1, theta2, theta3, theta4 + pi, -theta5, theta6 - pi];
+        }
+        theta
+    }
+
+    /// Converts the model angles of one branch into joint values (offsets, sign corrections
+    /// and J3 coupling applied), wrapped into [-pi, pi] if told. Writes into the given joints,
+    /// returning false if any angle is not finite (the content of joints is then unspecified).
+    pub fn normalize_into(&self, theta: &[T; 6], sol: &mut GenericJoints<T>, wrap: bool) -> bool {
+        let wrapped = |angle: T| if wrap { wrap_to_pi(angle) } else { angle };
+        for j in 0..6 {
+            let angle = (theta[j] + self.offsets[j]) * self.signs[j];
+            if !angle.is_finite() {
+                return false;
+            }
+            sol[j] = wrapped(angle);
+        }
+        if self.j3_coupling != T::zero() {
+            sol[2] = wrapped(sol[2] + self.j3_coupling * sol[1]);
+        }
+        true
+    }
+}
+
+/// Theta6 in the wrist singularity (theta5 = 0) when theta4 is set to zero, given the
+/// rotation matrix of the pose and sine and cosine of theta1.
+pub(crate) fn singular_theta6<T: RealField + Copy>(matrix: &Rotation3<T>, sin1: T, cos1: T) -> T {
+    let matrix = matrix.matrix();
+    let yc = Vector3::new(-sin1, cos1, T::zero());
+    let xc = yc.cross(&matrix.column(2));
+    let xe = matrix.column(0);
+    yc.dot(&xe).atan2(xc.dot(&xe))
+}
+
+/// Angle wrapped into [-pi, pi], as `crate::utils::wrap_to_pi`.
+fn wrap_to_pi<T: RealField + Copy>(angle: T) -> T {
+    let pi = T::pi();
+    if (angle >= -pi && angle <= pi) || !angle.is_finite() {
+        return angle;
+    }
+    let mut wrapped = (angle + pi) % T::two_pi();
+    if wrapped < T::zero() {
+        wrapped += T::two_pi();
+    }
+    wrapped -= pi;
+    if wrapped == -pi { pi } else { wrapped }
+}
+
+/// Kinematics of the OPW robot in the given scalar type (f32 or f64).
+#[derive(Debug, Clone)]
+pub struct GenericOPWKinematics<T: RealField + Copy> {
+    core: OpwCore<T>,
+
+    /// Maximal distance between the requested pose and the forward kinematics of the solution.
+    pub distance_tolerance: T,
+    /// Maximal angle between the requested pose and the forward kinematics of the solution.
+    pub angular_tolerance: T,
+}
+
+impl<T: RealField + Copy> GenericOPWKinematics<T> {
+    /// Creates the solver, converting f64 parameters into the scalar type. The verification
+    /// tolerances are 1 micrometer and 1 microradian, unless the scalar type has much lower
+    /// precision (f32), where they are set to 1000 machine epsilons.
+    pub fn new(parameters: &Parameters) -> Self {
+        let tolerance = convert::<f64, T>(1E-6).max(T::default_epsilon() * convert(1000.0));
+        GenericOPWKinematics {
+            core: OpwCore::new(parameters),
+            distance_tolerance: tolerance,
+            angular_tolerance: tolerance,
+        }
+    }
+
+    /// Forward kinematics, pose of the flange from joint positions.
+    pub fn forward(&self, joints: &GenericJoints<T>) -> Isometry3<T> {
+        self.core.forward_model(&self.core.model_angles(joints))
+    }
+
+    /// Inverse kinematics. Returns all valid solutions, normalized into [-pi, pi] and
+    /// cross-checked with forward kinematics.
+    pub fn inverse(&self, pose: &Isometry3<T>) -> Vec<GenericJoints<T>> {
+        let (arm, _) = self.core.arm_branches(pose, None, T::zero());
+        let theta = self.core.wrist_branches(pose, &arm, T::zero());
+        let mut result = Vec::with_capacity(8);
+        for branch in theta.iter() {
+            let mut sol = [T::zero(); 6];
+            if !self.core.normalize_into(branch, &mut sol, true) {
+                continue;
+            }
+            let check = self.forward(&sol);
+            if (check.translation.vector - pose.translation.vector).norm() <= self.distance_tolerance
+                && check.rotation.angle_to(&pose.rotation) <= self.angular_tolerance {
+                result.push(sol);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::Kinematics;
+    use crate::kinematics_impl::OPWKinematics;
+
+    #[test]
+    fn test_f64_matches_reference() {
+        let parameters = Parameters::kuka_kr6_r700_sixx();
+        let reference = OPWKinematics::new(parameters.clone());
+        let generic = GenericOPWKinematics::<f64>::new(&parameters);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+
+        let expected = reference.forward(&joints);
+        let pose = generic.forward(&joints);
+        assert!((pose.translation.vector - expected.translation.vector).norm() < 1E-12);
+        assert!(pose.rotation.angle_to(&expected.rotation) < 1E-9);
+
+        let expected = reference.inverse(&expected);
+        let solutions = generic.inverse(&pose);
+        assert_eq!(solutions.len(), expected.len());
+        for (a, b) in solutions.iter().zip(expected.iter()) {
+            for j in 0..6 {
+                assert!((a[j] - b[j]).abs() < 1E-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_j3_coupling() {
+        let parameters = Parameters { j3_coupling: -1.0, ..Parameters::staubli_tx40() };
+        let reference = OPWKinematics::new(parameters.clone());
+        let generic = GenericOPWKinematics::<f64>::new(&parameters);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = generic.forward(&joints);
+        assert!((pose.translation.vector - reference.forward(&joints).translation.vector).norm()
+            < 1E-12);
+        let (solutions, expected) = (generic.inverse(&pose), reference.inverse(&pose));
+        assert_eq!(solutions.len(), expected.len());
+        for (a, b) in solutions.iter().zip(expected.iter()) {
+            assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1E-9));
+        }
+    }
+
+    #[test]
+    fn test_f32_round_trip() {
+        let robot = GenericOPWKinematics::<f32>::new(&Parameters::irb2400_10());
+        let joints: [f32; 6] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let solutions = robot.inverse(&pose);
+        assert!(!solutions.is_empty());
+        assert!(solutions.iter().any(|s| {
+            s.iter().zip(joints.iter()).all(|(a, b)| (a - b).abs() < 1E-3)
+        }));
+    }
+}
@@ -1,35 +1,141 @@
 use std::f64::{consts::PI};
-use crate::kinematic_traits::{Kinematics, Solutions, Pose, Singularity, Joints};
-use crate::parameters::opw_kinematics::{Parameters};
+use crate::kinematic_traits::{Kinematics, Solutions, Pose, Singularity, Joints, ArmConfiguration,
+                              Configuration, FixedSolutions, SingularityProximity, Stateless};
+use crate::parameters::opw_kinematics::{Parameters, RobotModel};
 use crate::utils::opw_kinematics::{is_valid};
-use nalgebra::{Isometry3, Matrix3, OVector, Rotation3, Translation3, U3, Unit, UnitQuaternion,
-               Vector3};
-
-const DEBUG: bool = false;
+use crate::utils::{angle_difference, compare_poses, normalize_near, sort_by_closeness};
+use crate::kinematics_generic::OpwCore;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
 
+/// The solver has no mutable state: each call only depends on its arguments, so one instance
+/// can be shared between threads without locking, and cloning it is cheap (no heap data).
+#[derive(Debug, Clone)]
 pub struct OPWKinematics {
     parameters: Parameters,
-    unit_z: Unit<OVector<f64, U3>>,
+    /// Analytic solution shared with the generic solver, with the constants derived from the
+    /// parameters computed once in the constructor.
+    core: OpwCore<f64>,
+
+    /// Arguments of acos and sqrt that exceed their domain by less than this value are
+    /// clamped to the boundary, see `with_domain_epsilon`.
+    domain_epsilon: f64,
+
+    /// Solutions are wrapped into [-pi, pi], see `with_wrap`.
+    wrap: bool,
 }
 
 impl OPWKinematics {
     /// Creates a new `OPWKinematics` instance with the given parameters.
     #[allow(dead_code)]
+    #[inline]
     pub fn new(parameters: Parameters) -> Self {
         OPWKinematics {
+            core: OpwCore::new(&parameters),
             parameters,
-            unit_z: Unit::new_normalize(Vector3::z_axis().into_inner()),
+            domain_epsilon: 0.0,
+            wrap: true,
+        }
+    }
+
+    /// Allows poses marginally beyond the reach (as they often are after floating point round
+    /// trips): acos and sqrt arguments exceeding their domain by less than epsilon are clamped
+    /// and the boundary solution (arm fully extended or folded) is produced instead of NaN.
+    /// Such solutions miss the requested position slightly, they are returned (flagged as
+    /// clamped) by `inverse_detailed` only. Default epsilon is 0 (no clamping).
+    pub fn with_domain_epsilon(mut self, epsilon: f64) -> Self {
+        self.domain_epsilon = epsilon;
+        self
+    }
+
+    /// If false, the solutions are not wrapped into [-pi, pi] but returned as the raw branch
+    /// angles (within about ±2 pi, offsets and sign corrections applied), for the robots with
+    /// joint limits beyond ±180 degrees and for keeping count of the turns. The wrap the
+    /// default mode would apply is reported by `inverse_detailed`. Default is true.
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Maximal position error of the clamped solution that is still accepted. The acos
+    /// arguments change by about 1 / reach per meter, so this is epsilon times the reach.
+    pub(crate) fn clamped_distance_tolerance(&self) -> f64 {
+        DISTANCE_TOLERANCE + 2.0 * self.domain_epsilon * (self.parameters.c2 + self.core.k)
+    }
+
+    /// Creates the solver for the robot model selected at compile time, see
+    /// [`crate::robot_model`]. As the parameters are constants here, the derived constants
+    /// can be folded at compile time when this call is inlined.
+    #[inline]
+    pub fn from_model<R: RobotModel>() -> Self {
+        Self::new(R::PARAMETERS)
+    }
+
+    /// Converts joint values into the angles of the kinematic model (paper convention),
+    /// removing the J3 coupling and applying sign corrections and offsets.
+    fn model_angles(&self, joints: &Joints) -> [f64; 6] {
+        self.core.model_angles(joints)
+    }
+
+    /// Returns the poses of the robot links 1 to 6 in the base frame. The pose of each link
+    /// is located at the origin of its joint, with the joint rotation already applied. The
+    /// last link is located at the wrist center; the flange is c4 further along its z axis
+    /// and its pose is returned by `forward`.
+    pub fn link_poses(&self, joints: &Joints) -> [Pose; 6] {
+        let p = &self.parameters;
+        let q = self.model_angles(joints);
+
+        let rz = |angle: f64| UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle);
+        let ry = |angle: f64| UnitQuaternion::from_axis_angle(&Vector3::y_axis(), angle);
+        let link = |x: f64, y: f64, z: f64, rotation: UnitQuaternion<f64>| {
+            Pose::from_parts(Translation3::new(x, y, z), rotation)
+        };
+
+        let l1 = link(0.0, 0.0, 0.0, rz(q[0]));
+        let l2 = l1 * link(p.a1, p.b, p.c1, ry(q[1]));
+        let l3 = l2 * link(0.0, 0.0, p.c2, ry(q[2]));
+        let l4 = l3 * link(p.a2, 0.0, 0.0, rz(q[3]));
+        let l5 = l4 * link(0.0, 0.0, p.c3, ry(q[4]));
+        let l6 = l5 * link(0.0, 0.0, 0.0, rz(q[5]));
+        [l1, l2, l3, l4, l5, l6]
+    }
+
+    /// Parameters this robot has been constructed with.
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    /// Returns the configuration (shoulder, elbow, wrist flip) of the given joint position.
+    pub fn configuration(&self, joints: &Joints) -> Configuration {
+        let q = self.model_angles(joints);
+        let (q2, q3, q5) = (q[J2], q[J3], q[J5]);
+        let psi3 = self.core.psi3;
+
+        // Wrist center in the arm plane, J1 rotation removed
+        let (cx1, _) = self.core.wrist_center_in_arm(q2, q3);
+        let front = cx1 >= 0.0;
+        // Sign of the cross product (wrist - shoulder) x (elbow - shoulder) is the sign
+        // of sin(q3 + psi3). The elbow is "up" if it is above the line when looking
+        // towards the wrist center.
+        let elbow_up = ((q3 + psi3).sin() > 0.0) == front;
+
+        Configuration {
+            arm: ArmConfiguration { front, elbow_up },
+            wrist_flipped: q5.sin() < 0.0,
         }
     }
 }
 
 const MM: f64 = 0.001;
-const DISTANCE_TOLERANCE: f64 = 0.001 * MM;
-const ANGULAR_TOLERANCE: f64 = 1E-6;
+pub(crate) const DISTANCE_TOLERANCE: f64 = 0.001 * MM;
+pub(crate) const ANGULAR_TOLERANCE: f64 = 1E-6;
 
 // Use for singularity checks.
 const SINGULARITY_ANGLE_THR: f64 = 0.01 * PI / 180.0;
 
+/// Distance of the wrist center from the J1 axis below which the robot is in the overhead
+/// (alignment) singularity, where the direction of the wrist center is lost in rounding.
+const SINGULARITY_DISTANCE_THR: f64 = 0.01 * MM;
+
 // Define indices for easier reading (numbering in array starts from 0 and this one-off is
 // contra - intuitive)
 #[allow(dead_code)]
@@ -45,303 +151,186 @@ const J5: usize = 4;
 #[allow(dead_code)]
 const J6: usize = 5;
 
-impl Kinematics for OPWKinematics {
-    fn inverse(&self, pose: &Pose) -> Solutions {
-        let params = &self.parameters;
-
-        // Adjust to wrist center
-        let matrix = pose.rotation.to_rotation_matrix();
-        let translation_vector = pose.translation.vector; // Get the translation vector component
-        let scaled_z_axis = params.c4 * matrix.transform_vector(&Vector3::z_axis()); // Scale and rotate the z-axis vector
+impl OPWKinematics {
+    /// Same as `inverse` but guaranteed not to allocate on the heap: the solutions are
+    /// returned in the fixed capacity container on the stack. Use in real time loops.
+    pub fn inverse_fixed(&self, pose: &Pose) -> FixedSolutions {
+        let theta = self.wrist_branches(pose, &self.arm_branches(pose));
+        self.verified_solutions(pose, &theta)
+    }
 
-        let c = translation_vector - scaled_z_axis;
-
-        let nx1 = ((c.x * c.x + c.y * c.y) - params.b * params.b).sqrt() - params.a1;
+    /// Same solutions as `inverse`, in the same order, yielded one by one. The analytic
+    /// branches are computed upfront, but the forward kinematics cross-check only runs for
+    /// the solutions actually consumed, so taking the first acceptable one is cheaper.
+    pub fn inverse_iter(&self, pose: &Pose) -> impl Iterator<Item = Joints> + '_ {
+        let pose = *pose;
+        let theta = self.wrist_branches(&pose, &self.arm_branches(&pose));
+        theta.into_iter().filter_map(move |theta| {
+            let joints = self.normalized_joints(&theta)?;
+            self.verify(&pose, &joints, false).then_some(joints)
+        })
+    }
 
-        let tmp1 = c.y.atan2(c.x); // Rust's method call syntax for atan2(y, x)
-        let tmp2 = params.b.atan2(nx1 + params.a1);
-
-        let theta1_i = tmp1 - tmp2;
-        let theta1_ii = tmp1 + tmp2 - PI;
-
-        let tmp3 = c.z - params.c1; // Access z directly for nalgebra's Vector3
-        let s1_2 = nx1 * nx1 + tmp3 * tmp3;
-
-        let tmp4 = nx1 + 2.0 * params.a1;
-        let s2_2 = tmp4 * tmp4 + tmp3 * tmp3;
-        let kappa_2 = params.a2 * params.a2 + params.c3 * params.c3;
-
-        let c2_2 = params.c2 * params.c2;
-
-        let tmp5 = s1_2 + c2_2 - kappa_2;
-
-        let s1 = f64::sqrt(s1_2);
-        let s2 = f64::sqrt(s2_2);
-
-        let tmp13 = f64::acos(tmp5 / (2.0 * s1 * params.c2));
-        let tmp14 = f64::atan2(nx1, c.z - params.c1);
-        let theta2_i = -tmp13 + tmp14;
-        let theta2_ii = tmp13 + tmp14;
-
-        let tmp6 = s2_2 + c2_2 - kappa_2;
-
-        let tmp15 = f64::acos(tmp6 / (2.0 * s2 * params.c2));
-        let tmp16 = f64::atan2(nx1 + 2.0 * params.a1, c.z - params.c1);
-        let theta2_iii = -tmp15 - tmp16;
-        let theta2_iv = tmp15 - tmp16;
-
-        // theta3
-        let tmp7 = s1_2 - c2_2 - kappa_2;
-        let tmp8 = s2_2 - c2_2 - kappa_2;
-        let tmp9 = 2.0 * params.c2 * f64::sqrt(kappa_2);
-        let tmp10 = f64::atan2(params.a2, params.c3);
-
-        let tmp11 = f64::acos(tmp7 / tmp9);
-        let theta3_i = tmp11 - tmp10;
-        let theta3_ii = -tmp11 - tmp10;
-
-        let tmp12 = f64::acos(tmp8 / tmp9);
-        let theta3_iii = tmp12 - tmp10;
-        let theta3_iv = -tmp12 - tmp10;
-
-        let theta1_i_sin = theta1_i.sin();
-        let theta1_i_cos = theta1_i.cos();
-        let theta1_ii_sin = theta1_ii.sin();
-        let theta1_ii_cos = theta1_ii.cos();
-
-        // orientation part
-        let sin1: [f64; 4] = [
-            theta1_i_sin, theta1_i_sin, theta1_ii_sin, theta1_ii_sin,
-        ];
-
-        let cos1: [f64; 4] = [
-            theta1_i_cos, theta1_i_cos, theta1_ii_cos, theta1_ii_cos
-        ];
-
-        let s23: [f64; 4] = [
-            (theta2_i + theta3_i).sin(),
-            (theta2_ii + theta3_ii).sin(),
-            (theta2_iii + theta3_iii).sin(),
-            (theta2_iv + theta3_iv).sin(),
-        ];
-
-        let c23: [f64; 4] = [
-            (theta2_i + theta3_i).cos(),
-            (theta2_ii + theta3_ii).cos(),
-            (theta2_iii + theta3_iii).cos(),
-            (theta2_iv + theta3_iv).cos(),
-        ];
-
-        let m: [f64; 4] = [
-            matrix[(0, 2)] * s23[0] * cos1[0] + matrix[(1, 2)] * s23[0] * sin1[0] + matrix[(2, 2)] * c23[0],
-            matrix[(0, 2)] * s23[1] * cos1[1] + matrix[(1, 2)] * s23[1] * sin1[1] + matrix[(2, 2)] * c23[1],
-            matrix[(0, 2)] * s23[2] * cos1[2] + matrix[(1, 2)] * s23[2] * sin1[2] + matrix[(2, 2)] * c23[2],
-            matrix[(0, 2)] * s23[3] * cos1[3] + matrix[(1, 2)] * s23[3] * sin1[3] + matrix[(2, 2)] * c23[3],
-        ];
-
-        let theta5_i = f64::atan2((1.0 - m[0] * m[0]).sqrt(), m[0]);
-        let theta5_ii = f64::atan2((1.0 - m[1] * m[1]).sqrt(), m[1]);
-        let theta5_iii = f64::atan2((1.0 - m[2] * m[2]).sqrt(), m[2]);
-        let theta5_iv = f64::atan2((1.0 - m[3] * m[3]).sqrt(), m[3]);
-
-        let theta5_v = -theta5_i;
-        let theta5_vi = -theta5_ii;
-        let theta5_vii = -theta5_iii;
-        let theta5_viii = -theta5_iv;
-
-        let zero_threshold: f64 = 1e-6;
-        let theta4_i;
-        let theta6_i;
-
-        if theta5_i.abs() < zero_threshold {
-            theta4_i = 0.0;
-            let xe = Vector3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]);
-            let mut rc = Matrix3::zeros(); // Assuming Matrix3::zeros() creates a 3x3 matrix filled with 0.0
-
-            // Set columns of Rc
-            rc.set_column(1, &Vector3::new(-theta1_i.sin(), theta1_i.cos(), 0.0)); // yc
-            rc.set_column(2, &Vector3::new(matrix[(0, 2)], matrix[(1, 2)], matrix[(2, 2)])); // zc = ze
-            rc.set_column(0, &rc.column(1).cross(&rc.column(2))); // xc
-
-            let xec = rc.transpose() * xe;
-            theta6_i = xec[1].atan2(xec[0]);
-        } else {
-            let theta4_iy = matrix[(1, 2)] * cos1[0] - matrix[(0, 2)] * sin1[0];
-            let theta4_ix = matrix[(0, 2)] * c23[0] * cos1[0] + matrix[(1, 2)] * c23[0] * sin1[0] - matrix[(2, 2)] * s23[0];
-            theta4_i = theta4_iy.atan2(theta4_ix);
+    /// Orientation part of the inverse kinematics. Each arm configuration has two wrist
+    /// solutions: the one computed here goes to the first half of the solution table, the
+    /// flipped one (theta5 negated, theta4 and theta6 rotated by 180 degrees) goes to the
+    /// second half. Angles are in the kinematic model convention, as for `arm_branches`.
+    pub(crate) fn wrist_branches(&self, pose: &Pose, arm: &[[f64; 3]; 4]) -> [[f64; 6]; 8] {
+        self.core.wrist_branches(pose, arm, self.domain_epsilon)
+    }
 
-            let theta6_iy = matrix[(0, 1)] * s23[0] * cos1[0] + matrix[(1, 1)] * s23[0] * sin1[0] + matrix[(2, 1)] * c23[0];
-            let theta6_ix = -matrix[(0, 0)] * s23[0] * cos1[0] - matrix[(1, 0)] * s23[0] * sin1[0] - matrix[(2, 0)] * c23[0];
-            theta6_i = theta6_iy.atan2(theta6_ix);
-        }
+    /// Arm part of the inverse kinematics: theta1, theta2 and theta3 (angles of the kinematic
+    /// model, before offsets and sign corrections) for each of the four arm configurations.
+    /// Unreachable configurations contain NaN.
+    pub(crate) fn arm_branches(&self, pose: &Pose) -> [[f64; 3]; 4] {
+        self.arm_branches_clamped(pose).0
+    }
 
-        let theta4_ii;
-        let theta6_ii;
+    /// Same as `arm_branches`, also telling which arm configurations were computed with
+    /// clamped acos or sqrt arguments (see `with_domain_epsilon`).
+    pub(crate) fn arm_branches_clamped(&self, pose: &Pose) -> ([[f64; 3]; 4], [bool; 4]) {
+        self.arm_branches_toward(pose, None)
+    }
 
-        if theta5_ii.abs() < zero_threshold {
-            theta4_ii = 0.0;
-            let xe = Vector3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]);
-            let mut rc = Matrix3::zeros();
+    /// Same as `arm_branches_clamped`, with theta1 of the first two configurations given
+    /// rather than computed from the direction of the wrist center, for the wrist center on
+    /// the J1 axis where this direction is undefined.
+    fn arm_branches_toward(&self, pose: &Pose, theta1: Option<f64>) -> ([[f64; 3]; 4], [bool; 4]) {
+        self.core.arm_branches(pose, theta1, self.domain_epsilon)
+    }
 
-            // Set columns of Rc
-            rc.set_column(1, &Vector3::new(-theta1_i.sin(), theta1_i.cos(), 0.0)); // yc
-            rc.set_column(2, &Vector3::new(matrix[(0, 2)], matrix[(1, 2)], matrix[(2, 2)])); // zc = ze
-            rc.set_column(0, &rc.column(1).cross(&rc.column(2))); // xc
+    /// Same as `inverse_fixed`, but if the wrist center is on the J1 axis (singularity B),
+    /// theta1 is taken from the previous joints instead of the undefined direction.
+    fn inverse_fixed_near(&self, pose: &Pose, previous: &Joints) -> FixedSolutions {
+        let center = pose.translation.vector
+            - self.parameters.c4 * (pose.rotation * Vector3::z_axis().into_inner());
+        if center.x.hypot(center.y) >= SINGULARITY_DISTANCE_THR {
+            return self.inverse_fixed(pose);
+        }
+        let theta1 = previous[J1] * self.core.signs[J1] - self.parameters.offsets[J1];
+        let (arm, _) = self.arm_branches_toward(pose, Some(theta1));
+        self.verified_solutions(pose, &self.wrist_branches(pose, &arm))
+    }
 
-            let xec = rc.transpose() * xe;
-            theta6_ii = xec[1].atan2(xec[0]);
-        } else {
-            let theta4_iiy = matrix[(1, 2)] * cos1[1] - matrix[(0, 2)] * sin1[1];
-            let theta4_iix = matrix[(0, 2)] * c23[1] * cos1[1] + matrix[(1, 2)] * c23[1] * sin1[1] - matrix[(2, 2)] * s23[1];
-            theta4_ii = theta4_iiy.atan2(theta4_iix);
+    /// Continuous distance from the singularities, see `SingularityProximity`. The flag of
+    /// `kinematic_singularity` is set when it falls below the (small) thresholds.
+    pub fn singularity_proximity(&self, joints: &Joints) -> SingularityProximity {
+        let p = &self.parameters;
+        let [_, q2, q3, _, q5, _] = self.model_angles(joints);
+        // Distance of the wrist center from the J1 axis, as in forward
+        let (cx1, _) = self.core.wrist_center_in_arm(q2, q3);
+        SingularityProximity {
+            wrist: q5.sin().abs(),
+            alignment: cx1.hypot(p.b),
+            elbow: (q3 + self.core.psi3).sin().abs(),
+        }
+    }
 
-            let theta6_iiy = matrix[(0, 1)] * s23[1] * cos1[1] + matrix[(1, 1)] * s23[1] * sin1[1] + matrix[(2, 1)] * c23[1];
-            let theta6_iix = -matrix[(0, 0)] * s23[1] * cos1[1] - matrix[(1, 0)] * s23[1] * sin1[1] - matrix[(2, 0)] * c23[1];
-            theta6_ii = theta6_iiy.atan2(theta6_iix);
+    /// Applies offsets and sign corrections to the analytic branches, normalizes the angles
+    /// and keeps only the solutions that pass the forward kinematics cross-check.
+    pub(crate) fn verified_solutions(&self, pose: &Pose, theta: &[[f64; 6]; 8]) -> FixedSolutions {
+        let mut result = FixedSolutions::new();
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        for (si, theta) in theta.iter().enumerate() {
+            // The candidate is built directly in the free slot of the result and only
+            // committed if valid, so the joint values are never copied around.
+            let Some(sol) = result.spare() else { break };
+            if self.normalize_into(theta, sol, self.wrap) {
+                let check_pose = self.forward(sol);
+                if compare_poses(pose, &check_pose, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE).is_ok() {
+                    result.commit();
+                } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(branch = si, solution = ?sol, "solution rejected by forward check");
+                }
+            }
         }
 
-        let theta4_iii;
-        let theta6_iii;
+        result
+    }
 
-        if theta5_iii.abs() < zero_threshold {
-            theta4_iii = 0.0;
-            let xe = Vector3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]);
-            let mut rc = Matrix3::zeros();
+    /// Forward kinematics cross-check of the solution with the tolerances of `inverse`.
+    /// Clamped solutions are checked with the position tolerance widened accordingly.
+    pub(crate) fn verify(&self, pose: &Pose, joints: &Joints, clamped: bool) -> bool {
+        self.accepts(self.residual(pose, joints), clamped)
+    }
 
-            // Set columns of Rc
-            rc.set_column(1, &Vector3::new(-theta1_ii.sin(), theta1_ii.cos(), 0.0)); // yc
-            rc.set_column(2, &Vector3::new(matrix[(0, 2)], matrix[(1, 2)], matrix[(2, 2)])); // zc = ze
-            rc.set_column(0, &rc.column(1).cross(&rc.column(2))); // xc
+    /// Distance (meters) and angle (radians) between the pose and the forward kinematics of
+    /// the joints.
+    pub(crate) fn residual(&self, pose: &Pose, joints: &Joints) -> (f64, f64) {
+        let check = self.forward(joints);
+        ((pose.translation.vector - check.translation.vector).norm(),
+         pose.rotation.angle_to(&check.rotation))
+    }
 
-            let xec = rc.transpose() * xe;
-            theta6_iii = xec[1].atan2(xec[0]);
+    /// True if the residual is within the tolerances of `verify`.
+    pub(crate) fn accepts(&self, (translation, rotation): (f64, f64), clamped: bool) -> bool {
+        let distance_tolerance = if clamped {
+            self.clamped_distance_tolerance()
         } else {
-            let theta4_iiiy = matrix[(1, 2)] * cos1[2] - matrix[(0, 2)] * sin1[2];
-            let theta4_iiix = matrix[(0, 2)] * c23[2] * cos1[2] + matrix[(1, 2)] * c23[2] * sin1[2] - matrix[(2, 2)] * s23[2];
-            theta4_iii = theta4_iiiy.atan2(theta4_iiix);
-
-            let theta6_iiiy = matrix[(0, 1)] * s23[2] * cos1[2] + matrix[(1, 1)] * s23[2] * sin1[2] + matrix[(2, 1)] * c23[2];
-            let theta6_iiix = -matrix[(0, 0)] * s23[2] * cos1[2] - matrix[(1, 0)] * s23[2] * sin1[2] - matrix[(2, 0)] * c23[2];
-            theta6_iii = theta6_iiiy.atan2(theta6_iiix);
-        }
-
-        let theta4_iv;
-        let theta6_iv;
+            DISTANCE_TOLERANCE
+        };
+        translation <= distance_tolerance && rotation <= ANGULAR_TOLERANCE
+    }
 
-        if theta5_iv.abs() < zero_threshold {
-            theta4_iv = 0.0;
-            let xe = Vector3::new(matrix[(0, 0)], matrix[(1, 0)], matrix[(2, 0)]);
-            let mut rc = Matrix3::zeros();
-            rc.set_column(1, &Vector3::new(-theta1_ii.sin(), theta1_ii.cos(), 0.0));
-            rc.set_column(2, &Vector3::new(matrix[(0, 2)], matrix[(1, 2)], matrix[(2, 2)]));
-            rc.set_column(0, &rc.column(1).cross(&rc.column(2)));
+    /// Converts the model angles of one branch into joint values (offsets, sign corrections
+    /// and J3 coupling applied), normalized into [-pi, pi] unless disabled by `with_wrap`.
+    /// None if any angle is not finite.
+    pub(crate) fn normalized_joints(&self, theta: &[f64; 6]) -> Option<Joints> {
+        self.joints_wrapped(theta, self.wrap)
+    }
 
-            let xec = rc.transpose() * xe;
-            theta6_iv = xec[1].atan2(xec[0]);
+    /// Same as `normalized_joints`, wrapping into [-pi, pi] as told.
+    pub(crate) fn joints_wrapped(&self, theta: &[f64; 6], wrap: bool) -> Option<Joints> {
+        let mut sol: Joints = [0.0; 6];
+        if self.normalize_into(theta, &mut sol, wrap) {
+            Some(sol)
         } else {
-            let theta4_ivy = matrix[(1, 2)] * cos1[3] - matrix[(0, 2)] * sin1[3];
-            let theta4_ivx = matrix[(0, 2)] * c23[3] * cos1[3] + matrix[(1, 2)] * c23[3] * sin1[3] - matrix[(2, 2)] * s23[3];
-            theta4_iv = theta4_ivy.atan2(theta4_ivx);
-
-            let theta6_ivy = matrix[(0, 1)] * s23[3] * cos1[3] + matrix[(1, 1)] * s23[3] * sin1[3] + matrix[(2, 1)] * c23[3];
-            let theta6_ivx = -matrix[(0, 0)] * s23[3] * cos1[3] - matrix[(1, 0)] * s23[3] * sin1[3] - matrix[(2, 0)] * c23[3];
-            theta6_iv = theta6_ivy.atan2(theta6_ivx);
-        }
-
-        let theta4_v = theta4_i + PI;
-        let theta4_vi = theta4_ii + PI;
-        let theta4_vii = theta4_iii + PI;
-        let theta4_viii = theta4_iv + PI;
-
-        let theta6_v = theta6_i - PI;
-        let theta6_vi = theta6_ii - PI;
-        let theta6_vii = theta6_iii - PI;
-        let theta6_viii = theta6_iv - PI;
-
-        let theta: [[f64; 6]; 8] = [
-            [theta1_i, theta2_i, theta3_i, theta4_i, theta5_i, theta6_i],
-            [theta1_i, theta2_ii, theta3_ii, theta4_ii, theta5_ii, theta6_ii],
-            [theta1_ii, theta2_iii, theta3_iii, theta4_iii, theta5_iii, theta6_iii],
-            [theta1_ii, theta2_iv, theta3_iv, theta4_iv, theta5_iv, theta6_iv],
-            [theta1_i, theta2_i, theta3_i, theta4_v, theta5_v, theta6_v],
-            [theta1_i, theta2_ii, theta3_ii, theta4_vi, theta5_vi, theta6_vi],
-            [theta1_ii, theta2_iii, theta3_iii, theta4_vii, theta5_vii, theta6_vii],
-            [theta1_ii, theta2_iv, theta3_iv, theta4_viii, theta5_viii, theta6_viii],
-        ];
-
-        let mut sols: [[f64; 6]; 8] = [[f64::NAN; 6]; 8];
-        for si in 0..sols.len() {
-            for ji in 0..6 {
-                sols[si][ji] = (theta[si][ji] + params.offsets[ji]) *
-                    params.sign_corrections[ji] as f64;
-            }
-        }
-
-        let mut result: Solutions = Vec::with_capacity(8);
-
-        // Debug check. Solution failing cross-verification is flagged
-        // as invalid. This loop also normalizes valid solutions to 0
-        for si in 0..sols.len() {
-            let mut valid = true;
-            for ji in 0..6 {
-                let mut angle = sols[si][ji];
-                if angle.is_finite() {
-                    while angle > PI {
-                        angle -= 2.0 * PI;
-                    }
-                    while angle < -PI {
-                        angle += 2.0 * PI;
-                    }
-                    sols[si][ji] = angle;
-                } else {
-                    valid = false;
-                    break;
-                }
-            };
-            if valid {
-                let check_pose = self.forward(&sols[si]);
-                if compare_poses(&pose, &check_pose, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE) {
-                    result.push(sols[si]);
-                } else {
-                    if DEBUG {
-                        println!("********** Pose Failure sol {} *********", si);
-                    }
-                }
-            }
+            None
         }
+    }
 
-        result
+    /// Same as `joints_wrapped` but writes into the given joints, returning false if any
+    /// angle is not finite (the content of joints is then unspecified).
+    fn normalize_into(&self, theta: &[f64; 6], sol: &mut Joints, wrap: bool) -> bool {
+        self.core.normalize_into(theta, sol, wrap)
     }
 
-    // Replaces singularity with correct solution
-    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+    /// Same as `inverse_continuing` but guaranteed not to allocate on the heap: the solutions
+    /// are returned in the fixed capacity container on the stack. Use in real time loops.
+    /// Replaces singularity with correct solution.
+    pub fn inverse_continuing_fixed(&self, pose: &Pose, previous: &Joints) -> FixedSolutions {
         const SINGULARITY_SHIFT: f64 = DISTANCE_TOLERANCE / 8.;
         const SINGULARITY_SHIFTS: [[f64; 3]; 4] =
             [[0., 0., 0., ], [SINGULARITY_SHIFT, 0., 0.],
                 [0., SINGULARITY_SHIFT, 0.], [0., 0., SINGULARITY_SHIFT]];
 
-        let mut solutions: Vec<Joints> = Vec::with_capacity(9);
+        let mut solutions = FixedSolutions::new();
         let pt = pose.translation;
 
         let rotation = pose.rotation;
         'shifts: for d in SINGULARITY_SHIFTS {
             let shifted = Pose::from_parts(
                 Translation3::new(pt.x + d[0], pt.y + d[1], pt.z + d[2]), rotation);
-            let ik = self.inverse(&shifted);
-            // Self::dump_shifted_solutions(d, &ik);
+            let ik = self.inverse_fixed_near(&shifted, previous);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(dx = d[0], dy = d[1], dz = d[2], solutions = ?ik.as_slice(),
+                "shifted solutions");
             if solutions.is_empty() {
                 // Unshifted version that comes first is always included into results
-                solutions.extend(&ik);
+                for sol in ik.iter() {
+                    solutions.push(*sol);
+                }
             }
 
-            for s_idx in 0..ik.len() {
+            for sol in ik.iter() {
                 let singularity =
-                    self.kinematic_singularity(&ik[s_idx]);
-                if singularity.is_some() && is_valid(&ik[s_idx]) {
+                    self.kinematic_singularity(sol);
+                if singularity == Some(Singularity::A) && is_valid(sol) {
                     let s;
                     let s_n;
                     if let Some(Singularity::A) = singularity {
-                        let mut now = ik[s_idx];
+                        let mut now = *sol;
                         if are_angles_close(now[J5], 0.) {
                             // J5 = 0 singlularity, J4 and J6 rotate same direction
                             s = previous[J4] + previous[J6];
@@ -357,21 +346,14 @@ impl Kinematics for OPWKinematics {
                             normalize_near(&mut now[J5], previous[J5]);
                         }
 
-                        let mut angle = s_n - s;
-                        while angle > PI {
-                            angle -= 2.0 * PI;
-                        }
-                        while angle < -PI {
-                            angle += 2.0 * PI;
-                        }
-                        let j_d = angle / 2.0;
+                        let j_d = angle_difference(s_n, s) / 2.0;
 
                         now[J4] = previous[J4] + j_d;
                         now[J6] = previous[J6] + j_d;
 
                         // Check last time if the pose is ok
                         let check_pose = self.forward(&now);
-                        if compare_poses(&pose, &check_pose, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE) {
+                        if compare_poses(pose, &check_pose, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE).is_ok() {
                             solutions.push(now);
                             // We only expect one singularity case hence once we found, we can end
                             break 'shifts;
@@ -384,74 +366,42 @@ impl Kinematics for OPWKinematics {
         }
         // Before any sorting, normalize all angles to be close to
         // 'previous'
-        for s_idx in 0..solutions.len() {
+        for solution in solutions.iter_mut() {
             for joint_idx in 0..6 {
-                normalize_near(&mut solutions[s_idx][joint_idx], previous[joint_idx]);
+                let before = solution[joint_idx];
+                normalize_near(&mut solution[joint_idx], previous[joint_idx]);
+                if joint_idx == J2 {
+                    // Full turn of J2 also turns the coupled J3
+                    solution[J3] += self.parameters.j3_coupling * (solution[J2] - before);
+                }
             }
         }
-        sort_by_closeness(&mut solutions, &previous);
+        sort_by_closeness(&mut solutions, previous);
         solutions
     }
+}
 
-    fn forward(&self, joints: &Joints) -> Pose {
-        let p = &self.parameters;
+impl Stateless for OPWKinematics {}
+
+impl Kinematics for OPWKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.inverse_fixed(pose).to_vec()
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.inverse_continuing_fixed(pose, previous).to_vec()
+    }
 
-        let q1 = joints[0] * p.sign_corrections[0] as f64 - p.offsets[0];
-        let q2 = joints[1] * p.sign_corrections[1] as f64 - p.offsets[1];
-        let q3 = joints[2] * p.sign_corrections[2] as f64 - p.offsets[2];
-        let q4 = joints[3] * p.sign_corrections[3] as f64 - p.offsets[3];
-        let q5 = joints[4] * p.sign_corrections[4] as f64 - p.offsets[4];
-        let q6 = joints[5] * p.sign_corrections[5] as f64 - p.offsets[5];
-
-        let psi3 = f64::atan2(p.a2, p.c3);
-        let k = f64::sqrt(p.a2 * p.a2 + p.c3 * p.c3);
-
-        let cx1 = p.c2 * f64::sin(q2) + k * f64::sin(q2 + q3 + psi3) + p.a1;
-        let cy1 = p.b;
-        let cz1 = p.c2 * f64::cos(q2) + k * f64::cos(q2 + q3 + psi3);
-
-        let cx0 = cx1 * f64::cos(q1) - cy1 * f64::sin(q1);
-        let cy0 = cx1 * f64::sin(q1) + cy1 * f64::cos(q1);
-        let cz0 = cz1 + p.c1;
-
-        let s1 = f64::sin(q1);
-        let s2 = f64::sin(q2);
-        let s3 = f64::sin(q3);
-        let s4 = f64::sin(q4);
-        let s5 = f64::sin(q5);
-        let s6 = f64::sin(q6);
-
-        let c1 = f64::cos(q1);
-        let c2 = f64::cos(q2);
-        let c3 = f64::cos(q3);
-        let c4 = f64::cos(q4);
-        let c5 = f64::cos(q5);
-        let c6 = f64::cos(q6);
-
-        let r_0c = Matrix3::new(
-            c1 * c2 * c3 - c1 * s2 * s3, -s1, c1 * c2 * s3 + c1 * s2 * c3,
-            s1 * c2 * c3 - s1 * s2 * s3, c1, s1 * c2 * s3 + s1 * s2 * c3,
-            -s2 * c3 - c2 * s3, 0.0, -s2 * s3 + c2 * c3,
-        );
-
-        let r_ce = Matrix3::new(
-            c4 * c5 * c6 - s4 * s6, -c4 * c5 * s6 - s4 * c6, c4 * s5,
-            s4 * c5 * c6 + c4 * s6, -s4 * c5 * s6 + c4 * c6, s4 * s5,
-            -s5 * c6, s5 * s6, c5,
-        );
-
-        let r_oe = r_0c * r_ce;
-
-        let translation = Vector3::new(cx0, cy0, cz0) + p.c4 * r_oe * *self.unit_z;
-        let rotation = Rotation3::from_matrix_unchecked(r_oe);
-
-        Pose::from_parts(Translation3::from(translation),
-                         UnitQuaternion::from_rotation_matrix(&rotation))
+    fn forward(&self, joints: &Joints) -> Pose {
+        self.core.forward_model(&self.model_angles(joints))
     }
 
     fn kinematic_singularity(&self, joints: &Joints) -> Option<Singularity> {
         if is_close_to_multiple_of_pi(joints[J5], SINGULARITY_ANGLE_THR) {
-            Some(Singularity::A)
+            return Some(Singularity::A);
+        }
+        if self.singularity_proximity(joints).alignment < SINGULARITY_DISTANCE_THR {
+            Some(Singularity::B)
         } else {
             None
         }
@@ -469,90 +419,6 @@ fn is_close_to_multiple_of_pi(joint_value: f64, threshold: f64) -> bool {
 }
 
 fn are_angles_close(angle1: f64, angle2: f64) -> bool {
-    let mut diff = (angle1 - angle2).abs();
-    diff = diff % (2.0 * PI);
-    while diff > PI {
-        diff = (2.0 * PI) - diff;
-    }
-    diff < SINGULARITY_ANGLE_THR
-}
-
-/// Normalizes the angle `now` to be as close as possible to `prev`
-///
-/// # Arguments
-///
-/// * `now` - A mutable reference to the angle to be normalized, radians
-/// * `prev` - The reference angle, radians
-fn normalize_near(now: &mut f64, must_be_near: f64) {
-    let two_pi = 2.0 * PI;
-
-    fn adjust(now: &mut f64, prev: f64, two_pi: f64) {
-        if (*now - prev).abs() > ((*now - two_pi) - prev).abs() {
-            *now -= two_pi;
-        }
-        if (*now - prev).abs() > ((*now + two_pi) - prev).abs() {
-            *now += two_pi;
-        }
-        // Handle case -pi and pi that are identical angles
-        if (*now).abs() == PI && (prev.signum() != (*now).signum()) {
-            *now = -*now;
-        }
-    }
-
-    // Perform the adjustment potentially twice to ensure minimum difference
-    adjust(now, must_be_near, two_pi);
-    adjust(now, must_be_near, two_pi);
-}
-
-
-fn calculate_distance(joint1: &Joints, joint2: &Joints) -> f64 {
-    joint1.iter()
-        .zip(joint2.iter())
-        .map(|(a, b)| (a - b).abs())
-        .sum()
+    angle_difference(angle1, angle2).abs() < SINGULARITY_ANGLE_THR
 }
 
-/// Sorts the solutions vector by closeness to the `previous` joint.
-/// Joints must be pre-normalized to be as close as possible, not away by 360 degrees
-fn sort_by_closeness(solutions: &mut Solutions, previous: &Joints) {
-    solutions.sort_by(|a, b| {
-        let distance_a = calculate_distance(a, previous);
-        let distance_b = calculate_distance(b, previous);
-        distance_a.partial_cmp(&distance_b).unwrap_or(std::cmp::Ordering::Equal)
-    });
-}
-
-// Compare two poses with the given tolerance.
-fn compare_poses(ta: &Isometry3<f64>, tb: &Isometry3<f64>,
-                 distance_tolerance: f64, angular_tolerance: f64) -> bool {
-    let translation_distance = (ta.translation.vector - tb.translation.vector).norm();
-    let angular_distance = ta.rotation.angle_to(&tb.rotation);
-
-    if translation_distance.abs() > distance_tolerance {
-        if DEBUG {
-            println!("Positioning error: {}", translation_distance);
-        }
-        return false;
-    }
-
-    if angular_distance.abs() > angular_tolerance {
-        if DEBUG {
-            println!("Orientation errors: {}", angular_distance);
-        }
-        return false;
-    }
-    true
-}
-
-#[allow(dead_code)]
-fn dump_shifted_solutions(d: [f64; 3], ik: &Solutions) {
-    println!("Shifted solutions {} {} {}", d[0], d[1], d[2]);
-    for sol_idx in 0..ik.len() {
-        let mut row_str = String::new();
-        for joint_idx in 0..6 {
-            let computed = ik[sol_idx][joint_idx];
-            row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
-        }
-        println!("[{}]", row_str.trim_end()); // Trim trailing space for aesthetics
-    }
-}
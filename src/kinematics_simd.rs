@@ -0,0 +1,91 @@
+//! SIMD version of the inverse kinematics (feature `simd`). The four arm configurations are
+//! data-parallel, so the wrist part (theta4, theta5, theta6) is computed for all of them at
+//! once in 4-lane f64 vectors using the `wide` crate. The arm part and the forward kinematics
+//! cross-check are shared with the scalar solver, so the results are the same within the
+//! verification tolerance.
+
+use wide::f64x4;
+use crate::kinematic_traits::{FixedSolutions, Pose};
+use crate::kinematics_generic::singular_theta6;
+use crate::kinematics_impl::OPWKinematics;
+use std::f64::consts::PI;
+
+impl OPWKinematics {
+    /// Inverse kinematics with the wrist part computed in SIMD lanes. Returns the same
+    /// solutions as `inverse_fixed` (possibly more in the wrist singularity, where the
+    /// SIMD version clamps the rounding errors) and, like it, does not allocate.
+    pub fn inverse_simd(&self, pose: &Pose) -> FixedSolutions {
+        let arm = self.arm_branches(pose);
+        let matrix = pose.rotation.to_rotation_matrix();
+        let m = |r: usize, c: usize| f64x4::splat(matrix[(r, c)]);
+
+        let theta1 = f64x4::from([arm[0][0], arm[1][0], arm[2][0], arm[3][0]]);
+        let theta23 = f64x4::from([arm[0][1] + arm[0][2], arm[1][1] + arm[1][2],
+            arm[2][1] + arm[2][2], arm[3][1] + arm[3][2]]);
+
+        let (sin1, cos1) = theta1.sin_cos();
+        let (s23, c23) = theta23.sin_cos();
+
+        let mm = m(0, 2) * s23 * cos1 + m(1, 2) * s23 * sin1 + m(2, 2) * c23;
+        // Vectorized trigonometry may differ from the scalar one in the last bits, so |mm| can
+        // slightly exceed 1 in the wrist singularity. Clamp, the result is verified anyway.
+        let theta5 = (f64x4::ONE - mm * mm).max(f64x4::ZERO).sqrt().atan2(mm);
+
+        let theta4_y = m(1, 2) * cos1 - m(0, 2) * sin1;
+        let theta4_x = m(0, 2) * c23 * cos1 + m(1, 2) * c23 * sin1 - m(2, 2) * s23;
+        let theta4 = theta4_y.atan2(theta4_x);
+
+        let theta6_y = m(0, 1) * s23 * cos1 + m(1, 1) * s23 * sin1 + m(2, 1) * c23;
+        let theta6_x = -m(0, 0) * s23 * cos1 - m(1, 0) * s23 * sin1 - m(2, 0) * c23;
+        let theta6 = theta6_y.atan2(theta6_x);
+
+        let (sin1, cos1) = (sin1.to_array(), cos1.to_array());
+        let theta4 = theta4.to_array();
+        let theta5 = theta5.to_array();
+        let theta6 = theta6.to_array();
+
+        let zero_threshold: f64 = 1e-6;
+        let mut theta: [[f64; 6]; 8] = [[f64::NAN; 6]; 8];
+        for (i, &[theta1, theta2, theta3]) in arm.iter().enumerate() {
+            let (theta4, theta6) = if theta5[i].abs() < zero_threshold {
+                // Wrist singularity is rare; handle this lane with scalar code
+                (0.0, singular_theta6(&matrix, sin1[i], cos1[i]))
+            } else {
+                (theta4[i], theta6[i])
+            };
+            theta[i] = [theta1, theta2, theta3, theta4, theta5[i], theta6];
+            theta[i + 4] = [theta1, theta2, theta3, theta4 + PI, -theta5[i], theta6 - PI];
+        }
+
+        self.verified_solutions(pose, &theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kinematic_traits::Kinematics;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        for parameters in [Parameters::irb2400_10(), Parameters::kuka_kr6_r700_sixx(),
+            Parameters::staubli_tx40()] {
+            let robot = OPWKinematics::new(parameters);
+            for joints in [[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], [0.0, 0.1, 0.2, 0.3, 0.0, 0.5],
+                [-1.0, 0.5, -0.4, 2.0, -1.2, 3.0]] {
+                let pose = robot.forward(&joints);
+                let scalar = robot.inverse_fixed(&pose);
+                let simd = robot.inverse_simd(&pose);
+                // In the wrist singularity, the scalar solver may lose the branches where
+                // rounding puts |cos(theta5)| above 1; the clamped SIMD one keeps them.
+                assert!(simd.len() >= scalar.len());
+                for a in scalar.iter() {
+                    assert!(simd.iter().any(|b| {
+                        a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1E-9)
+                    }), "{:?} not found in {:?}", a, simd.as_slice());
+                }
+            }
+        }
+    }
+}
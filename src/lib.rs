@@ -1,10 +1,77 @@
 pub mod parameters;
 pub mod parameters_robots;
+#[cfg(feature = "files")]
 pub mod parameters_from_file;
 
 pub mod utils;
 pub mod kinematic_traits;
 pub mod kinematics_impl;
+pub mod kinematics_generic;
+#[cfg(feature = "simd")]
+pub mod kinematics_simd;
+pub mod workspace;
+pub mod scene_graph;
+pub mod mounting;
+pub mod frames;
+pub mod workcell;
+pub mod tool;
+pub mod keep_out;
+pub mod solver;
+pub mod cache;
+pub mod diagnostics;
+pub mod factory;
+pub mod extended_precision;
+pub mod calibrated;
+pub mod hand_eye;
+pub mod corrections;
+pub mod palletizer;
+pub mod scara;
+pub mod branches;
+pub mod validation;
+pub mod euler;
+pub mod vendor;
+pub mod pose_formats;
+pub mod joints;
+pub mod into_pose;
+pub mod interpolation;
+pub mod pose_distance;
+pub mod ranking;
+pub mod partial;
+pub mod sticky;
+pub mod constraints;
+pub mod jog;
+pub mod stream;
+pub mod servo;
+pub mod command;
+pub mod jacobian;
+pub mod statics;
+pub mod singularity_scan;
+pub mod ros2_control;
+pub mod joint_names;
+pub mod parameters_import;
+pub mod spherical_wrist;
+#[cfg(feature = "files")]
+pub mod conformance;
+#[cfg(feature = "rosrust")]
+pub mod ros1;
+#[cfg(feature = "mint")]
+pub mod mint_interop;
+#[cfg(feature = "glam")]
+pub mod glam_interop;
+#[cfg(feature = "k")]
+pub mod k_chain;
+#[cfg(feature = "uom")]
+pub mod units;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 #[cfg(test)]
 mod tests;
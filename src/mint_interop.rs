@@ -0,0 +1,60 @@
+//! Conversions of poses from and to [mint](https://docs.rs/mint) types (feature `mint`), so that
+//! cgmath, glam, ultraviolet and other mint-aware types can be passed without reordering the
+//! components by hand. Joints are plain `[f64; 6]` arrays and need no conversion.
+
+use nalgebra::{Matrix3, Matrix4, Rotation3, Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::Pose;
+
+/// Pose as the homogeneous 4 x 4 transformation matrix.
+pub fn pose_to_mint(pose: &Pose) -> mint::ColumnMatrix4<f64> {
+    pose.to_homogeneous().into()
+}
+
+/// Pose from the homogeneous 4 x 4 transformation matrix. The rotation part is assumed
+/// to be orthonormal.
+pub fn pose_from_mint(matrix: impl Into<mint::ColumnMatrix4<f64>>) -> Pose {
+    let matrix: Matrix4<f64> = matrix.into().into();
+    let rotation: Matrix3<f64> = matrix.fixed_view::<3, 3>(0, 0).into_owned();
+    let translation: Vector3<f64> = matrix.fixed_view::<3, 1>(0, 3).into_owned();
+    Pose::from_parts(
+        Translation3::from(translation),
+        UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(rotation)))
+}
+
+/// Pose as the translation vector and rotation quaternion.
+pub fn pose_to_mint_parts(pose: &Pose) -> (mint::Vector3<f64>, mint::Quaternion<f64>) {
+    (pose.translation.vector.into(), pose.rotation.into())
+}
+
+/// Pose from the translation vector and rotation quaternion. The quaternion is normalized.
+pub fn pose_from_mint_parts(translation: impl Into<mint::Vector3<f64>>,
+                            rotation: impl Into<mint::Quaternion<f64>>) -> Pose {
+    let translation: Vector3<f64> = translation.into().into();
+    let rotation: nalgebra::Quaternion<f64> = rotation.into().into();
+    Pose::from_parts(Translation3::from(translation), UnitQuaternion::from_quaternion(rotation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::Kinematics;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_mint_round_trip() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+
+        let from_matrix = pose_from_mint(pose_to_mint(&pose));
+        let (translation, rotation) = pose_to_mint_parts(&pose);
+        assert_eq!(translation.x, pose.translation.x);
+        assert_eq!(rotation.s, pose.rotation.w);
+        let from_parts = pose_from_mint_parts(translation, rotation);
+
+        for restored in [from_matrix, from_parts] {
+            assert!((restored.translation.vector - pose.translation.vector).norm() < 1E-12);
+            assert!(restored.rotation.angle_to(&pose.rotation) < 1E-9);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Robot mounting (floor, wall, ceiling or tilted pedestal) and the kinematics of the
+//! robot with the base placed somewhere in the world, possibly on the moving platform.
+//! The mounting also narrows the joint limits of the data sheet (given for the floor) where
+//! the arm would turn against the wall or the ceiling, see `Mounting::joint_limits`.
+
+use std::f64::consts::PI;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use crate::constraints::JointLimits;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Range of J1 on the wall, in the angles of the kinematic model (before offsets and sign
+/// corrections): the arm turns at most to the horizontal, never up along the wall.
+pub const WALL_J1_RANGE: (f64, f64) = (-PI / 2.0, PI / 2.0);
+
+/// Range of J2 on the ceiling, in the angles of the kinematic model: the upper arm turns at
+/// most to the horizontal, never up towards the ceiling.
+pub const CEILING_J2_RANGE: (f64, f64) = (-PI / 2.0, PI / 2.0);
+
+/// Common robot mountings. The mounting defines the orientation of the robot base
+/// in the world frame, the world z axis pointing up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mounting {
+    /// Standard mounting, robot base z axis points up.
+    Floor,
+    /// Robot hangs from the ceiling, base z axis points down. The base is rotated 180
+    /// degrees around the world x axis so robot x axis still matches the world x axis.
+    Ceiling,
+    /// Robot is mounted on the wall, base z axis points along the world +x and the
+    /// base x axis points down.
+    Wall,
+    /// Robot is mounted on the pedestal, tilted by the given angle (radians) around the
+    /// world y axis. Positive angle tilts the robot forward (towards +x).
+    Tilted(f64),
+}
+
+impl Mounting {
+    /// Rotation of the robot base in the world frame.
+    pub fn rotation(&self) -> UnitQuaternion<f64> {
+        match self {
+            Mounting::Floor => UnitQuaternion::identity(),
+            Mounting::Ceiling => UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI),
+            Mounting::Wall => UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI / 2.0),
+            Mounting::Tilted(angle) => UnitQuaternion::from_axis_angle(&Vector3::y_axis(), *angle),
+        }
+    }
+
+    /// Pose of the robot base in the world frame, with the base origin at the given point
+    /// (mounting flange of the robot base).
+    pub fn base_pose(&self, x: f64, y: f64, z: f64) -> Pose {
+        Pose::from_parts(Translation3::new(x, y, z), self.rotation())
+    }
+
+    /// Direction of gravity (unit vector) expressed in the robot base frame. Mounting does
+    /// not change the meaning of joint values, but it changes the direction of the gravity
+    /// load the joints see (and so their usable range, see `joint_limits`).
+    pub fn gravity_in_base(&self) -> Vector3<f64> {
+        self.rotation().inverse() * Vector3::new(0.0, 0.0, -1.0)
+    }
+
+    /// The floor limits of the robot narrowed for this mounting: J1 to `WALL_J1_RANGE` on the
+    /// wall and J2 to `CEILING_J2_RANGE` on the ceiling, converted into joint values with the
+    /// offsets and sign corrections of the parameters. The floor and the tilted pedestal keep
+    /// the limits as given (check the rated tilt in the manual of the robot).
+    pub fn joint_limits(&self, parameters: &Parameters, limits: &JointLimits) -> JointLimits {
+        let (joint, (min, max)) = match self {
+            Mounting::Wall => (0, WALL_J1_RANGE),
+            Mounting::Ceiling => (1, CEILING_J2_RANGE),
+            Mounting::Floor | Mounting::Tilted(_) => return *limits,
+        };
+        let to_joint = |angle: f64| {
+            (angle + parameters.offsets[joint]) * parameters.sign_corrections[joint] as f64
+        };
+        let (a, b) = (to_joint(min), to_joint(max));
+        let mut adjusted = *limits;
+        adjusted.min[joint] = limits.min[joint].max(a.min(b));
+        adjusted.max[joint] = limits.max[joint].min(a.max(b));
+        adjusted
+    }
+}
+
+/// Robot with the base placed into the world frame. Poses accepted by `inverse` and returned
+/// by `forward` are in the world frame, joint values are not affected.
+#[derive(Debug, Clone)]
+pub struct Base<K: Kinematics> {
+    pub robot: K,
+    /// Pose of the robot base in the world frame.
+    pub base: Pose,
+}
+
+impl<K: Kinematics> Base<K> {
+    /// Place the robot base with the given mounting at the given point in the world.
+    pub fn mounted(robot: K, mounting: Mounting, x: f64, y: f64, z: f64) -> Self {
+        Base { robot, base: mounting.base_pose(x, y, z) }
+    }
+
+    /// Robot standing on the floor at the given point.
+    pub fn floor(robot: K, x: f64, y: f64, z: f64) -> Self {
+        Self::mounted(robot, Mounting::Floor, x, y, z)
+    }
+
+    /// Robot hanging from the ceiling at the given point.
+    pub fn ceiling(robot: K, x: f64, y: f64, z: f64) -> Self {
+        Self::mounted(robot, Mounting::Ceiling, x, y, z)
+    }
+
+    /// Robot mounted on the wall at the given point.
+    pub fn wall(robot: K, x: f64, y: f64, z: f64) -> Self {
+        Self::mounted(robot, Mounting::Wall, x, y, z)
+    }
+
+    /// Robot on the pedestal tilted by the given angle around the world y axis.
+    pub fn tilted(robot: K, angle: f64, x: f64, y: f64, z: f64) -> Self {
+        Self::mounted(robot, Mounting::Tilted(angle), x, y, z)
+    }
+}
+
+impl<K: Stateless> Stateless for Base<K> {}
+
+impl<K: Kinematics> Kinematics for Base<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.robot.inverse(&(self.base.inverse() * pose))
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(&(self.base.inverse() * pose), previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.base * self.robot.forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+/// Robot on the mobile platform (AGV, linear track carriage): mounted at the fixed pose on the
+/// platform, the platform pose in the world updated every cycle from the localization. Poses
+/// accepted by `inverse` and returned by `forward` are in the world frame, so the world
+/// targets stay put while the platform moves. The update only composes the poses, the
+/// kinematics is not rebuilt.
+#[derive(Debug, Clone)]
+pub struct MobileBase<K: Kinematics> {
+    pub robot: K,
+    /// Pose of the robot base on the platform.
+    mount: Pose,
+    /// Pose of the platform in the world.
+    platform: Pose,
+    /// Pose of the robot base in the world, and its inverse.
+    base: Pose,
+    base_inverse: Pose,
+}
+
+impl<K: Kinematics> MobileBase<K> {
+    /// Robot mounted at the given pose on the platform, the platform at the world origin.
+    pub fn new(robot: K, mount: Pose) -> Self {
+        MobileBase { robot, mount, platform: Pose::identity(), base: mount,
+            base_inverse: mount.inverse() }
+    }
+
+    /// Moves the platform to the given pose in the world.
+    pub fn set_platform(&mut self, platform: Pose) {
+        self.platform = platform;
+        self.base = platform * self.mount;
+        self.base_inverse = self.base.inverse();
+    }
+
+    pub fn platform(&self) -> Pose {
+        self.platform
+    }
+
+    pub fn mount(&self) -> Pose {
+        self.mount
+    }
+
+    /// Pose of the robot base in the world.
+    pub fn base(&self) -> Pose {
+        self.base
+    }
+
+    /// The world pose in the robot base frame, at the current platform pose.
+    pub fn to_base(&self, pose: &Pose) -> Pose {
+        self.base_inverse * pose
+    }
+
+    /// Inverse kinematics for the world pose with the platform at the given pose (as the
+    /// predicted pose at the time the command takes effect), leaving the current one as is.
+    pub fn inverse_at(&self, pose: &Pose, platform: &Pose) -> Solutions {
+        self.robot.inverse(&((platform * self.mount).inverse() * pose))
+    }
+
+    /// As `inverse_at`, continuing from the previous joints.
+    pub fn inverse_continuing_at(&self, pose: &Pose, previous: &Joints, platform: &Pose)
+                                 -> Solutions {
+        self.robot.inverse_continuing(&((platform * self.mount).inverse() * pose), previous)
+    }
+}
+
+impl<K: Stateless> Stateless for MobileBase<K> {}
+
+impl<K: Kinematics> Kinematics for MobileBase<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.robot.inverse(&self.to_base(pose))
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(&self.to_base(pose), previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.base * self.robot.forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_ceiling_points_down() {
+        let robot = Base::ceiling(OPWKinematics::new(Parameters::irb2400_10()), 0.0, 0.0, 3.0);
+        let joints = [0.0; 6];
+        let pose = robot.forward(&joints);
+        let original = robot.robot.forward(&joints);
+        assert!((pose.translation.z - (3.0 - original.translation.z)).abs() < 1E-9);
+        assert!((pose.translation.x - original.translation.x).abs() < 1E-9);
+        assert!((Mounting::Ceiling.gravity_in_base() - Vector3::z()).norm() < 1E-9);
+    }
+
+    #[test]
+    fn test_wall_gravity() {
+        // On the wall, the robot x axis points down so gravity pulls along it.
+        assert!((Mounting::Wall.gravity_in_base() - Vector3::x()).norm() < 1E-9);
+    }
+
+    #[test]
+    fn test_mounting_joint_limits() {
+        let limits = JointLimits::from_degrees([(-180.0, 180.0), (-100.0, 150.0), (-210.0, 65.0),
+            (-200.0, 200.0), (-125.0, 125.0), (-400.0, 400.0)]);
+        let parameters = Parameters::irb2400_10();
+        assert_eq!(Mounting::Floor.joint_limits(&parameters, &limits), limits);
+        assert_eq!(Mounting::Tilted(0.3).joint_limits(&parameters, &limits), limits);
+
+        let wall = Mounting::Wall.joint_limits(&parameters, &limits);
+        assert_eq!((wall.min[0], wall.max[0]), (-PI / 2.0, PI / 2.0));
+        assert_eq!(wall.min[1..], limits.min[1..]);
+
+        let ceiling = Mounting::Ceiling.joint_limits(&parameters, &limits);
+        assert_eq!((ceiling.min[1], ceiling.max[1]), (-PI / 2.0, PI / 2.0));
+        assert_eq!(ceiling.max[0], limits.max[0]);
+
+        // KUKA: J2 is offset by -90 degrees, the range becomes [-180, 0] within [-100, 150]
+        let parameters = Parameters::kuka_kr6_r700_sixx();
+        let ceiling = Mounting::Ceiling.joint_limits(&parameters, &limits);
+        assert_eq!(ceiling.min[1], limits.min[1]);
+        assert!(ceiling.max[1].abs() < 1E-12);
+
+        // The arm of the wall mounted robot cannot turn up along the wall
+        let robot = Base::wall(OPWKinematics::new(Parameters::irb2400_10()), 0.0, 0.0, 1.0);
+        let up = robot.forward(&[PI, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(up.translation.z > 1.0);
+        assert!(!wall.contains(&[PI, 0.0, 0.0, 0.0, 0.0, 0.0]));
+        assert!(wall.contains(&[0.5, 0.0, 0.0, 0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_mounted_round_trip() {
+        let robot = Base::tilted(OPWKinematics::new(Parameters::irb2400_10()),
+                                 0.3, 1.0, 2.0, 0.5);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let solutions = robot.inverse_continuing(&pose, &joints);
+        assert!(!solutions.is_empty());
+        for (a, b) in solutions[0].iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1E-6);
+        }
+    }
+
+    #[test]
+    fn test_mobile_base() {
+        let mount = Pose::translation(0.3, 0.0, 0.8);
+        let mut robot = MobileBase::new(OPWKinematics::new(Parameters::irb2400_10()), mount);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let target = robot.forward(&joints);
+        assert_eq!(robot.base(), mount);
+
+        // The platform drives 5 cm forward, the world target stays where it was
+        let platform = Pose::from_parts(Translation3::new(0.05, 0.0, 0.0),
+                                        UnitQuaternion::from_euler_angles(0.0, 0.0, 0.02));
+        let predicted = robot.inverse_continuing_at(&target, &joints, &platform);
+        robot.set_platform(platform);
+        assert_eq!(robot.platform(), platform);
+        let solutions = robot.inverse_continuing(&target, &joints);
+        assert_eq!(solutions, predicted);
+        assert!(solutions[0].iter().zip(&joints).any(|(a, b)| (a - b).abs() > 1E-3));
+        let reached = robot.forward(&solutions[0]);
+        assert!((reached.translation.vector - target.translation.vector).norm() < 1E-9);
+        assert_eq!(robot.inverse_at(&target, &platform), robot.inverse(&target));
+    }
+}
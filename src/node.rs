@@ -0,0 +1,197 @@
+//! Node.js native addon (feature `node`), built with napi-rs: `npm run build` in bindings/node.
+//! Values are passed as in the JavaScript bindings (`wasm`):
+//! - joints are 6 values in radians,
+//! - poses are 7 values: translation x, y, z and quaternion x, y, z, w, rejected if not finite
+//!   or the quaternion is far from unit,
+//! - solutions are returned flattened, 6 values per solution.
+//!
+//! Batch calls take and return Float64Array with one joint position or pose after another.
+//! The inverse batch pads the solutions of each pose with NaN to MAX_SOLUTIONS; its async
+//! variant runs on the libuv thread pool and returns the Promise, not blocking the event loop.
+
+use napi::bindgen_prelude::{AsyncTask, Float64Array};
+use napi::{Env, Error, Result, Task};
+use napi_derive::napi;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions, MAX_SOLUTIONS};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use crate::validation::{pose_from_raw, validate_parameters, ParametersValidationError};
+
+/// Robot kinematics exported to Node.js.
+#[napi]
+pub struct Robot {
+    robot: OPWKinematics,
+}
+
+#[napi]
+impl Robot {
+    /// Robot with the given OPW parameters, 6 joint offsets (radians) and 6 sign corrections.
+    #[napi(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(a1: f64, a2: f64, b: f64, c1: f64, c2: f64, c3: f64, c4: f64,
+               offsets: Vec<f64>, sign_corrections: Vec<i32>) -> Result<Self> {
+        let offsets: [f64; 6] = offsets.try_into()
+            .map_err(|_| Error::from_reason("offsets must have 6 values"))?;
+        let sign_corrections: [i32; 6] = sign_corrections.try_into()
+            .map_err(|_| Error::from_reason("sign corrections must have 6 values"))?;
+        if let Some(i) = sign_corrections.iter().position(|s| !matches!(s, 1 | -1)) {
+            return Err(Error::from_reason(
+                ParametersValidationError::InvalidSignCorrection(i + 1).to_string()));
+        }
+        let parameters = Parameters {
+            a1, a2, b, c1, c2, c3, c4, offsets,
+            sign_corrections: sign_corrections.map(|s| s as i8),
+            ..Parameters::new()
+        };
+        validate_parameters(&parameters).map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(Robot { robot: OPWKinematics::new(parameters) })
+    }
+
+    /// Robot from the built-in preset, by the name of the preset function
+    /// (like "irb2400_10" or "kuka_kr6_r700_sixx").
+    #[napi(factory)]
+    pub fn preset(name: String) -> Result<Self> {
+        let parameters = Parameters::preset(&name)
+            .ok_or_else(|| Error::from_reason(format!("unknown robot preset {}", name)))?;
+        Ok(Robot { robot: OPWKinematics::new(parameters) })
+    }
+
+    /// Pose of the flange for the given joints.
+    #[napi]
+    pub fn forward(&self, joints: Vec<f64>) -> Result<Vec<f64>> {
+        Ok(pose_to_array(&self.robot.forward(&to_joints(&joints)?)).to_vec())
+    }
+
+    /// All solutions for the pose, flattened.
+    #[napi]
+    pub fn inverse(&self, pose: Vec<f64>) -> Result<Vec<f64>> {
+        Ok(flatten(&self.robot.inverse(&to_pose(&pose)?)))
+    }
+
+    /// All solutions for the pose, closest to the previous joints first, flattened.
+    #[napi]
+    pub fn inverse_continuing(&self, pose: Vec<f64>, previous: Vec<f64>) -> Result<Vec<f64>> {
+        Ok(flatten(&self.robot.inverse_continuing(&to_pose(&pose)?, &to_joints(&previous)?)))
+    }
+
+    /// Poses (7 values each) for the joints (6 values each).
+    #[napi]
+    pub fn forward_batch(&self, joints: Float64Array) -> Result<Float64Array> {
+        if !joints.len().is_multiple_of(6) {
+            return Err(Error::from_reason("joints length must be a multiple of 6"));
+        }
+        let poses = joints.chunks_exact(6).flat_map(|q| {
+            pose_to_array(&self.robot.forward(&q.try_into().unwrap()))
+        }).collect::<Vec<f64>>();
+        Ok(Float64Array::new(poses))
+    }
+
+    /// Solutions for the poses (7 values each), MAX_SOLUTIONS x 6 values per pose padded with
+    /// NaN. If the previous joints are given (6 values per pose), the solutions are computed
+    /// as by `inverseContinuing`, closest first.
+    #[napi]
+    pub fn inverse_batch(&self, poses: Float64Array, previous: Option<Float64Array>)
+                         -> Result<Float64Array> {
+        let batch = InverseBatch::new(&self.robot, &poses, previous.as_deref())?;
+        Ok(Float64Array::new(batch.solve()))
+    }
+
+    /// As `inverseBatch`, computed on the thread pool.
+    #[napi]
+    pub fn inverse_batch_async(&self, poses: Float64Array, previous: Option<Float64Array>)
+                               -> Result<AsyncTask<InverseBatch>> {
+        Ok(AsyncTask::new(InverseBatch::new(&self.robot, &poses, previous.as_deref())?))
+    }
+}
+
+/// Inverse kinematics of the pose batch, as the task for the thread pool.
+pub struct InverseBatch {
+    robot: OPWKinematics,
+    poses: Vec<Pose>,
+    previous: Option<Vec<Joints>>,
+}
+
+impl InverseBatch {
+    fn new(robot: &OPWKinematics, poses: &[f64], previous: Option<&[f64]>) -> Result<Self> {
+        if !poses.len().is_multiple_of(7) {
+            return Err(Error::from_reason("poses length must be a multiple of 7"));
+        }
+        let poses: Vec<Pose> = poses.chunks_exact(7).map(to_pose).collect::<Result<_>>()?;
+        let previous = match previous {
+            Some(previous) if previous.len() != poses.len() * 6 => {
+                return Err(Error::from_reason("previous must have 6 values per pose"));
+            }
+            Some(previous) => Some(previous.chunks_exact(6).map(to_joints).collect::<Result<_>>()?),
+            None => None,
+        };
+        Ok(InverseBatch { robot: robot.clone(), poses, previous })
+    }
+
+    fn solve(&self) -> Vec<f64> {
+        let mut result = vec![f64::NAN; self.poses.len() * MAX_SOLUTIONS * 6];
+        for (i, (pose, values)) in self.poses.iter()
+            .zip(result.chunks_exact_mut(MAX_SOLUTIONS * 6)).enumerate() {
+            let solutions = match &self.previous {
+                Some(previous) => self.robot.inverse_continuing_fixed(pose, &previous[i]),
+                None => self.robot.inverse_fixed(pose),
+            };
+            for (value, solution) in values.iter_mut().zip(solutions.iter().flatten()) {
+                *value = *solution;
+            }
+        }
+        result
+    }
+}
+
+impl Task for InverseBatch {
+    type Output = Vec<f64>;
+    type JsValue = Float64Array;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(self.solve())
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(Float64Array::new(output))
+    }
+}
+
+fn to_joints(values: &[f64]) -> Result<Joints> {
+    values.try_into().map_err(|_| Error::from_reason("joints must have 6 values"))
+}
+
+fn to_pose(values: &[f64]) -> Result<Pose> {
+    let [x, y, z, qx, qy, qz, qw]: [f64; 7] = values.try_into()
+        .map_err(|_| Error::from_reason("pose must have 7 values: x, y, z, qx, qy, qz, qw"))?;
+    pose_from_raw([x, y, z], [qx, qy, qz, qw]).map_err(|e| Error::from_reason(e.to_string()))
+}
+
+fn pose_to_array(pose: &Pose) -> [f64; 7] {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    [t.x, t.y, t.z, q.i, q.j, q.k, q.w]
+}
+
+fn flatten(solutions: &Solutions) -> Vec<f64> {
+    solutions.iter().flatten().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse_batch() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = pose_to_array(&robot.forward(&joints));
+        let poses = [pose, pose].concat();
+        let batch = InverseBatch::new(&robot, &poses, Some(&[joints, joints].concat())).unwrap();
+        let solutions = batch.solve();
+        assert_eq!(solutions.len(), 2 * MAX_SOLUTIONS * 6);
+        for (a, b) in solutions[MAX_SOLUTIONS * 6..][..6].iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1E-6);
+        }
+        assert!(InverseBatch::new(&robot, &poses[..10], None).is_err());
+    }
+}
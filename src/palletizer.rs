@@ -0,0 +1,126 @@
+//! Four-axis palletizing robots: the arm of the OPW robot (J1 to J3) with the flange kept
+//! pointing straight down by the linkage, and J6 turning the tool about the vertical. There is
+//! no J4 and J5, so these robots cannot be solved by the six-axis solver directly; this one
+//! reuses its arm math with the wrist held vertical. The parameters are the usual OPW ones,
+//! c4 being the distance from the wrist center down to the flange.
+
+use std::f64::consts::PI;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::{OPWKinematics, ANGULAR_TOLERANCE, DISTANCE_TOLERANCE};
+use crate::parameters::opw_kinematics::Parameters;
+use crate::utils::{compare_poses, normalize_near, sort_by_closeness};
+
+/// Joint values of the palletizer: J1, J2, J3 and J6 (the tool rotation).
+pub type PalletizerJoints = [f64; 4];
+
+/// Kinematics of the four-axis palletizer. Through the `Kinematics` trait the joints are
+/// given as six values [J1, J2, J3, 0, 0, J6]: J4 and J5 are ignored and returned as zero.
+/// Only the poses with the flange Z pointing down are reachable.
+#[derive(Debug, Clone)]
+pub struct PalletizerKinematics {
+    robot: OPWKinematics,
+}
+
+impl PalletizerKinematics {
+    pub fn new(parameters: Parameters) -> Self {
+        PalletizerKinematics { robot: OPWKinematics::new(parameters) }
+    }
+
+    /// Joints of the equivalent six-axis robot: J4 at its zero, J5 keeping the flange vertical.
+    pub fn six_axis_joints(&self, joints: &PalletizerJoints) -> Joints {
+        let p = self.robot.parameters();
+        let [j1, j2, j3, j6] = *joints;
+        let decoupled = p.decoupled(&[j1, j2, j3, 0.0, 0.0, j6]);
+        let model = |i: usize| decoupled[i] * p.sign_corrections[i] as f64 - p.offsets[i];
+        let joint = |i: usize, q: f64| (q + p.offsets[i]) * p.sign_corrections[i] as f64;
+        // Flange Z points down when q2 + q3 + q5 is PI
+        [j1, j2, j3, joint(3, 0.0), joint(4, PI - model(1) - model(2)), j6]
+    }
+
+    /// Flange pose for the palletizer joints.
+    pub fn forward_palletizer(&self, joints: &PalletizerJoints) -> Pose {
+        self.robot.forward(&self.six_axis_joints(joints))
+    }
+
+    /// Palletizer joints reaching the pose, verified with the forward kinematics.
+    pub fn inverse_palletizer(&self, pose: &Pose) -> Vec<PalletizerJoints> {
+        let mut result: Vec<PalletizerJoints> = Vec::new();
+        for solution in self.robot.inverse(pose) {
+            let joints = [solution[0], solution[1], solution[2], solution[5]];
+            let check = self.forward_palletizer(&joints);
+            if compare_poses(pose, &check, DISTANCE_TOLERANCE, ANGULAR_TOLERANCE).is_ok()
+                && !result.contains(&joints) {
+                result.push(joints);
+            }
+        }
+        result
+    }
+}
+
+fn to_six(joints: &PalletizerJoints) -> Joints {
+    [joints[0], joints[1], joints[2], 0.0, 0.0, joints[3]]
+}
+
+fn from_six(joints: &Joints) -> PalletizerJoints {
+    [joints[0], joints[1], joints[2], joints[5]]
+}
+
+impl Stateless for PalletizerKinematics {}
+
+impl Kinematics for PalletizerKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.inverse_palletizer(pose).iter().map(to_six).collect()
+    }
+
+    /// Solutions normalized near the previous joints and sorted by the distance to them.
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        let previous = to_six(&from_six(previous));
+        let mut solutions = self.inverse(pose);
+        for solution in solutions.iter_mut() {
+            for (joint, previous) in solution.iter_mut().zip(&previous) {
+                normalize_near(joint, *previous);
+            }
+        }
+        sort_by_closeness(&mut solutions, &previous);
+        solutions
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.forward_palletizer(&from_six(qs))
+    }
+
+    /// The palletizer has no wrist singularity.
+    fn kinematic_singularity(&self, _qs: &Joints) -> Option<Singularity> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use super::*;
+
+    #[test]
+    fn test_palletizer() {
+        let robot = PalletizerKinematics::new(Parameters::irb2600_12_165());
+        let joints = [0.3, 0.2, -0.1, 1.2];
+        let pose = robot.forward_palletizer(&joints);
+        assert!((pose.rotation * Vector3::z() + Vector3::z()).norm() < 1E-12);
+
+        let solutions = robot.inverse_palletizer(&pose);
+        assert!(solutions.iter().any(|s| s.iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-9)));
+        for solution in &solutions {
+            assert!(compare_poses(&robot.forward_palletizer(solution), &pose, 1E-9, 1E-9).is_ok());
+        }
+
+        // Through the six-axis trait
+        let six = to_six(&joints);
+        assert_eq!(robot.forward(&six), pose);
+        let continuing = robot.inverse_continuing(&pose, &six);
+        assert!(continuing[0].iter().zip(&six).all(|(a, b)| (a - b).abs() < 1E-9));
+
+        // Tilted flange is not reachable
+        let tilted = pose * nalgebra::UnitQuaternion::from_euler_angles(0.2, 0.0, 0.0);
+        assert!(robot.inverse(&tilted).is_empty());
+    }
+}
@@ -2,6 +2,7 @@ pub mod opw_kinematics {
 
     /// Parameters for the robot. See parameters_robots.rs for examples for concrete robot models.
     #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Parameters {
         pub a1: f64,
         pub a2: f64,
@@ -12,5 +13,39 @@ pub mod opw_kinematics {
         pub c4: f64,
         pub offsets: [f64; 6],
         pub sign_corrections: [i8; 6],
+        /// Coupling of J3 to J2 by the parallelogram linkage: the J3 value of the controller
+        /// includes this multiple of J2 (like 1.0 if J3 is measured against the horizontal
+        /// rather than relative to the upper arm), removed before solving the OPW model.
+        /// Zero for the serial arm.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub j3_coupling: f64,
     }
+
+    /// Robot model known at compile time. The parameters are the associated constant, so the
+    /// code generic over the model sees them as constants. Use [`crate::robot_model`] to
+    /// declare the model from the preset or custom parameters.
+    pub trait RobotModel {
+        const PARAMETERS: Parameters;
+    }
+}
+
+/// Declares the unit struct implementing [`opw_kinematics::RobotModel`] with the given
+/// parameters, that must be a constant expression (like the built-in presets):
+/// ```
+/// use rs_opw_kinematics::robot_model;
+/// use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+/// use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+///
+/// robot_model!(Irb2400, Parameters::irb2400_10());
+/// let robot = OPWKinematics::from_model::<Irb2400>();
+/// ```
+#[macro_export]
+macro_rules! robot_model {
+    ($name:ident, $parameters:expr) => {
+        pub struct $name;
+
+        impl $crate::parameters::opw_kinematics::RobotModel for $name {
+            const PARAMETERS: $crate::parameters::opw_kinematics::Parameters = $parameters;
+        }
+    };
 }
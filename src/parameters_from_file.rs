@@ -5,6 +5,8 @@ use regex::Regex;
 use serde::Deserialize;
 use thiserror::Error;
 use crate::parameters::opw_kinematics::Parameters;
+use crate::parameters_import::{DhRow, ImportError};
+use crate::statics::{LinkMass, MassProperties};
 
 /// https://github.com/ros-industrial/fanuc/blob/3ea2842baca3184cc621071b785cbf0c588a4046/fanuc_m16ib_support/config/opw_parameters_m16ib20.yaml
 /// Defines the parameters loading error
@@ -16,6 +18,8 @@ pub enum ParametersError {
     YamlParseError(#[from] serde_yaml::Error),
     #[error("failed to process YAML content")]
     YamlProcessError(#[from] regex::Error),
+    #[error("failed to import the robot geometry")]
+    ImportError(#[from] ImportError),
 }
 
 impl Parameters {
@@ -35,6 +39,9 @@ impl Parameters {
     /// opw_kinematics_joint_offsets: [0.0, 0.0, deg(-90.0), 0.0, 0.0, deg(180.0)]
     /// opw_kinematics_joint_sign_corrections: [1, 1, -1, -1, -1, -1]
     ///
+    /// The optional opw_kinematics_j3_coupling gives `Parameters::j3_coupling` of the robots
+    /// with the parallelogram linkage.
+    ///
     /// FANUC provides may such files for their robots on GitHub
     /// (ros-industrial/fanuc, see fanuc_m10ia_support/config/opw_parameters_m10ia.yaml
     /// YAML extension to parse the deg(angle) function is supported.  
@@ -42,8 +49,31 @@ impl Parameters {
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
+        Self::from_yaml(&contents)
+    }
+
+    /// Reads the robot from the file of any supported format: URDF (.urdf, .xml), the
+    /// Denavit-Hartenberg table (YAML list of six rows with a, alpha, d, theta) or the
+    /// parameters (YAML, as read by `from_yaml`).
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParametersError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("urdf") | Some("xml") => Ok(Self::from_urdf(&contents)?),
+            _ => match serde_yaml::from_str::<[DhRow; 6]>(&contents) {
+                Ok(table) => Ok(Self::from_dh(&table)?),
+                Err(_) => Self::from_yaml(&contents),
+            },
+        }
+    }
 
-        let processed_contents = preprocess_yaml_contents(&contents)?;
+    /// Reads the robot configuration from the YAML string, in the ROS-Industrial format above
+    /// or as written by `to_yaml`.
+    pub fn from_yaml(contents: &str) -> Result<Self, ParametersError> {
+        let processed_contents = preprocess_yaml_contents(contents)?;
+        if !processed_contents.contains("opw_kinematics_geometric_parameters") {
+            return Ok(serde_yaml::from_str(&processed_contents)?);
+        }
 
         let deserialized: YamlParameters = serde_yaml::from_str(&processed_contents)?;
 
@@ -57,8 +87,35 @@ impl Parameters {
             c4: deserialized.opw_kinematics_geometric_parameters.c4,
             offsets: deserialized.opw_kinematics_joint_offsets,
             sign_corrections: deserialized.opw_kinematics_joint_sign_corrections,
+            j3_coupling: deserialized.opw_kinematics_j3_coupling,
         })
     }
+
+    /// Writes the parameters as YAML (the fields of `Parameters`, angles in radians).
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+}
+
+impl MassProperties {
+    /// Reads the masses from the robot YAML file (as read by `Parameters::from_yaml`) that
+    /// has the optional keys:
+    ///
+    /// opw_kinematics_link_masses: [80.0, 30.0, 20.0, 5.0, 2.0, 0.5]
+    /// opw_kinematics_link_centers_of_mass: [[0.05, 0.0, 0.3], [0.0, 0.05, 0.35], ...]
+    /// opw_kinematics_payload: { mass: 10.0, center: [0.02, 0.0, 0.1] }
+    ///
+    /// The centers default to the link origins and the payload to none. None if the file
+    /// has no link masses.
+    pub fn from_yaml(contents: &str) -> Result<Option<Self>, ParametersError> {
+        let processed_contents = preprocess_yaml_contents(contents)?;
+        let deserialized: YamlMasses = serde_yaml::from_str(&processed_contents)?;
+        Ok(deserialized.opw_kinematics_link_masses.map(|masses| {
+            let centers = deserialized.opw_kinematics_link_centers_of_mass.unwrap_or_default();
+            MassProperties::new(std::array::from_fn(|i| LinkMass::new(masses[i], centers[i])))
+                .with_payload(deserialized.opw_kinematics_payload)
+        }))
+    }
 }
 
 fn preprocess_yaml_contents(contents: &str) -> Result<String, regex::Error> {
@@ -79,6 +136,18 @@ struct YamlParameters {
     opw_kinematics_geometric_parameters: GeometricParameters,
     opw_kinematics_joint_offsets: [f64; 6],
     opw_kinematics_joint_sign_corrections: [i8; 6],
+    #[serde(default)]
+    opw_kinematics_j3_coupling: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct YamlMasses {
+    #[serde(default)]
+    opw_kinematics_link_masses: Option<[f64; 6]>,
+    #[serde(default)]
+    opw_kinematics_link_centers_of_mass: Option<[[f64; 3]; 6]>,
+    #[serde(default)]
+    opw_kinematics_payload: LinkMass,
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,293 @@
+//! Import of the OPW parameters from the generic description of the serial chain, as given by
+//! the URDF or the Denavit-Hartenberg table. The geometry is measured with all joints at zero
+//! and must satisfy the OPW assumptions (J1 vertical through the base origin, J2 and J3
+//! parallel to the base y axis, spherical wrist), the joint offsets and sign corrections are
+//! derived from the zero position and the axis directions of the chain. The result is verified
+//! by comparing the flange positions of both models over a set of joint values.
+//!
+//! The flange frame of the URDF (tool0) may be rotated relative to the OPW flange frame; only
+//! the positions are compared.
+
+use std::f64::consts::PI;
+use nalgebra::{Translation3, Unit, UnitQuaternion, Vector3};
+use thiserror::Error;
+use crate::joint_names::JointMappingError;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Tolerance of the geometry checks (meters and direction cosines).
+const GEOMETRY_TOLERANCE: f64 = 1E-6;
+
+/// Defines the parameter import error
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ImportError {
+    #[error("URDF: {0}")]
+    Urdf(#[from] JointMappingError),
+    #[error("geometry does not satisfy the OPW assumptions: {0}")]
+    NotOpw(String),
+    #[error("the wrist is not spherical: {0}")]
+    NotSphericalWrist(String),
+}
+
+/// Row of the classic (distal) Denavit-Hartenberg table: Rz(theta) Tz(d) Tx(a) Rx(alpha).
+/// Joint i rotates about the z axis of the frame i - 1, theta is the value at joint zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DhRow {
+    pub a: f64,
+    pub alpha: f64,
+    pub d: f64,
+    pub theta: f64,
+}
+
+/// Serial chain of six revolute joints. Each joint is given by the transform from the frame
+/// of the previous joint (after its rotation; the base frame for J1) and the rotation axis
+/// in that frame.
+#[derive(Debug, Clone)]
+pub struct SerialChain {
+    pub joints: [(Pose, Unit<Vector3<f64>>); 6],
+    /// Transform from the frame of J6 to the flange.
+    pub flange: Pose,
+}
+
+impl SerialChain {
+    pub fn forward(&self, joints: &Joints) -> Pose {
+        self.joints.iter().zip(joints).fold(Pose::identity(), |pose, ((origin, axis), q)| {
+            pose * origin * UnitQuaternion::from_axis_angle(axis, *q)
+        }) * self.flange
+    }
+
+    /// Poses of the links J1 to J6 (the frames after the rotation of their joint). For a chain
+    /// from the URDF, these are the frames of the child links of the joints.
+    pub fn link_frames(&self, joints: &Joints) -> [Pose; 6] {
+        let mut pose = Pose::identity();
+        std::array::from_fn(|i| {
+            let (origin, axis) = &self.joints[i];
+            pose = pose * origin * UnitQuaternion::from_axis_angle(axis, joints[i]);
+            pose
+        })
+    }
+
+    /// Poses of the joint frames (J1 to J6, then the flange) with all joints at zero.
+    fn zero_frames(&self) -> [Pose; 7] {
+        let mut frames = [Pose::identity(); 7];
+        let mut pose = Pose::identity();
+        for (i, (origin, _)) in self.joints.iter().enumerate() {
+            pose *= origin;
+            frames[i] = pose;
+        }
+        frames[6] = pose * self.flange;
+        frames
+    }
+
+    pub fn from_dh(table: &[DhRow; 6]) -> Self {
+        let transform = |row: &DhRow| {
+            let theta = UnitQuaternion::from_euler_angles(0.0, 0.0, row.theta);
+            Pose::from_parts(Translation3::identity(), theta)
+                * Pose::from_parts(Translation3::new(row.a, 0.0, row.d),
+                                   UnitQuaternion::from_euler_angles(row.alpha, 0.0, 0.0))
+        };
+        SerialChain {
+            joints: std::array::from_fn(|i| {
+                let origin = if i == 0 { Pose::identity() } else { transform(&table[i - 1]) };
+                (origin, Vector3::z_axis())
+            }),
+            flange: transform(&table[5]),
+        }
+    }
+
+    /// Chain from the root link of the URDF to the flange. The joints J1 to J6 are detected as
+    /// by [`crate::joint_names::JointMapping::from_urdf`]. The flange is the link tool0 (or
+    /// flange) fixed to the last link, or else the last link itself.
+    #[cfg(feature = "files")]
+    pub fn from_urdf(urdf: &str) -> Result<Self, ImportError> {
+        use crate::joint_names::{urdf_chain, urdf_joints, JointMapping};
+
+        let joints = urdf_joints(urdf)?;
+        let mapping = JointMapping::from_urdf(urdf)?;
+        let last = joints.iter().find(|j| j.name == mapping.names()[5])
+            .ok_or(JointMappingError::NotDetected)?;
+
+        let mut origins = Vec::with_capacity(6);
+        let mut fixed = Pose::identity();
+        for joint in urdf_chain(&joints, last) {
+            if !joint.movable {
+                fixed *= joint.origin;
+                continue;
+            }
+            if mapping.index_of(&joint.name) != Some(origins.len()) {
+                let message = format!("unexpected joint {} in the chain", joint.name);
+                return Err(ImportError::NotOpw(message));
+            }
+            origins.push((fixed * joint.origin, Unit::new_normalize(joint.axis)));
+            fixed = Pose::identity();
+        }
+
+        // Follow the fixed joints from the last link to tool0 or flange
+        let mut flange = Pose::identity();
+        for name in ["tool0", "flange"] {
+            let mut link = name;
+            let mut path = Pose::identity();
+            while let Some(joint) = joints.iter().find(|j| j.child == link && !j.movable) {
+                path = joint.origin * path;
+                if joint.parent == last.child {
+                    flange = path;
+                    break;
+                }
+                link = &joint.parent;
+            }
+            if flange != Pose::identity() {
+                break;
+            }
+        }
+
+        Ok(SerialChain {
+            joints: origins.try_into().map_err(|_| JointMappingError::NotDetected)?,
+            flange,
+        })
+    }
+}
+
+impl Parameters {
+    /// Parameters of the chain, see the module documentation.
+    pub fn from_chain(chain: &SerialChain) -> Result<Self, ImportError> {
+        let frames = chain.zero_frames();
+        let points: [Vector3<f64>; 6] = std::array::from_fn(|i| frames[i].translation.vector);
+        let axes: [Vector3<f64>; 6] =
+            std::array::from_fn(|i| frames[i].rotation * chain.joints[i].1.into_inner());
+        let flange = frames[6].translation.vector;
+        let near = |a: f64, b: f64| (a - b).abs() < GEOMETRY_TOLERANCE;
+        let fail = |what: &str| Err(ImportError::NotOpw(what.to_string()));
+
+        if !(near(axes[0].z.abs(), 1.0) && near(points[0].x, 0.0) && near(points[0].y, 0.0)) {
+            return fail("J1 is not vertical through the base origin");
+        }
+        if !(1..3).all(|i| near(axes[i].y.abs(), 1.0)) {
+            return fail("J2 and J3 are not parallel to the base y axis");
+        }
+        if !(near(axes[4].y.abs(), 1.0) && near(axes[3].y, 0.0)
+            && axes[3].cross(&axes[5]).norm() < GEOMETRY_TOLERANCE) {
+            return fail("wrist axes are not aligned (J4, J6 in the arm plane, J5 along y)");
+        }
+
+        // Wrist center, where the J4 axis (within the xz plane) crosses the J5 axis (along y)
+        let t = (points[4] - points[3]).xz().dot(&axes[3].xz());
+        let wrist = points[3] + axes[3] * t;
+        if !near(wrist.x, points[4].x) || !near(wrist.z, points[4].z)
+            || (wrist - points[5]).cross(&axes[5]).norm() > GEOMETRY_TOLERANCE {
+            return fail("the wrist is not spherical");
+        }
+        if (flange - wrist).cross(&axes[5]).norm() > GEOMETRY_TOLERANCE {
+            return fail("the flange is not on the J6 axis");
+        }
+
+        // Forearm direction, pointing from the wrist center to the flange
+        let u = if (flange - wrist).dot(&axes[3]) < 0.0 { -axes[3] } else { axes[3] };
+        let upper_arm = points[2] - points[1];
+        let phi = upper_arm.x.atan2(upper_arm.z);
+        let psi = u.x.atan2(u.z);
+        let forearm = wrist - points[2];
+        let sign = |v: f64| if v < 0.0 { -1 } else { 1 };
+        // Remove the rounding noise of the chain transforms (nanometers, right angles)
+        let length = |v: f64| (v * 1E9).round() / 1E9 + 0.0;
+        let angle = |v: f64| {
+            let right = (v / (PI / 2.0)).round() * (PI / 2.0);
+            normalize(if (v - right).abs() < 1E-12 { right } else { v }) + 0.0
+        };
+
+        let parameters = Parameters {
+            a1: length(points[1].x),
+            a2: length(forearm.x * psi.cos() - forearm.z * psi.sin()),
+            b: length(wrist.y),
+            c1: length(points[1].z),
+            c2: length(upper_arm.xz().norm()),
+            c3: length(forearm.x * psi.sin() + forearm.z * psi.cos()),
+            c4: length((flange - wrist).dot(&u)),
+            offsets: [0.0, angle(-phi), angle(phi - psi), 0.0, 0.0, 0.0],
+            sign_corrections: [sign(axes[0].z), sign(axes[1].y), sign(axes[2].y),
+                sign(axes[3].dot(&u)), sign(axes[4].y), sign(axes[5].dot(&u))],
+            ..Parameters::new()
+        };
+
+        let robot = OPWKinematics::new(parameters.clone());
+        for i in 0..32 {
+            let joints: Joints = std::array::from_fn(|j| ((i * 7 + j * 3) % 11) as f64 * 0.3 - 1.5);
+            let error = (robot.forward(&joints).translation.vector
+                - chain.forward(&joints).translation.vector).norm();
+            if error > GEOMETRY_TOLERANCE {
+                return fail(&format!("flange positions differ by {:.3e} m", error));
+            }
+        }
+        Ok(parameters)
+    }
+
+    pub fn from_dh(table: &[DhRow; 6]) -> Result<Self, ImportError> {
+        Self::from_chain(&SerialChain::from_dh(table))
+    }
+
+    #[cfg(feature = "files")]
+    pub fn from_urdf(urdf: &str) -> Result<Self, ImportError> {
+        Self::from_chain(&SerialChain::from_urdf(urdf)?)
+    }
+}
+
+/// Angle normalized to (-PI, PI].
+fn normalize(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped == -PI { PI } else { wrapped }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_robot(a: &Parameters, b: &Parameters) {
+        let (a, b) = (OPWKinematics::new(a.clone()), OPWKinematics::new(b.clone()));
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let (pa, pb) = (a.forward(&joints), b.forward(&joints));
+        assert!((pa.translation.vector - pb.translation.vector).norm() < 1E-9);
+    }
+
+    #[test]
+    fn test_from_dh() {
+        // ABB IRB 2400/10 as the DH table; at zero the forearm is horizontal
+        let table = [
+            DhRow { a: 0.100, alpha: -PI / 2.0, d: 0.615, theta: 0.0 },
+            DhRow { a: 0.705, alpha: 0.0, d: 0.0, theta: -PI / 2.0 },
+            DhRow { a: 0.135, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: PI / 2.0, d: 0.755, theta: 0.0 },
+            DhRow { a: 0.0, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: 0.0, d: 0.085, theta: 0.0 },
+        ];
+        let parameters = Parameters::from_dh(&table).unwrap();
+        assert_same_robot(&parameters, &Parameters::irb2400_10());
+        assert_eq!((parameters.c2, parameters.c3, parameters.c4), (0.705, 0.755, 0.085));
+
+        let mut skewed = table;
+        skewed[1].alpha = 0.1;
+        assert!(matches!(Parameters::from_dh(&skewed), Err(ImportError::NotOpw(_))));
+    }
+
+    #[cfg(feature = "files")]
+    #[test]
+    fn test_from_urdf() {
+        // ABB IRB 2400/10 as in the ROS-Industrial abb_irb2400_support package
+        let joint = |name: &str, parent: &str, child: &str, xyz: &str, axis: &str| format!(
+            r#"<joint name="{}" type="revolute"><parent link="{}"/><child link="{}"/>
+               <origin xyz="{}" rpy="0 0 0"/><axis xyz="{}"/></joint>"#, name, parent, child, xyz, axis);
+        let urdf = format!(r#"<robot name="irb2400">{}{}{}{}{}{}
+            <joint name="joint_6-tool0" type="fixed"><parent link="link_6"/><child link="tool0"/>
+              <origin xyz="0 0 0" rpy="0 1.5707963267948966 0"/></joint></robot>"#,
+            joint("joint_1", "base_link", "link_1", "0 0 0", "0 0 1"),
+            joint("joint_2", "link_1", "link_2", "0.1 0 0.615", "0 1 0"),
+            joint("joint_3", "link_2", "link_3", "0 0 0.705", "0 1 0"),
+            joint("joint_4", "link_3", "link_4", "0 0 0.135", "1 0 0"),
+            joint("joint_5", "link_4", "link_5", "0.755 0 0", "0 1 0"),
+            joint("joint_6", "link_5", "link_6", "0.085 0 0", "1 0 0"));
+        let parameters = Parameters::from_urdf(&urdf).unwrap();
+        assert_same_robot(&parameters, &Parameters::irb2400_10());
+        assert_eq!(parameters.sign_corrections, [1; 6]);
+        assert!((parameters.offsets[2] + PI / 2.0).abs() < 1E-9);
+    }
+}
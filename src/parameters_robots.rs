@@ -1,12 +1,19 @@
 pub mod opw_kinematics {
     use std::f64::consts::PI;
+    use crate::kinematic_traits::Joints;
     use crate::parameters::opw_kinematics::Parameters;
 
+    impl Default for Parameters {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     #[allow(dead_code)]
     impl Parameters {
 
         // Provides default values
-        pub fn new() -> Self {
+        pub const fn new() -> Self {
             Parameters {
                 a1: 0.0,
                 a2: 0.0,
@@ -17,10 +24,59 @@ pub mod opw_kinematics {
                 c4: 0.0,
                 offsets: [0.0; 6],
                 sign_corrections: [1; 6],
+                j3_coupling: 0.0,
             }
         }
        
-        pub fn irb2400_10() -> Self {
+        /// Preset by the name of its constructor function (like "irb2400_10"), for selecting
+        /// the robot from configuration files or foreign language bindings.
+        pub fn preset(name: &str) -> Option<Self> {
+            Some(match name {
+                "irb2400_10" => Self::irb2400_10(),
+                "irb2600_12_165" => Self::irb2600_12_165(),
+                "irb4600_60_205" => Self::irb4600_60_205(),
+                "kuka_kr6_r700_sixx" => Self::kuka_kr6_r700_sixx(),
+                "fanuc_r2000ib_200r" => Self::fanuc_r2000ib_200r(),
+                "staubli_tx40" => Self::staubli_tx40(),
+                "staubli_tx2_140" => Self::staubli_tx2_140(),
+                "staubli_tx2_160" => Self::staubli_tx2_160(),
+                "staubli_tx2_160l" => Self::staubli_tx2_160l(),
+                _ => return None,
+            })
+        }
+
+        /// Joint values of the serial OPW model for the joint values of the controller,
+        /// with the parallelogram coupling (`j3_coupling`) removed.
+        pub fn decoupled(&self, joints: &Joints) -> Joints {
+            let mut decoupled = *joints;
+            decoupled[2] -= self.j3_coupling * joints[1];
+            decoupled
+        }
+
+        /// Joint values of the controller for the joint values of the serial OPW model,
+        /// the inverse of `decoupled`.
+        pub fn coupled(&self, joints: &Joints) -> Joints {
+            let mut coupled = *joints;
+            coupled[2] += self.j3_coupling * joints[1];
+            coupled
+        }
+
+        /// Left-hand variant of the robot, mirrored about the XZ plane of the base. J1, J4 and
+        /// J6 turn about the axes in the mirror plane so they change the direction (sign and
+        /// offset), and the lateral offset `b` moves to the other side. With the same joint
+        /// values the mirrored robot reaches the pose mirrored about the XZ plane, so
+        /// `M * pose * M` with M = diag(1, -1, 1).
+        pub fn mirrored(&self) -> Self {
+            let mut mirrored = self.clone();
+            mirrored.b = -self.b;
+            for i in [0, 3, 5] {
+                mirrored.sign_corrections[i] = -self.sign_corrections[i];
+                mirrored.offsets[i] = -self.offsets[i];
+            }
+            mirrored
+        }
+
+        pub const fn irb2400_10() -> Self {
             Parameters {
                 a1: 0.100,
                 a2: -0.135,
@@ -38,7 +94,7 @@ pub mod opw_kinematics {
         // See https://www.staubli.com/content/dam/robotics/products/robots/tx2/TX2-140-160-datasheet-EN.pdf.
         // These three Staubli robots have spherical wrist and mostly identical plan, with only
         // two parameters being different.
-        pub fn staubli_tx2() -> Self {
+        pub const fn staubli_tx2() -> Self {
             Parameters {
                 a1: 0.150,
                 a2: 0.000,
@@ -51,7 +107,7 @@ pub mod opw_kinematics {
                 ..Self::new()
             }
         }
-        pub fn staubli_tx2_140() -> Self {
+        pub const fn staubli_tx2_140() -> Self {
             Parameters {
                 c2: 0.625,
                 c3: 0.625,
@@ -59,7 +115,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn staubli_tx2_160() -> Self {
+        pub const fn staubli_tx2_160() -> Self {
             Parameters {
                 c2: 0.825,
                 c3: 0.625,
@@ -67,7 +123,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn staubli_tx2_160l() -> Self {
+        pub const fn staubli_tx2_160l() -> Self {
             Parameters {
                 c2: 0.825,
                 c3: 0.925,
@@ -75,7 +131,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn fanuc_r2000ib_200r() -> Self {
+        pub const fn fanuc_r2000ib_200r() -> Self {
             Parameters {
                 a1: 0.720,
                 a2: -0.225,
@@ -89,7 +145,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn kuka_kr6_r700_sixx() -> Self {
+        pub const fn kuka_kr6_r700_sixx() -> Self {
             Parameters {
                 a1: 0.025,
                 a2: -0.035,
@@ -104,7 +160,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn staubli_tx40() -> Self {
+        pub const fn staubli_tx40() -> Self {
             Parameters {
                 a1: 0.000,
                 a2: 0.000,
@@ -118,7 +174,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn irb2600_12_165() -> Self {
+        pub const fn irb2600_12_165() -> Self {
             Parameters {
                 a1: 0.150,
                 a2: -0.115,
@@ -132,7 +188,7 @@ pub mod opw_kinematics {
             }
         }
 
-        pub fn irb4600_60_205() -> Self {
+        pub const fn irb4600_60_205() -> Self {
             Parameters {
                 a1: 0.175,
                 a2: -0.175,
@@ -147,3 +203,47 @@ pub mod opw_kinematics {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::kinematic_traits::Kinematics;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::{Parameters, RobotModel};
+
+    // Presets are usable in constant context
+    const KUKA: Parameters = Parameters::kuka_kr6_r700_sixx();
+    crate::robot_model!(Tx160, Parameters::staubli_tx2_160());
+
+    #[test]
+    fn test_const_presets() {
+        assert_eq!(KUKA.sign_corrections, [-1, 1, 1, -1, 1, -1]);
+        assert_eq!(Tx160::PARAMETERS.c2, 0.825);
+        assert_eq!(Tx160::PARAMETERS.a1, 0.150);
+
+        let robot = OPWKinematics::from_model::<Tx160>();
+        let reference = OPWKinematics::new(Parameters::staubli_tx2_160());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        assert_eq!(robot.forward(&joints), reference.forward(&joints));
+
+        assert_eq!(Parameters::preset("staubli_tx2_160").unwrap().c2, 0.825);
+        assert!(Parameters::preset("no_such_robot").is_none());
+    }
+
+    #[test]
+    fn test_mirrored() {
+        let flip = nalgebra::Matrix4::from_diagonal(&nalgebra::Vector4::new(1.0, -1.0, 1.0, 1.0));
+        let joints = [0.3, 0.2, -0.4, 0.7, 0.5, -1.1];
+        for parameters in [Parameters::irb2400_10(), Parameters::kuka_kr6_r700_sixx(),
+                           Parameters::staubli_tx40()] {
+            let robot = OPWKinematics::new(parameters.mirrored());
+            let reference = OPWKinematics::new(parameters.clone());
+            let expected = flip * reference.forward(&joints).to_homogeneous() * flip;
+            let pose = robot.forward(&joints).to_homogeneous();
+            assert!((pose - expected).norm() < 1E-12);
+
+            let solutions = robot.inverse_continuing(&robot.forward(&joints), &joints);
+            assert!(solutions[0].iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-9));
+            assert_eq!(parameters.mirrored().mirrored().offsets, parameters.offsets);
+        }
+    }
+}
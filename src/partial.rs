@@ -0,0 +1,310 @@
+//! Inverse kinematics with the partially constrained pose: the degrees of freedom of the zero
+//! weight in the `PoseMetric` are left free, like the rotation about the axis of the drill or
+//! the height over the conveyor. The analytic solver does the constrained ones exactly, the
+//! free ones are chosen by a small search keeping the joints closest to the previous ones.
+//!
+//! The tool axis can also be allowed to deviate within the cone (the process window of
+//! drilling or deburring), the deviation chosen for the best margin to the limits and
+//! singularities as defined by the application. For the insertion, the `ToleranceFunnel`
+//! bounds the deviation from the nominal pose per tool axis, asymmetrically if needed.
+
+use std::f64::consts::PI;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::pose_distance::PoseMetric;
+use crate::utils::joint_distance;
+
+/// Slack, meters or radians, of `ToleranceFunnel::contains` for the rounding.
+const CONTAINS_TOLERANCE: f64 = 1E-9;
+
+/// Samples of the coarse scan of every free degree of freedom, before the search refines it.
+const SCAN_SAMPLES: usize = 12;
+
+/// Step, radians or meters, at which the search stops.
+const SEARCH_TOLERANCE: f64 = 1E-7;
+
+/// Tolerances of the pose around the nominal one, per axis of the tool frame, like tight
+/// laterally and loose along the approach for the peg-in-hole insertion. The deviation is
+/// the translation in the tool frame followed by the rotation (rotation vector components,
+/// radians); each has the (min, max) bounds that must include zero.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToleranceFunnel {
+    /// Bounds of the translation along the tool X, Y and Z, meters.
+    pub translation: [(f64, f64); 3],
+    /// Bounds of the rotation about the tool X, Y and Z, radians.
+    pub rotation: [(f64, f64); 3],
+}
+
+impl ToleranceFunnel {
+    /// Funnel with the symmetric lateral (tool X and Y) tolerance, the approach (tool Z)
+    /// bounds and the symmetric tolerance of the tilt (rotation about tool X and Y).
+    pub fn new(lateral: f64, approach: (f64, f64), tilt: f64) -> Self {
+        ToleranceFunnel {
+            translation: [(-lateral, lateral), (-lateral, lateral), approach],
+            rotation: [(-tilt, tilt), (-tilt, tilt), (0.0, 0.0)],
+        }
+    }
+
+    /// Sets the bounds of the rotation about the tool Z.
+    pub fn spin(self, min: f64, max: f64) -> Self {
+        let mut rotation = self.rotation;
+        rotation[2] = (min, max);
+        ToleranceFunnel { rotation, ..self }
+    }
+
+    /// Pose deviating from the nominal one by the translation and rotation.
+    pub fn deviate(nominal: &Pose, deviation: &[f64; 6]) -> Pose {
+        nominal * Pose::from_parts(
+            Translation3::new(deviation[0], deviation[1], deviation[2]),
+            UnitQuaternion::from_scaled_axis(Vector3::new(deviation[3], deviation[4], deviation[5])))
+    }
+
+    /// True if the pose is within the funnel around the nominal one.
+    pub fn contains(&self, nominal: &Pose, pose: &Pose) -> bool {
+        let deviation = nominal.inverse() * pose;
+        let translation = deviation.translation.vector;
+        let rotation = deviation.rotation.scaled_axis();
+        let within = |value: f64, (min, max): (f64, f64)| {
+            value >= min - CONTAINS_TOLERANCE && value <= max + CONTAINS_TOLERANCE
+        };
+        (0..3).all(|i| within(translation[i], self.translation[i])
+            && within(rotation[i], self.rotation[i]))
+    }
+}
+
+/// Inverse kinematics with the free degrees of freedom. Implemented for all solvers.
+pub trait PartialKinematics: Kinematics {
+    /// Pose matching `pose` in all degrees of freedom of nonzero weight in `metric` (the
+    /// translation along the base axes and the rotation about the tool axes), with the joints
+    /// to reach it. The free rotations range over a full turn, the free translations over
+    /// `travel` meters either side of `pose`. Among the poses, the one with the solution
+    /// closest to the previous joints (`joint_distance`) is taken. None if no pose is reachable
+    /// in the sampled range.
+    fn inverse_partial(&self, pose: &Pose, previous: &Joints, metric: &PoseMetric, travel: f64)
+                       -> Option<(Pose, Joints)> {
+        // Free degrees of freedom: 0..3 translation, 3..6 rotation, with their half range
+        let free: Vec<(usize, f64)> = metric.translation_axes.iter()
+            .chain(metric.rotation_axes.iter()).enumerate()
+            .filter(|(_, weight)| **weight == 0.0)
+            .map(|(dof, _)| (dof, if dof < 3 { travel } else { PI }))
+            .collect();
+
+        let candidate = |values: &[f64]| -> Pose {
+            let mut shift = [0.0; 6];
+            for ((dof, _), value) in free.iter().zip(values) {
+                shift[*dof] = *value;
+            }
+            let translation = pose.translation.vector
+                + Vector3::new(shift[0], shift[1], shift[2]);
+            let rotation = pose.rotation
+                * UnitQuaternion::from_scaled_axis(Vector3::new(shift[3], shift[4], shift[5]));
+            Pose::from_parts(Translation3::from(translation), rotation)
+        };
+        let best = |values: &[f64]| -> Option<(f64, Joints)> {
+            self.inverse_continuing(&candidate(values), previous).into_iter()
+                .map(|joints| (joint_distance(&joints, previous), joints))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+        };
+        let cost = |values: &[f64]| best(values).map_or(f64::INFINITY, |(cost, _)| cost);
+
+        let bounds: Vec<(f64, f64)> = free.iter().map(|(_, range)| (-range, *range)).collect();
+        let values = minimize(&bounds, cost);
+        best(&values).map(|(_, joints)| (candidate(&values), joints))
+    }
+
+    /// Pose with the tool Z axis within the cone of `half_angle` radians around the tool Z of
+    /// `pose` (the position and the rotation about the tool axis are kept), with the joints
+    /// to reach it, choosing the deviation and the solution of `inverse_continuing` of the
+    /// highest `margin`, like the distance to the joint limits or the manipulability. The
+    /// solutions of the margin of NaN or negative infinity are rejected. None if none is left.
+    fn inverse_cone<M: Fn(&Joints) -> f64>(&self, pose: &Pose, previous: &Joints, half_angle: f64,
+                                           margin: M) -> Option<(Pose, Joints)> {
+        // Tilt is the rotation vector perpendicular to the tool Z
+        let candidate = |tilt: &[f64]| -> Pose {
+            *pose * UnitQuaternion::from_scaled_axis(Vector3::new(tilt[0], tilt[1], 0.0))
+        };
+        let best = |tilt: &[f64]| -> Option<(f64, Joints)> {
+            if tilt[0].hypot(tilt[1]) > half_angle {
+                return None;
+            }
+            self.inverse_continuing(&candidate(tilt), previous).into_iter()
+                .map(|joints| (-margin(&joints), joints))
+                .filter(|(cost, _)| !cost.is_nan() && *cost != f64::INFINITY)
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+        };
+        let cost = |tilt: &[f64]| best(tilt).map_or(f64::INFINITY, |(cost, _)| cost);
+
+        let tilt = minimize(&[(-half_angle, half_angle); 2], cost);
+        best(&tilt).map(|(_, joints)| (candidate(&tilt), joints))
+    }
+
+    /// Pose within the funnel around the `nominal` one with the solution closest to the
+    /// previous joints (`joint_distance`), and this solution. The nominal pose is taken
+    /// if none is closer. None if no pose in the funnel is reachable.
+    fn inverse_funnel(&self, nominal: &Pose, previous: &Joints, funnel: &ToleranceFunnel)
+                      -> Option<(Pose, Joints)> {
+        // Only the bounds wider than a point are searched
+        let bounds: Vec<(f64, f64)> = funnel.translation.iter().chain(funnel.rotation.iter())
+            .copied().collect();
+        let free: Vec<usize> = (0..6).filter(|i| bounds[*i].0 < bounds[*i].1).collect();
+        let candidate = |values: &[f64]| -> Pose {
+            let mut deviation = [0.0; 6];
+            for (i, (min, max)) in bounds.iter().enumerate() {
+                deviation[i] = 0.0_f64.clamp(*min, *max);
+            }
+            for (i, value) in free.iter().zip(values) {
+                deviation[*i] = *value;
+            }
+            ToleranceFunnel::deviate(nominal, &deviation)
+        };
+        let best = |values: &[f64]| -> Option<(f64, Joints)> {
+            self.inverse_continuing(&candidate(values), previous).into_iter()
+                .map(|joints| (joint_distance(&joints, previous), joints))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+        };
+        let cost = |values: &[f64]| best(values).map_or(f64::INFINITY, |(cost, _)| cost);
+
+        let values = minimize(&free.iter().map(|i| bounds[*i]).collect::<Vec<_>>(), cost);
+        best(&values).map(|(_, joints)| (candidate(&values), joints))
+    }
+}
+
+impl<K: Kinematics + ?Sized> PartialKinematics for K {}
+
+/// Values within the bounds (min, max) of each that minimize the cost (zeros, or the nearest
+/// bounds, if nothing is better). The coarse scan of each value comes first, as the branches
+/// make the cost discontinuous, then the pattern search refines it.
+fn minimize<C: Fn(&[f64]) -> f64>(bounds: &[(f64, f64)], cost: C) -> Vec<f64> {
+    let mut values: Vec<f64> = bounds.iter().map(|(min, max)| 0.0_f64.clamp(*min, *max)).collect();
+    let mut lowest = cost(&values);
+    for (i, (min, max)) in bounds.iter().enumerate() {
+        for sample in 0..=SCAN_SAMPLES {
+            let mut trial = values.clone();
+            trial[i] = min + (max - min) * sample as f64 / SCAN_SAMPLES as f64;
+            let trial_cost = cost(&trial);
+            if trial_cost < lowest {
+                lowest = trial_cost;
+                values = trial;
+            }
+        }
+    }
+
+    let mut step = bounds.iter().map(|(min, max)| (max - min) / SCAN_SAMPLES as f64)
+        .fold(0.0, f64::max);
+    while lowest.is_finite() && step > SEARCH_TOLERANCE {
+        let mut improved = false;
+        for (i, (min, max)) in bounds.iter().enumerate() {
+            for direction in [-1.0, 1.0] {
+                let mut trial = values.clone();
+                trial[i] = (trial[i] + direction * step).clamp(*min, *max);
+                let trial_cost = cost(&trial);
+                if trial_cost < lowest {
+                    lowest = trial_cost;
+                    values = trial;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            step /= 2.0;
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_free_tool_rotation() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let previous = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        // Target is the current pose turned about the tool Z, that is free
+        let pose = robot.forward(&previous) * UnitQuaternion::from_euler_angles(0.0, 0.0, 1.5);
+        let metric = PoseMetric::default().rotation_axes([1.0, 1.0, 0.0]);
+        let (reached, joints) = robot.inverse_partial(&pose, &previous, &metric, 0.0).unwrap();
+        assert!(joint_distance(&joints, &previous) < 1E-5);
+        assert!(metric.distance(&pose, &reached) < 1E-9);
+        assert!((robot.forward(&joints).translation.vector - pose.translation.vector).norm() < 1E-6);
+
+        // Fully constrained, the pose is taken as is
+        let (reached, joints) = robot.inverse_partial(&pose, &previous, &PoseMetric::default(), 0.0)
+            .unwrap();
+        assert_eq!(reached, pose);
+        assert!(joint_distance(&joints, &previous) > 1.0);
+    }
+
+    #[test]
+    fn test_free_height() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let previous = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let current = robot.forward(&previous);
+        let pose = Pose::from_parts(Translation3::new(current.translation.x,
+                                                      current.translation.y, 5.0),
+                                    current.rotation);
+        let metric = PoseMetric::default().translation_axes([1.0, 1.0, 0.0]);
+        assert!(robot.inverse(&pose).is_empty());
+        let (reached, joints) = robot.inverse_partial(&pose, &previous, &metric, 5.0).unwrap();
+        assert!((reached.translation.z - current.translation.z).abs() < 1E-5);
+        assert!(joint_distance(&joints, &previous) < 1E-4);
+
+        // Not enough travel to reach
+        assert!(robot.inverse_partial(&pose, &previous, &metric, 0.1).is_none());
+    }
+
+    #[test]
+    fn test_cone() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let previous = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&previous);
+        let half_angle = 0.2;
+
+        // Keep J5 as far from the wrist singularity as the cone allows
+        let margin = |joints: &Joints| joints[4].abs();
+        let (tilted, joints) = robot.inverse_cone(&pose, &previous, half_angle, margin).unwrap();
+        let deviation = (pose.rotation * Vector3::z()).angle(&(tilted.rotation * Vector3::z()));
+        assert!(deviation <= half_angle + 1E-9);
+        assert!(joints[4] > 0.5 + half_angle - 1E-3);
+        assert!((tilted.translation.vector - pose.translation.vector).norm() < 1E-12);
+        assert!((robot.forward(&joints).translation.vector - pose.translation.vector).norm() < 1E-6);
+
+        // Without the cone the pose is taken as is, rejected solutions are never returned
+        let (exact, _) = robot.inverse_cone(&pose, &previous, 0.0, margin).unwrap();
+        assert_eq!(exact, pose);
+        assert!(robot.inverse_cone(&pose, &previous, half_angle, |_| f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_funnel() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let previous = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let current = robot.forward(&previous);
+        // Nominal pose is 1 mm aside and 20 mm further along the approach than the robot is
+        let nominal = ToleranceFunnel::deviate(&current, &[0.001, 0.0, -0.02, 0.0, 0.0, 0.0]);
+        let funnel = ToleranceFunnel::new(0.002, (-0.01, 0.03), 0.0);
+
+        let (pose, joints) = robot.inverse_funnel(&nominal, &previous, &funnel).unwrap();
+        assert!(funnel.contains(&nominal, &pose));
+        assert!((robot.forward(&joints).translation.vector - pose.translation.vector).norm() < 1E-6);
+        // The robot can stay where it is
+        assert!(joint_distance(&joints, &previous) < 1E-4);
+        assert!(funnel.contains(&nominal, &current));
+
+        // Tight along the approach, the robot moves to the bound nearest to it
+        let tight = ToleranceFunnel::new(0.002, (-0.001, 0.001), 0.0);
+        assert!(!tight.contains(&nominal, &current));
+        let (pose, _) = robot.inverse_funnel(&nominal, &previous, &tight).unwrap();
+        assert!(tight.contains(&nominal, &pose));
+        let along = (nominal.inverse() * pose).translation.z;
+        assert!((along - 0.001).abs() < 1E-5);
+
+        let exact = ToleranceFunnel::default();
+        assert_eq!(robot.inverse_funnel(&nominal, &previous, &exact).unwrap().0, nominal);
+        let far = Pose::translation(10.0, 0.0, 0.0);
+        assert!(robot.inverse_funnel(&far, &previous, &funnel).is_none());
+    }
+}
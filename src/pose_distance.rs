@@ -0,0 +1,94 @@
+//! Distance between poses combining translation (meters) and rotation (radians) with explicit
+//! weights, for ranking and thresholding poses the same way everywhere instead of mixing the
+//! units ad hoc:
+//! ```
+//! use rs_opw_kinematics::kinematic_traits::Pose;
+//! use rs_opw_kinematics::pose_distance::PoseMetric;
+//!
+//! // One radian counts as 0.2 m, the rotation about the tool Z axis is ignored
+//! let metric = PoseMetric::new(1.0, 0.2).rotation_axes([1.0, 1.0, 0.0]);
+//! let a = Pose::translation(0.5, 0.0, 0.5);
+//! let b = Pose::translation(0.5, 0.3, 0.9);
+//! assert!((metric.distance(&a, &b) - 0.5).abs() < 1E-12);
+//! ```
+
+use nalgebra::Vector3;
+use crate::kinematic_traits::Pose;
+
+/// Weighted SE(3) distance. The translation difference is taken along the base axes, the
+/// rotation difference as the rotation vector (axis times angle) in the frame of the first
+/// pose, so the rotation axis weights apply to the tool axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseMetric {
+    /// Meters of distance per meter of translation.
+    pub translation_weight: f64,
+    /// Meters of distance per radian of rotation.
+    pub rotation_weight: f64,
+    /// Relative weights of the translation along X, Y and Z of the base.
+    pub translation_axes: [f64; 3],
+    /// Relative weights of the rotation about X, Y and Z of the tool.
+    pub rotation_axes: [f64; 3],
+}
+
+impl Default for PoseMetric {
+    /// One radian counted as 0.1 m, about the size of the typical tool, all axes weighted equally.
+    fn default() -> Self {
+        Self::new(1.0, 0.1)
+    }
+}
+
+impl PoseMetric {
+    pub fn new(translation_weight: f64, rotation_weight: f64) -> Self {
+        PoseMetric { translation_weight, rotation_weight, translation_axes: [1.0; 3],
+            rotation_axes: [1.0; 3] }
+    }
+
+    pub fn translation_axes(self, translation_axes: [f64; 3]) -> Self {
+        PoseMetric { translation_axes, ..self }
+    }
+
+    pub fn rotation_axes(self, rotation_axes: [f64; 3]) -> Self {
+        PoseMetric { rotation_axes, ..self }
+    }
+
+    /// Weighted translation and rotation parts of the distance, meters each.
+    pub fn components(&self, a: &Pose, b: &Pose) -> (f64, f64) {
+        let translation = (b.translation.vector - a.translation.vector)
+            .component_mul(&Vector3::from(self.translation_axes)).norm();
+        let rotation = (a.rotation.inverse() * b.rotation).scaled_axis()
+            .component_mul(&Vector3::from(self.rotation_axes)).norm();
+        (self.translation_weight * translation, self.rotation_weight * rotation)
+    }
+
+    /// Distance between the poses, meters: the Euclidean combination of both components.
+    pub fn distance(&self, a: &Pose, b: &Pose) -> f64 {
+        let (translation, rotation) = self.components(a, b);
+        translation.hypot(rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use nalgebra::{Translation3, UnitQuaternion};
+    use super::*;
+
+    #[test]
+    fn test_pose_metric() {
+        let a = Pose::from_parts(Translation3::new(1.0, 0.0, 0.0),
+                                 UnitQuaternion::from_euler_angles(0.0, PI / 2.0, 0.0));
+        let turned = a * UnitQuaternion::from_euler_angles(0.0, 0.0, 0.5);
+        let metric = PoseMetric::default();
+        assert_eq!(metric.components(&a, &turned).0, 0.0);
+        assert!((metric.distance(&a, &turned) - 0.05).abs() < 1E-12);
+        assert!((metric.distance(&turned, &a) - 0.05).abs() < 1E-12);
+
+        // Rotation about the tool Z is ignored even though the tool Z is the base X here
+        assert!(metric.rotation_axes([1.0, 1.0, 0.0]).distance(&a, &turned) < 1E-12);
+
+        let moved = Pose::from_parts(Translation3::new(1.0, 0.0, 0.4), a.rotation);
+        assert!((metric.distance(&a, &moved) - 0.4).abs() < 1E-12);
+        assert_eq!(metric.translation_axes([1.0, 1.0, 0.0]).distance(&a, &moved), 0.0);
+    }
+}
@@ -0,0 +1,160 @@
+//! Poses as 4 x 4 homogeneous matrices, nested (`[[f64; 4]; 4]`) or flat (`[f64; 16]`). The
+//! layout is always given explicitly, as the row and column major conventions differ between
+//! libraries (OpenCV and most CAD formats are row major, OpenGL and Eigen column major).
+//!
+//! Poses can also be given as the position and rotation vector (unit axis times the angle in
+//! radians), as used by OpenCV `rvec`, many vision pipelines and Universal Robots controllers.
+
+use nalgebra::{Matrix3, Matrix4, Quaternion, Rotation3, Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::Pose;
+use crate::validation::PoseError;
+
+/// Order of the matrix elements in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatrixLayout {
+    /// Rows follow each other: `m[row][column]`, translation in the last column.
+    RowMajor,
+    /// Columns follow each other: `m[column][row]`, translation in the last nested array.
+    ColumnMajor,
+}
+
+/// Tolerance for the last row of the homogeneous matrix to differ from [0, 0, 0, 1].
+const HOMOGENEOUS_TOLERANCE: f64 = 1E-9;
+
+fn to_matrix4(m: &[[f64; 4]; 4], layout: MatrixLayout) -> Matrix4<f64> {
+    match layout {
+        MatrixLayout::RowMajor => Matrix4::from_fn(|r, c| m[r][c]),
+        MatrixLayout::ColumnMajor => Matrix4::from_fn(|r, c| m[c][r]),
+    }
+}
+
+/// Pose from the homogeneous matrix. Fails if the values are not finite or the last row is
+/// not [0, 0, 0, 1] (which is also what the matrix in the wrong layout usually looks like).
+/// The rotation part is replaced by the closest rotation, as the matrices written with
+/// limited precision are not exactly orthonormal.
+pub fn pose_from_matrix(m: &[[f64; 4]; 4], layout: MatrixLayout) -> Result<Pose, PoseError> {
+    let m = to_matrix4(m, layout);
+    if !m.iter().all(|v| v.is_finite()) {
+        return Err(PoseError::NotFinite);
+    }
+    let last = m.row(3);
+    if (last - Matrix4::<f64>::identity().row(3)).amax() > HOMOGENEOUS_TOLERANCE {
+        return Err(PoseError::NotHomogeneous);
+    }
+    let rotation: Matrix3<f64> = m.fixed_view::<3, 3>(0, 0).into_owned();
+    let rotation = Rotation3::from_matrix(&rotation);
+    Ok(Pose::from_parts(Translation3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]),
+                        UnitQuaternion::from_rotation_matrix(&rotation)))
+}
+
+/// Homogeneous matrix of the pose.
+pub fn pose_to_matrix(pose: &Pose, layout: MatrixLayout) -> [[f64; 4]; 4] {
+    let m = pose.to_homogeneous();
+    match layout {
+        MatrixLayout::RowMajor => std::array::from_fn(|r| std::array::from_fn(|c| m[(r, c)])),
+        MatrixLayout::ColumnMajor => std::array::from_fn(|c| std::array::from_fn(|r| m[(r, c)])),
+    }
+}
+
+/// Pose from the flat homogeneous matrix, see `pose_from_matrix`.
+pub fn pose_from_flat(m: &[f64; 16], layout: MatrixLayout) -> Result<Pose, PoseError> {
+    pose_from_matrix(&std::array::from_fn(|i| std::array::from_fn(|j| m[i * 4 + j])), layout)
+}
+
+/// Flat homogeneous matrix of the pose.
+pub fn pose_to_flat(pose: &Pose, layout: MatrixLayout) -> [f64; 16] {
+    let m = pose_to_matrix(pose, layout);
+    std::array::from_fn(|i| m[i / 4][i % 4])
+}
+
+/// Below this angle (radians), rotation vectors are converted with the series expansion of the
+/// sine instead of dividing by the small angle.
+const SMALL_ANGLE: f64 = 1E-6;
+
+/// Pose from the position and rotation vector. Zero and very small rotation vectors are
+/// handled without loss of precision.
+pub fn pose_from_rotation_vector(translation: [f64; 3], rotation: [f64; 3]) -> Pose {
+    let v = Vector3::from(rotation);
+    let angle = v.norm();
+    // sin(angle / 2) / angle, the scale of the quaternion vector part
+    let scale = if angle < SMALL_ANGLE {
+        0.5 - angle * angle / 48.0
+    } else {
+        (angle / 2.0).sin() / angle
+    };
+    let q = Quaternion::from_parts((angle / 2.0).cos(), v * scale);
+    Pose::from_parts(Translation3::from(Vector3::from(translation)),
+                     UnitQuaternion::from_quaternion(q))
+}
+
+/// Position and rotation vector of the pose. The rotation angle is in [0, pi].
+pub fn pose_to_rotation_vector(pose: &Pose) -> ([f64; 3], [f64; 3]) {
+    let q = pose.rotation.quaternion();
+    let q = if q.w < 0.0 { -q } else { *q };
+    let sin_half = q.imag().norm();
+    // angle / sin(angle / 2), the inverse of the scale above
+    let scale = if sin_half < SMALL_ANGLE {
+        2.0 + sin_half * sin_half / 3.0
+    } else {
+        2.0 * sin_half.atan2(q.w) / sin_half
+    };
+    let t = pose.translation.vector;
+    ([t.x, t.y, t.z], (q.imag() * scale).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_matrix_layouts() {
+        let pose = Pose::from_parts(Translation3::new(1.0, 2.0, 3.0),
+                                    UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3));
+
+        let rows = pose_to_matrix(&pose, MatrixLayout::RowMajor);
+        assert_eq!([rows[0][3], rows[1][3], rows[2][3]], [1.0, 2.0, 3.0]);
+        let flat = pose_to_flat(&pose, MatrixLayout::ColumnMajor);
+        assert_eq!(flat[12..], [1.0, 2.0, 3.0, 1.0]);
+
+        for layout in [MatrixLayout::RowMajor, MatrixLayout::ColumnMajor] {
+            let restored = pose_from_matrix(&pose_to_matrix(&pose, layout), layout).unwrap();
+            assert!((restored.translation.vector - pose.translation.vector).norm() < 1E-12);
+            assert!(restored.rotation.angle_to(&pose.rotation) < 1E-12);
+            let restored = pose_from_flat(&pose_to_flat(&pose, layout), layout).unwrap();
+            assert!(restored.rotation.angle_to(&pose.rotation) < 1E-12);
+        }
+
+        // Transposition bug is detected
+        assert_eq!(pose_from_matrix(&rows, MatrixLayout::ColumnMajor), Err(PoseError::NotHomogeneous));
+
+        // Slightly non-orthonormal rotation (as printed with 4 decimals) is accepted
+        let rounded = rows.map(|row| row.map(|v| (v * 1E4).round() / 1E4));
+        let restored = pose_from_matrix(&rounded, MatrixLayout::RowMajor).unwrap();
+        assert!(restored.rotation.angle_to(&pose.rotation) < 1E-3);
+        assert!(((restored.rotation * Vector3::x()).norm() - 1.0).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_rotation_vector() {
+        for rotation in [[0.3, -0.2, 0.1], [0.0, 0.0, 3.0], [1E-9, 0.0, -2E-9], [0.0; 3],
+            [0.0, 0.0, std::f64::consts::PI]] {
+            let pose = pose_from_rotation_vector([1.0, 2.0, 3.0], rotation);
+            let expected = UnitQuaternion::from_scaled_axis(Vector3::from(rotation));
+            assert!(pose.rotation.angle_to(&expected) < 1E-12);
+            let (translation, restored) = pose_to_rotation_vector(&pose);
+            assert_eq!(translation, [1.0, 2.0, 3.0]);
+            let restored = pose_from_rotation_vector(translation, restored);
+            assert!(pose.rotation.angle_to(&restored.rotation) < 1E-12);
+        }
+
+        // Tiny rotations are preserved rather than rounded to zero
+        let (_, tiny) = pose_to_rotation_vector(&pose_from_rotation_vector([0.0; 3], [1E-12, 0.0, 0.0]));
+        assert!((tiny[0] - 1E-12).abs() < 1E-24);
+
+        // Angles above pi come back as the equivalent rotation about the opposite axis
+        let (_, wrapped) = pose_to_rotation_vector(&pose_from_rotation_vector([0.0; 3], [0.0, 0.0, 4.0]));
+        assert!((wrapped[2] - (4.0 - 2.0 * std::f64::consts::PI)).abs() < 1E-12);
+    }
+}
@@ -0,0 +1,135 @@
+//! Python bindings (feature `python`), built with PyO3. Build the wheel with
+//! [maturin](https://www.maturin.rs/): `maturin develop --features python`.
+//!
+//! Single calls take and return lists: joints (6 values, radians) and poses (7 values:
+//! x, y, z, qx, qy, qz, qw). Batch calls take and return numpy arrays with one joint
+//! position or pose per row, and release the GIL while computing. Poses with non-finite
+//! values or the quaternion far from unit raise ValueError.
+
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyReadonlyArray2};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions, MAX_SOLUTIONS};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use crate::validation::{pose_from_raw, validate_parameters};
+
+/// Robot kinematics exported to Python.
+#[pyclass(name = "Robot", frozen)]
+pub struct PyRobot {
+    robot: OPWKinematics,
+}
+
+#[pymethods]
+impl PyRobot {
+    /// Robot with the given OPW parameters, raises ValueError if `validate_parameters` fails.
+    #[new]
+    #[pyo3(signature = (a1, a2, b, c1, c2, c3, c4, offsets = [0.0; 6], sign_corrections = [1; 6]))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(a1: f64, a2: f64, b: f64, c1: f64, c2: f64, c3: f64, c4: f64,
+           offsets: [f64; 6], sign_corrections: [i8; 6]) -> PyResult<Self> {
+        let parameters = Parameters { a1, a2, b, c1, c2, c3, c4, offsets, sign_corrections,
+            ..Parameters::new() };
+        validate_parameters(&parameters).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyRobot { robot: OPWKinematics::new(parameters) })
+    }
+
+    /// Robot from the built-in preset, like "irb2400_10".
+    #[staticmethod]
+    fn preset(name: &str) -> PyResult<Self> {
+        let parameters = Parameters::preset(name)
+            .ok_or_else(|| PyKeyError::new_err(format!("unknown robot preset {}", name)))?;
+        Ok(PyRobot { robot: OPWKinematics::new(parameters) })
+    }
+
+    fn forward(&self, joints: Joints) -> [f64; 7] {
+        pose_to_array(&self.robot.forward(&joints))
+    }
+
+    fn inverse(&self, pose: [f64; 7]) -> PyResult<Solutions> {
+        Ok(self.robot.inverse(&array_to_pose(&pose)?))
+    }
+
+    fn inverse_continuing(&self, pose: [f64; 7], previous: Joints) -> PyResult<Solutions> {
+        Ok(self.robot.inverse_continuing(&array_to_pose(&pose)?, &previous))
+    }
+
+    /// Forward kinematics for N x 6 array of joints, returns N x 7 array of poses.
+    fn forward_batch<'py>(&self, py: Python<'py>, joints: PyReadonlyArray2<'py, f64>)
+                          -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let joints = joints.as_array();
+        if joints.ncols() != 6 {
+            return Err(PyValueError::new_err("joints must be N x 6 array"));
+        }
+        let joints = joints.to_owned();
+        let poses = py.detach(|| {
+            let mut poses = Array2::zeros((joints.nrows(), 7));
+            for (row, mut pose) in joints.rows().into_iter().zip(poses.rows_mut()) {
+                let q: Joints = std::array::from_fn(|i| row[i]);
+                for (dst, src) in pose.iter_mut().zip(pose_to_array(&self.robot.forward(&q))) {
+                    *dst = src;
+                }
+            }
+            poses
+        });
+        Ok(poses.into_pyarray(py))
+    }
+
+    /// Inverse kinematics for N x 7 array of poses. Returns N x 9 x 6 array with all
+    /// solutions per pose, padded with NaN. If the previous joints are given (N x 6),
+    /// solutions are computed as by `inverse_continuing`, closest first.
+    #[pyo3(signature = (poses, previous = None))]
+    fn inverse_batch<'py>(&self, py: Python<'py>, poses: PyReadonlyArray2<'py, f64>,
+                          previous: Option<PyReadonlyArray2<'py, f64>>)
+                          -> PyResult<Bound<'py, PyArray3<f64>>> {
+        let poses = poses.as_array();
+        if poses.ncols() != 7 {
+            return Err(PyValueError::new_err("poses must be N x 7 array"));
+        }
+        let poses = poses.rows().into_iter()
+            .map(|row| array_to_pose(&std::array::from_fn(|j| row[j])))
+            .collect::<PyResult<Vec<_>>>()?;
+        let previous = previous.map(|p| p.as_array().to_owned());
+        if let Some(previous) = &previous {
+            if previous.dim() != (poses.len(), 6) {
+                return Err(PyValueError::new_err("previous must be N x 6 array"));
+            }
+        }
+        let solutions = py.detach(|| {
+            let mut result = Array3::from_elem((poses.len(), MAX_SOLUTIONS, 6), f64::NAN);
+            for (i, pose) in poses.iter().enumerate() {
+                let solutions = match &previous {
+                    Some(previous) => {
+                        let q: Joints = std::array::from_fn(|j| previous[(i, j)]);
+                        self.robot.inverse_continuing_fixed(pose, &q)
+                    }
+                    None => self.robot.inverse_fixed(pose),
+                };
+                for (s, solution) in solutions.iter().enumerate() {
+                    for (j, value) in solution.iter().enumerate() {
+                        result[(i, s, j)] = *value;
+                    }
+                }
+            }
+            result
+        });
+        Ok(solutions.into_pyarray(py))
+    }
+}
+
+fn array_to_pose(values: &[f64; 7]) -> PyResult<Pose> {
+    let [x, y, z, qx, qy, qz, qw] = *values;
+    pose_from_raw([x, y, z], [qx, qy, qz, qw]).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn pose_to_array(pose: &Pose) -> [f64; 7] {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    [t.x, t.y, t.z, q.i, q.j, q.k, q.w]
+}
+
+#[pymodule]
+fn rs_opw_kinematics(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRobot>()
+}
@@ -0,0 +1,114 @@
+//! Ranking of the inverse kinematics solutions by the cost defined by the application, like
+//! combining the distance to the seed with the margin to the joint limits and manipulability:
+//! ```
+//! use rs_opw_kinematics::kinematic_traits::Kinematics;
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//! use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+//! use rs_opw_kinematics::ranking::RankedKinematics;
+//! use rs_opw_kinematics::utils::joint_distance;
+//!
+//! let robot = OPWKinematics::new(Parameters::irb2400_10());
+//! let seed = [0.0, 0.1, 0.2, 0.0, 0.5, 0.0];
+//! let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+//! let solutions = robot.inverse_ranked(&pose, &seed, |joints| {
+//!     if joints[3].abs() > 2.0 {
+//!         return f64::INFINITY; // J4 cable limit
+//!     }
+//!     joint_distance(joints, &seed) - 0.1 * robot.manipulability(joints)
+//! });
+//! assert!(solutions.iter().all(|s| s[3].abs() <= 2.0));
+//! ```
+
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+
+/// Inverse kinematics ranked by the cost. Implemented for all solvers.
+pub trait RankedKinematics: Kinematics {
+    /// Solutions as returned by `inverse_continuing` (so normalized near the previous joints,
+    /// with the singularity handled), ordered by the ascending cost. Solutions of the equal
+    /// cost keep the order of `inverse_continuing`. Solutions with the cost of NaN or positive
+    /// infinity are dropped, so the cost can also reject them.
+    fn inverse_ranked<C: Fn(&Joints) -> f64>(&self, pose: &Pose, previous: &Joints, cost: C)
+                                              -> Solutions {
+        let mut scored: Vec<(f64, Joints)> = self.inverse_continuing(pose, previous).into_iter()
+            .map(|joints| (cost(&joints), joints))
+            .filter(|(cost, _)| !cost.is_nan() && *cost != f64::INFINITY)
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored.into_iter().map(|(_, joints)| joints).collect()
+    }
+
+    /// Best solution among the candidate goals (like alternative grasps): solves every goal
+    /// continuing from the previous joints and returns the index of the goal and the solution
+    /// of the lowest cost, costs compared across the goals and branches alike. On equal cost,
+    /// the earlier goal and then the earlier solution of `inverse_continuing` wins. Rejected
+    /// solutions (cost of NaN or positive infinity) are never returned. None if no goal has
+    /// the acceptable solution.
+    fn inverse_best<C: Fn(usize, &Joints) -> f64>(&self, goals: &[Pose], previous: &Joints,
+                                                   cost: C) -> Option<(usize, Joints)> {
+        let mut best: Option<(f64, usize, Joints)> = None;
+        for (goal, pose) in goals.iter().enumerate() {
+            for joints in self.inverse_continuing(pose, previous) {
+                let cost = cost(goal, &joints);
+                if !cost.is_nan() && cost != f64::INFINITY
+                    && best.is_none_or(|(best, _, _)| cost < best) {
+                    best = Some((cost, goal, joints));
+                }
+            }
+        }
+        best.map(|(_, goal, joints)| (goal, joints))
+    }
+}
+
+impl<K: Kinematics + ?Sized> RankedKinematics for K {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_inverse_ranked() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        let continuing = robot.inverse_continuing(&pose, &joints);
+
+        // Constant cost keeps the order of inverse_continuing
+        assert_eq!(robot.inverse_ranked(&pose, &joints, |_| 1.0), continuing);
+
+        // Prefer the elbow down and the J1 pointing backwards, reject J5 negative
+        let ranked = robot.inverse_ranked(&pose, &joints, |s| {
+            if s[4] < 0.0 { f64::NAN } else { -s[2] + s[0].cos() }
+        });
+        assert!(!ranked.is_empty() && ranked.len() < continuing.len());
+        assert!(ranked.iter().all(|s| s[4] >= 0.0));
+        assert!(ranked.windows(2).all(|w| -w[0][2] + w[0][0].cos() <= -w[1][2] + w[1][0].cos()));
+
+        // Works through the trait object as well
+        let dynamic: &dyn Kinematics = &robot;
+        assert_eq!(dynamic.inverse_ranked(&pose, &joints, |_| 0.0), continuing);
+    }
+
+    #[test]
+    fn test_inverse_best() {
+        use crate::utils::joint_distance;
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let previous = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let far = robot.forward(&[1.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let near = robot.forward(&[0.2, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let unreachable = Pose::translation(10.0, 0.0, 0.0);
+        let goals = [unreachable, far, near];
+
+        let (goal, joints) = robot.inverse_best(&goals, &previous,
+                                                |_, s| joint_distance(s, &previous)).unwrap();
+        assert_eq!(goal, 2);
+        assert!((joints[0] - 0.2).abs() < 1E-9);
+
+        // Penalizing the goal by its index, the ties go to the earlier goal
+        let (goal, _) = robot.inverse_best(&goals, &previous, |g, _| -(g as f64).min(1.0)).unwrap();
+        assert_eq!(goal, 1);
+        assert!(robot.inverse_best(&goals[..1], &previous, |_, _| 0.0).is_none());
+        assert!(robot.inverse_best(&goals, &previous, |_, _| f64::NAN).is_none());
+    }
+}
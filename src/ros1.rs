@@ -0,0 +1,96 @@
+//! Conversions from and to ROS 1 messages (feature `rosrust`). The message types are generated
+//! by rosrust at build time, so the build needs the ROS environment (ROS Noetic with
+//! geometry_msgs, sensor_msgs and trajectory_msgs) to be sourced.
+//!
+//! Joint messages address the joints by name; the names of J1 to J6 are given by the
+//! `JointMapping`, as they differ between robot descriptions.
+
+use crate::joint_names::JointMapping;
+use crate::kinematic_traits::{Joints, Pose};
+use crate::validation::{pose_from_raw, PoseError};
+
+/// Generated message types.
+pub mod msg {
+    rosrust::rosmsg_include!(geometry_msgs / Pose, sensor_msgs / JointState,
+                             trajectory_msgs / JointTrajectory);
+}
+
+use msg::geometry_msgs::{Point, Pose as PoseMsg, Quaternion as QuaternionMsg};
+use msg::sensor_msgs::JointState;
+use msg::trajectory_msgs::{JointTrajectory, JointTrajectoryPoint};
+
+/// Pose from the message, validated as by `validation::pose_from_raw`.
+pub fn pose_from_msg(msg: &PoseMsg) -> Result<Pose, PoseError> {
+    let (p, q) = (&msg.position, &msg.orientation);
+    pose_from_raw([p.x, p.y, p.z], [q.x, q.y, q.z, q.w])
+}
+
+pub fn pose_to_msg(pose: &Pose) -> PoseMsg {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    PoseMsg {
+        position: Point { x: t.x, y: t.y, z: t.z },
+        orientation: QuaternionMsg { x: q.i, y: q.j, z: q.k, w: q.w },
+    }
+}
+
+/// Joint positions from the joint state. The state may contain other joints (like the gripper)
+/// in any order. None if any of the named joints is missing.
+pub fn joints_from_joint_state(state: &JointState, mapping: &JointMapping) -> Option<Joints> {
+    mapping.joints_from_named(&state.name, &state.position)
+}
+
+pub fn joint_state_from_joints(joints: &Joints, mapping: &JointMapping) -> JointState {
+    JointState {
+        name: mapping.names().to_vec(),
+        position: joints.to_vec(),
+        ..Default::default()
+    }
+}
+
+/// Joint trajectory through the given joint positions, `time_step` seconds apart (the first
+/// point at zero time).
+pub fn trajectory_to_msg(trajectory: &[Joints], mapping: &JointMapping, time_step: f64)
+                         -> JointTrajectory {
+    JointTrajectory {
+        joint_names: mapping.names().to_vec(),
+        points: trajectory.iter().enumerate().map(|(i, joints)| JointTrajectoryPoint {
+            positions: joints.to_vec(),
+            time_from_start: rosrust::Duration::from_nanos((i as f64 * time_step * 1E9) as i64),
+            ..Default::default()
+        }).collect(),
+        ..Default::default()
+    }
+}
+
+/// Joint positions of all points of the trajectory. None if any of the named joints is missing.
+pub fn trajectory_from_msg(msg: &JointTrajectory, mapping: &JointMapping) -> Option<Vec<Joints>> {
+    msg.points.iter().map(|point| mapping.joints_from_named(&msg.joint_names, &point.positions))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Translation3, UnitQuaternion};
+    use super::*;
+
+    #[test]
+    fn test_ros1_messages() {
+        let pose = Pose::from_parts(Translation3::new(1.0, 2.0, 3.0),
+                                    UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3));
+        let restored = pose_from_msg(&pose_to_msg(&pose)).unwrap();
+        assert!(restored.rotation.angle_to(&pose.rotation) < 1E-12);
+        assert_eq!(pose_from_msg(&PoseMsg::default()), Err(PoseError::NotUnitQuaternion(0.0)));
+
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let names = JointMapping::new(["joint_1", "joint_2", "joint_3", "joint_4", "joint_5", "joint_6"]);
+        let mut state = joint_state_from_joints(&joints, &names);
+        state.name.reverse();
+        state.position.reverse();
+        assert_eq!(joints_from_joint_state(&state, &names), Some(joints));
+
+        let msg = trajectory_to_msg(&[joints, joints], &names, 0.5);
+        assert_eq!(msg.points[1].time_from_start.nanos(), 500_000_000);
+        assert_eq!(trajectory_from_msg(&msg, &names), Some(vec![joints, joints]));
+    }
+}
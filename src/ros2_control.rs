@@ -0,0 +1,115 @@
+//! Adapter with the semantics of the ros2_control `kinematics_interface::KinematicsInterface`,
+//! as used by the admittance and impedance controllers. The C++ plugin only needs to forward
+//! the calls and convert the Eigen types. Links are named as in `SceneDescription` (by default
+//! base_link, link_1 ... link_6, tool0); all transforms and deltas are in the base frame, and
+//! Cartesian deltas are (vx, vy, vz, wx, wy, wz).
+
+use nalgebra::{Matrix6, Vector6};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::scene_graph::SceneDescription;
+
+/// Default damping of the least squares inversion of the Jacobian, as the `alpha` parameter
+/// of the ros2_control KDL plugin.
+pub const DEFAULT_ALPHA: f64 = 5E-6;
+
+/// Kinematics interface for ros2_control. All methods return None for unknown link names.
+#[derive(Debug, Clone)]
+pub struct KinematicsInterface {
+    robot: OPWKinematics,
+    names: SceneDescription,
+    alpha: f64,
+}
+
+impl KinematicsInterface {
+    pub fn new(robot: OPWKinematics) -> Self {
+        Self::with_names(robot, SceneDescription::ros_industrial())
+    }
+
+    pub fn with_names(robot: OPWKinematics, names: SceneDescription) -> Self {
+        KinematicsInterface { robot, names, alpha: DEFAULT_ALPHA }
+    }
+
+    /// Sets the damping used when inverting the Jacobian.
+    pub fn with_alpha(self, alpha: f64) -> Self {
+        KinematicsInterface { alpha, ..self }
+    }
+
+    /// Index of the link: None for the base, 0 to 5 for the links, 6 for the flange.
+    fn link_index(&self, link_name: &str) -> Option<Option<usize>> {
+        if link_name == self.names.base {
+            Some(None)
+        } else if link_name == self.names.flange {
+            Some(Some(6))
+        } else {
+            self.names.links.iter().position(|n| n == link_name).map(Some)
+        }
+    }
+
+    /// Transform of the link in the base frame.
+    pub fn calculate_link_transform(&self, joints: &Joints, link_name: &str) -> Option<Pose> {
+        Some(match self.link_index(link_name)? {
+            None => Pose::identity(),
+            Some(6) => self.robot.forward(joints),
+            Some(link) => self.robot.link_poses(joints)[link],
+        })
+    }
+
+    /// Jacobian of the link origin.
+    pub fn calculate_jacobian(&self, joints: &Joints, link_name: &str) -> Option<Matrix6<f64>> {
+        match self.link_index(link_name)? {
+            None => Some(Matrix6::zeros()),
+            Some(link) => {
+                let origin = self.calculate_link_transform(joints, link_name)?.translation.vector;
+                Some(self.robot.point_jacobian(joints, link, &origin))
+            }
+        }
+    }
+
+    /// Joint deltas producing the Cartesian delta of the link, by damped least squares.
+    pub fn convert_cartesian_deltas_to_joint_deltas(&self, joints: &Joints, delta_x: &Vector6<f64>,
+                                                    link_name: &str) -> Option<Joints> {
+        let j = self.calculate_jacobian(joints, link_name)?;
+        let jjt = j * j.transpose() + Matrix6::identity() * self.alpha;
+        let delta_theta = j.transpose() * jjt.try_inverse()? * delta_x;
+        Some(delta_theta.into())
+    }
+
+    /// Cartesian delta of the link produced by the joint deltas.
+    pub fn convert_joint_deltas_to_cartesian_deltas(&self, joints: &Joints, delta_theta: &Joints,
+                                                    link_name: &str) -> Option<Vector6<f64>> {
+        Some(self.calculate_jacobian(joints, link_name)? * Vector6::from(*delta_theta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_kinematics_interface() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let interface = KinematicsInterface::new(robot.clone());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+
+        assert_eq!(interface.calculate_link_transform(&joints, "tool0"), Some(robot.forward(&joints)));
+        assert_eq!(interface.calculate_link_transform(&joints, "link_3"),
+                   Some(robot.link_poses(&joints)[2]));
+        assert!(interface.calculate_link_transform(&joints, "unknown").is_none());
+
+        // Joint deltas to Cartesian and back
+        let delta_theta = [1E-3, -2E-3, 1E-3, 3E-3, -1E-3, 2E-3];
+        let delta_x = interface.convert_joint_deltas_to_cartesian_deltas(&joints, &delta_theta, "tool0")
+            .unwrap();
+        let restored = interface.convert_cartesian_deltas_to_joint_deltas(&joints, &delta_x, "tool0")
+            .unwrap();
+        for (a, b) in restored.iter().zip(delta_theta.iter()) {
+            assert!((a - b).abs() < 1E-6);
+        }
+
+        // Only the first three joints move link 3
+        let j3 = interface.calculate_jacobian(&joints, "link_3").unwrap();
+        assert!(j3.columns(3, 3).iter().all(|v| *v == 0.0));
+    }
+}
@@ -0,0 +1,148 @@
+//! Analytic kinematics of the four-axis SCARA robot, under the same `Kinematics` trait as the
+//! articulated robots so mixed cells can use one crate. J1 and J2 turn the arm in the
+//! horizontal plane, J3 moves the quill vertically (meters, positive up) and J4 turns the tool
+//! about the vertical. The flange Z points down.
+
+use std::f64::consts::PI;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::{ANGULAR_TOLERANCE, DISTANCE_TOLERANCE};
+use crate::utils::{compare_poses, normalize_near, sort_by_closeness, wrap_to_pi};
+
+/// Geometry of the SCARA robot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScaraParameters {
+    /// Length of the first arm, J1 to J2 axis, meters.
+    pub a1: f64,
+    /// Length of the second arm, J2 to the quill axis, meters.
+    pub a2: f64,
+    /// Height of the flange above the base with J3 at zero, meters.
+    pub c1: f64,
+}
+
+impl ScaraParameters {
+    /// Epson LS3-B401S, 400 mm reach. The base frame is at the flange height with J3 at zero.
+    pub const fn epson_ls3_b401() -> Self {
+        ScaraParameters { a1: 0.225, a2: 0.175, c1: 0.0 }
+    }
+
+    /// Epson LS6-B602S, 600 mm reach. The base frame is at the flange height with J3 at zero.
+    pub const fn epson_ls6_b602() -> Self {
+        ScaraParameters { a1: 0.325, a2: 0.275, c1: 0.0 }
+    }
+}
+
+/// Kinematics of the SCARA robot. Through the `Kinematics` trait the joints are given as six
+/// values [J1, J2, J3, J4, 0, 0]: the last two are ignored and returned as zero. Only the
+/// poses with the flange Z pointing down are reachable.
+#[derive(Debug, Clone)]
+pub struct ScaraKinematics {
+    pub parameters: ScaraParameters,
+}
+
+impl ScaraKinematics {
+    pub fn new(parameters: ScaraParameters) -> Self {
+        ScaraKinematics { parameters }
+    }
+
+    /// True if the arm is stretched or folded (J2 at 0 or PI), where both elbow solutions meet.
+    pub fn elbow_singularity(&self, joints: &Joints) -> bool {
+        joints[1].sin().abs() < ANGULAR_TOLERANCE
+    }
+}
+
+fn flange_rotation(yaw: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_axis_angle(&Vector3::z_axis(), yaw)
+        * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI)
+}
+
+impl Stateless for ScaraKinematics {}
+
+impl Kinematics for ScaraKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        let p = &self.parameters;
+        let t = pose.translation.vector;
+        let cos2 = (t.x * t.x + t.y * t.y - p.a1 * p.a1 - p.a2 * p.a2) / (2.0 * p.a1 * p.a2);
+        // Only the rotation about the vertical is free, the flange must point down
+        let yaw = {
+            let x = pose.rotation * Vector3::x();
+            x.y.atan2(x.x)
+        };
+
+        let mut solutions = Solutions::new();
+        // Rounding can push the stretched arm slightly beyond the reach
+        if cos2.abs() > 1.0 + DISTANCE_TOLERANCE {
+            return solutions;
+        }
+        let sin2 = (1.0 - cos2.clamp(-1.0, 1.0).powi(2)).sqrt();
+        for sin2 in [sin2, -sin2] {
+            let j2 = sin2.atan2(cos2);
+            let j1 = t.y.atan2(t.x) - (p.a2 * sin2).atan2(p.a1 + p.a2 * cos2);
+            let joints = [wrap_to_pi(j1), j2, t.z - p.c1, wrap_to_pi(yaw - j1 - j2), 0.0, 0.0];
+            if compare_poses(pose, &self.forward(&joints), DISTANCE_TOLERANCE, ANGULAR_TOLERANCE)
+                .is_ok() && !solutions.contains(&joints) {
+                solutions.push(joints);
+            }
+        }
+        solutions
+    }
+
+    /// Solutions with the rotary joints normalized near the previous joints, sorted by the
+    /// distance to them.
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        let mut solutions = self.inverse(pose);
+        for solution in solutions.iter_mut() {
+            for i in [0, 1, 3] {
+                normalize_near(&mut solution[i], previous[i]);
+            }
+        }
+        sort_by_closeness(&mut solutions, previous);
+        solutions
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        let p = &self.parameters;
+        let (j1, j12) = (qs[0], qs[0] + qs[1]);
+        Pose::from_parts(
+            Translation3::new(p.a1 * j1.cos() + p.a2 * j12.cos(),
+                              p.a1 * j1.sin() + p.a2 * j12.sin(),
+                              p.c1 + qs[2]),
+            flange_rotation(j12 + qs[3]))
+    }
+
+    /// The SCARA has no wrist singularity, see `elbow_singularity` for the arm.
+    fn kinematic_singularity(&self, _qs: &Joints) -> Option<Singularity> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scara() {
+        let robot = ScaraKinematics::new(ScaraParameters::epson_ls6_b602());
+        let joints = [0.3, 1.1, -0.05, 0.4, 0.0, 0.0];
+        let pose = robot.forward(&joints);
+        assert!((pose.rotation * Vector3::z() + Vector3::z()).norm() < 1E-12);
+
+        let solutions = robot.inverse(&pose);
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions.iter().all(|s| compare_poses(&robot.forward(s), &pose, 1E-9, 1E-9)
+            .is_ok()));
+        let continuing = robot.inverse_continuing(&pose, &joints);
+        assert!(continuing[0].iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-9));
+        assert!(continuing[1][1] < 0.0);
+
+        // Stretched arm has the single solution
+        let stretched = [0.5, 0.0, 0.0, 0.0, 0.0, 0.0];
+        assert!(robot.elbow_singularity(&stretched));
+        assert_eq!(robot.inverse(&robot.forward(&stretched)).len(), 1);
+
+        assert!(robot.inverse(&Pose::translation(1.0, 0.0, 0.0)).is_empty());
+        let tilted = pose * UnitQuaternion::from_euler_angles(0.1, 0.0, 0.0);
+        assert!(robot.inverse(&tilted).is_empty());
+    }
+}
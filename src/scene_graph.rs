@@ -0,0 +1,287 @@
+//! Export of the robot configuration as a simple scene graph: link name, world transform and
+//! (optionally) the mesh used to render the link. This allows external renderers and digital
+//! twin systems to consume the solver output without knowing anything about the OPW model.
+//!
+//! A description read with `SceneDescription::from_urdf` takes the link names and the visual
+//! meshes from the URDF, and the nodes are the URDF link frames. Otherwise the nodes are the
+//! OPW model frames of `OPWKinematics::link_poses`, which are generally not the frames the
+//! meshes of a URDF are modelled in.
+
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters_import::SerialChain;
+#[cfg(feature = "files")]
+use crate::parameters_import::ImportError;
+
+/// Names of the scene graph nodes and the meshes attached to them.
+#[derive(Debug, Clone)]
+pub struct SceneDescription {
+    /// Name of the robot base node.
+    pub base: String,
+    /// Names of the six links, J1 to J6.
+    pub links: [String; 6],
+    /// Name of the flange node.
+    pub flange: String,
+    /// Mesh files (link name to mesh path). Links without mesh are still exported.
+    pub meshes: HashMap<String, String>,
+    /// Poses of the meshes in their link frames (the URDF visual origins). Identity for the
+    /// meshes not listed.
+    pub mesh_origins: HashMap<String, Pose>,
+    /// Chain of the URDF. If set, the nodes are the URDF link frames, otherwise the OPW model
+    /// frames.
+    pub chain: Option<SerialChain>,
+    /// Pose of the robot base in the world. Identity if the robot base is the world.
+    pub base_pose: Pose,
+}
+
+impl SceneDescription {
+    /// Link naming used by ROS-Industrial support packages (base_link, link_1 ... link_6, tool0).
+    pub fn ros_industrial() -> Self {
+        SceneDescription {
+            base: "base_link".to_string(),
+            links: std::array::from_fn(|i| format!("link_{}", i + 1)),
+            flange: "tool0".to_string(),
+            meshes: HashMap::new(),
+            mesh_origins: HashMap::new(),
+            chain: None,
+            base_pose: Pose::identity(),
+        }
+    }
+
+    /// Link names, visual meshes and link frames from the URDF. The base is the root link of
+    /// the chain, the flange is the link tool0 (or flange) if present, or else the link of J6.
+    /// The joint values are taken as the URDF joint values, as by `Parameters::from_urdf`.
+    #[cfg(feature = "files")]
+    pub fn from_urdf(urdf: &str) -> Result<Self, ImportError> {
+        use crate::joint_names::{urdf_chain, urdf_joints, urdf_visuals, JointMapping,
+                                 JointMappingError};
+
+        let chain = SerialChain::from_urdf(urdf)?;
+        let joints = urdf_joints(urdf)?;
+        let mapping = JointMapping::from_urdf(urdf)?;
+        let child = |name: &String| joints.iter().find(|j| &j.name == name)
+            .ok_or(JointMappingError::NotDetected);
+        let links = mapping.names().iter().map(|name| child(name).map(|j| j.child.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let base = urdf_chain(&joints, child(&mapping.names()[0])?)[0].parent.clone();
+        let flange = ["tool0", "flange"].into_iter()
+            .find(|name| joints.iter().any(|j| j.child == *name))
+            .map_or_else(|| links[5].clone(), str::to_string);
+
+        let mut description = SceneDescription {
+            base,
+            links: links.try_into().map_err(|_| JointMappingError::NotDetected)?,
+            flange,
+            chain: Some(chain),
+            ..Self::ros_industrial()
+        };
+        for (link, visual) in urdf_visuals(urdf)? {
+            description.meshes.insert(link.clone(), visual.mesh);
+            description.mesh_origins.insert(link, visual.origin);
+        }
+        Ok(description)
+    }
+
+    /// Attach the mesh to the named link.
+    pub fn with_mesh(mut self, link: &str, mesh: &str) -> Self {
+        self.meshes.insert(link.to_string(), mesh.to_string());
+        self
+    }
+
+    /// Attach the mesh to the named link, with the pose of the mesh in the link frame.
+    pub fn with_mesh_origin(mut self, link: &str, mesh: &str, origin: Pose) -> Self {
+        self.mesh_origins.insert(link.to_string(), origin);
+        self.with_mesh(link, mesh)
+    }
+}
+
+impl Default for SceneDescription {
+    fn default() -> Self {
+        Self::ros_industrial()
+    }
+}
+
+/// Single node of the scene graph. The transform is in the world frame.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneNode {
+    pub name: String,
+    pub parent: Option<String>,
+    pub translation: [f64; 3],
+    /// Rotation quaternion, [x, y, z, w] ordering.
+    pub rotation: [f64; 4],
+    pub mesh: Option<String>,
+    /// Pose of the mesh in the frame of this node: translation, then quaternion [x, y, z, w].
+    pub mesh_origin: [f64; 7],
+}
+
+impl SceneNode {
+    fn new(name: &str, parent: Option<&str>, pose: &Pose, description: &SceneDescription) -> Self {
+        let [x, y, z, qx, qy, qz, qw] = to_array(pose);
+        let origin = description.mesh_origins.get(name).copied().unwrap_or(Pose::identity());
+        SceneNode {
+            name: name.to_string(),
+            parent: parent.map(|p| p.to_string()),
+            translation: [x, y, z],
+            rotation: [qx, qy, qz, qw],
+            mesh: description.meshes.get(name).cloned(),
+            mesh_origin: to_array(&origin),
+        }
+    }
+
+    /// World transform of this node as Pose.
+    pub fn pose(&self) -> Pose {
+        let [x, y, z] = self.translation;
+        let [qx, qy, qz, qw] = self.rotation;
+        Pose::from_parts(
+            nalgebra::Translation3::new(x, y, z),
+            nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(qw, qx, qy, qz)))
+    }
+
+    /// World transform of the mesh of this node as Pose.
+    pub fn mesh_pose(&self) -> Pose {
+        let [x, y, z, qx, qy, qz, qw] = self.mesh_origin;
+        self.pose() * Pose::from_parts(
+            nalgebra::Translation3::new(x, y, z),
+            nalgebra::UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(qw, qx, qy, qz)))
+    }
+}
+
+fn to_array(pose: &Pose) -> [f64; 7] {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    [t.x, t.y, t.z, q.i, q.j, q.k, q.w]
+}
+
+/// Scene graph of the robot in one configuration. Nodes are ordered from base to flange,
+/// every node except the base has the previous one as parent.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneGraph {
+    pub nodes: Vec<SceneNode>,
+}
+
+impl SceneGraph {
+    /// Build the scene graph for the given joint positions. The nodes are the URDF link frames
+    /// if the description has the chain of the URDF, otherwise the OPW model frames.
+    pub fn new(robot: &OPWKinematics, description: &SceneDescription, joints: &Joints) -> Self {
+        let (links, flange) = match &description.chain {
+            Some(chain) => (chain.link_frames(joints), chain.forward(joints)),
+            None => (robot.link_poses(joints), robot.forward(joints)),
+        };
+        let base = &description.base_pose;
+        let mut nodes = Vec::with_capacity(8);
+        nodes.push(SceneNode::new(&description.base, None, base, description));
+
+        let mut parent = description.base.as_str();
+        for (name, link) in description.links.iter().zip(links.iter()) {
+            nodes.push(SceneNode::new(name, Some(parent), &(base * link), description));
+            parent = name;
+        }
+        let flange = base * flange;
+        nodes.push(SceneNode::new(&description.flange, Some(parent), &flange, description));
+        SceneGraph { nodes }
+    }
+
+    /// Build the scene graphs for all configurations of the trajectory.
+    pub fn for_trajectory(robot: &OPWKinematics, description: &SceneDescription,
+                          trajectory: &[Joints]) -> Vec<SceneGraph> {
+        trajectory.iter().map(|joints| SceneGraph::new(robot, description, joints)).collect()
+    }
+
+    /// Serialize the scene graph into YAML.
+    #[cfg(feature = "files")]
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_flange_matches_forward() {
+        let robot = OPWKinematics::new(Parameters::kuka_kr6_r700_sixx());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let description = SceneDescription::ros_industrial()
+            .with_mesh("link_1", "meshes/link_1.stl");
+        let graph = SceneGraph::new(&robot, &description, &joints);
+
+        assert_eq!(graph.nodes.len(), 8);
+        assert_eq!(graph.nodes[1].mesh.as_deref(), Some("meshes/link_1.stl"));
+        assert_eq!(graph.nodes[7].parent.as_deref(), Some("link_6"));
+
+        let flange = graph.nodes[7].pose();
+        let expected = robot.forward(&joints);
+        assert!((flange.translation.vector - expected.translation.vector).norm() < 1E-9);
+        assert!(flange.rotation.angle_to(&expected.rotation) < 1E-9);
+    }
+
+    #[cfg(feature = "files")]
+    #[test]
+    fn test_from_urdf() {
+        // ABB IRB 2400/10 as in the ROS-Industrial abb_irb2400_support package
+        let link = |name: &str| format!(
+            r#"<link name="{0}"><visual><origin xyz="0 0 0.1" rpy="0 0 1.5707963267948966"/>
+               <geometry><mesh filename="package://meshes/{0}.stl"/></geometry></visual></link>"#,
+            name);
+        let joint = |name: &str, parent: &str, child: &str, xyz: &str, axis: &str| format!(
+            r#"<joint name="{}" type="revolute"><parent link="{}"/><child link="{}"/>
+               <origin xyz="{}" rpy="0 0 0"/><axis xyz="{}"/></joint>"#, name, parent, child, xyz, axis);
+        let urdf = format!(r#"<robot name="irb2400">{}{}{}{}{}{}{}{}
+            <joint name="joint_6-tool0" type="fixed"><parent link="link_6"/><child link="tool0"/>
+              <origin xyz="0 0 0" rpy="0 1.5707963267948966 0"/></joint></robot>"#,
+            link("base_link"), link("link_2"),
+            joint("joint_1", "base_link", "link_1", "0 0 0", "0 0 1"),
+            joint("joint_2", "link_1", "link_2", "0.1 0 0.615", "0 1 0"),
+            joint("joint_3", "link_2", "link_3", "0 0 0.705", "0 1 0"),
+            joint("joint_4", "link_3", "link_4", "0 0 0.135", "1 0 0"),
+            joint("joint_5", "link_4", "link_5", "0.755 0 0", "0 1 0"),
+            joint("joint_6", "link_5", "link_6", "0.085 0 0", "1 0 0"));
+        let description = SceneDescription::from_urdf(&urdf).unwrap();
+        assert_eq!(description.base, "base_link");
+        assert_eq!(description.links[1], "link_2");
+        assert_eq!(description.flange, "tool0");
+
+        let robot = OPWKinematics::new(Parameters::from_urdf(&urdf).unwrap());
+        let joints = [0.3, -0.2, 0.5, 1.0, -0.7, 0.2];
+        let graph = SceneGraph::new(&robot, &description, &joints);
+        assert_eq!(graph.nodes[0].mesh.as_deref(), Some("package://meshes/base_link.stl"));
+        assert_eq!(graph.nodes[1].mesh, None);
+
+        // link_2 rotates about its own y axis at 0.1 0 0.615 from link_1, which rotates about z
+        use nalgebra::{Point3, UnitQuaternion, Vector3};
+        let j1 = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), joints[0]);
+        let j2 = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), joints[1]);
+        let link_2 = graph.nodes[2].pose();
+        assert!((link_2.translation.vector - j1 * Vector3::new(0.1, 0.0, 0.615)).norm() < 1E-9);
+        assert!(link_2.rotation.angle_to(&(j1 * j2)) < 1E-9);
+        let mesh = graph.nodes[2].mesh_pose();
+        let mesh_position = link_2 * Point3::new(0.0, 0.0, 0.1);
+        assert!((mesh.translation.vector - mesh_position.coords).norm() < 1E-9);
+        assert!(mesh.rotation.angle_to(&(j1 * j2 * UnitQuaternion::from_euler_angles(
+            0.0, 0.0, std::f64::consts::FRAC_PI_2))) < 1E-9);
+
+        let flange = graph.nodes[7].pose();
+        let expected = robot.forward(&joints);
+        assert!((flange.translation.vector - expected.translation.vector).norm() < 1E-9);
+    }
+
+    #[test]
+    fn test_wrist_center() {
+        let parameters = Parameters::irb2400_10();
+        let robot = OPWKinematics::new(parameters.clone());
+        let joints = [0.3, -0.2, 0.5, 1.0, -0.7, 0.2];
+        let links = robot.link_poses(&joints);
+        let flange = robot.forward(&joints);
+        let wrist_center = flange.translation.vector -
+            parameters.c4 * (flange.rotation * nalgebra::Vector3::z());
+        assert!((links[4].translation.vector - wrist_center).norm() < 1E-9);
+        assert!((links[5].translation.vector - wrist_center).norm() < 1E-9);
+    }
+}
@@ -0,0 +1,154 @@
+//! Fixed rate servo loop around the streaming session, for checking that the solver keeps up
+//! with the controller rate (typically 250 Hz to 1 kHz). Each cycle takes the pose, solves it
+//! with `IkStream::next` and hands the result on; the solve latency is measured and the cycles
+//! ending past their deadline (the end of their period) are counted as misses.
+
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::stream::{IkStream, StreamError};
+
+/// Latency and deadline statistics of the servo loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServoStats {
+    pub cycles: u64,
+    /// Cycles that ended past their deadline.
+    pub misses: u64,
+    pub max_latency: Duration,
+    pub total_latency: Duration,
+}
+
+impl ServoStats {
+    pub fn mean_latency(&self) -> Duration {
+        if self.cycles == 0 {
+            Duration::ZERO
+        } else {
+            self.total_latency.div_f64(self.cycles as f64)
+        }
+    }
+}
+
+/// Outcome of one servo cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoCycle {
+    pub joints: Result<Joints, StreamError>,
+    /// Time taken by the solve.
+    pub latency: Duration,
+    /// The solve ended past the deadline of the cycle.
+    pub missed: bool,
+}
+
+/// Servo loop, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct ServoLoop<K: Kinematics> {
+    pub stream: IkStream<K>,
+    period: Duration,
+    stats: ServoStats,
+}
+
+impl<K: Kinematics> ServoLoop<K> {
+    /// Loop running the session at the given rate, Hz. None if the rate is not positive and
+    /// finite, or so high that the period rounds to zero nanoseconds.
+    pub fn new(stream: IkStream<K>, rate: f64) -> Option<Self> {
+        let period = Duration::try_from_secs_f64(1.0 / rate).ok()
+            .filter(|period| !period.is_zero())?;
+        Some(ServoLoop { stream, period, stats: Default::default() })
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn stats(&self) -> &ServoStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Default::default();
+    }
+
+    /// Single cycle for the loops driven by the caller (as by the controller interrupt): solves
+    /// the pose, a miss if the solve takes longer than the period.
+    pub fn cycle(&mut self, pose: &Pose) -> ServoCycle {
+        self.cycle_until(pose, Instant::now() + self.period)
+    }
+
+    /// Runs at the fixed rate until the source returns no pose: each period takes the pose from
+    /// the source, solves it and passes the outcome to the sink, then sleeps to the next period.
+    /// After a miss, the next period starts immediately, the missed periods are not caught up.
+    pub fn run(&mut self, mut source: impl FnMut() -> Option<Pose>,
+               mut sink: impl FnMut(&ServoCycle)) {
+        let mut tick = Instant::now();
+        while let Some(pose) = source() {
+            let deadline = tick + self.period;
+            let cycle = self.cycle_until(&pose, deadline);
+            sink(&cycle);
+            let now = Instant::now();
+            if now < deadline {
+                thread::sleep(deadline - now);
+                tick = deadline;
+            } else {
+                tick = now;
+            }
+        }
+    }
+
+    fn cycle_until(&mut self, pose: &Pose, deadline: Instant) -> ServoCycle {
+        let start = Instant::now();
+        let joints = self.stream.next(pose);
+        let end = Instant::now();
+        let latency = end - start;
+        let missed = end > deadline;
+
+        self.stats.cycles += 1;
+        self.stats.misses += missed as u64;
+        self.stats.max_latency = self.stats.max_latency.max(latency);
+        self.stats.total_latency += latency;
+        ServoCycle { joints, latency, missed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_servo_loop() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let poses: Vec<Pose> = (1..=20)
+            .map(|i| robot.forward(&[0.1 + 0.001 * i as f64, 0.2, 0.3, 0.4, 0.5, 0.6]))
+            .collect();
+        let mut servo = ServoLoop::new(IkStream::new(robot, start), 1000.0).unwrap();
+        assert_eq!(servo.period(), Duration::from_millis(1));
+
+        let mut targets = poses.iter();
+        let mut results = Vec::new();
+        let begin = Instant::now();
+        servo.run(|| targets.next().copied(), |cycle| results.push(cycle.joints));
+        // 20 cycles, sleeping after each of them unless it missed
+        assert!(begin.elapsed() >= Duration::from_millis(20 - servo.stats().misses));
+        assert_eq!(results.len(), 20);
+        assert!(results.iter().all(|joints| joints.is_ok()));
+        let stats = *servo.stats();
+        assert_eq!(stats.cycles, 20);
+        assert!(stats.max_latency >= stats.mean_latency());
+        assert!(stats.mean_latency() > Duration::ZERO);
+
+        // No solver keeps up with a nanosecond period
+        let mut servo = ServoLoop::new(servo.stream, 1E9).unwrap();
+        for pose in &poses {
+            assert!(servo.cycle(pose).missed);
+        }
+        assert_eq!((servo.stats().cycles, servo.stats().misses), (20, 20));
+        servo.reset_stats();
+        assert_eq!(*servo.stats(), ServoStats::default());
+
+        for rate in [0.0, -250.0, f64::NAN, f64::INFINITY, 1E300] {
+            assert!(ServoLoop::new(servo.stream.clone(), rate).is_none());
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Scan of the joint trajectory for the segments passing near the singularities. Near the
+//! singularity, the small Cartesian motion requires the large joint motion, so these segments
+//! are where the robot slows down, or faults on the joint speed limits, when following the
+//! path. The scan tells where they are and how bad they get before the trajectory is run.
+
+use crate::kinematic_traits::{Joints, SingularityProximity};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Consecutive points of the trajectory closer to the singularity than the thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SingularInterval {
+    /// Index of the first point in the interval.
+    pub start: usize,
+    /// Index of the last point in the interval (inclusive).
+    pub end: usize,
+    /// Smallest proximity of each kind over the interval. The kinds below the threshold are
+    /// those the interval is near to.
+    pub closest: SingularityProximity,
+    /// Largest ratio of the joint speed to the flange speed over the interval, the inverse of
+    /// the smallest singular value of the Jacobian (radians per meter or per radian). Infinite
+    /// if the trajectory passes exactly through the singularity.
+    pub peak_amplification: f64,
+}
+
+impl SingularityProximity {
+    /// True if any of the proximities is below its threshold.
+    pub fn below(&self, thresholds: &SingularityProximity) -> bool {
+        self.wrist < thresholds.wrist || self.alignment < thresholds.alignment
+            || self.elbow < thresholds.elbow
+    }
+
+    fn min(&self, other: &SingularityProximity) -> SingularityProximity {
+        SingularityProximity {
+            wrist: self.wrist.min(other.wrist),
+            alignment: self.alignment.min(other.alignment),
+            elbow: self.elbow.min(other.elbow),
+        }
+    }
+}
+
+impl OPWKinematics {
+    /// Intervals of the trajectory where any singularity proximity drops below the given
+    /// threshold, in the trajectory order.
+    pub fn scan_singularities(&self, trajectory: &[Joints], thresholds: &SingularityProximity)
+                              -> Vec<SingularInterval> {
+        let mut intervals: Vec<SingularInterval> = Vec::new();
+        let mut open = false;
+        for (i, joints) in trajectory.iter().enumerate() {
+            let proximity = self.singularity_proximity(joints);
+            if !proximity.below(thresholds) {
+                open = false;
+                continue;
+            }
+            let amplification = 1.0 / self.min_singular_value(joints);
+            match intervals.last_mut() {
+                Some(interval) if open => {
+                    interval.end = i;
+                    interval.closest = interval.closest.min(&proximity);
+                    interval.peak_amplification = interval.peak_amplification.max(amplification);
+                }
+                _ => intervals.push(SingularInterval {
+                    start: i,
+                    end: i,
+                    closest: proximity,
+                    peak_amplification: amplification,
+                }),
+            }
+            open = true;
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_scan_singularities() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        // J5 passes through zero once, in the middle of the trajectory
+        let trajectory: Vec<Joints> = (0..=20)
+            .map(|i| [0.1, 0.2, 0.3, 0.4, 0.5 - 0.05 * i as f64, 0.6])
+            .collect();
+        let thresholds = SingularityProximity { wrist: 0.12, alignment: 0.01, elbow: 0.05 };
+        let intervals = robot.scan_singularities(&trajectory, &thresholds);
+        assert_eq!(intervals.len(), 1);
+        let interval = intervals[0];
+        // |sin J5| < 0.12 for J5 of -0.1 to 0.1
+        assert_eq!((interval.start, interval.end), (8, 12));
+        assert!(interval.closest.wrist < 1E-12);
+        assert!(interval.closest.elbow > thresholds.elbow);
+        assert!(interval.peak_amplification > 1E6);
+
+        // Two separate passes, not touching the singularity
+        let trajectory: Vec<Joints> = [0.3, 0.1, 0.08, 0.1, 0.3, 0.1, 0.3]
+            .iter().map(|j5| [0.1, 0.2, 0.3, 0.4, *j5, 0.6]).collect();
+        let intervals = robot.scan_singularities(&trajectory, &thresholds);
+        assert_eq!(intervals.iter().map(|i| (i.start, i.end)).collect::<Vec<_>>(),
+                   vec![(1, 3), (5, 5)]);
+        assert!(intervals[0].peak_amplification.is_finite());
+        assert!(intervals[0].peak_amplification > intervals[1].peak_amplification);
+
+        assert!(robot.scan_singularities(&trajectory[..1], &thresholds).is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Reusable solver for batch processing. The `Solver` borrows the robot and owns all buffers
+//! the inverse kinematics needs, so it can be created once per thread and used for any number
+//! of calls without allocating. In multithreaded batch pipelines, create one solver per worker
+//! thread (the robot itself is shared by reference).
+
+use crate::kinematic_traits::{FixedSolutions, Joints, Pose};
+use crate::kinematics_impl::OPWKinematics;
+
+pub struct Solver<'a> {
+    robot: &'a OPWKinematics,
+    solutions: FixedSolutions,
+    path: Vec<Joints>,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(robot: &'a OPWKinematics) -> Self {
+        Solver { robot, solutions: FixedSolutions::new(), path: Vec::new() }
+    }
+
+    /// The robot this solver works for.
+    pub fn robot(&self) -> &'a OPWKinematics {
+        self.robot
+    }
+
+    /// Inverse kinematics, see `Kinematics::inverse`. The returned slice borrows the
+    /// internal buffer and is valid until the next call.
+    pub fn inverse(&mut self, pose: &Pose) -> &[Joints] {
+        self.solutions = self.robot.inverse_fixed(pose);
+        self.solutions.as_slice()
+    }
+
+    /// Inverse kinematics, see `Kinematics::inverse_continuing`. The returned slice borrows
+    /// the internal buffer and is valid until the next call.
+    pub fn inverse_continuing(&mut self, pose: &Pose, previous: &Joints) -> &[Joints] {
+        self.solutions = self.robot.inverse_continuing_fixed(pose, previous);
+        self.solutions.as_slice()
+    }
+
+    /// Solves all poses independently, passing the index of the pose and its solutions
+    /// to the closure.
+    pub fn inverse_batch<F: FnMut(usize, &[Joints])>(&mut self, poses: &[Pose], mut f: F) {
+        for (i, pose) in poses.iter().enumerate() {
+            f(i, self.inverse(pose));
+        }
+    }
+
+    /// Follows the path of poses starting from the given joint position, taking the closest
+    /// solution for each pose. Returns None if some pose on the path is not reachable.
+    /// The buffer for the joint path is reused between the calls, so after the first
+    /// call of the similar length this does not allocate.
+    pub fn follow(&mut self, poses: &[Pose], start: &Joints) -> Option<&[Joints]> {
+        self.path.clear();
+        let mut previous = *start;
+        for pose in poses {
+            let solutions = self.robot.inverse_continuing_fixed(pose, &previous);
+            previous = *solutions.first()?;
+            self.path.push(previous);
+        }
+        Some(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematic_traits::Kinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_solver_matches_robot() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let mut solver = Solver::new(&robot);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints);
+        assert_eq!(solver.inverse(&pose), robot.inverse(&pose).as_slice());
+        assert_eq!(solver.inverse_continuing(&pose, &joints),
+                   robot.inverse_continuing(&pose, &joints).as_slice());
+
+        let poses: Vec<Pose> = (0..10)
+            .map(|i| robot.forward(&[0.1, 0.2 + 0.01 * i as f64, 0.3, 0.4, 0.5, 0.6]))
+            .collect();
+        let mut count = 0;
+        solver.inverse_batch(&poses, |_, solutions| count += solutions.len());
+        assert!(count >= poses.len());
+
+        let path = solver.follow(&poses, &joints).expect("path must be reachable");
+        assert_eq!(path.len(), poses.len());
+        assert!((path[9][1] - 0.29).abs() < 1E-6);
+    }
+}
@@ -0,0 +1,341 @@
+//! Inverse kinematics of the six-axis robots with the spherical wrist that do not satisfy the
+//! OPW assumptions (offset or skewed arm axes, as found by the import). Following Pieper, the
+//! wrist center depends on J1 to J3 only: its position is solved by Newton iterations started
+//! from the seeds spread over the arm joints, then the wrist joints are solved analytically
+//! (Paden-Kahan subproblems) for the orientation. This is slower than the OPW solver but
+//! accepts any chain of six revolute joints whose last three axes intersect.
+//!
+//! `kinematics_from_chain` selects the OPW solver if the chain allows it and this one
+//! otherwise, so the robots imported from URDF can be used either way.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+use nalgebra::{Matrix3, Point3, Unit, UnitQuaternion, Vector3};
+use crate::kinematic_traits::{Joints, Kinematics, JOINTS_AT_ZERO, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::{OPWKinematics, ANGULAR_TOLERANCE, DISTANCE_TOLERANCE};
+use crate::parameters::opw_kinematics::Parameters;
+use crate::parameters_import::{ImportError, SerialChain};
+use crate::tool::Tool;
+use crate::utils::{angle_difference, compare_poses, normalize_near, sort_by_closeness,
+                   wrap_to_pi};
+
+/// Distance, meters, of the wrist axes from their common point, still taken as spherical.
+const WRIST_TOLERANCE: f64 = 1E-6;
+
+/// Seeds per arm joint J2 and J3 (J1 is seeded towards the wrist center and away from it).
+const SEEDS: usize = 6;
+
+/// Maximal number of the Newton iterations positioning the wrist center.
+const MAX_ITERATIONS: usize = 30;
+
+/// Sine of the angle between J4 and J6 below which the wrist is singular.
+const SINGULARITY_THRESHOLD: f64 = 1E-4;
+
+/// Solver for the six-axis robot with the spherical wrist, the joints as in the chain.
+#[derive(Debug, Clone)]
+pub struct SphericalWristKinematics {
+    chain: SerialChain,
+    /// Wrist center in the frame of J3 (after its rotation).
+    wrist_in_arm: Vector3<f64>,
+    /// Wrist center in the flange frame.
+    wrist_in_flange: Vector3<f64>,
+}
+
+impl SphericalWristKinematics {
+    /// Solver for the chain, ImportError::NotSphericalWrist if the axes of J4, J5 and J6 do
+    /// not meet in one point.
+    pub fn new(chain: SerialChain) -> Result<Self, ImportError> {
+        let frames = frames(&chain, &[0.0; 6]);
+        let axis = |i: usize| frames[i].rotation * chain.joints[i].1.into_inner();
+        let point = |i: usize| frames[i].translation.vector;
+
+        // Point of the J4 axis closest to the J5 axis
+        let (a4, a5) = (axis(3), axis(4));
+        let normal = a4.cross(&a5);
+        if normal.norm() < WRIST_TOLERANCE {
+            return Err(ImportError::NotSphericalWrist("J4 and J5 are parallel".into()));
+        }
+        let t = (point(4) - point(3)).cross(&a5).dot(&normal) / normal.norm_squared();
+        let center = point(3) + a4 * t;
+        for i in 3..6 {
+            if (center - point(i)).cross(&axis(i)).norm() > WRIST_TOLERANCE {
+                let message = format!("J{} does not pass through the wrist center", i + 1);
+                return Err(ImportError::NotSphericalWrist(message));
+            }
+        }
+
+        Ok(SphericalWristKinematics {
+            wrist_in_arm: frames[2].inverse_transform_point(&center.into()).coords,
+            wrist_in_flange: frames[6].inverse_transform_point(&center.into()).coords,
+            chain,
+        })
+    }
+
+    pub fn chain(&self) -> &SerialChain {
+        &self.chain
+    }
+
+    /// Frame of J3 after its rotation.
+    fn arm_frame(&self, frames: &[Pose; 7], q3: f64) -> Pose {
+        frames[2] * UnitQuaternion::from_axis_angle(&self.chain.joints[2].1, q3)
+    }
+
+    /// Arm joints J1 to J3 placing the wrist center at the given point, all distinct solutions.
+    fn arm_solutions(&self, center: &Vector3<f64>) -> Vec<[f64; 3]> {
+        let mut result: Vec<[f64; 3]> = Vec::new();
+        let toward = center.y.atan2(center.x);
+        for q1 in [toward, toward + PI] {
+            for i in 0..SEEDS {
+                for j in 0..SEEDS {
+                    let seed = |k: usize| -PI + 2.0 * PI * (k as f64 + 0.5) / SEEDS as f64;
+                    let Some(arm) = self.position_arm(center, [q1, seed(i), seed(j)]) else {
+                        continue;
+                    };
+                    let arm = arm.map(wrap_to_pi);
+                    if !result.iter().any(|known| known.iter().zip(&arm)
+                        .all(|(a, b)| angle_difference(*a, *b).abs() < 1E-6)) {
+                        result.push(arm);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Newton iterations of the arm joints towards the wrist center, None if they diverge.
+    fn position_arm(&self, center: &Vector3<f64>, mut arm: [f64; 3]) -> Option<[f64; 3]> {
+        for _ in 0..MAX_ITERATIONS {
+            let joints = [arm[0], arm[1], arm[2], 0.0, 0.0, 0.0];
+            let frames = frames(&self.chain, &joints);
+            let wrist = self.arm_frame(&frames, arm[2]) * Point3::from(self.wrist_in_arm);
+            let error = center - wrist.coords;
+            if error.norm() < DISTANCE_TOLERANCE * 1E-4 {
+                return Some(arm);
+            }
+            let mut jacobian = Matrix3::zeros();
+            for (j, frame) in frames.iter().take(3).enumerate() {
+                // Frame of the joint before its rotation shares the axis and the origin
+                let axis = frame.rotation * self.chain.joints[j].1.into_inner();
+                let column = axis.cross(&(wrist.coords - frame.translation.vector));
+                jacobian.set_column(j, &column);
+            }
+            let step = jacobian.lu().solve(&error)?;
+            // Limit the step, the arm equations are far from linear
+            let scale = (0.5 / step.amax()).min(1.0);
+            for j in 0..3 {
+                arm[j] += step[j] * scale;
+            }
+        }
+        None
+    }
+
+    /// Wrist joints J4 to J6 giving the orientation with the arm joints, up to two solutions.
+    fn wrist_solutions(&self, rotation: &UnitQuaternion<f64>, arm: &[f64; 3]) -> Vec<[f64; 3]> {
+        let joints = [arm[0], arm[1], arm[2], 0.0, 0.0, 0.0];
+        let arm_rotation = self.arm_frame(&frames(&self.chain, &joints), arm[2]).rotation;
+        let origin = |i: usize| self.chain.joints[i].0.rotation;
+        let axis = |i: usize| self.chain.joints[i].1.into_inner();
+
+        // rotation = arm O4 R(a4, q4) O5 R(a5, q5) O6 R(a6, q6) F, moved into the form
+        // R(a4, q4) R(b5, q5) R(b6, q6) with b5 = O5 a5 and b6 = O5 O6 a6
+        let fixed = origin(4) * origin(5);
+        let target = origin(3).inverse() * arm_rotation.inverse() * rotation
+            * self.chain.flange.rotation.inverse() * fixed.inverse();
+        let (a4, b5, b6) = (axis(3), origin(4) * axis(4), fixed * axis(5));
+        let v = target * b6;
+
+        let mut result = Vec::with_capacity(2);
+        for z in subproblem_2(&a4, &b5, &b6, &v) {
+            let q5 = subproblem_1(&b5, &b6, &z);
+            let q4 = subproblem_1(&a4, &z, &v);
+            let rest = (rotation_about(&a4, q4) * rotation_about(&b5, q5)).inverse() * target;
+            let u = b6.cross(&if b6.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() });
+            let q6 = subproblem_1(&b6, &u, &(rest * u));
+            result.push([q4, q5, q6]);
+        }
+        result
+    }
+}
+
+/// Frames of the joints J1 to J6 (before their rotation, so the axis is as in the chain) and
+/// the flange, in the base frame.
+fn frames(chain: &SerialChain, joints: &Joints) -> [Pose; 7] {
+    let mut frames = [Pose::identity(); 7];
+    let mut pose = Pose::identity();
+    for (i, (origin, axis)) in chain.joints.iter().enumerate() {
+        frames[i] = pose * origin;
+        pose = frames[i] * UnitQuaternion::from_axis_angle(axis, joints[i]);
+    }
+    frames[6] = pose * chain.flange;
+    frames
+}
+
+fn rotation_about(axis: &Vector3<f64>, angle: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_axis_angle(&Unit::new_unchecked(*axis), angle)
+}
+
+/// Angle rotating u into v about the unit axis (Paden-Kahan subproblem 1).
+fn subproblem_1(axis: &Vector3<f64>, u: &Vector3<f64>, v: &Vector3<f64>) -> f64 {
+    let u = u - axis * axis.dot(u);
+    let v = v - axis * axis.dot(v);
+    axis.dot(&u.cross(&v)).atan2(u.dot(&v))
+}
+
+/// Vectors z with R(w2, t2) p = z and R(w1, t1) z = q for some angles (Paden-Kahan
+/// subproblem 2), for the unit axes and vectors: none, one or two.
+fn subproblem_2(w1: &Vector3<f64>, w2: &Vector3<f64>, p: &Vector3<f64>, q: &Vector3<f64>)
+                -> Vec<Vector3<f64>> {
+    let c = w1.dot(w2);
+    let (w1q, w2p) = (w1.dot(q), w2.dot(p));
+    let alpha = (w1q - c * w2p) / (1.0 - c * c);
+    let beta = (w2p - c * w1q) / (1.0 - c * c);
+    let normal = w1.cross(w2);
+    let gamma_2 = (p.norm_squared() - alpha * alpha - beta * beta - 2.0 * alpha * beta * c)
+        / normal.norm_squared();
+    if gamma_2 < -ANGULAR_TOLERANCE {
+        return Vec::new();
+    }
+    let gamma = gamma_2.max(0.0).sqrt();
+    let base = w1 * alpha + w2 * beta;
+    if gamma == 0.0 {
+        vec![base]
+    } else {
+        vec![base + normal * gamma, base - normal * gamma]
+    }
+}
+
+impl Stateless for SphericalWristKinematics {}
+
+impl Kinematics for SphericalWristKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        let center = pose * Point3::from(self.wrist_in_flange);
+        let mut solutions = Solutions::new();
+        for arm in self.arm_solutions(&center.coords) {
+            for wrist in self.wrist_solutions(&pose.rotation, &arm) {
+                let joints = [arm[0], arm[1], arm[2], wrist[0], wrist[1], wrist[2]].map(wrap_to_pi);
+                if compare_poses(pose, &self.forward(&joints), DISTANCE_TOLERANCE,
+                                 ANGULAR_TOLERANCE).is_ok() {
+                    solutions.push(joints);
+                }
+            }
+        }
+        solutions
+    }
+
+    /// Solutions normalized near the previous joints and sorted by the distance to them.
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        let mut solutions = self.inverse(pose);
+        for solution in solutions.iter_mut() {
+            for (joint, previous) in solution.iter_mut().zip(previous) {
+                normalize_near(joint, *previous);
+            }
+        }
+        sort_by_closeness(&mut solutions, previous);
+        solutions
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.chain.forward(qs)
+    }
+
+    /// Wrist singularity: the axes of J4 and J6 are aligned.
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        let frames = frames(&self.chain, qs);
+        let axis = |i: usize| frames[i].rotation * self.chain.joints[i].1.into_inner();
+        if axis(3).cross(&axis(5)).norm() < SINGULARITY_THRESHOLD {
+            Some(Singularity::A)
+        } else {
+            None
+        }
+    }
+}
+
+/// Solver for the chain: the OPW solver if the chain satisfies the OPW assumptions, otherwise
+/// the spherical wrist solver. Either way the poses are of the flange of the chain: the OPW
+/// solver is wrapped as the tool correcting its flange frame, see [`crate::parameters_import`].
+pub fn kinematics_from_chain(chain: SerialChain)
+                             -> Result<Arc<dyn Kinematics + Send + Sync>, ImportError> {
+    match Parameters::from_chain(&chain) {
+        Ok(parameters) => {
+            let opw = OPWKinematics::new(parameters);
+            let flange = opw.forward(&JOINTS_AT_ZERO).inverse() * chain.forward(&JOINTS_AT_ZERO);
+            Ok(Arc::new(Tool::new(opw, flange)))
+        }
+        Err(ImportError::NotOpw(_)) => Ok(Arc::new(SphericalWristKinematics::new(chain)?)),
+        Err(error) => Err(error),
+    }
+}
+
+/// Solver for the robot described by the URDF, as selected by `kinematics_from_chain`.
+#[cfg(feature = "files")]
+pub fn kinematics_from_urdf(urdf: &str) -> Result<Arc<dyn Kinematics + Send + Sync>, ImportError> {
+    kinematics_from_chain(SerialChain::from_urdf(urdf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters_import::DhRow;
+    use crate::utils::joint_distance;
+
+    /// IRB 2400/10 as the DH table with J3 skewed and offset sideways, not OPW any more.
+    fn skewed() -> SerialChain {
+        SerialChain::from_dh(&[
+            DhRow { a: 0.100, alpha: -PI / 2.0, d: 0.615, theta: 0.0 },
+            DhRow { a: 0.705, alpha: 0.05, d: 0.02, theta: -PI / 2.0 },
+            DhRow { a: 0.135, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: PI / 2.0, d: 0.755, theta: 0.0 },
+            DhRow { a: 0.0, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: 0.0, d: 0.085, theta: 0.0 },
+        ])
+    }
+
+    #[test]
+    fn test_spherical_wrist() {
+        let chain = skewed();
+        assert!(matches!(Parameters::from_chain(&chain), Err(ImportError::NotOpw(_))));
+        let robot = SphericalWristKinematics::new(chain).unwrap();
+        for joints in [[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], [-1.0, 0.5, -0.4, 2.0, -1.2, 0.3]] {
+            let pose = robot.forward(&joints);
+            let solutions = robot.inverse(&pose);
+            assert!(solutions.len() >= 4, "{} solutions", solutions.len());
+            assert!(solutions.iter().all(|s| compare_poses(&robot.forward(s), &pose, 1E-9, 1E-9)
+                .is_ok()));
+            let continuing = robot.inverse_continuing(&pose, &joints);
+            assert!(continuing[0].iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-7));
+        }
+        assert!(robot.inverse(&Pose::translation(5.0, 0.0, 0.0)).is_empty());
+        assert_eq!(robot.kinematic_singularity(&[0.1, 0.2, 0.3, 0.4, 0.0, 0.6]),
+                   Some(Singularity::A));
+        assert_eq!(robot.kinematic_singularity(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]), None);
+    }
+
+    #[test]
+    fn test_selection() {
+        let opw = SerialChain::from_dh(&[
+            DhRow { a: 0.100, alpha: -PI / 2.0, d: 0.615, theta: 0.0 },
+            DhRow { a: 0.705, alpha: 0.0, d: 0.0, theta: -PI / 2.0 },
+            DhRow { a: 0.135, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: PI / 2.0, d: 0.755, theta: 0.0 },
+            DhRow { a: 0.0, alpha: -PI / 2.0, d: 0.0, theta: 0.0 },
+            DhRow { a: 0.0, alpha: 0.0, d: 0.085, theta: 0.0 },
+        ]);
+        assert!(Parameters::from_chain(&opw).is_ok());
+        for chain in [opw, skewed()] {
+            let robot = kinematics_from_chain(chain.clone()).unwrap();
+            for joints in [[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], [-1.0, 0.5, -0.4, 2.0, -1.2, 0.3]] {
+                // Both solvers give the full pose of the chain flange, the rotation included
+                let pose = robot.forward(&joints);
+                assert!(compare_poses(&pose, &chain.forward(&joints), 1E-9, 1E-9).is_ok());
+                let solutions = robot.inverse(&pose);
+                assert!(solutions.iter().any(|s| joint_distance(s, &joints) < 1E-7));
+                assert!(solutions.iter().all(|s| compare_poses(&chain.forward(s), &pose, 1E-9,
+                                                               1E-9).is_ok()));
+            }
+        }
+
+        let mut offset = skewed();
+        offset.joints[4].0.translation.vector.x += 0.01;
+        assert!(matches!(kinematics_from_chain(offset), Err(ImportError::NotSphericalWrist(_))));
+    }
+}
@@ -0,0 +1,231 @@
+//! Static joint torques: the gravity load of the links and the payload, for the quick checks
+//! whether the payload (or the mounting, which turns the gravity in the base frame) is within
+//! the ratings of the drives. The torques are those the drives must exert to hold the robot
+//! still, from the masses and centers of mass of the links; the inertia is not needed.
+//!
+//! The process forces (drilling thrust, polishing pressure) map to the joint torques through
+//! the transposed Jacobian, so each inverse kinematics solution can be checked against the
+//! drive ratings, as the same force loads the joints very differently in the different
+//! configurations.
+
+use nalgebra::{Point3, Vector3, Vector6};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+
+/// Standard gravity, m/s².
+pub const STANDARD_GRAVITY: f64 = 9.80665;
+
+/// Mass and the center of mass of the body.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkMass {
+    /// Mass, kg.
+    pub mass: f64,
+    /// Center of mass in the frame of the link, meters.
+    pub center: [f64; 3],
+}
+
+impl LinkMass {
+    pub fn new(mass: f64, center: [f64; 3]) -> Self {
+        LinkMass { mass, center }
+    }
+}
+
+/// Masses of the robot links 1 to 6, the centers in the link frames as returned by
+/// `OPWKinematics::link_poses`, and of the payload, the center in the flange frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MassProperties {
+    pub links: [LinkMass; 6],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub payload: LinkMass,
+}
+
+impl MassProperties {
+    pub fn new(links: [LinkMass; 6]) -> Self {
+        MassProperties { links, payload: LinkMass::default() }
+    }
+
+    pub fn with_payload(self, payload: LinkMass) -> Self {
+        MassProperties { payload, ..self }
+    }
+}
+
+impl OPWKinematics {
+    /// Torques (N·m) the drives must exert to hold the robot still
+    /// against the gravity, given as the acceleration vector in the base frame, m/s² (like
+    /// `Mounting::gravity_in_base() * STANDARD_GRAVITY`).
+    pub fn gravity_torques(&self, joints: &Joints, masses: &MassProperties,
+                           gravity: &Vector3<f64>) -> Joints {
+        let links = self.link_poses(joints);
+        let flange = self.forward(joints);
+        let bodies = links.iter().zip(&masses.links).enumerate()
+            .map(|(i, (pose, body))| (i, pose, body))
+            .chain(std::iter::once((6, &flange, &masses.payload)));
+        let mut torques = [0.0; 6];
+        for (link, pose, body) in bodies.filter(|(_, _, body)| body.mass != 0.0) {
+            let center = pose * Point3::from(Vector3::from(body.center));
+            let jacobian = self.point_jacobian(joints, link, &center.coords);
+            // Holding against the weight m g takes the torque -Jᵀ m g
+            let force = -body.mass * gravity;
+            for (j, torque) in torques.iter_mut().enumerate() {
+                *torque += jacobian.fixed_view::<3, 1>(0, j).dot(&force);
+            }
+        }
+        torques
+    }
+
+    /// Torques (N·m) the drives must exert for the tool to apply the wrench at the TCP (pose in
+    /// the flange frame). The wrench is the force (N) and the moment (N·m) the tool exerts on
+    /// the work, in the axes of the TCP frame. Add `gravity_torques` for the total load.
+    pub fn wrench_torques(&self, joints: &Joints, tcp: &Pose, wrench: &Vector6<f64>) -> Joints {
+        let torques = self.jacobian_in(joints, tcp).transpose() * wrench;
+        std::array::from_fn(|j| torques[j])
+    }
+
+    /// Solutions for the TCP pose (in the base frame) with the torques of applying the wrench
+    /// there, see `wrench_torques`, and the check against the drive ratings (N·m, either way).
+    pub fn inverse_loaded(&self, pose: &Pose, tcp: &Pose, wrench: &Vector6<f64>,
+                          ratings: &[f64; 6]) -> Vec<LoadedSolution> {
+        self.inverse(&(pose * tcp.inverse())).into_iter().map(|joints| {
+            let torques = self.wrench_torques(&joints, tcp, wrench);
+            let overloaded = (0..6).find(|&j| torques[j].abs() > ratings[j]);
+            LoadedSolution { joints, torques, overloaded }
+        }).collect()
+    }
+}
+
+/// Inverse kinematics solution with its static joint torques.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadedSolution {
+    pub joints: Joints,
+    pub torques: Joints,
+    /// First joint with the torque above its rating, None if all are within.
+    pub overloaded: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Translation3, UnitQuaternion};
+    use super::*;
+    use crate::mounting::Mounting;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    fn masses() -> MassProperties {
+        MassProperties::new([
+            LinkMass::new(80.0, [0.05, 0.0, 0.3]),
+            LinkMass::new(30.0, [0.0, 0.05, 0.35]),
+            LinkMass::new(20.0, [0.3, 0.0, 0.05]),
+            LinkMass::new(5.0, [0.0, 0.0, 0.3]),
+            LinkMass::new(2.0, [0.0, 0.0, 0.02]),
+            LinkMass::new(0.5, [0.0, 0.0, 0.05]),
+        ]).with_payload(LinkMass::new(10.0, [0.02, 0.0, 0.1]))
+    }
+
+    /// Potential energy of the robot, -sum of m g·p.
+    fn potential(robot: &OPWKinematics, joints: &Joints, masses: &MassProperties,
+                 gravity: &Vector3<f64>) -> f64 {
+        let links = robot.link_poses(joints);
+        let poses = links.iter().chain(std::iter::once(&robot.forward(joints)))
+            .copied().collect::<Vec<_>>();
+        masses.links.iter().chain(std::iter::once(&masses.payload)).zip(&poses)
+            .map(|(body, pose)| {
+                let center = pose * Point3::from(Vector3::from(body.center));
+                -body.mass * gravity.dot(&center.coords)
+            }).sum()
+    }
+
+    #[test]
+    fn test_gravity_torques() {
+        let masses = masses();
+        let joints = [0.1, 0.4, -0.3, 0.5, 0.7, 0.2];
+        let parallelogram = Parameters { j3_coupling: 1.0, ..Parameters::irb2400_10() };
+        for (parameters, mounting) in [(Parameters::irb2400_10(), Mounting::Floor),
+                                       (Parameters::fanuc_r2000ib_200r(), Mounting::Tilted(0.5)),
+                                       (parallelogram, Mounting::Ceiling)] {
+            let robot = OPWKinematics::new(parameters);
+            let gravity = mounting.gravity_in_base() * STANDARD_GRAVITY;
+            let torques = robot.gravity_torques(&joints, &masses, &gravity);
+            // The holding torque is the derivative of the potential energy
+            for j in 0..6 {
+                let (mut plus, mut minus) = (joints, joints);
+                plus[j] += 1E-6;
+                minus[j] -= 1E-6;
+                let derivative = (potential(&robot, &plus, &masses, &gravity)
+                    - potential(&robot, &minus, &masses, &gravity)) / 2E-6;
+                assert!((torques[j] - derivative).abs() < 1E-4, "J{}: {} vs {}", j + 1,
+                        torques[j], derivative);
+            }
+        }
+
+        // J1 of the floor robot turns about the vertical, the gravity does not load it
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let gravity = Mounting::Floor.gravity_in_base() * STANDARD_GRAVITY;
+        let torques = robot.gravity_torques(&joints, &masses, &gravity);
+        assert!(torques[0].abs() < 1E-9);
+        assert!(torques[1].abs() > 10.0);
+        assert_eq!(robot.gravity_torques(&joints, &MassProperties::default(), &gravity), [0.0; 6]);
+    }
+
+    #[test]
+    fn test_wrench_torques() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let joints = [0.1, 0.4, -0.3, 0.5, 0.7, 0.2];
+        let tcp = Pose::from_parts(Translation3::new(0.02, 0.0, 0.25),
+                                   UnitQuaternion::from_euler_angles(0.0, 0.3, 0.0));
+        // Thrust along the tool axis and a small moment about it
+        let wrench = Vector6::new(0.0, 0.0, 200.0, 0.0, 0.0, 5.0);
+        let torques = robot.wrench_torques(&joints, &tcp, &wrench);
+
+        // Virtual work: the torque times the joint motion equals the wrench times the TCP
+        // motion, in the TCP axes
+        let start = robot.forward(&joints) * tcp;
+        for j in 0..6 {
+            let mut moved = joints;
+            moved[j] += 1E-7;
+            let end = robot.forward(&moved) * tcp;
+            let linear = start.rotation.inverse() * (end.translation.vector
+                - start.translation.vector);
+            let angular = start.rotation.inverse() * (end.rotation * start.rotation.inverse())
+                .scaled_axis();
+            let work = wrench.fixed_rows::<3>(0).dot(&linear)
+                + wrench.fixed_rows::<3>(3).dot(&angular);
+            assert!((torques[j] - work / 1E-7).abs() < 1E-3, "J{}", j + 1);
+        }
+
+        // The vertical force does not load J1 of the floor robot
+        let down = start.rotation.inverse() * Vector3::new(0.0, 0.0, -100.0);
+        let vertical = Vector6::new(down.x, down.y, down.z, 0.0, 0.0, 0.0);
+        assert!(robot.wrench_torques(&joints, &tcp, &vertical)[0].abs() < 1E-9);
+
+        let solutions = robot.inverse_loaded(&start, &tcp, &wrench, &[1E4; 6]);
+        assert_eq!(solutions.len(), robot.inverse(&(start * tcp.inverse())).len());
+        assert!(solutions.iter().all(|s| s.overloaded.is_none()));
+        let loaded = solutions.iter()
+            .find(|s| s.joints.iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-6)).unwrap();
+        assert!(loaded.torques.iter().zip(&torques).all(|(a, b)| (a - b).abs() < 1E-6));
+        // The J6 rating below the moment about the tool axis
+        let ratings = [1E4, 1E4, 1E4, 1E4, 1E4, 1.0];
+        assert!(robot.inverse_loaded(&start, &tcp, &wrench, &ratings).iter()
+            .all(|s| s.overloaded == Some(5)));
+    }
+
+    #[test]
+    #[cfg(feature = "files")]
+    fn test_masses_from_yaml() {
+        let yaml = std::fs::read_to_string("src/tests/fanuc_m16ib20.yaml").unwrap();
+        assert_eq!(MassProperties::from_yaml(&yaml).unwrap(), None);
+
+        let yaml = yaml + "\nopw_kinematics_link_masses: [80.0, 30.0, 20.0, 5.0, 2.0, 0.5]
+opw_kinematics_link_centers_of_mass: [[0.05, 0.0, 0.3], [0.0, 0.05, 0.35], [0.3, 0.0, 0.05],
+  [0.0, 0.0, 0.3], [0.0, 0.0, 0.02], [0.0, 0.0, 0.05]]
+opw_kinematics_payload: { mass: 10.0, center: [0.02, 0.0, 0.1] }
+";
+        let masses = MassProperties::from_yaml(&yaml).unwrap().unwrap();
+        assert_eq!(masses.links[2], LinkMass::new(20.0, [0.3, 0.0, 0.05]));
+        assert_eq!(masses.payload, LinkMass::new(10.0, [0.02, 0.0, 0.1]));
+        // The geometry is read from the same file
+        assert_eq!(Parameters::from_yaml(&yaml).unwrap().c2, 0.77);
+    }
+}
@@ -0,0 +1,161 @@
+//! Sticky configuration, as in the robot programs: once the configuration (shoulder, elbow,
+//! wrist flip) is chosen, the motion stays in it and the poses that would need another one
+//! are not reachable, rather than the arm silently flipping over.
+//!
+//! With the hysteresis, the configuration is only preferred: another one is taken if it is
+//! closer by more than the margin. This stops the joints chattering between the branches that
+//! are nearly equidistant from the seed in streamed inverse kinematics.
+
+use std::sync::Mutex;
+use crate::kinematic_traits::{Configuration, Joints, Kinematics, Pose, Singularity, Solutions};
+use crate::kinematics_impl::OPWKinematics;
+use crate::utils::joint_distance;
+
+/// Kinematics that keep the configuration once chosen. The configuration is chosen with `lock`
+/// or, if none is locked, taken from the first solution of `inverse_continuing`. While locked,
+/// `inverse` and `inverse_continuing` only return the solutions in this configuration (in the
+/// wrist singularity, where the wrist flip is undefined, the arm configuration must match).
+/// `release` lets the next `inverse_continuing` choose again. Created `with_hysteresis`, the
+/// configuration is preferred rather than enforced.
+pub struct StickyKinematics {
+    pub robot: OPWKinematics,
+    /// Margin of the hysteresis, radians of `joint_distance`, None if the configuration is kept.
+    hysteresis: Option<f64>,
+    locked: Mutex<Option<Configuration>>,
+}
+
+impl StickyKinematics {
+    pub fn new(robot: OPWKinematics) -> Self {
+        StickyKinematics { robot, hysteresis: None, locked: Mutex::new(None) }
+    }
+
+    /// Kinematics preferring the configuration of the previous `inverse_continuing` result:
+    /// it comes first unless the closest solution of another configuration is closer to the
+    /// previous joints by more than `margin` (`joint_distance`, radians); then that one comes
+    /// first and its configuration is preferred from now on. No solutions are removed.
+    pub fn with_hysteresis(robot: OPWKinematics, margin: f64) -> Self {
+        StickyKinematics { robot, hysteresis: Some(margin), locked: Mutex::new(None) }
+    }
+
+    /// Locks the given configuration.
+    pub fn lock(&self, configuration: Configuration) {
+        *self.locked.lock().unwrap() = Some(configuration);
+    }
+
+    /// Releases the configuration, the next `inverse_continuing` chooses it again.
+    pub fn release(&self) {
+        *self.locked.lock().unwrap() = None;
+    }
+
+    /// The configuration currently locked.
+    pub fn locked(&self) -> Option<Configuration> {
+        *self.locked.lock().unwrap()
+    }
+
+    fn matches(&self, joints: &Joints, configuration: &Configuration) -> bool {
+        let actual = self.robot.configuration(joints);
+        actual.arm == configuration.arm && (actual.wrist_flipped == configuration.wrist_flipped
+            || self.robot.kinematic_singularity(joints) == Some(Singularity::A))
+    }
+
+    /// Applies the locked configuration to the solutions, and chooses it if the previous
+    /// joints are given (`inverse_continuing`).
+    fn apply(&self, mut solutions: Solutions, previous: Option<&Joints>) -> Solutions {
+        let mut locked = self.locked.lock().unwrap();
+        match (*locked, self.hysteresis, previous) {
+            (Some(configuration), None, _) => solutions.retain(|s| self.matches(s, &configuration)),
+            (Some(configuration), Some(margin), Some(previous)) => {
+                let preferred = solutions.iter().position(|s| self.matches(s, &configuration));
+                if let (Some(i), Some(first)) = (preferred, solutions.first()) {
+                    if joint_distance(&solutions[i], previous)
+                        <= joint_distance(first, previous) + margin {
+                        solutions[..=i].rotate_right(1);
+                    }
+                }
+                *locked = solutions.first().map(|s| self.robot.configuration(s));
+            }
+            (None, _, Some(_)) => *locked = solutions.first().map(|s| self.robot.configuration(s)),
+            _ => {}
+        }
+        solutions
+    }
+}
+
+impl Kinematics for StickyKinematics {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.apply(self.robot.inverse(pose), None)
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.apply(self.robot.inverse_continuing(pose, previous), Some(previous))
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(qs)
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_sticky_configuration() {
+        let sticky = StickyKinematics::new(OPWKinematics::new(Parameters::irb2400_10()));
+        let start = [0.0, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = sticky.forward(&start);
+        assert_eq!(sticky.locked(), None);
+        assert_eq!(sticky.inverse(&pose).len(), 8);
+
+        // The first continuing call locks the configuration of the closest solution
+        let solutions = sticky.inverse_continuing(&pose, &start);
+        let configuration = sticky.robot.configuration(&start);
+        assert_eq!(sticky.locked(), Some(configuration));
+        assert!(solutions.len() > 1);
+        assert_eq!(sticky.inverse_continuing(&pose, &start).len(), 1);
+        assert!(sticky.inverse(&pose).iter()
+            .all(|s| sticky.robot.configuration(s) == configuration));
+
+        // Locking the flipped wrist returns its solution instead
+        let other = Configuration { wrist_flipped: !configuration.wrist_flipped, ..configuration };
+        sticky.lock(other);
+        let flipped = sticky.inverse_continuing(&pose, &start);
+        assert_eq!(flipped.len(), 1);
+        assert_eq!(sticky.robot.configuration(&flipped[0]), other);
+
+        sticky.release();
+        assert_eq!(sticky.inverse_continuing(&pose, &start)[0], solutions[0]);
+        assert_eq!(sticky.locked(), Some(configuration));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.0, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&start);
+        let configuration = robot.configuration(&start);
+        let other = Configuration { wrist_flipped: !configuration.wrist_flipped, ..configuration };
+        let solutions = robot.inverse_continuing(&pose, &start);
+        let flipped = solutions.iter().find(|s| robot.configuration(s) == other).unwrap();
+        let gap = joint_distance(flipped, &start);
+
+        // Within the margin, the preferred configuration comes first, all solutions are kept
+        let sticky = StickyKinematics::with_hysteresis(robot.clone(), gap + 0.1);
+        sticky.lock(other);
+        let kept = sticky.inverse_continuing(&pose, &start);
+        assert_eq!(kept.len(), solutions.len());
+        assert_eq!(&kept[0], flipped);
+        assert_eq!(sticky.locked(), Some(other));
+
+        // The better solution beyond the margin takes over the preference
+        let sticky = StickyKinematics::with_hysteresis(robot, gap - 0.1);
+        sticky.lock(other);
+        assert_eq!(sticky.inverse_continuing(&pose, &start)[0], solutions[0]);
+        assert_eq!(sticky.locked(), Some(configuration));
+    }
+}
@@ -0,0 +1,125 @@
+//! [proptest](https://crates.io/crates/proptest) strategies and round trip properties (feature
+//! `proptest`), for fuzzing the integrations of the solver the way it is tested itself:
+//! ```
+//! use proptest::prelude::*;
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//! use rs_opw_kinematics::parameters::opw_kinematics::Parameters;
+//! use rs_opw_kinematics::strategies::{check_round_trip, joints_away_from_singularity};
+//!
+//! proptest!(|(joints in joints_away_from_singularity(0.01))| {
+//!     let robot = OPWKinematics::new(Parameters::irb2400_10());
+//!     check_round_trip(&robot, &joints, 1E-6)?;
+//! });
+//! ```
+
+use std::f64::consts::PI;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::parameters::opw_kinematics::Parameters;
+use crate::utils::angle_difference;
+
+/// Joints within [-PI, PI].
+pub fn joints() -> impl Strategy<Value = Joints> {
+    joints_within([(-PI, PI); 6])
+}
+
+/// Joints within the given (min, max) range of each joint, radians.
+pub fn joints_within(limits: [(f64, f64); 6]) -> impl Strategy<Value = Joints> {
+    limits.map(|(min, max)| min..=max)
+}
+
+/// Joints within [-PI, PI] with J5 at least `margin` radians from the wrist singularity
+/// (0 or PI), where the inverse kinematics cannot recover J4 and J6 separately.
+pub fn joints_away_from_singularity(margin: f64) -> impl Strategy<Value = Joints> {
+    joints().prop_filter("J5 in the wrist singularity", move |joints| {
+        joints[4].sin().abs() > margin.sin()
+    })
+}
+
+/// Reachable pose of the robot, together with the joints producing it.
+pub fn reachable_pose<K: Kinematics + 'static>(robot: K) -> impl Strategy<Value = (Joints, Pose)> {
+    joints().prop_map(move |joints| (joints, robot.forward(&joints)))
+}
+
+/// Parameters with each length changed by up to `length` meters and each offset by up to
+/// `angle` radians. Sign corrections are kept.
+pub fn perturbed_parameters(base: Parameters, length: f64, angle: f64)
+                            -> impl Strategy<Value = Parameters> {
+    let lengths = [(); 7].map(|_| -length..=length);
+    let offsets = [(); 6].map(|_| -angle..=angle);
+    (lengths, offsets).prop_map(move |(l, o)| Parameters {
+        a1: base.a1 + l[0],
+        a2: base.a2 + l[1],
+        b: base.b + l[2],
+        c1: base.c1 + l[3],
+        c2: base.c2 + l[4],
+        c3: base.c3 + l[5],
+        c4: base.c4 + l[6],
+        offsets: std::array::from_fn(|i| base.offsets[i] + o[i]),
+        sign_corrections: base.sign_corrections,
+        j3_coupling: base.j3_coupling,
+    })
+}
+
+/// Property: the inverse kinematics of the pose produced by the joints returns these joints
+/// (within the tolerance, radians; angles 2 PI apart are equal). Holds outside singularities.
+pub fn check_round_trip<K: Kinematics>(robot: &K, joints: &Joints, tolerance: f64)
+                                       -> Result<(), TestCaseError> {
+    let pose = robot.forward(joints);
+    let solutions = robot.inverse(&pose);
+    prop_assert!(solutions.iter().any(|s| same_joints(s, joints, tolerance)),
+                 "joints {:?} not among the solutions {:?}", joints, solutions);
+    Ok(())
+}
+
+/// Property: `inverse_continuing` returns the joints producing the pose first, if continuing
+/// from these joints. Holds in the singularities as well.
+pub fn check_continuing_round_trip<K: Kinematics>(robot: &K, joints: &Joints, tolerance: f64)
+                                                  -> Result<(), TestCaseError> {
+    let solutions = robot.inverse_continuing(&robot.forward(joints), joints);
+    prop_assert!(solutions.first().is_some_and(|s| same_joints(s, joints, tolerance)),
+                 "joints {:?} do not come first in {:?}", joints, solutions);
+    Ok(())
+}
+
+/// Property: every solution of the inverse kinematics produces the pose (translation within
+/// `tolerance` meters, rotation within `tolerance` radians).
+pub fn check_solutions_reach_pose<K: Kinematics>(robot: &K, pose: &Pose, tolerance: f64)
+                                                 -> Result<(), TestCaseError> {
+    for solution in robot.inverse(pose) {
+        let reached = robot.forward(&solution);
+        prop_assert!((reached.translation.vector - pose.translation.vector).norm() <= tolerance
+                         && reached.rotation.angle_to(&pose.rotation) <= tolerance,
+                     "solution {:?} reaches {:?} instead of {:?}", solution, reached, pose);
+    }
+    Ok(())
+}
+
+fn same_joints(a: &Joints, b: &Joints, tolerance: f64) -> bool {
+    a.iter().zip(b).all(|(a, b)| {
+        angle_difference(*a, *b).abs() <= tolerance
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+
+    proptest! {
+        #[test]
+        fn test_round_trips(joints in joints_away_from_singularity(0.01)) {
+            let robot = OPWKinematics::new(Parameters::staubli_tx2_160l());
+            check_round_trip(&robot, &joints, 1E-6)?;
+            check_continuing_round_trip(&robot, &joints, 1E-6)?;
+        }
+
+        #[test]
+        fn test_perturbed_robots(
+            parameters in perturbed_parameters(Parameters::irb2400_10(), 0.01, 0.01),
+            (_, pose) in reachable_pose(OPWKinematics::new(Parameters::irb2400_10()))) {
+            check_solutions_reach_pose(&OPWKinematics::new(parameters), &pose, 1E-6)?;
+        }
+    }
+}
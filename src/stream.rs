@@ -0,0 +1,332 @@
+//! Streaming inverse kinematics session for the real time consumers (visual servoing, hand
+//! tracking, external path generators): the session keeps the previous solution as the seed,
+//! the joint limits, the continuity threshold and the turn counters, so each cycle is just
+//! `next(pose)`. The configuration is kept by taking the closest solution and refusing the
+//! jumps; for the strict configuration lock, stream with `StickyKinematics` as the robot.
+//!
+//! The noisy targets (from the vision or the hand tracking) can be smoothed by the low-pass
+//! filter before solving, so the noise does not turn into the joint jitter.
+//!
+//! If the next few poses are known (buffered by the caller), `next_ahead` chooses the solution
+//! that can also follow them, rather than the closest one that becomes infeasible a couple of
+//! cycles later, as it may happen when approaching the wrist singularity.
+
+use thiserror::Error;
+use std::f64::consts::PI;
+use crate::constraints::{JointLimits, TurnCounter};
+use crate::interpolation::interpolate;
+use crate::jog::{check_step, JogError};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+
+/// Why the pose was not accepted. The session stays at the previous joints.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum StreamError {
+    #[error("the pose is out of reach")]
+    Unreachable,
+    #[error("the pose is out of the joint limits")]
+    JointLimits,
+    #[error("the pose requires J{} to move by {change} rad in one cycle", joint + 1)]
+    Discontinuity { joint: usize, change: f64 },
+    #[error("the pose would wind J{} by {winding} rad beyond the budget", joint + 1)]
+    OverBudget { joint: usize, winding: f64 },
+}
+
+impl From<JogError> for StreamError {
+    fn from(error: JogError) -> Self {
+        match error {
+            JogError::Unreachable => StreamError::Unreachable,
+            JogError::JointLimits => StreamError::JointLimits,
+            JogError::Discontinuity { joint, change } => {
+                StreamError::Discontinuity { joint, change }
+            }
+        }
+    }
+}
+
+/// Exponential smoothing of the poses: each output moves from the previous output towards
+/// the input by the fraction alpha, the rotation along the shorter arc (slerp).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoseFilter {
+    /// Weight of the new input, 1 for no filtering, smaller for the stronger smoothing.
+    pub alpha: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    output: Option<Pose>,
+}
+
+impl PoseFilter {
+    pub fn new(alpha: f64) -> Self {
+        PoseFilter { alpha, output: None }
+    }
+
+    /// Filter with the given cutoff frequency at the given input rate, both in Hz.
+    pub fn from_cutoff(cutoff: f64, rate: f64) -> Self {
+        Self::new(1.0 - (-2.0 * PI * cutoff / rate).exp())
+    }
+
+    /// Filtered pose for the next input. The first input passes unchanged.
+    pub fn apply(&mut self, pose: &Pose) -> Pose {
+        let output = match &self.output {
+            Some(previous) => interpolate(previous, pose, self.alpha),
+            None => *pose,
+        };
+        self.output = Some(output);
+        output
+    }
+
+    /// Forgets the previous output, the next input passes unchanged.
+    pub fn reset(&mut self) {
+        self.output = None;
+    }
+}
+
+/// Streaming session, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct IkStream<K: Kinematics> {
+    pub robot: K,
+    previous: Joints,
+    limits: Option<JointLimits>,
+    max_joint_step: f64,
+    counter: Option<TurnCounter>,
+    filter: Option<PoseFilter>,
+}
+
+impl<K: Kinematics> IkStream<K> {
+    /// Session starting at the given joints, with no limits and no continuity threshold.
+    pub fn new(robot: K, start: Joints) -> Self {
+        IkStream { robot, previous: start, limits: None, max_joint_step: f64::INFINITY,
+            counter: None, filter: None }
+    }
+
+    pub fn with_limits(self, limits: JointLimits) -> Self {
+        IkStream { limits: Some(limits), ..self }
+    }
+
+    /// Largest change of any joint in one cycle, radians.
+    pub fn with_max_joint_step(self, max_joint_step: f64) -> Self {
+        IkStream { max_joint_step, ..self }
+    }
+
+    /// Counts the turns of the joints from the start, with the budget as in `TurnCounter`.
+    pub fn with_turn_budget(self, budget: [f64; 6]) -> Self {
+        IkStream { counter: Some(TurnCounter::new(self.previous, budget)), ..self }
+    }
+
+    /// Smooths the poses with the filter before solving.
+    pub fn with_filter(self, filter: PoseFilter) -> Self {
+        IkStream { filter: Some(filter), ..self }
+    }
+
+    /// Joints of the last accepted pose (or the start).
+    pub fn previous(&self) -> Joints {
+        self.previous
+    }
+
+    /// Turn counter, if counting.
+    pub fn counter(&self) -> Option<&TurnCounter> {
+        self.counter.as_ref()
+    }
+
+    /// Continues from the given joints, as after the robot was moved by other means. The turn
+    /// counter and the filter (if any) start over.
+    pub fn reset(&mut self, joints: Joints) {
+        self.previous = joints;
+        if let Some(filter) = &mut self.filter {
+            filter.reset();
+        }
+        if let Some(counter) = &mut self.counter {
+            *counter = TurnCounter::new(joints, counter.budget);
+        }
+    }
+
+    /// Joints for the next pose: the solution closest to the previous joints that passes the
+    /// limits, continuity and turn checks. On error, the error of the closest solution. The
+    /// pose is filtered first, if the filter is set.
+    pub fn next(&mut self, pose: &Pose) -> Result<Joints, StreamError> {
+        self.next_ahead(pose, &[])
+    }
+
+    /// As `next`, but the solution is chosen knowing the poses that follow: the closest
+    /// solution from which all of them can be followed, each with the closest passing solution.
+    /// If there is none, the one that follows the most of them. Only the given pose is
+    /// consumed, the following ones are expected again in the later cycles.
+    pub fn next_ahead(&mut self, pose: &Pose, ahead: &[Pose]) -> Result<Joints, StreamError> {
+        // The filter is advanced only by the given pose, a copy filters the following ones
+        let mut filter = self.filter;
+        let pose = filter.as_mut().map_or(*pose, |filter| filter.apply(pose));
+        let mut ahead_filter = filter;
+        let ahead: Vec<Pose> = ahead.iter()
+            .map(|pose| ahead_filter.as_mut().map_or(*pose, |filter| filter.apply(pose)))
+            .collect();
+
+        let mut error = StreamError::Unreachable;
+        let mut best: Option<(Joints, Option<TurnCounter>, usize)> = None;
+        let solutions = self.robot.inverse_continuing(&pose, &self.previous);
+        for (i, joints) in solutions.iter().enumerate() {
+            let counter = match self.check(&self.previous, &self.counter, joints) {
+                Ok(counter) => counter,
+                Err(e) => {
+                    if i == 0 {
+                        error = e;
+                    }
+                    continue;
+                }
+            };
+            let followed = self.follow(joints, counter, &ahead);
+            if best.is_none_or(|(_, _, most)| followed > most) {
+                best = Some((*joints, counter, followed));
+            }
+            if followed == ahead.len() {
+                break;
+            }
+        }
+
+        let (joints, counter, _) = best.ok_or(error)?;
+        self.filter = filter;
+        self.previous = joints;
+        self.counter = counter;
+        Ok(joints)
+    }
+
+    /// Number of the poses followed from the joints before the first that cannot be.
+    fn follow(&self, joints: &Joints, mut counter: Option<TurnCounter>, poses: &[Pose]) -> usize {
+        let mut previous = *joints;
+        for (index, pose) in poses.iter().enumerate() {
+            let next = self.robot.inverse_continuing(pose, &previous).into_iter()
+                .find_map(|joints| {
+                    self.check(&previous, &counter, &joints).ok().map(|c| (joints, c))
+                });
+            match next {
+                Some((joints, next_counter)) => (previous, counter) = (joints, next_counter),
+                None => return index,
+            }
+        }
+        poses.len()
+    }
+
+    /// The turn counter advanced to the joints, if they pass the checks.
+    fn check(&self, previous: &Joints, counter: &Option<TurnCounter>, joints: &Joints)
+             -> Result<Option<TurnCounter>, StreamError> {
+        check_step(previous, joints, self.limits.as_ref(), self.max_joint_step)?;
+        let mut counter = *counter;
+        if let Some(counter) = &mut counter {
+            counter.advance(joints)
+                .map_err(|(joint, winding)| StreamError::OverBudget { joint, winding })?;
+        }
+        Ok(counter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_stream() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let mut stream = IkStream::new(robot.clone(), start).with_max_joint_step(0.05);
+
+        // Small steps along J1 are followed
+        for i in 1..=10 {
+            let joints = [0.1 + 0.01 * i as f64, 0.2, 0.3, 0.4, 0.5, 0.6];
+            let next = stream.next(&robot.forward(&joints)).unwrap();
+            assert!(next.iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-9));
+        }
+        let previous = stream.previous();
+
+        // Refused poses leave the session where it was
+        let jump = robot.forward(&[0.5, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        assert!(matches!(stream.next(&jump), Err(StreamError::Discontinuity { joint: 0, .. })));
+        let far = Pose::translation(10.0, 0.0, 0.0);
+        assert_eq!(stream.next(&far), Err(StreamError::Unreachable));
+        assert_eq!(stream.previous(), previous);
+
+        let limits = JointLimits::new([-1.0; 6], [0.205, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        let mut stream = IkStream::new(robot.clone(), start).with_limits(limits);
+        assert_eq!(stream.next(&robot.forward(&[0.21, 0.2, 0.3, 0.4, 0.5, 0.6])),
+                   Err(StreamError::JointLimits));
+
+        // J6 turning round and round is stopped by the budget of one turn (the continuity
+        // threshold stops the escape to the flipped wrist)
+        let mut stream = IkStream::new(robot.clone(), start).with_max_joint_step(0.6)
+            .with_turn_budget([f64::INFINITY, f64::INFINITY, f64::INFINITY, f64::INFINITY,
+                f64::INFINITY, 2.0 * std::f64::consts::PI]);
+        let mut result = Ok(start);
+        for i in 1..=20 {
+            let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6 + 0.5 * i as f64];
+            result = stream.next(&robot.forward(&joints));
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(StreamError::OverBudget { joint: 5, .. })));
+        assert!(stream.counter().unwrap().turns()[5] <= 1.0);
+
+        stream.reset(start);
+        assert_eq!(stream.counter().unwrap().turns(), [0.0; 6]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut filter = PoseFilter::new(0.5);
+        let a = Pose::translation(0.0, 0.0, 1.0);
+        let b = Pose::translation(0.1, 0.0, 1.0);
+        assert_eq!(filter.apply(&a), a);
+        assert!((filter.apply(&b).translation.x - 0.05).abs() < 1E-12);
+        assert!((filter.apply(&b).translation.x - 0.075).abs() < 1E-12);
+        filter.reset();
+        assert_eq!(filter.apply(&b), b);
+        assert!((PoseFilter::from_cutoff(1E6, 1.0).alpha - 1.0).abs() < 1E-12);
+
+        // Noise on the target is damped in the joints
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&start);
+        let mut raw = IkStream::new(robot.clone(), start);
+        let mut smooth = IkStream::new(robot, start)
+            .with_filter(PoseFilter::from_cutoff(5.0, 250.0));
+        let (mut raw_jitter, mut smooth_jitter) = (0.0f64, 0.0f64);
+        for i in 0..100 {
+            let noise = if i % 2 == 0 { 0.001 } else { -0.001 };
+            let noisy = Pose::translation(noise, -noise, noise) * pose;
+            let (r, s) = (raw.next(&noisy).unwrap(), smooth.next(&noisy).unwrap());
+            // Past the transient of the first, unfiltered, pose
+            if i >= 50 {
+                raw_jitter = raw_jitter.max((r[1] - start[1]).abs());
+                smooth_jitter = smooth_jitter.max((s[1] - start[1]).abs());
+            }
+        }
+        assert!(smooth_jitter < 0.2 * raw_jitter);
+    }
+
+    #[test]
+    fn test_look_ahead() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let start = [0.1, 0.2, 0.3, 0.4, 1.4, 0.6];
+        let poses: Vec<Pose> = (1..=5)
+            .map(|i| robot.forward(&[0.1, 0.2, 0.3, 0.4, 1.4 + 0.1 * i as f64, 0.6]))
+            .collect();
+        // J5 runs into its upper limit, the flipped wrist (J5 negated, J4 and J6 turned by pi)
+        // can be reached while J5 has not moved too far from zero yet
+        let limits = JointLimits::new([-4.0, -4.0, -4.0, -4.0, -2.0, -4.0],
+                                      [4.0, 4.0, 4.0, 4.0, 1.75, 4.0]);
+        let session = IkStream::new(robot, start).with_limits(limits)
+            .with_max_joint_step(3.3);
+
+        let mut greedy = session.clone();
+        let results: Vec<_> = poses.iter().map(|pose| greedy.next(pose)).collect();
+        assert!(results[..3].iter().all(|r| r.is_ok_and(|j| j[4] > 0.0)));
+        assert_eq!(results[3], Err(StreamError::JointLimits));
+
+        let mut ahead = session;
+        for i in 0..poses.len() {
+            let window = &poses[i + 1..(i + 4).min(poses.len())];
+            let joints = ahead.next_ahead(&poses[i], window).unwrap();
+            assert!(joints[4] < 0.0);
+        }
+        assert!((ahead.previous()[4] + 1.9).abs() < 1E-9);
+    }
+}
@@ -0,0 +1,84 @@
+//! Verifies that the real time methods of the solver do not allocate on the heap.
+//! The counting allocator only counts allocations made by the current thread, so
+//! tests running in parallel do not interfere.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_during<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|a| a.get());
+    f();
+    ALLOCATIONS.with(|a| a.get()) - before
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hint::black_box;
+    use super::*;
+    use crate::kinematic_traits::{Kinematics, JOINTS_AT_ZERO};
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::solver::Solver;
+
+    #[test]
+    fn test_real_time_methods_do_not_allocate() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        // Second pose is in J5 = 0 singularity, exercising the singularity branch.
+        for joints in [[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], [0.0, 0.1, 0.2, 0.3, 0.0, 0.5]] {
+            let allocations = allocations_during(|| {
+                let pose = black_box(robot.forward(&joints));
+                let solutions = black_box(robot.inverse_fixed(&pose));
+                assert!(!solutions.is_empty());
+                assert!(black_box(robot.inverse_iter(&pose).next()).is_some());
+                let solutions = black_box(robot.inverse_continuing_fixed(&pose, &joints));
+                assert!(!solutions.is_empty());
+                black_box(robot.inverse_continuing_fixed(&pose, &JOINTS_AT_ZERO));
+            });
+            assert_eq!(allocations, 0, "Real time methods allocated {} times", allocations);
+        }
+    }
+
+    #[test]
+    fn test_reused_solver_does_not_allocate() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let poses: Vec<_> = (0..20)
+            .map(|i| robot.forward(&[0.1, 0.2 + 0.01 * i as f64, 0.3, 0.4, 0.5, 0.6]))
+            .collect();
+        let start = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let mut solver = Solver::new(&robot);
+        // The first run sizes the path buffer.
+        assert!(solver.follow(&poses, &start).is_some());
+        let allocations = allocations_during(|| {
+            assert!(black_box(solver.follow(&poses, &start)).is_some());
+            solver.inverse_batch(&poses, |_, solutions| { black_box(solutions); });
+        });
+        assert_eq!(allocations, 0, "Reused solver allocated {} times", allocations);
+    }
+
+    #[test]
+    fn test_allocations_are_counted() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        assert!(allocations_during(|| { black_box(robot.inverse(&pose)); }) > 0);
+    }
+}
@@ -0,0 +1,138 @@
+//! Differential tests against the original C++ opw_kinematics (feature `cpp_reference`).
+//! Forward and inverse kinematics are compared over random joint sweeps for all robots from
+//! parameters_robots. The discrepancy report is printed (run with --nocapture to see it) and
+//! the test fails if any discrepancy is found.
+
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use nalgebra::Matrix4;
+use std::f64::consts::PI;
+
+extern "C" {
+    fn opw_cpp_forward(p: *const f64, offsets: *const f64, signs: *const i8,
+                       joints: *const f64, matrix: *mut f64);
+    fn opw_cpp_inverse(p: *const f64, offsets: *const f64, signs: *const i8,
+                       matrix: *const f64, solutions: *mut f64);
+}
+
+const SWEEP: usize = 100_000;
+
+struct Reference {
+    p: [f64; 7],
+    offsets: [f64; 6],
+    signs: [i8; 6],
+}
+
+impl Reference {
+    fn new(parameters: &Parameters) -> Self {
+        Reference {
+            p: [parameters.a1, parameters.a2, parameters.b, parameters.c1, parameters.c2,
+                parameters.c3, parameters.c4],
+            offsets: parameters.offsets,
+            signs: parameters.sign_corrections,
+        }
+    }
+
+    fn forward(&self, joints: &Joints) -> Matrix4<f64> {
+        let mut matrix = Matrix4::zeros();
+        unsafe {
+            opw_cpp_forward(self.p.as_ptr(), self.offsets.as_ptr(), self.signs.as_ptr(),
+                            joints.as_ptr(), matrix.as_mut_ptr());
+        }
+        matrix
+    }
+
+    /// Valid solutions, normalized into [-pi, pi] as the Rust solver does.
+    fn inverse(&self, pose: &Pose) -> Vec<Joints> {
+        let matrix = pose.to_homogeneous();
+        let mut raw = [f64::NAN; 48];
+        unsafe {
+            opw_cpp_inverse(self.p.as_ptr(), self.offsets.as_ptr(), self.signs.as_ptr(),
+                            matrix.as_ptr(), raw.as_mut_ptr());
+        }
+        raw.chunks(6)
+            .filter(|s| s.iter().all(|v| v.is_finite()))
+            .map(|s| std::array::from_fn(|j| (s[j] + PI).rem_euclid(2.0 * PI) - PI))
+            .collect()
+    }
+}
+
+/// Small deterministic generator so that the sweeps are reproducible without extra crates.
+struct XorShift(u64);
+
+impl XorShift {
+    fn angle(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64 * 2.0 * PI - PI
+    }
+}
+
+fn same_joints(a: &Joints, b: &Joints) -> bool {
+    a.iter().zip(b.iter()).all(|(x, y)| {
+        let d = (x - y).rem_euclid(2.0 * PI);
+        d.min(2.0 * PI - d) < 1E-6
+    })
+}
+
+#[derive(Default)]
+struct Report {
+    forward_mismatches: usize,
+    max_forward_error: f64,
+    missing_in_rust: usize,
+    missing_in_cpp: usize,
+}
+
+fn compare(name: &str, parameters: Parameters, report: &mut Report) {
+    let robot = OPWKinematics::new(parameters.clone());
+    let reference = Reference::new(&parameters);
+    let mut random = XorShift(0x2545F4914F6CDD1D);
+    for _ in 0..SWEEP {
+        let joints: Joints = std::array::from_fn(|_| random.angle());
+        let pose = robot.forward(&joints);
+        let error = (pose.to_homogeneous() - reference.forward(&joints)).abs().max();
+        report.max_forward_error = report.max_forward_error.max(error);
+        if error > 1E-9 {
+            report.forward_mismatches += 1;
+            println!("{}: FK mismatch {:e} for {:?}", name, error, joints);
+        }
+
+        let ours = robot.inverse(&pose);
+        let theirs = reference.inverse(&pose);
+        // The C++ version does not cross-check its solutions, only compare the verified ones
+        let theirs: Vec<Joints> = theirs.into_iter()
+            .filter(|s| (robot.forward(s).to_homogeneous() - pose.to_homogeneous()).abs().max() < 1E-6)
+            .collect();
+        for s in theirs.iter().filter(|s| !ours.iter().any(|o| same_joints(o, s))) {
+            report.missing_in_rust += 1;
+            println!("{}: C++ solution {:?} missing in Rust for {:?}", name, s, joints);
+        }
+        for s in ours.iter().filter(|s| !theirs.iter().any(|t| same_joints(t, s))) {
+            report.missing_in_cpp += 1;
+            println!("{}: Rust solution {:?} missing in C++ for {:?}", name, s, joints);
+        }
+    }
+}
+
+#[test]
+fn test_against_cpp_opw_kinematics() {
+    let robots = [
+        ("irb2400_10", Parameters::irb2400_10()),
+        ("kuka_kr6_r700_sixx", Parameters::kuka_kr6_r700_sixx()),
+        ("fanuc_r2000ib_200r", Parameters::fanuc_r2000ib_200r()),
+        ("staubli_tx40", Parameters::staubli_tx40()),
+    ];
+    let mut report = Report::default();
+    for (name, parameters) in robots {
+        compare(name, parameters, &mut report);
+    }
+    println!("Compared {} poses per robot. FK mismatches: {}, max FK error: {:e}, \
+              solutions missing in Rust: {}, missing in C++: {}",
+             SWEEP, report.forward_mismatches, report.max_forward_error,
+             report.missing_in_rust, report.missing_in_cpp);
+    assert_eq!(report.forward_mismatches, 0);
+    assert_eq!(report.missing_in_rust, 0);
+    assert_eq!(report.missing_in_cpp, 0);
+}
@@ -1 +1,8 @@
-mod testcases;
\ No newline at end of file
+#[cfg(feature = "files")]
+mod testcases;
+#[cfg(feature = "files")]
+mod serialization;
+mod allocations;
+mod thread_safety;
+#[cfg(feature = "cpp_reference")]
+mod cpp_reference;
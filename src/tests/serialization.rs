@@ -0,0 +1,50 @@
+//! Round trips of the public data types through YAML.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::branches::Branch;
+use crate::joints::JointVector;
+use crate::keep_out::{KeepOutZone, KeepOutZones};
+use crate::kinematic_traits::{FixedSolutions, Kinematics, Pose, MAX_SOLUTIONS};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    serde_yaml::from_str(&serde_yaml::to_string(value).unwrap()).unwrap()
+}
+
+#[test]
+fn test_round_trips() {
+    let parameters = Parameters::irb2400_10();
+    let restored = round_trip(&parameters);
+    assert_eq!((restored.c2, restored.offsets), (parameters.c2, parameters.offsets));
+    let restored = Parameters::from_yaml(&parameters.to_yaml().unwrap()).unwrap();
+    assert_eq!((restored.c4, restored.sign_corrections), (parameters.c4, parameters.sign_corrections));
+
+    let robot = OPWKinematics::new(parameters);
+    let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+    let pose = robot.forward(&joints);
+    let restored: Pose = round_trip(&pose);
+    assert!((restored.translation.vector - pose.translation.vector).norm() < 1E-15);
+
+    let solutions = robot.inverse_fixed(&pose);
+    assert_eq!(round_trip(&solutions).as_slice(), solutions.as_slice());
+    assert_eq!(round_trip(&robot.inverse(&pose)), solutions.to_vec());
+
+    // Newtype is serialized as the plain array
+    assert_eq!(serde_yaml::to_string(&JointVector(joints)).unwrap(),
+               serde_yaml::to_string(&joints).unwrap());
+    assert_eq!(round_trip(&Branch::ALL[5]), Branch::ALL[5]);
+
+    let zones = KeepOutZones::new(vec![KeepOutZone::HalfSpace {
+        point: nalgebra::Vector3::new(0.0, 0.0, 0.1),
+        normal: nalgebra::Vector3::new(0.0, 0.0, -1.0),
+    }]);
+    assert_eq!(round_trip(&zones).zones, zones.zones);
+}
+
+#[test]
+fn test_too_many_solutions_rejected() {
+    let yaml = serde_yaml::to_string(&vec![[0.0; 6]; MAX_SOLUTIONS + 1]).unwrap();
+    assert!(serde_yaml::from_str::<FixedSolutions>(&yaml).is_err());
+}
@@ -1,105 +1,17 @@
-use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Read;
-use serde_yaml;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Pose {
-    translation: [f64; 3],
-    quaternion: [f64; 4], // Assuming [x, y, z, w] ordering here
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Case {
-    id: i32,
-    parameters: String,
-    joints: [f64; 6],
-    solutions: Vec<[f64; 6]>,
-    pose: Pose,
-}
-
-impl Case {
-    // Method to return joints in radians
-    pub fn joints_in_radians(&self) -> [f64; 6] {
-        let mut joints_in_radians = [0.0; 6];
-        for (i, &joint) in self.joints.iter().enumerate() {
-            joints_in_radians[i] = joint.to_radians();
-        }
-        joints_in_radians
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Cases {
-    cases: Vec<Case>,
-}
-
-use nalgebra::{Isometry3, Quaternion, Translation3, UnitQuaternion};
-
-impl Pose {
-    pub fn to_isometry(&self) -> Isometry3<f64> {
-        let translation = Translation3::new(self.translation[0], self.translation[1], self.translation[2]);
-
-        // Adjusting quaternion creation to match [x, y, z, w] ordering
-        let quaternion = UnitQuaternion::from_quaternion(Quaternion::new(
-            self.quaternion[3], // w
-            self.quaternion[0], // x
-            self.quaternion[1], // y
-            self.quaternion[2], // z
-        ));
-
-        Isometry3::from_parts(translation, quaternion)
-    }
-
-    pub fn from_isometry(isometry: &Isometry3<f64>) -> Self {
-        let translation = isometry.translation.vector;
-
-        // Extract the quaternion from isometry (rotation part)
-        let quaternion = isometry.rotation.quaternion();
-
-        Pose {
-            translation: [translation.x, translation.y, translation.z],
-            quaternion: [quaternion.i, quaternion.j, quaternion.k, quaternion.w], // [x, y, z, w] ordering
-        }
-    }
-}
-
-fn load_yaml(filename: &str) -> Result<Cases, serde_yaml::Error> {
-    let mut file = File::open(filename).expect("Unable to open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("Unable to read the file");
-    serde_yaml::from_str(&contents)
-}
-
-fn are_isometries_approx_equal(a: &Isometry3<f64>, b: &Isometry3<f64>, tolerance: f64) -> bool {
-    let translation_diff = a.translation.vector - b.translation.vector;
-    if translation_diff.norm() > tolerance {
-        return false;
-    }
-
-    // Check if the rotation components are approximately equal
-    // This part is a bit more complex due to quaternion properties.
-    // One way is to calculate the angle between the two quaternions and see if it's within the tolerance.
-    // This involves converting the unit quaternion difference into an angle.
-    let rotation_diff = a.rotation.inverse() * b.rotation;
-    let angle = rotation_diff.angle();
-
-    angle.abs() <= tolerance
-}
-
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
     use std::f64::consts::PI;
     use crate::kinematic_traits::{Kinematics, Singularity, Solutions};
     use crate::parameters::opw_kinematics::Parameters;
     use crate::kinematics_impl::OPWKinematics;
-    use super::*;
+    use crate::utils::compare_poses;
+    use crate::utils::dump_solutions;
+    use crate::conformance::{case_parameters, CasePose, ConformanceSuite};
 
     #[test]
     fn test_load_yaml() {
         let filename = "src/tests/cases.yaml";
-        let result = load_yaml(filename);
+        let result = ConformanceSuite::from_file(filename);
 
         if let Err(e) = &result {
             println!("Error loading or parsing YAML file: {}", e);
@@ -115,25 +27,21 @@ mod tests {
 
     #[test]
     fn test_forward_ik() {
-        let filename = "src/tests/cases.yaml";
-        let result = load_yaml(filename);
-        assert!(result.is_ok(), "Failed to load or parse the YAML file");
-        let cases = result.expect("Expected a valid Cases struct after parsing");
-        let all_parameters = create_parameter_map();
+        let cases = ConformanceSuite::builtin().expect("Failed to parse the built-in cases");
         println!("Forward IK: {} test cases", cases.cases.len());
 
         for case in cases.cases.iter() {
-            let parameters = all_parameters.get(&case.parameters).unwrap_or_else(|| {
+            let parameters = case_parameters(&case.parameters).unwrap_or_else(|| {
                 panic!("Parameters for the robot [{}] are unknown", &case.parameters)
             });
             let kinematics = OPWKinematics::new(parameters.clone());
 
             // Try forward on the initial data set first.
             let ik = kinematics.forward(&case.joints_in_radians());
-            let pose = Pose::from_isometry(&ik);
+            let pose = CasePose::from_pose(&ik);
 
-            if !are_isometries_approx_equal(&ik, &case.pose.to_isometry(), 0.00001) {
-                println!("Seems not equal");
+            if let Err(mismatch) = compare_poses(&ik, &case.pose.to_pose(), 0.00001, 0.00001) {
+                println!("Seems not equal: {}", mismatch);
                 println!("joints: {:?} ", &case.joints);
                 println!("case: {:?} ", &pose);
                 println!("IK  : {:?} ", &case.pose);
@@ -146,15 +54,11 @@ mod tests {
 
     #[test]
     fn test_inverse_ik() {
-        let filename = "src/tests/cases.yaml";
-        let result = load_yaml(filename);
-        assert!(result.is_ok(), "Failed to load or parse the YAML file");
-        let cases = result.expect("Expected a valid Cases struct after parsing");
-        let all_parameters = create_parameter_map();
+        let cases = ConformanceSuite::builtin().expect("Failed to parse the built-in cases");
         println!("Inverse IK: {} test cases", cases.cases.len());
 
         for case in cases.cases.iter() {
-            let parameters = all_parameters.get(&case.parameters).unwrap_or_else(|| {
+            let parameters = case_parameters(&case.parameters).unwrap_or_else(|| {
                 panic!("Parameters for the robot [{}] are unknown", &case.parameters)
             });
             let kinematics = OPWKinematics::new(parameters.clone());
@@ -162,7 +66,10 @@ mod tests {
             // Exclude singularity cases that are covered by another test
             if kinematics.kinematic_singularity(&case.joints_in_radians()).is_none() {
                 // Try forward on the initial data set first.
-                let solutions = kinematics.inverse(&case.pose.to_isometry());
+                let solutions = kinematics.inverse(&case.pose.to_pose());
+                // Lazy iterator yields the same solutions
+                assert_eq!(kinematics.inverse_iter(&case.pose.to_pose()).collect::<Vec<_>>(),
+                           solutions);
                 if found_joints_approx_equal(&solutions, &case.joints_in_radians(),
                                              0.001_f64.to_radians()).is_none() {
                     println!("**** No valid solution for case {} on {} ****", case.id, case.parameters);
@@ -173,14 +80,7 @@ mod tests {
                     println!("Expected joints: [{}]", joints_str);
 
                     println!("Solutions Matrix:");
-                    for sol_idx in 0..solutions.len() {
-                        let mut row_str = String::new();
-                        for joint_idx in 0..6 {
-                            let computed = solutions[sol_idx][joint_idx];
-                            row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
-                        }
-                        println!("[{}]", row_str.trim_end());
-                    }
+                    dump_solutions(&solutions);
 
                     println!("---");
                     panic!("Inverse kinematics does not produce valid solution");
@@ -191,23 +91,19 @@ mod tests {
 
     #[test]
     fn test_inverse_ik_continuing() {
-        let filename = "src/tests/cases.yaml";
-        let result = load_yaml(filename);
-        assert!(result.is_ok(), "Failed to load or parse the YAML file");
-        let cases = result.expect("Expected a valid Cases struct after parsing");
-        let all_parameters = create_parameter_map();
+        let cases = ConformanceSuite::builtin().expect("Failed to parse the built-in cases");
         println!("Inverse IK: {} test cases", cases.cases.len());
 
         for case in cases.cases.iter() {
             if case.id != 1241 {
                 //continue;
             }
-            let parameters = all_parameters.get(&case.parameters).unwrap_or_else(|| {
+            let parameters = case_parameters(&case.parameters).unwrap_or_else(|| {
                 panic!("Parameters for the robot [{}] are unknown", &case.parameters)
             });
             let kinematics = OPWKinematics::new(parameters.clone());
             let solutions = kinematics.inverse_continuing(
-                &case.pose.to_isometry(), &case.joints_in_radians());
+                &case.pose.to_pose(), &case.joints_in_radians());
             let found_matching =
                 found_joints_approx_equal(&solutions, &case.joints_in_radians(),
                                           0.001_f64.to_radians());
@@ -221,14 +117,7 @@ mod tests {
                 println!("Expected joints: [{}]", joints_str);
 
                 println!("Solutions Matrix:");
-                for sol_idx in 0..solutions.len() {
-                    let mut row_str = String::new();
-                    for joint_idx in 0..6 {
-                        let computed = solutions[sol_idx][joint_idx];
-                        row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
-                    }
-                    println!("[{}]", row_str.trim_end());
-                }
+                dump_solutions(&solutions);
 
                 println!("---");
             }
@@ -271,10 +160,9 @@ mod tests {
         println!("Joints joints: [{}]", joints_str);
 
         println!("Solutions:");
-        for sol_idx in 0..solutions.len() {
+        for (sol_idx, solution) in solutions.iter().enumerate() {
             let mut row_str = String::new();
-            for joint_idx in 0..6 {
-                let computed = solutions[sol_idx][joint_idx];
+            for computed in solution {
                 row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
             }
             println!("{}. [{}]", sol_idx, row_str.trim_end());
@@ -288,12 +176,12 @@ mod tests {
     }
 
     fn found_joints_approx_equal(solutions: &Solutions, expected: &[f64; 6], tolerance: f64) -> Option<i32> {
-        for sol_idx in 0..solutions.len() {
+        for (sol_idx, solution) in solutions.iter().enumerate() {
             // println!("Checking solution at index {}", sol_idx);
 
             let mut solution_matches = true;
             for joint_idx in 0..6 {
-                let computed = solutions[sol_idx][joint_idx];
+                let computed = solution[joint_idx];
                 let asserted = expected[joint_idx];
 
                 let diff = (computed - asserted).abs();
@@ -313,28 +201,39 @@ mod tests {
         }
 
         println!("No matching solution found");
-        return None; // Explicitly indicate that no matching column was found
+        None // Explicitly indicate that no matching column was found
     }
 
-    fn create_parameter_map() -> HashMap<String, Parameters> {
-// Create map to get actual parameters that are not in the yaml file (maybe should be?)
-        let all_parameters: HashMap<String, Parameters> = vec![
-            (String::from("Irb2400_10"), Parameters::irb2400_10()),
-            (String::from("KukaKR6_R700_sixx"), Parameters::kuka_kr6_r700_sixx()),
-            (String::from("Fanuc_r2000ib_200r"), Parameters::fanuc_r2000ib_200r()),
-            (String::from("Staubli_tx40"), Parameters::staubli_tx40()),
-            (String::from("Irb2600_12_165"), Parameters::irb2600_12_165()),
-            (String::from("Irb4600_60_205"), Parameters::irb4600_60_205()),
-            (String::from("Staubli_tx2_140"), Parameters::staubli_tx2_140()),
-            (String::from("Staubli_tx2_160"), Parameters::staubli_tx2_160()),
-            (String::from("Staubli_tx2_160l"), Parameters::staubli_tx2_160l()),
-        ]
-            .into_iter()
-            .collect();
-        all_parameters
+    #[test]
+    fn test_conformance_suite() {
+        use crate::conformance::{Check, Tolerances};
+        let suite = ConformanceSuite::builtin().expect("Failed to load the conformance suite");
+        let report = suite.run(|p| OPWKinematics::new(p.clone()), &Tolerances::default());
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.checked, suite.cases.len());
+        assert_eq!(case_parameters("Staubli_tx2_160l").unwrap().c4,
+                   Parameters::staubli_tx2_160l().c4);
+        assert!(case_parameters("Unknown").is_none());
+
+        // Solver with wrong parameters for one robot fails its cases, other robots are skipped
+        let report = suite.run_with(|name| (name == "Irb2400_10").then(|| {
+            let mut parameters = Parameters::irb2400_10();
+            parameters.c4 += 0.001;
+            OPWKinematics::new(parameters)
+        }), &Tolerances::default());
+        assert!(report.skipped > 0 && report.checked > 0);
+        assert!(report.failures.iter().any(|f| f.check == Check::Forward));
+
+        // Generated cases pass after the compact YAML round trip
+        let generated = ConformanceSuite::generate(&Parameters::staubli_tx40(), "Tx40", 64, 7, 100);
+        let reloaded = ConformanceSuite::from_yaml(&generated.to_compact_yaml()).unwrap();
+        assert_eq!(reloaded.cases.len(), 64);
+        assert_eq!(reloaded.cases[0].id, 100);
+        let report = reloaded.run_with(|name| (name == "Tx40")
+            .then(|| OPWKinematics::new(Parameters::staubli_tx40())), &Tolerances::default());
+        assert!(report.is_ok() && report.checked == 64, "{}", report);
     }
 
-
     #[test]
     fn test_singularity_a() {
         // Assuming joint[4] close to π triggers A type singularity
@@ -349,6 +248,57 @@ mod tests {
                    Singularity::A);
     }
 
+    #[test]
+    fn test_singularity_b() {
+        let parameters = Parameters::irb2400_10();
+        let robot = OPWKinematics::new(parameters.clone());
+        // J3 putting the wrist center on the J1 axis with J2 at zero
+        let k = parameters.a2.hypot(parameters.c3);
+        let q3 = (-parameters.a1 / k).asin() - parameters.a2.atan2(parameters.c3);
+        let joints = [0.7, 0.0, q3 + parameters.offsets[2], 0.3, 0.5, 0.2];
+        assert_eq!(robot.kinematic_singularity(&joints), Some(Singularity::B));
+        let pose = robot.forward(&joints);
+
+        // J1 is kept from the previous joints, whatever it is
+        for j1 in [0.7, -0.4, 2.5] {
+            let previous = [j1, 0.0, joints[2], 0.3, 0.5, 0.2];
+            let solutions = robot.inverse_continuing(&pose, &previous);
+            assert!((solutions[0][0] - j1).abs() < 1E-9, "{:?}", solutions[0]);
+            for solution in &solutions {
+                let check = robot.forward(solution);
+                assert!((check.translation.vector - pose.translation.vector).norm() < 1E-9);
+                assert!(check.rotation.angle_to(&pose.rotation) < 1E-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_singularity_proximity() {
+        let parameters = Parameters::irb2400_10();
+        let robot = OPWKinematics::new(parameters.clone());
+        let psi3 = parameters.a2.atan2(parameters.c3);
+        let regular = robot.singularity_proximity(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        assert!((regular.wrist - 0.5f64.sin()).abs() < 1E-12);
+        assert!(regular.alignment > 0.5 && regular.elbow > 0.5);
+
+        // Proximity decreases gradually towards the wrist singularity
+        let wrist = |j5: f64| robot.singularity_proximity(&[0.1, 0.2, 0.3, 0.4, j5, 0.6]).wrist;
+        assert!(wrist(0.0) < 1E-12 && wrist(0.01) < wrist(0.1) && wrist(0.1) < wrist(0.5));
+
+        // Stretched arm
+        let stretched = [0.0, 0.3, -psi3 + parameters.offsets[2], 0.0, 0.5, 0.0];
+        assert!(robot.singularity_proximity(&stretched).elbow < 1E-12);
+
+        // Wrist center moved by J2 towards the J1 axis
+        let alignment = |j2: f64| robot.singularity_proximity(&[0.0, j2, 0.0, 0.0, 0.5, 0.0])
+            .alignment;
+        assert!(alignment(-0.1) < alignment(0.0) && alignment(0.0) < alignment(0.1));
+        let flange = robot.forward(&[0.0, 0.1, 0.0, 0.0, 0.5, 0.0]);
+        let center = flange.translation.vector
+            - parameters.c4 * (flange.rotation * nalgebra::Vector3::z());
+        assert!((alignment(0.1) - center.x.hypot(center.y)).abs() < 1E-9);
+    }
+
     #[test]
     fn test_no_singularity() {
         let robot = OPWKinematics::new(Parameters::irb2400_10());
@@ -372,6 +322,7 @@ mod tests {
             c4: 0.10,
             offsets: [0.0, 0.0, -90.0_f64.to_radians(), 0.0, 0.0, 180.0_f64.to_radians()],
             sign_corrections: [1, 1, -1, -1, -1, -1],
+            j3_coupling: 0.0,
         };
 
 
@@ -385,4 +336,34 @@ mod tests {
         assert_eq!(expected.offsets, loaded.offsets);
         assert_eq!(expected.sign_corrections, loaded.sign_corrections);
     }
+
+    #[test]
+    fn test_j3_coupling() {
+        // J3 of the controller measured against the horizontal
+        let serial = OPWKinematics::new(Parameters::irb2400_10());
+        let parameters = Parameters { j3_coupling: 1.0, ..Parameters::irb2400_10() };
+        let coupled = OPWKinematics::new(parameters.clone());
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = coupled.forward(&joints);
+        assert_eq!(parameters.coupled(&parameters.decoupled(&joints)), joints);
+        assert!(compare_poses(&pose, &serial.forward(&[0.1, 0.2, 0.1, 0.4, 0.5, 0.6]),
+                              1E-12, 1E-12).is_ok());
+        assert_eq!(coupled.configuration(&joints),
+                   serial.configuration(&parameters.decoupled(&joints)));
+
+        let solutions = coupled.inverse(&pose);
+        assert_eq!(solutions.len(), serial.inverse(&pose).len());
+        assert!(solutions.iter().all(|s| compare_poses(&coupled.forward(s), &pose, 1E-9, 1E-9)
+            .is_ok()));
+        // J2 a full turn away from the previous joints also turns the coupled J3
+        let previous = [0.1, 0.2 + 2.0 * PI, 0.3 + 2.0 * PI, 0.4, 0.5, 0.6];
+        let continuing = coupled.inverse_continuing(&pose, &previous);
+        assert!(continuing[0].iter().zip(&previous).all(|(a, b)| (a - b).abs() < 1E-9));
+
+        // Jacobian of the coupled joints: J2 column is the serial J2 minus J3
+        let expected = serial.jacobian(&parameters.decoupled(&joints));
+        let jacobian = coupled.jacobian(&joints);
+        assert!((jacobian.column(1) - (expected.column(1) - expected.column(2))).norm() < 1E-12);
+        assert!((jacobian.column(2) - expected.column(2)).norm() < 1E-12);
+    }
 }
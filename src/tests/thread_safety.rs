@@ -0,0 +1,39 @@
+//! Checks that the solvers can be shared between threads without locking.
+
+use std::sync::Arc;
+use crate::kinematic_traits::{Kinematics, Stateless};
+use crate::keep_out::{KeepOutKinematics, KeepOutZones};
+use crate::kinematics_impl::OPWKinematics;
+use crate::mounting::Base;
+use crate::parameters::opw_kinematics::Parameters;
+
+fn assert_stateless<K: Stateless>() {}
+
+#[test]
+fn test_solvers_are_stateless() {
+    assert_stateless::<OPWKinematics>();
+    assert_stateless::<KeepOutKinematics>();
+    assert_stateless::<Base<OPWKinematics>>();
+    assert_stateless::<Base<KeepOutKinematics>>();
+}
+
+#[test]
+fn test_shared_without_mutex() {
+    let robot = Arc::new(OPWKinematics::new(Parameters::irb2400_10()));
+    let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+    let pose = robot.forward(&joints);
+    let expected = robot.inverse(&pose);
+    let handles: Vec<_> = (0..4).map(|_| {
+        let robot = Arc::clone(&robot);
+        std::thread::spawn(move || robot.inverse(&pose))
+    }).collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+
+    // Clone is independent and gives the same results
+    let copy = (*robot).clone();
+    assert_eq!(copy.inverse(&pose), expected);
+    let zones = KeepOutKinematics { robot: copy, zones: KeepOutZones::default() };
+    assert_eq!(zones.clone().inverse(&pose), expected);
+}
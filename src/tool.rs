@@ -0,0 +1,280 @@
+//! Robot with the tool: the poses are of the tool center point (TCP) rather than the flange.
+//! Besides the active tool, named tools can be added, and single calls can target the flange,
+//! the active TCP or a named tool, for programs mixing tool-relative and flange-relative moves.
+//! The trajectory can be split into segments for different tools, as with the tool changer.
+//!
+//! Tools may carry their shape, approximated by spheres, to check the tool body against the
+//! keep-out zones, which only check the points of the robot itself.
+
+use std::collections::HashMap;
+use thiserror::Error;
+use nalgebra::{Matrix6, Vector3};
+use crate::keep_out::{KeepOutZone, KeepOutZones};
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Singularity, Solutions, Stateless};
+use crate::kinematics_impl::OPWKinematics;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ToolError {
+    #[error("unknown tool {0}")]
+    UnknownTool(String),
+    #[error("pose {index} of segment {segment} is not reachable")]
+    Unreachable { segment: usize, index: usize },
+}
+
+/// Tool body as the spheres (center in the flange frame, radius), meters.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToolShape {
+    pub spheres: Vec<(Vector3<f64>, f64)>,
+}
+
+impl ToolShape {
+    pub fn sphere(center: Vector3<f64>, radius: f64) -> Self {
+        ToolShape { spheres: vec![(center, radius)] }
+    }
+
+    /// Spheres covering the capsule between the two points, as the neck of the torch or the
+    /// gripper finger. The spheres overlap by at least half the radius.
+    pub fn capsule(a: Vector3<f64>, b: Vector3<f64>, radius: f64) -> Self {
+        let count = ((b - a).norm() / (0.5 * radius)).ceil().max(1.0) as usize;
+        let spheres = (0..=count)
+            .map(|i| (a + (b - a) * (i as f64 / count as f64), radius))
+            .collect();
+        ToolShape { spheres }
+    }
+
+    /// Adds the spheres of the other shape.
+    pub fn union(mut self, other: ToolShape) -> Self {
+        self.spheres.extend(other.spheres);
+        self
+    }
+
+    /// Checks if any sphere reaches into any zone, with the flange at the given pose in the
+    /// frame of the zones.
+    pub fn intersects(&self, flange: &Pose, zones: &[KeepOutZone]) -> bool {
+        self.spheres.iter().any(|(center, radius)| {
+            let center = flange.transform_point(&(*center).into()).coords;
+            zones.iter().any(|zone| zone.intersects_sphere(&center, *radius))
+        })
+    }
+}
+
+/// Frame the pose of the call refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target<'a> {
+    Flange,
+    /// TCP of the active tool.
+    Tcp,
+    /// TCP of the named tool.
+    Tool(&'a str),
+}
+
+/// Robot with the tool. Poses accepted by `inverse` and returned by `forward` are of the TCP
+/// of the active tool, joint values are not affected.
+#[derive(Debug, Clone)]
+pub struct Tool<K: Kinematics> {
+    pub robot: K,
+    /// Pose of the active TCP in the flange frame.
+    pub tool: Pose,
+    /// Shape of the active tool, if known.
+    pub shape: Option<ToolShape>,
+    tools: HashMap<String, Pose>,
+    shapes: HashMap<String, ToolShape>,
+}
+
+impl<K: Kinematics> Tool<K> {
+    /// Robot with the given TCP (pose in the flange frame) active.
+    pub fn new(robot: K, tool: Pose) -> Self {
+        Tool { robot, tool, shape: None, tools: HashMap::new(), shapes: HashMap::new() }
+    }
+
+    /// Sets the shape of the active tool.
+    pub fn with_shape(self, shape: ToolShape) -> Self {
+        Tool { shape: Some(shape), ..self }
+    }
+
+    /// Adds the named tool (TCP pose in the flange frame).
+    pub fn with_tool(mut self, name: &str, tool: Pose) -> Self {
+        self.tools.insert(name.to_string(), tool);
+        self
+    }
+
+    /// Adds the named tool with its shape.
+    pub fn with_shaped_tool(mut self, name: &str, tool: Pose, shape: ToolShape) -> Self {
+        self.shapes.insert(name.to_string(), shape);
+        self.with_tool(name, tool)
+    }
+
+    /// Makes the named tool active, with its shape if it has one.
+    pub fn select(&mut self, name: &str) -> Result<(), ToolError> {
+        self.tool = self.tool_pose(&Target::Tool(name))?;
+        self.shape = self.shapes.get(name).cloned();
+        Ok(())
+    }
+
+    /// Shape of the target: of the active or the named tool, none for the flange.
+    pub fn tool_shape(&self, target: &Target) -> Option<&ToolShape> {
+        match target {
+            Target::Flange => None,
+            Target::Tcp => self.shape.as_ref(),
+            Target::Tool(name) => self.shapes.get(*name),
+        }
+    }
+
+    /// Checks if the shape of the target reaches into the keep-out zones in this joint
+    /// position. The robot points are checked by `KeepOutZones::violated`.
+    pub fn tool_violates(&self, joints: &Joints, target: &Target, zones: &KeepOutZones) -> bool {
+        self.tool_shape(target).is_some_and(|shape| {
+            shape.intersects(&(zones.base * self.robot.forward(joints)), &zones.zones)
+        })
+    }
+
+    /// Pose of the target in the flange frame.
+    pub fn tool_pose(&self, target: &Target) -> Result<Pose, ToolError> {
+        match target {
+            Target::Flange => Ok(Pose::identity()),
+            Target::Tcp => Ok(self.tool),
+            Target::Tool(name) => self.tools.get(*name).copied()
+                .ok_or_else(|| ToolError::UnknownTool(name.to_string())),
+        }
+    }
+
+    /// Inverse kinematics for the pose of the given target.
+    pub fn inverse_to(&self, pose: &Pose, target: &Target) -> Result<Solutions, ToolError> {
+        Ok(self.robot.inverse(&(pose * self.tool_pose(target)?.inverse())))
+    }
+
+    /// Inverse kinematics continuing from the previous joints for the pose of the given target.
+    pub fn inverse_continuing_to(&self, pose: &Pose, previous: &Joints, target: &Target)
+                                 -> Result<Solutions, ToolError> {
+        Ok(self.robot.inverse_continuing(&(pose * self.tool_pose(target)?.inverse()), previous))
+    }
+
+    /// Pose of the given target.
+    pub fn forward_to(&self, joints: &Joints, target: &Target) -> Result<Pose, ToolError> {
+        Ok(self.robot.forward(joints) * self.tool_pose(target)?)
+    }
+
+    /// Joints along the trajectory made of the segments, each with the poses of its target
+    /// (as the tools changed between the segments). Each pose is solved continuing from the
+    /// joints of the previous one, starting at the given joints.
+    pub fn follow_segments(&self, start: &Joints, segments: &[(Target, &[Pose])])
+                           -> Result<Vec<Joints>, ToolError> {
+        let mut previous = *start;
+        let mut path = Vec::new();
+        for (segment, (target, poses)) in segments.iter().enumerate() {
+            let tool = self.tool_pose(target)?.inverse();
+            for (index, pose) in poses.iter().enumerate() {
+                previous = *self.robot.inverse_continuing(&(pose * tool), &previous).first()
+                    .ok_or(ToolError::Unreachable { segment, index })?;
+                path.push(previous);
+            }
+        }
+        Ok(path)
+    }
+}
+
+impl Tool<OPWKinematics> {
+    /// Jacobian of the given target (see `OPWKinematics::jacobian_at`), rows in the base frame.
+    pub fn jacobian_to(&self, joints: &Joints, target: &Target)
+                       -> Result<Matrix6<f64>, ToolError> {
+        Ok(self.robot.jacobian_at(joints, &self.tool_pose(target)?.translation.vector))
+    }
+}
+
+impl<K: Stateless> Stateless for Tool<K> {}
+
+impl<K: Kinematics> Kinematics for Tool<K> {
+    fn inverse(&self, pose: &Pose) -> Solutions {
+        self.robot.inverse(&(pose * self.tool.inverse()))
+    }
+
+    fn inverse_continuing(&self, pose: &Pose, previous: &Joints) -> Solutions {
+        self.robot.inverse_continuing(&(pose * self.tool.inverse()), previous)
+    }
+
+    fn forward(&self, qs: &Joints) -> Pose {
+        self.robot.forward(qs) * self.tool
+    }
+
+    fn kinematic_singularity(&self, qs: &Joints) -> Option<Singularity> {
+        self.robot.kinematic_singularity(qs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Translation3, UnitQuaternion};
+    use super::*;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_targets() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let gripper = Pose::from_parts(Translation3::new(0.0, 0.0, 0.2),
+                                       UnitQuaternion::identity());
+        let torch = Pose::from_parts(Translation3::new(0.05, 0.0, 0.3),
+                                     UnitQuaternion::from_euler_angles(0.0, 0.5, 0.0));
+        let mut tool = Tool::new(robot.clone(), gripper).with_tool("torch", torch);
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let flange = robot.forward(&joints);
+
+        assert_eq!(tool.forward_to(&joints, &Target::Flange).unwrap(), flange);
+        assert_eq!(tool.forward(&joints), flange * gripper);
+        let torch_pose = tool.forward_to(&joints, &Target::Tool("torch")).unwrap();
+        assert_eq!(torch_pose, flange * torch);
+
+        let near = |solutions: &Solutions| solutions.iter()
+            .any(|s| s.iter().zip(&joints).all(|(a, b)| (a - b).abs() < 1E-6));
+        assert!(near(&tool.inverse(&(flange * gripper))));
+        assert!(near(&tool.inverse_to(&flange, &Target::Flange).unwrap()));
+        assert!(near(&tool.inverse_to(&torch_pose, &Target::Tool("torch")).unwrap()));
+        assert_eq!(tool.inverse_to(&flange, &Target::Tool("laser")),
+                   Err(ToolError::UnknownTool("laser".into())));
+
+        tool.select("torch").unwrap();
+        assert!(near(&tool.inverse_continuing(&torch_pose, &joints)));
+        assert!(near(&tool.inverse_continuing_to(&torch_pose, &joints, &Target::Tcp).unwrap()));
+        assert!(tool.select("laser").is_err());
+
+        assert_eq!(tool.jacobian_to(&joints, &Target::Flange).unwrap(), robot.jacobian(&joints));
+        assert_eq!(tool.jacobian_to(&joints, &Target::Tcp).unwrap(),
+                   robot.jacobian_at(&joints, &torch.translation.vector));
+    }
+
+    #[test]
+    fn test_tool_change() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let gripper = Pose::translation(0.0, 0.0, 0.2);
+        let torch = Pose::translation(0.0, 0.0, 0.4);
+        let neck = ToolShape::capsule(Vector3::zeros(), Vector3::new(0.0, 0.0, 0.4), 0.03);
+        assert!(neck.spheres.len() > 20);
+        let mut tool = Tool::new(robot.clone(), gripper)
+            .with_shape(ToolShape::sphere(Vector3::new(0.0, 0.0, 0.1), 0.1))
+            .with_shaped_tool("torch", torch, neck);
+
+        let start = [0.0, 0.3, 0.2, 0.0, 0.8, 0.0];
+        let flange = robot.forward(&start);
+        let picks = [flange * gripper, Pose::translation(0.0, 0.01, 0.0) * flange * gripper];
+        let welds = [flange * torch, Pose::translation(0.01, 0.0, 0.0) * flange * torch];
+        let path = tool.follow_segments(&start, &[(Target::Tcp, &picks),
+            (Target::Tool("torch"), &welds)]).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(path[0].iter().zip(&start).all(|(a, b)| (a - b).abs() < 1E-9));
+        assert!(path[2].iter().zip(&start).all(|(a, b)| (a - b).abs() < 1E-9));
+        let far = [Pose::translation(10.0, 0.0, 0.0)];
+        assert_eq!(tool.follow_segments(&start, &[(Target::Tcp, &picks), (Target::Tcp, &far)]),
+                   Err(ToolError::Unreachable { segment: 1, index: 0 }));
+
+        // The plane just below the torch tip touches the torch but not the gripper
+        let tip = (flange * torch).translation.vector;
+        let normal = (flange * torch).rotation * Vector3::z();
+        let zones = KeepOutZones::new(vec![KeepOutZone::HalfSpace {
+            point: tip + normal * 0.01, normal }]);
+        assert!(!tool.tool_violates(&start, &Target::Tcp, &zones));
+        assert!(tool.tool_violates(&start, &Target::Tool("torch"), &zones));
+        assert!(!tool.tool_violates(&start, &Target::Flange, &zones));
+        tool.select("torch").unwrap();
+        assert!(tool.tool_violates(&start, &Target::Tcp, &zones));
+    }
+}
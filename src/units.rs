@@ -0,0 +1,175 @@
+//! Typed units (feature `uom`). The solver itself works with plain meters and radians; this
+//! layer takes and returns [uom](https://docs.rs/uom) quantities instead, so mixing up degrees
+//! and radians or millimeters and meters becomes a compile time error:
+//! ```
+//! use uom::si::f64::{Angle, Length};
+//! use uom::si::angle::degree;
+//! use uom::si::length::millimeter;
+//! use rs_opw_kinematics::units::{TypedKinematics, TypedParameters};
+//! use rs_opw_kinematics::kinematics_impl::OPWKinematics;
+//!
+//! let mm = |v: f64| Length::new::<millimeter>(v);
+//! let parameters = TypedParameters {
+//!     a1: mm(100.0), a2: mm(-135.0), b: mm(0.0),
+//!     c1: mm(615.0), c2: mm(705.0), c3: mm(755.0), c4: mm(85.0),
+//!     offsets: [Angle::new::<degree>(0.0); 6],
+//!     sign_corrections: [1; 6],
+//!     j3_coupling: 0.0,
+//! };
+//! let robot = OPWKinematics::new(parameters.to_parameters());
+//! let joints = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0].map(Angle::new::<degree>);
+//! let pose = robot.forward_typed(&joints);
+//! assert!(!robot.inverse_typed(&pose).is_empty());
+//! ```
+
+use uom::si::angle::radian;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f64::{Angle, AngularVelocity, Length};
+use uom::si::length::meter;
+use crate::constraints::JointLimits;
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Joint positions as angles.
+pub type TypedJoints = [Angle; 6];
+
+/// Robot parameters with typed lengths and offsets, see `Parameters` for their meaning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypedParameters {
+    pub a1: Length,
+    pub a2: Length,
+    pub b: Length,
+    pub c1: Length,
+    pub c2: Length,
+    pub c3: Length,
+    pub c4: Length,
+    pub offsets: TypedJoints,
+    pub sign_corrections: [i8; 6],
+    /// Dimensionless ratio, as `Parameters::j3_coupling`.
+    pub j3_coupling: f64,
+}
+
+impl TypedParameters {
+    /// Parameters in meters and radians, as used by the solver.
+    pub fn to_parameters(&self) -> Parameters {
+        let m = |v: Length| v.get::<meter>();
+        Parameters {
+            a1: m(self.a1),
+            a2: m(self.a2),
+            b: m(self.b),
+            c1: m(self.c1),
+            c2: m(self.c2),
+            c3: m(self.c3),
+            c4: m(self.c4),
+            offsets: joints_from_typed(&self.offsets),
+            sign_corrections: self.sign_corrections,
+            j3_coupling: self.j3_coupling,
+        }
+    }
+}
+
+impl From<&Parameters> for TypedParameters {
+    fn from(p: &Parameters) -> Self {
+        let m = Length::new::<meter>;
+        TypedParameters {
+            a1: m(p.a1),
+            a2: m(p.a2),
+            b: m(p.b),
+            c1: m(p.c1),
+            c2: m(p.c2),
+            c3: m(p.c3),
+            c4: m(p.c4),
+            offsets: joints_to_typed(&p.offsets),
+            sign_corrections: p.sign_corrections,
+            j3_coupling: p.j3_coupling,
+        }
+    }
+}
+
+/// Joint positions in radians.
+pub fn joints_from_typed(joints: &TypedJoints) -> Joints {
+    joints.map(|j| j.get::<radian>())
+}
+
+/// Joint positions from radians.
+pub fn joints_to_typed(joints: &Joints) -> TypedJoints {
+    joints.map(Angle::new::<radian>)
+}
+
+/// Position and velocity limits of the joints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypedJointLimits {
+    pub lower: TypedJoints,
+    pub upper: TypedJoints,
+    pub max_velocity: [AngularVelocity; 6],
+}
+
+impl TypedJointLimits {
+    /// Checks if all joints are within the position limits.
+    pub fn contains(&self, joints: &TypedJoints) -> bool {
+        (0..6).all(|i| joints[i] >= self.lower[i] && joints[i] <= self.upper[i])
+    }
+
+    /// Lower and upper position limits in radians.
+    pub fn position_limits(&self) -> (Joints, Joints) {
+        (joints_from_typed(&self.lower), joints_from_typed(&self.upper))
+    }
+
+    /// Maximal joint velocities in radians per second.
+    pub fn velocity_limits(&self) -> [f64; 6] {
+        self.max_velocity.map(|v| v.get::<radian_per_second>())
+    }
+}
+
+impl From<&TypedJointLimits> for JointLimits {
+    fn from(limits: &TypedJointLimits) -> Self {
+        let (min, max) = limits.position_limits();
+        JointLimits::new(min, max)
+    }
+}
+
+/// Forward and inverse kinematics with typed joints. Poses stay in meters.
+/// Implemented for all solvers.
+pub trait TypedKinematics: Kinematics {
+    fn forward_typed(&self, joints: &TypedJoints) -> Pose {
+        self.forward(&joints_from_typed(joints))
+    }
+
+    fn inverse_typed(&self, pose: &Pose) -> Vec<TypedJoints> {
+        self.inverse(pose).iter().map(joints_to_typed).collect()
+    }
+
+    fn inverse_continuing_typed(&self, pose: &Pose, previous: &TypedJoints) -> Vec<TypedJoints> {
+        self.inverse_continuing(pose, &joints_from_typed(previous)).iter()
+            .map(joints_to_typed).collect()
+    }
+}
+
+impl<K: Kinematics + ?Sized> TypedKinematics for K {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uom::si::angle::degree;
+    use uom::si::angular_velocity::degree_per_second;
+    use uom::si::length::millimeter;
+
+    #[test]
+    fn test_typed_units() {
+        let parameters = Parameters::irb2400_10();
+        let typed = TypedParameters::from(&parameters);
+        assert!((typed.c2.get::<millimeter>() - parameters.c2 * 1000.0).abs() < 1E-9);
+        assert_eq!(typed.to_parameters().c2, parameters.c2);
+
+        let limits = TypedJointLimits {
+            lower: [Angle::new::<degree>(-90.0); 6],
+            upper: [Angle::new::<degree>(90.0); 6],
+            max_velocity: [AngularVelocity::new::<degree_per_second>(180.0); 6],
+        };
+        assert!(limits.contains(&[Angle::new::<degree>(45.0); 6]));
+        assert!(!limits.contains(&[Angle::new::<degree>(100.0); 6]));
+        assert!((limits.velocity_limits()[0] - std::f64::consts::PI).abs() < 1E-12);
+        assert!((limits.position_limits().1[0] - std::f64::consts::FRAC_PI_2).abs() < 1E-12);
+        assert!(crate::constraints::JointLimits::from(&limits).contains(&[0.5; 6]));
+    }
+}
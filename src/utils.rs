@@ -1,4 +1,7 @@
-use crate::kinematic_traits::{Joints, Solutions};
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+use thiserror::Error;
+use crate::kinematic_traits::{FixedSolutions, Joints, Pose, Solutions};
 
 /// Checks the solution for validity. This is only internally needed as all returned
 /// solutions are already checked.
@@ -11,28 +14,158 @@ pub(crate) mod opw_kinematics {
     }
 }
 
+/// Angle wrapped into [-PI, PI], radians. Angles already in this range are returned unchanged
+/// (so both -PI and PI are kept), others are shifted by the multiple of 2 PI.
+pub fn wrap_to_pi(angle: f64) -> f64 {
+    if (-PI..=PI).contains(&angle) || !angle.is_finite() {
+        return angle;
+    }
+    let wrapped = (angle + PI).rem_euclid(TAU) - PI;
+    if wrapped == -PI { PI } else { wrapped }
+}
+
+/// Angle wrapped into [min, min + 2 PI), radians, like [0, 2 PI) for the min of 0.
+pub fn wrap_to_range(angle: f64, min: f64) -> f64 {
+    let wrapped = min + (angle - min).rem_euclid(TAU);
+    if wrapped >= min + TAU { min } else { wrapped }
+}
+
+/// Shortest signed rotation from `from` to `to`, within [-PI, PI], radians.
+pub fn angle_difference(to: f64, from: f64) -> f64 {
+    wrap_to_pi(to - from)
+}
+
+/// Distance between the joint positions as used by `inverse_continuing` to rank the solutions:
+/// the sum of the absolute joint differences, each wrapped into [-PI, PI] so that the
+/// positions differing by the full turn of some joint are the same. Radians.
+pub fn joint_distance(a: &Joints, b: &Joints) -> f64 {
+    weighted_joint_distance(a, b, &[1.0; 6])
+}
+
+/// As `joint_distance`, with the difference of each joint multiplied by its weight (like
+/// the higher weights for the heavier axes).
+pub fn weighted_joint_distance(a: &Joints, b: &Joints, weights: &[f64; 6]) -> f64 {
+    a.iter().zip(b).zip(weights).map(|((a, b), w)| w * angle_difference(*a, *b).abs()).sum()
+}
+
+/// Normalizes the angle `now` to be as close as possible to `prev`
+///
+/// # Arguments
+///
+/// * `now` - A mutable reference to the angle to be normalized, radians
+/// * `prev` - The reference angle, radians
+pub(crate) fn normalize_near(now: &mut f64, must_be_near: f64) {
+    let two_pi = 2.0 * PI;
+
+    fn adjust(now: &mut f64, prev: f64, two_pi: f64) {
+        if (*now - prev).abs() > ((*now - two_pi) - prev).abs() {
+            *now -= two_pi;
+        }
+        if (*now - prev).abs() > ((*now + two_pi) - prev).abs() {
+            *now += two_pi;
+        }
+        // Handle case -pi and pi that are identical angles
+        if (*now).abs() == PI && (prev.signum() != (*now).signum()) {
+            *now = -*now;
+        }
+    }
+
+    // Perform the adjustment potentially twice to ensure minimum difference
+    adjust(now, must_be_near, two_pi);
+    adjust(now, must_be_near, two_pi);
+}
+
+/// Sorts the solutions by closeness to the `previous` joint.
+/// Joints must be pre-normalized to be as close as possible, not away by 360 degrees
+/// This is a stable insertion sort: there are at most 9 solutions and, unlike slice::sort_by,
+/// it is guaranteed not to allocate.
+pub(crate) fn sort_by_closeness(solutions: &mut [Joints], previous: &Joints) {
+    for i in 1..solutions.len() {
+        let mut j = i;
+        while j > 0 && joint_distance(&solutions[j], previous) <
+            joint_distance(&solutions[j - 1], previous) {
+            solutions.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Difference between the poses that exceeds the tolerance of `compare_poses`.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+#[error("poses differ by {translation} m in translation and {rotation} rad in rotation")]
+pub struct PoseMismatch {
+    /// Distance between the translations, meters.
+    pub translation: f64,
+    /// Angle of the rotation between the orientations, radians.
+    pub rotation: f64,
+}
+
+/// Compares the poses: they match if the translations are within `translation_tolerance`
+/// meters and the orientations within `angular_tolerance` radians. Otherwise the error
+/// gives both differences.
+pub fn compare_poses(actual: &Pose, expected: &Pose, translation_tolerance: f64,
+                     angular_tolerance: f64) -> Result<(), PoseMismatch> {
+    let translation = (actual.translation.vector - expected.translation.vector).norm();
+    let rotation = actual.rotation.angle_to(&expected.rotation);
+    if translation <= translation_tolerance && rotation <= angular_tolerance {
+        return Ok(());
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(translation, rotation, "pose mismatch");
+    Err(PoseMismatch { translation, rotation })
+}
+
+/// Displays the solutions as the table in degrees, one solution per row, with the columns
+/// aligned. The precision (2 decimal places by default) can be set with `precision` or in the
+/// format string, like `{:.4}`.
+pub struct SolutionsTable<'a> {
+    solutions: &'a [Joints],
+    precision: usize,
+}
+
+impl<'a> SolutionsTable<'a> {
+    pub fn new(solutions: &'a [Joints]) -> Self {
+        SolutionsTable { solutions, precision: 2 }
+    }
+
+    pub fn precision(self, precision: usize) -> Self {
+        SolutionsTable { precision, ..self }
+    }
+}
+
+impl fmt::Display for SolutionsTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(self.precision);
+        let cell = |value: f64| format!("{:.*}", precision, value.to_degrees());
+        let width = self.solutions.iter().flatten().map(|v| cell(*v).len()).max().unwrap_or(0);
+        for (i, solution) in self.solutions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let row: Vec<String> = solution.iter()
+                .map(|v| format!("{:>width$}", cell(*v), width = width)).collect();
+            write!(f, "[{}]", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FixedSolutions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&SolutionsTable::new(self), f)
+    }
+}
+
 /// Print joint values for all solutions, converting radianst to degrees.
 #[allow(dead_code)]
 pub fn dump_solutions(solutions: &Solutions) {
-    for sol_idx in 0..solutions.len() {
-        let mut row_str = String::new();
-        for joint_idx in 0..6 {
-            let computed = solutions[sol_idx][joint_idx];
-            row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
-        }
-        println!("[{}]", row_str.trim_end());
-    }
+    println!("{}", SolutionsTable::new(solutions));
 }
 
 /// Print joint values, converting radianst to degrees.
 #[allow(dead_code)]
 pub fn dump_joints(joints: &Joints) {
-    let mut row_str = String::new();
-    for joint_idx in 0..6 {
-        let computed = joints[joint_idx];
-        row_str.push_str(&format!("{:5.2} ", computed.to_degrees()));
-    }
-    println!("[{}]", row_str.trim_end());
+    println!("{}", SolutionsTable::new(std::slice::from_ref(joints)));
 }
 
 #[cfg(test)]
@@ -57,4 +190,59 @@ mod tests {
         let qs = [0.0, f64::INFINITY, 1.0, -1.0, 0.5, -0.5];
         assert!(!is_valid(&qs));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_solutions_table() {
+        use crate::utils::SolutionsTable;
+        let solutions = vec![[0.0, PI / 2.0, -PI, 0.1, 0.2, 0.3], [PI; 6]];
+        let table = SolutionsTable::new(&solutions).to_string();
+        assert_eq!(table.lines().next(),
+                   Some("[   0.00   90.00 -180.00    5.73   11.46   17.19]"));
+        assert_eq!(table.lines().count(), 2);
+        assert_eq!(format!("{:.0}", SolutionsTable::new(&solutions[1..])),
+                   "[180 180 180 180 180 180]");
+        assert_eq!(SolutionsTable::new(&[]).to_string(), "");
+    }
+
+    #[test]
+    fn test_angle_wrapping() {
+        use crate::utils::{angle_difference, wrap_to_pi, wrap_to_range};
+        let close = |a: f64, b: f64| (a - b).abs() < 1E-12;
+        assert_eq!(wrap_to_pi(-PI), -PI);
+        assert_eq!(wrap_to_pi(0.5), 0.5);
+        assert!(close(wrap_to_pi(3.0 * PI), PI));
+        assert!(close(wrap_to_pi(-PI - 0.1), PI - 0.1));
+        assert!(close(wrap_to_pi(7.0), 7.0 - 2.0 * PI));
+        assert!(close(wrap_to_range(-0.5, 0.0), 2.0 * PI - 0.5));
+        assert!(close(wrap_to_range(5.0 * PI, -1.0), PI));
+        assert_eq!(wrap_to_range(0.0, 0.0), 0.0);
+        assert!(close(angle_difference(-PI + 0.1, PI - 0.1), 0.2));
+        assert!(close(angle_difference(0.1, 0.3), -0.2));
+        assert!(wrap_to_pi(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_joint_distance() {
+        use crate::utils::{joint_distance, weighted_joint_distance};
+        let a = [0.0, 0.1, 0.2, PI - 0.1, 0.0, 0.0];
+        let b = [0.0, 0.2, 0.2, -PI + 0.1, 0.0, 2.0 * PI];
+        assert!((joint_distance(&a, &b) - 0.3).abs() < 1E-12);
+        let weights = [1.0, 10.0, 1.0, 1.0, 1.0, 1.0];
+        assert!((weighted_joint_distance(&a, &b, &weights) - 1.2).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_compare_poses() {
+        use nalgebra::{Translation3, UnitQuaternion};
+        use crate::kinematic_traits::Pose;
+        use crate::utils::{compare_poses, PoseMismatch};
+        let a = Pose::from_parts(Translation3::new(1.0, 2.0, 3.0),
+                                 UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3));
+        let b = Pose::from_parts(Translation3::new(1.0, 2.0, 3.001),
+                                 UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3));
+        assert!(compare_poses(&a, &a, 1E-12, 1E-9).is_ok());
+        assert!(compare_poses(&a, &b, 0.01, 1E-9).is_ok());
+        let PoseMismatch { translation, rotation } = compare_poses(&a, &b, 1E-4, 0.1).unwrap_err();
+        assert!((translation - 0.001).abs() < 1E-9 && rotation < 1E-9);
+    }
+}
@@ -0,0 +1,147 @@
+//! Validation of the input poses. `UnitQuaternion` can still hold non-unit or non-finite values
+//! if it was built unchecked or deserialized, and poses from vision systems often come as raw
+//! quaternion components that are only approximately normalized. Such poses produce garbage
+//! solutions silently, so the checked methods here reject them (or fix the small deviations).
+
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use thiserror::Error;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::parameters::opw_kinematics::Parameters;
+
+/// Default tolerance for the quaternion norm deviation from 1 that is silently normalized.
+pub const QUATERNION_NORM_TOLERANCE: f64 = 1E-3;
+
+/// Defines the pose validation error
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum PoseError {
+    #[error("pose contains NaN or infinite values")]
+    NotFinite,
+    #[error("quaternion norm {0} is too far from 1")]
+    NotUnitQuaternion(f64),
+    #[error("last row of the homogeneous matrix is not [0, 0, 0, 1]")]
+    NotHomogeneous,
+}
+
+/// Defines the robot parameters validation error
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum ParametersValidationError {
+    #[error("parameter {0} is NaN or infinite")]
+    NotFinite(&'static str),
+    #[error("sign correction of J{0} is not 1 or -1")]
+    InvalidSignCorrection(usize),
+    #[error("parameter {0} must be positive")]
+    NotPositive(&'static str),
+}
+
+/// Checks the robot parameters for the values the solver cannot work with: non-finite values,
+/// sign corrections other than 1 or -1, and zero upper arm or forearm length.
+pub fn validate_parameters(parameters: &Parameters) -> Result<(), ParametersValidationError> {
+    let p = parameters;
+    let lengths = [("a1", p.a1), ("a2", p.a2), ("b", p.b), ("c1", p.c1), ("c2", p.c2), ("c3", p.c3),
+        ("c4", p.c4)];
+    if let Some((name, _)) = lengths.iter().find(|(_, value)| !value.is_finite()) {
+        return Err(ParametersValidationError::NotFinite(name));
+    }
+    if !p.offsets.iter().all(|v| v.is_finite()) {
+        return Err(ParametersValidationError::NotFinite("offsets"));
+    }
+    if !p.j3_coupling.is_finite() {
+        return Err(ParametersValidationError::NotFinite("j3_coupling"));
+    }
+    if let Some(i) = p.sign_corrections.iter().position(|s| !matches!(s, 1 | -1)) {
+        return Err(ParametersValidationError::InvalidSignCorrection(i + 1));
+    }
+    if p.c2 <= 0.0 {
+        return Err(ParametersValidationError::NotPositive("c2"));
+    }
+    if p.a2.hypot(p.c3) <= 0.0 {
+        return Err(ParametersValidationError::NotPositive("c3"));
+    }
+    Ok(())
+}
+
+/// Checks the pose for NaN and infinite values and for the quaternion norm. The quaternion is
+/// normalized if its norm differs from 1 by at most the tolerance, otherwise this is an error.
+pub fn validate_pose(pose: &Pose, tolerance: f64) -> Result<Pose, PoseError> {
+    let q = pose.rotation.quaternion();
+    let t = pose.translation.vector;
+    if !(t.iter().all(|v| v.is_finite()) && q.coords.iter().all(|v| v.is_finite())) {
+        return Err(PoseError::NotFinite);
+    }
+    let norm = q.norm();
+    if (norm - 1.0).abs() > tolerance {
+        return Err(PoseError::NotUnitQuaternion(norm));
+    }
+    Ok(Pose::from_parts(pose.translation, UnitQuaternion::new_unchecked(q / norm)))
+}
+
+/// Builds the pose from raw values: translation x, y, z and quaternion x, y, z, w (the order
+/// used by ROS), validated as by `validate_pose` with the default tolerance.
+pub fn pose_from_raw(translation: [f64; 3], quaternion: [f64; 4]) -> Result<Pose, PoseError> {
+    let [x, y, z, w] = quaternion;
+    let pose = Pose::from_parts(
+        Translation3::new(translation[0], translation[1], translation[2]),
+        UnitQuaternion::new_unchecked(Quaternion::new(w, x, y, z)));
+    validate_pose(&pose, QUATERNION_NORM_TOLERANCE)
+}
+
+/// Inverse kinematics with the input pose validated first (default tolerance).
+/// Implemented for all solvers.
+pub trait CheckedKinematics: Kinematics {
+    fn inverse_checked(&self, pose: &Pose) -> Result<Solutions, PoseError> {
+        Ok(self.inverse(&validate_pose(pose, QUATERNION_NORM_TOLERANCE)?))
+    }
+
+    fn inverse_continuing_checked(&self, pose: &Pose, previous: &Joints)
+                                  -> Result<Solutions, PoseError> {
+        Ok(self.inverse_continuing(&validate_pose(pose, QUATERNION_NORM_TOLERANCE)?, previous))
+    }
+}
+
+impl<K: Kinematics + ?Sized> CheckedKinematics for K {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_pose_validation() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let pose = robot.forward(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let q = pose.rotation.quaternion();
+        let t = pose.translation.vector;
+
+        // Slightly denormalized quaternion (as from vision) is fixed
+        let s = 1.0005;
+        let raw = pose_from_raw([t.x, t.y, t.z], [q.i * s, q.j * s, q.k * s, q.w * s]).unwrap();
+        assert!((raw.rotation.quaternion().norm() - 1.0).abs() < 1E-12);
+        assert_eq!(robot.inverse_checked(&raw).unwrap().len(), robot.inverse(&pose).len());
+
+        let s = 1.1;
+        assert!(matches!(pose_from_raw([t.x, t.y, t.z], [q.i * s, q.j * s, q.k * s, q.w * s]),
+            Err(PoseError::NotUnitQuaternion(_))));
+        assert_eq!(pose_from_raw([f64::NAN, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]),
+                   Err(PoseError::NotFinite));
+
+        let mut broken = pose;
+        broken.translation.x = f64::INFINITY;
+        assert_eq!(robot.inverse_checked(&broken), Err(PoseError::NotFinite));
+    }
+
+    #[test]
+    fn test_parameters_validation() {
+        assert_eq!(validate_parameters(&Parameters::irb2400_10()), Ok(()));
+        let mut parameters = Parameters::staubli_tx40();
+        parameters.sign_corrections[4] = 0;
+        assert_eq!(validate_parameters(&parameters),
+                   Err(ParametersValidationError::InvalidSignCorrection(5)));
+        parameters.sign_corrections[4] = i8::MIN;
+        assert_eq!(validate_parameters(&parameters),
+                   Err(ParametersValidationError::InvalidSignCorrection(5)));
+        parameters.c1 = f64::NAN;
+        assert_eq!(validate_parameters(&parameters), Err(ParametersValidationError::NotFinite("c1")));
+        assert_eq!(validate_parameters(&Parameters::new()), Err(ParametersValidationError::NotPositive("c2")));
+    }
+}
@@ -0,0 +1,117 @@
+//! Poses in the formats shown on the teach pendants of the robot vendors, so the values can be
+//! copied as they are. Positions are in millimeters and angles in degrees, as on the pendants;
+//! the poses on the solver side are in meters and radians.
+//! - ABB: x, y, z and quaternion q1, q2, q3, q4 (scalar first),
+//! - KUKA: X, Y, Z, A, B, C, rotations about Z, then the new Y, then the new X,
+//! - Fanuc: X, Y, Z, W, P, R, rotations about the fixed X, Y and Z axes,
+//! - Stäubli: X, Y, Z, Rx, Ry, Rz, rotations about X, then the new Y, then the new Z.
+
+use nalgebra::{Quaternion, Translation3, UnitQuaternion};
+use crate::euler::{euler_from_rotation, rotation_from_euler, EulerFrame, EulerSequence};
+use crate::kinematic_traits::Pose;
+
+const MM: f64 = 0.001;
+
+fn translation_from_mm(x: f64, y: f64, z: f64) -> Translation3<f64> {
+    Translation3::new(x * MM, y * MM, z * MM)
+}
+
+fn translation_to_mm(pose: &Pose) -> [f64; 3] {
+    let t = pose.translation.vector / MM;
+    [t.x, t.y, t.z]
+}
+
+fn pose_from_euler_degrees(values: &[f64; 6], sequence: EulerSequence, frame: EulerFrame) -> Pose {
+    let [x, y, z, a, b, c] = *values;
+    let angles = [a.to_radians(), b.to_radians(), c.to_radians()];
+    Pose::from_parts(translation_from_mm(x, y, z), rotation_from_euler(angles, sequence, frame))
+}
+
+fn pose_to_euler_degrees(pose: &Pose, sequence: EulerSequence, frame: EulerFrame) -> [f64; 6] {
+    let [x, y, z] = translation_to_mm(pose);
+    let [a, b, c] = euler_from_rotation(&pose.rotation, sequence, frame);
+    [x, y, z, a.to_degrees(), b.to_degrees(), c.to_degrees()]
+}
+
+/// Pose from the ABB robtarget position (mm) and orientation (q1 to q4, q1 is the scalar part).
+/// The quaternion is normalized, as the pendant shows it rounded.
+pub fn pose_from_abb(position: [f64; 3], orientation: [f64; 4]) -> Pose {
+    let [q1, q2, q3, q4] = orientation;
+    Pose::from_parts(translation_from_mm(position[0], position[1], position[2]),
+                     UnitQuaternion::from_quaternion(Quaternion::new(q1, q2, q3, q4)))
+}
+
+/// ABB robtarget position (mm) and orientation (q1 to q4) of the pose, with q1 not negative.
+pub fn pose_to_abb(pose: &Pose) -> ([f64; 3], [f64; 4]) {
+    let q = pose.rotation.quaternion();
+    let q = if q.w < 0.0 { -q } else { *q };
+    (translation_to_mm(pose), [q.w, q.i, q.j, q.k])
+}
+
+/// Pose from KUKA X, Y, Z (mm), A, B, C (degrees).
+pub fn pose_from_kuka(values: [f64; 6]) -> Pose {
+    pose_from_euler_degrees(&values, EulerSequence::ZYX, EulerFrame::Intrinsic)
+}
+
+/// KUKA X, Y, Z (mm), A, B, C (degrees) of the pose.
+pub fn pose_to_kuka(pose: &Pose) -> [f64; 6] {
+    pose_to_euler_degrees(pose, EulerSequence::ZYX, EulerFrame::Intrinsic)
+}
+
+/// Pose from Fanuc X, Y, Z (mm), W, P, R (degrees).
+pub fn pose_from_fanuc(values: [f64; 6]) -> Pose {
+    pose_from_euler_degrees(&values, EulerSequence::XYZ, EulerFrame::Extrinsic)
+}
+
+/// Fanuc X, Y, Z (mm), W, P, R (degrees) of the pose.
+pub fn pose_to_fanuc(pose: &Pose) -> [f64; 6] {
+    pose_to_euler_degrees(pose, EulerSequence::XYZ, EulerFrame::Extrinsic)
+}
+
+/// Pose from Stäubli X, Y, Z (mm), Rx, Ry, Rz (degrees).
+pub fn pose_from_staubli(values: [f64; 6]) -> Pose {
+    pose_from_euler_degrees(&values, EulerSequence::XYZ, EulerFrame::Intrinsic)
+}
+
+/// Stäubli X, Y, Z (mm), Rx, Ry, Rz (degrees) of the pose.
+pub fn pose_to_staubli(pose: &Pose) -> [f64; 6] {
+    pose_to_euler_degrees(pose, EulerSequence::XYZ, EulerFrame::Intrinsic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+
+    fn assert_same(a: &Pose, b: &Pose) {
+        assert!((a.translation.vector - b.translation.vector).norm() < 1E-12);
+        assert!(a.rotation.angle_to(&b.rotation) < 1E-12);
+    }
+
+    #[test]
+    fn test_vendor_formats() {
+        // The same pose: 90 degrees about Z, then 30 degrees about the new Y
+        let expected = Pose::from_parts(
+            Translation3::new(0.5, -0.2, 0.8),
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 90f64.to_radians()) *
+                UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 30f64.to_radians()));
+
+        let kuka = pose_to_kuka(&expected);
+        assert!((kuka[3] - 90.0).abs() < 1E-9 && (kuka[4] - 30.0).abs() < 1E-9 && kuka[5].abs() < 1E-9);
+        assert_same(&pose_from_kuka([500.0, -200.0, 800.0, 90.0, 30.0, 0.0]), &expected);
+
+        // Fixed axes X, Y, Z: W and P, R are the same as C, B, A of KUKA
+        assert_same(&pose_from_fanuc([500.0, -200.0, 800.0, 0.0, 30.0, 90.0]), &expected);
+        let fanuc = pose_to_fanuc(&expected);
+        assert!(fanuc[3].abs() < 1E-9 && (fanuc[4] - 30.0).abs() < 1E-9 && (fanuc[5] - 90.0).abs() < 1E-9);
+
+        let staubli = pose_to_staubli(&expected);
+        assert_same(&pose_from_staubli(staubli), &expected);
+
+        let (position, orientation) = pose_to_abb(&expected);
+        assert!(orientation[0] >= 0.0);
+        assert_same(&pose_from_abb(position, orientation), &expected);
+        let identity = pose_from_abb([0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0]);
+        assert_same(&identity, &Pose::identity());
+    }
+}
@@ -0,0 +1,99 @@
+//! JavaScript bindings (feature `wasm`), built with wasm-bindgen. All values are plain arrays
+//! (Float64Array on the JavaScript side):
+//! - joints are 6 values in radians,
+//! - poses are 7 values: translation x, y, z and quaternion x, y, z, w, rejected if not finite
+//!   or the quaternion is far from unit,
+//! - solutions are returned flattened, 6 values per solution.
+
+use wasm_bindgen::prelude::*;
+use crate::kinematic_traits::{Joints, Kinematics, Pose, Solutions};
+use crate::kinematics_impl::OPWKinematics;
+use crate::parameters::opw_kinematics::Parameters;
+use crate::validation::{pose_from_raw, validate_parameters};
+
+/// Robot kinematics exported to JavaScript.
+#[wasm_bindgen]
+pub struct Robot {
+    robot: OPWKinematics,
+}
+
+#[wasm_bindgen]
+impl Robot {
+    /// Robot with the given OPW parameters, 6 joint offsets (radians) and 6 sign corrections,
+    /// rejected if `validate_parameters` fails.
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(a1: f64, a2: f64, b: f64, c1: f64, c2: f64, c3: f64, c4: f64,
+               offsets: &[f64], sign_corrections: &[i8]) -> Result<Robot, JsError> {
+        let offsets: [f64; 6] = offsets.try_into()
+            .map_err(|_| JsError::new("offsets must have 6 values"))?;
+        let sign_corrections: [i8; 6] = sign_corrections.try_into()
+            .map_err(|_| JsError::new("sign corrections must have 6 values"))?;
+        let parameters = Parameters { a1, a2, b, c1, c2, c3, c4, offsets, sign_corrections,
+            ..Parameters::new() };
+        validate_parameters(&parameters).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Robot { robot: OPWKinematics::new(parameters) })
+    }
+
+    /// Robot from the built-in preset, by the name of the preset function
+    /// (like "irb2400_10" or "kuka_kr6_r700_sixx").
+    pub fn preset(name: &str) -> Result<Robot, JsError> {
+        let parameters = Parameters::preset(name)
+            .ok_or_else(|| JsError::new(&format!("unknown robot preset {}", name)))?;
+        Ok(Robot { robot: OPWKinematics::new(parameters) })
+    }
+
+    /// Pose of the flange for the given joints.
+    pub fn forward(&self, joints: &[f64]) -> Result<Vec<f64>, JsError> {
+        Ok(pose_to_array(&self.robot.forward(&to_joints(joints)?)).to_vec())
+    }
+
+    /// All solutions for the pose, flattened.
+    pub fn inverse(&self, pose: &[f64]) -> Result<Vec<f64>, JsError> {
+        Ok(flatten(&self.robot.inverse(&to_pose(pose)?)))
+    }
+
+    /// All solutions for the pose, closest to the previous joints first, flattened.
+    #[wasm_bindgen(js_name = inverseContinuing)]
+    pub fn inverse_continuing(&self, pose: &[f64], previous: &[f64]) -> Result<Vec<f64>, JsError> {
+        Ok(flatten(&self.robot.inverse_continuing(&to_pose(pose)?, &to_joints(previous)?)))
+    }
+}
+
+fn to_joints(values: &[f64]) -> Result<Joints, JsError> {
+    values.try_into().map_err(|_| JsError::new("joints must have 6 values"))
+}
+
+fn to_pose(values: &[f64]) -> Result<Pose, JsError> {
+    let [x, y, z, qx, qy, qz, qw]: [f64; 7] = values.try_into()
+        .map_err(|_| JsError::new("pose must have 7 values: x, y, z, qx, qy, qz, qw"))?;
+    pose_from_raw([x, y, z], [qx, qy, qz, qw]).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn pose_to_array(pose: &Pose) -> [f64; 7] {
+    let t = pose.translation.vector;
+    let q = pose.rotation.quaternion();
+    [t.x, t.y, t.z, q.i, q.j, q.k, q.w]
+}
+
+fn flatten(solutions: &Solutions) -> Vec<f64> {
+    solutions.iter().flatten().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let robot = Robot::preset("irb2400_10").ok().unwrap();
+        let joints = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let pose = robot.forward(&joints).ok().unwrap();
+        assert_eq!(pose.len(), 7);
+        let solutions = robot.inverse_continuing(&pose, &joints).ok().unwrap();
+        assert_eq!(solutions.len() % 6, 0);
+        for (a, b) in solutions[..6].iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1E-6);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Workcell with the external axes: the robot carried by the linear rail (or any chain of the
+//! linear and rotary axes) and the part held by the positioner. The arm and the external axes
+//! form the redundant system; for a given pose of the flange relative to the part, the
+//! external axes are chosen to minimize the weighted motion of all axes from the previous
+//! state, and the arm is solved analytically for them. The weights say which axes should
+//! rather move: a heavy weight keeps the axis still, as for the slow rail.
+
+use nalgebra::{Translation3, Unit, UnitQuaternion, Vector3};
+use crate::kinematic_traits::{Joints, Kinematics, Pose};
+use crate::utils::angle_difference;
+
+/// Bound of the refinement steps, it normally ends much earlier when the step gets small.
+const MAX_ITERATIONS: usize = 1000;
+
+/// Largest grid of the coarse search (samples to the power of the number of external axes).
+/// Beyond it, the axes are scanned one at a time instead, see `Workcell::inverse`.
+pub const MAX_GRID_SAMPLES: usize = 100_000;
+
+/// Motion of the external axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AxisKind {
+    /// Translation along the axis, meters.
+    Linear,
+    /// Rotation about the axis, radians.
+    Rotary,
+}
+
+/// External axis: the motion along or about the unit axis, in the frame of its origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExternalAxis {
+    pub kind: AxisKind,
+    /// Pose of the axis at zero in the frame of the previous axis (or the world).
+    pub origin: Pose,
+    pub axis: Unit<Vector3<f64>>,
+    pub min: f64,
+    pub max: f64,
+    /// Weight of the motion of this axis, against the arm joints weighted 1 by default.
+    pub weight: f64,
+}
+
+impl ExternalAxis {
+    pub fn linear(origin: Pose, axis: Vector3<f64>, min: f64, max: f64) -> Self {
+        ExternalAxis { kind: AxisKind::Linear, origin, axis: Unit::new_normalize(axis), min, max,
+            weight: 1.0 }
+    }
+
+    pub fn rotary(origin: Pose, axis: Vector3<f64>, min: f64, max: f64) -> Self {
+        ExternalAxis { kind: AxisKind::Rotary, origin, axis: Unit::new_normalize(axis), min, max,
+            weight: 1.0 }
+    }
+
+    pub fn with_weight(self, weight: f64) -> Self {
+        ExternalAxis { weight, ..self }
+    }
+
+    /// Pose of the axis frame at the given value, in the frame of the previous axis.
+    pub fn transform(&self, value: f64) -> Pose {
+        self.origin * match self.kind {
+            AxisKind::Linear => Pose::from_parts(Translation3::from(self.axis.scale(value)),
+                                                 UnitQuaternion::identity()),
+            AxisKind::Rotary => Pose::from_parts(Translation3::identity(),
+                                                 UnitQuaternion::from_axis_angle(&self.axis, value)),
+        }
+    }
+}
+
+/// Values of all axes of the workcell.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CellJoints {
+    /// Values of the carrier axes, then of the positioner axes.
+    pub external: Vec<f64>,
+    pub joints: Joints,
+}
+
+/// Robot with the external axes, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct Workcell<K: Kinematics> {
+    pub robot: K,
+    /// Axes carrying the robot, from the world on.
+    pub carriers: Vec<ExternalAxis>,
+    /// Pose of the robot base on the last carrier axis (or in the world).
+    pub mount: Pose,
+    /// Axes holding the part, from the world on. Poses are given in the frame of the last one
+    /// (or in the world, if there are none).
+    pub positioners: Vec<ExternalAxis>,
+    /// Weights of the motion of the arm joints.
+    pub joint_weights: [f64; 6],
+    /// Samples per external axis of the coarse search, see `with_samples`.
+    samples: usize,
+}
+
+impl<K: Kinematics> Workcell<K> {
+    /// Robot mounted at the given pose in the world, no external axes yet.
+    pub fn new(robot: K, mount: Pose) -> Self {
+        Workcell { robot, carriers: Vec::new(), mount, positioners: Vec::new(),
+            joint_weights: [1.0; 6], samples: 9 }
+    }
+
+    /// Adds the carrier axis after the previous ones (the robot then mounted on it).
+    pub fn with_carrier(mut self, axis: ExternalAxis) -> Self {
+        self.carriers.push(axis);
+        self
+    }
+
+    /// Adds the positioner axis after the previous ones.
+    pub fn with_positioner(mut self, axis: ExternalAxis) -> Self {
+        self.positioners.push(axis);
+        self
+    }
+
+    pub fn with_joint_weights(self, joint_weights: [f64; 6]) -> Self {
+        Workcell { joint_weights, ..self }
+    }
+
+    /// Samples of each external axis range in the coarse search that precedes the local
+    /// refinement, 9 by default. More samples find the better optimum at the cost of samples
+    /// to the power of the number of axes inverse kinematics calls, see `inverse`.
+    pub fn with_samples(self, samples: usize) -> Self {
+        Workcell { samples: samples.max(2), ..self }
+    }
+
+    fn axes(&self) -> impl Iterator<Item=&ExternalAxis> {
+        self.carriers.iter().chain(&self.positioners)
+    }
+
+    /// Pose of the robot base in the world for the external axis values.
+    pub fn base(&self, external: &[f64]) -> Pose {
+        chain(&self.carriers, external) * self.mount
+    }
+
+    /// Pose of the part frame in the world for the external axis values.
+    pub fn part(&self, external: &[f64]) -> Pose {
+        chain(&self.positioners, &external[self.carriers.len().min(external.len())..])
+    }
+
+    /// Pose of the flange in the part frame.
+    pub fn forward(&self, state: &CellJoints) -> Pose {
+        self.part(&state.external).inverse() * self.base(&state.external)
+            * self.robot.forward(&state.joints)
+    }
+
+    /// All axes for the flange pose in the part frame, with the least weighted motion from
+    /// the previous state (found numerically over the external axes). None if the pose cannot
+    /// be reached within the external axis ranges.
+    ///
+    /// The search solves the arm samples^n times for the grid of n external axes (729 with
+    /// the default 9 samples and three axes), then 2n times per refinement step. The steps
+    /// are bounded by MAX_ITERATIONS (1000) but normally end after a few dozen, once the
+    /// step is below 1E-9 of the axis range. If the grid would exceed `MAX_GRID_SAMPLES`,
+    /// each axis is scanned alone (samples times n solutions) with the others at their
+    /// previous values, which may miss the optimum the full grid would find.
+    pub fn inverse(&self, pose: &Pose, previous: &CellJoints) -> Option<CellJoints> {
+        let axes: Vec<&ExternalAxis> = self.axes().collect();
+        let mut best = self.evaluate(pose, &previous.external, previous);
+        let samples = self.samples;
+        let sample = |axis: &ExternalAxis, k: usize| {
+            axis.min + (axis.max - axis.min) * k as f64 / (samples - 1) as f64
+        };
+        let mut consider = |external: &[f64]| {
+            let candidate = self.evaluate(pose, external, previous);
+            if candidate.as_ref().is_some_and(|c| best.as_ref().is_none_or(|b| c.1 < b.1)) {
+                best = candidate;
+            }
+        };
+        let grid = u32::try_from(axes.len()).ok().and_then(|n| samples.checked_pow(n))
+            .filter(|grid| *grid <= MAX_GRID_SAMPLES);
+        match grid {
+            Some(grid) => for index in 0..grid {
+                let external: Vec<f64> = axes.iter().enumerate().map(|(i, axis)| {
+                    sample(axis, index / samples.pow(i as u32) % samples)
+                }).collect();
+                consider(&external);
+            },
+            None => {
+                let start: Vec<f64> = axes.iter().enumerate().map(|(i, axis)| {
+                    previous.external.get(i).map_or(axis.min, |v| v.max(axis.min).min(axis.max))
+                }).collect();
+                for (i, axis) in axes.iter().enumerate() {
+                    for k in 0..samples {
+                        let mut external = start.clone();
+                        external[i] = sample(axis, k);
+                        consider(&external);
+                    }
+                }
+            }
+        }
+
+        // Compass search from the best sample
+        let (mut state, mut cost) = best?;
+        let mut steps: Vec<f64> = axes.iter()
+            .map(|axis| (axis.max - axis.min) / (2 * (samples - 1)) as f64).collect();
+        for _ in 0..MAX_ITERATIONS {
+            if steps.iter().zip(&axes).all(|(step, axis)| *step <= 1E-9 * (axis.max - axis.min)) {
+                break;
+            }
+            let mut improved = false;
+            for i in 0..axes.len() {
+                for sign in [1.0, -1.0] {
+                    let mut external = state.external.clone();
+                    external[i] += sign * steps[i];
+                    if let Some((next, next_cost)) = self.evaluate(pose, &external, previous) {
+                        if next_cost < cost {
+                            (state, cost, improved) = (next, next_cost, true);
+                        }
+                    }
+                }
+            }
+            if !improved {
+                steps.iter_mut().for_each(|step| *step *= 0.5);
+            }
+        }
+        Some(state)
+    }
+
+    /// State with the arm solved for the external axis values, and its cost.
+    fn evaluate(&self, pose: &Pose, external: &[f64], previous: &CellJoints)
+                -> Option<(CellJoints, f64)> {
+        let axes: Vec<&ExternalAxis> = self.axes().collect();
+        if external.len() != axes.len() || axes.iter().zip(external)
+            .any(|(axis, value)| *value < axis.min || *value > axis.max) {
+            return None;
+        }
+        let local = self.base(external).inverse() * self.part(external) * pose;
+        let joints = *self.robot.inverse_continuing(&local, &previous.joints).first()?;
+        let external_cost: f64 = axes.iter().zip(external).zip(&previous.external)
+            .map(|((axis, value), previous)| axis.weight * (value - previous).powi(2)).sum();
+        let joint_cost: f64 = (0..6)
+            .map(|i| self.joint_weights[i] * angle_difference(joints[i], previous.joints[i]).powi(2))
+            .sum();
+        Some((CellJoints { external: external.to_vec(), joints }, external_cost + joint_cost))
+    }
+}
+
+fn chain(axes: &[ExternalAxis], values: &[f64]) -> Pose {
+    axes.iter().zip(values).fold(Pose::identity(), |pose, (axis, value)| {
+        pose * axis.transform(*value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+    use crate::utils::compare_poses;
+
+    #[test]
+    fn test_workcell() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let rail = ExternalAxis::linear(Pose::identity(), Vector3::x(), -1.0, 4.0);
+        let cell = Workcell::new(robot.clone(), Pose::translation(0.0, 0.0, 0.3))
+            .with_carrier(rail);
+        let start = CellJoints { external: vec![0.0], joints: [0.0, 0.2, 0.1, 0.0, 0.6, 0.0] };
+
+        // Too far for the arm alone, the rail moves
+        let target = Pose::from_parts(Translation3::new(3.0, 0.2, 0.5),
+                                      UnitQuaternion::from_euler_angles(PI, 0.0, 0.0));
+        assert!(robot.inverse(&(cell.base(&start.external).inverse() * target)).is_empty());
+        let state = cell.inverse(&target, &start).unwrap();
+        assert!(state.external[0] > 1.0);
+        assert!(compare_poses(&cell.forward(&state), &target, 1E-9, 1E-9).is_ok());
+        // Nothing to do when already there
+        assert_eq!(cell.inverse(&target, &state).unwrap(), state);
+        assert!(cell.inverse(&Pose::translation(10.0, 0.0, 0.5), &start).is_none());
+
+        // The turntable in front of the robot holds the part. The heavy rail weight makes the
+        // turntable and the arm do more of the motion
+        let table = ExternalAxis::rotary(Pose::translation(1.3, 0.0, 0.0), Vector3::z(), -PI, PI);
+        let light = Workcell::new(robot.clone(), Pose::identity()).with_carrier(rail)
+            .with_positioner(table);
+        let heavy = Workcell::new(robot, Pose::identity()).with_carrier(rail.with_weight(100.0))
+            .with_positioner(table);
+        let start = CellJoints { external: vec![0.0, 0.0], joints: start.joints };
+        let on_part = Pose::from_parts(Translation3::new(0.3, 0.2, 0.6),
+                                       UnitQuaternion::from_euler_angles(PI, 0.0, 0.0));
+        let (a, b) = (light.inverse(&on_part, &start).unwrap(),
+                      heavy.inverse(&on_part, &start).unwrap());
+        for (cell, state) in [(&light, &a), (&heavy, &b)] {
+            assert!(compare_poses(&cell.forward(state), &on_part, 1E-9, 1E-9).is_ok());
+        }
+        assert!(b.external[0].abs() < a.external[0].abs());
+    }
+
+    #[test]
+    fn test_large_grid() {
+        // 9^21 samples overflow, the axes are scanned one by one
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let rail = ExternalAxis::linear(Pose::identity(), Vector3::x(), -1.0, 4.0);
+        let lift = ExternalAxis::linear(Pose::identity(), Vector3::z(), 0.0, 0.01);
+        let mut cell = Workcell::new(robot, Pose::identity()).with_carrier(rail);
+        for _ in 0..20 {
+            cell = cell.with_carrier(lift);
+        }
+        let start = CellJoints { external: vec![0.0; 21], joints: [0.0, 0.2, 0.1, 0.0, 0.6, 0.0] };
+        let target = Pose::from_parts(Translation3::new(3.0, 0.2, 0.5),
+                                      UnitQuaternion::from_euler_angles(PI, 0.0, 0.0));
+        let state = cell.inverse(&target, &start).unwrap();
+        assert!(state.external[0] > 1.0);
+        assert!(compare_poses(&cell.forward(&state), &target, 1E-9, 1E-9).is_ok());
+    }
+}
@@ -0,0 +1,439 @@
+//! Sampling of the reachable workspace and its 2D cross-sections.
+//!
+//! The cross-sections are built by sampling the joint space and keeping the forward kinematics
+//! points that fall into a thin slab around the section plane. With the `plot` feature enabled,
+//! the sections can be rendered into SVG files with `plotters`.
+//!
+//! The reachability partition shows which arm configurations can reach the given TCP position
+//! over the range of orientations, helping to select one configuration for the whole job.
+//!
+//! The solution count statistics summarize how comfortable the robot is in the task volume:
+//! how often the poses have all 8 solutions, fewer, none at all or are singular.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use nalgebra::{Translation3, UnitQuaternion, Vector3};
+use crate::kinematic_traits::{ArmConfiguration, Joints, Kinematics, Pose, MAX_SOLUTIONS};
+use crate::kinematics_impl::OPWKinematics;
+
+/// The plane of the workspace cross-section, in the robot base frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SectionPlane {
+    /// Vertical section through the J1 axis (y = 0). Points are (x, z).
+    XZ,
+    /// Horizontal section at the given height. Points are (x, y).
+    XY { z: f64 },
+}
+
+/// Controls how the joint space is sampled when building a cross-section.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingSettings {
+    /// Joint ranges (from, to) in radians, J1 to J6.
+    pub ranges: [(f64, f64); 6],
+    /// Number of samples per joint. J6 only spins the flange about its own axis and needs
+    /// one sample (in the middle of its range). J4 and J5 swing the flange around the wrist
+    /// center by the c4 distance, J4 only while J5 is not zero, and are sampled more sparsely
+    /// than the arm joints.
+    pub steps: [usize; 6],
+    /// Half thickness of the slab around the section plane, meters.
+    pub slab: f64,
+}
+
+impl Default for SamplingSettings {
+    fn default() -> Self {
+        SamplingSettings {
+            ranges: [(-PI, PI); 6],
+            steps: [60, 60, 60, 8, 12, 1],
+            slab: 0.01,
+        }
+    }
+}
+
+/// Sample the workspace and return the TCP points that lie in the given section plane.
+/// Points are 2D coordinates in the plane, see [`SectionPlane`].
+pub fn sample_cross_section(kinematics: &dyn Kinematics, plane: SectionPlane,
+                            settings: &SamplingSettings) -> Vec<(f64, f64)> {
+    let mut points = Vec::new();
+    let mut ranges = settings.ranges;
+    let mut steps = settings.steps;
+    if plane == SectionPlane::XZ {
+        // The section through the J1 axis is fully covered by the arm plane at J1 = 0
+        // (and J1 = 180 for the points behind the base), no need to sample J1 densely.
+        ranges[0] = (0.0, PI);
+        steps[0] = 2;
+    }
+
+    for_each_sample(&ranges, &steps, |joints| {
+        let pose = kinematics.forward(joints);
+        let t = pose.translation.vector;
+        match plane {
+            SectionPlane::XZ => {
+                if t.y.abs() <= settings.slab {
+                    points.push((t.x, t.z));
+                }
+            }
+            SectionPlane::XY { z } => {
+                if (t.z - z).abs() <= settings.slab {
+                    points.push((t.x, t.y));
+                }
+            }
+        }
+    });
+    points
+}
+
+/// Iterate over the regular grid in joint space defined by ranges and steps.
+/// A joint with a single step is sampled in the middle of its range.
+pub(crate) fn for_each_sample<F: FnMut(&Joints)>(ranges: &[(f64, f64); 6], steps: &[usize; 6],
+                                                 mut f: F) {
+    let values: Vec<Vec<f64>> = ranges.iter().zip(steps.iter())
+        .map(|(&(from, to), &n)| {
+            if n <= 1 {
+                vec![(from + to) / 2.0]
+            } else {
+                (0..n).map(|i| from + (to - from) * i as f64 / (n - 1) as f64).collect()
+            }
+        })
+        .collect();
+
+    let mut joints: Joints = [0.0; 6];
+    for &j1 in &values[0] {
+        joints[0] = j1;
+        for &j2 in &values[1] {
+            joints[1] = j2;
+            for &j3 in &values[2] {
+                joints[2] = j3;
+                for &j4 in &values[3] {
+                    joints[3] = j4;
+                    for &j5 in &values[4] {
+                        joints[4] = j5;
+                        for &j6 in &values[5] {
+                            joints[5] = j6;
+                            f(&joints);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sample tool orientations: approach directions (tool z axis) evenly spread over the sphere
+/// (Fibonacci lattice), each combined with the given number of rotations around the tool axis.
+pub fn sample_orientations(directions: usize, spins: usize) -> Vec<UnitQuaternion<f64>> {
+    let golden_angle = PI * (3.0 - 5.0_f64.sqrt());
+    let mut orientations = Vec::with_capacity(directions * spins);
+    for i in 0..directions {
+        let z = if directions > 1 { 1.0 - 2.0 * i as f64 / (directions - 1) as f64 } else { 1.0 };
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = golden_angle * i as f64;
+        let direction = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+        let tilt = UnitQuaternion::rotation_between(&Vector3::z(), &direction)
+            .unwrap_or_else(|| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI));
+        for j in 0..spins {
+            let spin = 2.0 * PI * j as f64 / spins as f64;
+            orientations.push(tilt * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), spin));
+        }
+    }
+    orientations
+}
+
+/// Which arm configurations reach the fixed TCP position, per sampled orientation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReachabilityPartition {
+    pub orientations: Vec<UnitQuaternion<f64>>,
+    /// For each arm configuration, one flag per orientation (same indexing as `orientations`).
+    pub reachable: HashMap<ArmConfiguration, Vec<bool>>,
+}
+
+impl ReachabilityPartition {
+    /// Compute the partition for the given TCP position (robot base frame) and orientations.
+    pub fn new(robot: &OPWKinematics, position: &Vector3<f64>,
+               orientations: Vec<UnitQuaternion<f64>>) -> Self {
+        let mut reachable: HashMap<ArmConfiguration, Vec<bool>> = ArmConfiguration::ALL.iter()
+            .map(|c| (*c, vec![false; orientations.len()]))
+            .collect();
+        let translation = Translation3::from(*position);
+        for (i, rotation) in orientations.iter().enumerate() {
+            let pose = Pose::from_parts(translation, *rotation);
+            for solution in &robot.inverse_fixed(&pose) {
+                let configuration = robot.configuration(solution).arm;
+                if let Some(flags) = reachable.get_mut(&configuration) {
+                    flags[i] = true;
+                }
+            }
+        }
+        ReachabilityPartition { orientations, reachable }
+    }
+
+    /// Fraction of the sampled orientations reachable in the given arm configuration.
+    pub fn coverage(&self, configuration: &ArmConfiguration) -> f64 {
+        match self.reachable.get(configuration) {
+            Some(flags) if !flags.is_empty() =>
+                flags.iter().filter(|&&r| r).count() as f64 / flags.len() as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Arm configuration covering the largest share of orientations, if any reaches at all.
+    pub fn best_configuration(&self) -> Option<ArmConfiguration> {
+        // On ties, the configuration listed first in ArmConfiguration::ALL wins
+        let mut best: Option<(ArmConfiguration, f64)> = None;
+        for configuration in ArmConfiguration::ALL {
+            let coverage = self.coverage(&configuration);
+            if coverage > 0.0 && best.is_none_or(|(_, c)| coverage > c) {
+                best = Some((configuration, coverage));
+            }
+        }
+        best.map(|(configuration, _)| configuration)
+    }
+}
+
+/// Task volume: the box in the robot base frame, sampled at the regular grid of TCP positions.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskVolume {
+    /// Corner with the smallest coordinates, meters.
+    pub min: Vector3<f64>,
+    /// Corner with the largest coordinates, meters.
+    pub max: Vector3<f64>,
+    /// Number of positions along X, Y and Z. A single step samples the middle of the range.
+    pub steps: [usize; 3],
+}
+
+impl TaskVolume {
+    /// Sampled TCP positions.
+    pub fn positions(&self) -> Vec<Vector3<f64>> {
+        let values: Vec<Vec<f64>> = (0..3).map(|axis| {
+            let (from, to, n) = (self.min[axis], self.max[axis], self.steps[axis]);
+            if n <= 1 {
+                vec![(from + to) / 2.0]
+            } else {
+                (0..n).map(|i| from + (to - from) * i as f64 / (n - 1) as f64).collect()
+            }
+        }).collect();
+        let mut positions = Vec::new();
+        for &x in &values[0] {
+            for &y in &values[1] {
+                for &z in &values[2] {
+                    positions.push(Vector3::new(x, y, z));
+                }
+            }
+        }
+        positions
+    }
+}
+
+/// Distribution of the number of inverse kinematics solutions over the task volume.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolutionCountStatistics {
+    /// Poses sampled (positions times orientations).
+    pub samples: usize,
+    /// Number of poses by the solution count (index), 0 to MAX_SOLUTIONS.
+    pub counts: Vec<usize>,
+    /// Poses with some solution in the singularity.
+    pub singular: usize,
+}
+
+impl SolutionCountStatistics {
+    /// Samples every position of the volume with every orientation.
+    pub fn new(robot: &OPWKinematics, volume: &TaskVolume,
+               orientations: &[UnitQuaternion<f64>]) -> Self {
+        let mut statistics = SolutionCountStatistics {
+            samples: 0,
+            counts: vec![0; MAX_SOLUTIONS + 1],
+            singular: 0,
+        };
+        for position in volume.positions() {
+            for rotation in orientations {
+                let solutions = robot.inverse_fixed(&Pose::from_parts(position.into(), *rotation));
+                statistics.samples += 1;
+                statistics.counts[solutions.len()] += 1;
+                if solutions.iter().any(|s| robot.kinematic_singularity(s).is_some()) {
+                    statistics.singular += 1;
+                }
+            }
+        }
+        statistics
+    }
+
+    /// Fraction of the poses with exactly the given number of solutions.
+    pub fn fraction(&self, solutions: usize) -> f64 {
+        self.share(self.counts.get(solutions).copied().unwrap_or(0))
+    }
+
+    /// Fraction of the poses with at least one solution.
+    pub fn reachable_fraction(&self) -> f64 {
+        1.0 - self.fraction(0)
+    }
+
+    /// Fraction of the poses with some solution in the singularity.
+    pub fn singular_fraction(&self) -> f64 {
+        self.share(self.singular)
+    }
+
+    /// Average number of solutions per pose.
+    pub fn mean_solutions(&self) -> f64 {
+        self.share(self.counts.iter().enumerate().map(|(n, c)| n * c).sum())
+    }
+
+    fn share(&self, count: usize) -> f64 {
+        if self.samples == 0 { 0.0 } else { count as f64 / self.samples as f64 }
+    }
+}
+
+impl fmt::Display for SolutionCountStatistics {
+    /// Percentage of the poses for each solution count that occurs, and of the singular ones.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} poses", self.samples)?;
+        for (solutions, count) in self.counts.iter().enumerate().filter(|(_, c)| **c > 0) {
+            write!(f, ", {} solutions: {:.1}%", solutions, 100.0 * self.share(*count))?;
+        }
+        write!(f, ", singular: {:.1}%", 100.0 * self.singular_fraction())
+    }
+}
+
+/// Render the cross-section points into SVG file. The axes are scaled equally so that
+/// the drawing is not distorted.
+#[cfg(feature = "plot")]
+pub fn plot_cross_section<P: AsRef<std::path::Path>>(points: &[(f64, f64)], plane: SectionPlane,
+                                                     path: P)
+                                                     -> Result<(), Box<dyn std::error::Error>> {
+    use plotters::prelude::*;
+
+    let extent = points.iter()
+        .fold(0.1_f64, |m, &(a, b)| m.max(a.abs()).max(b.abs())) * 1.05;
+
+    let (caption, x_label, y_label) = match plane {
+        SectionPlane::XZ => ("Workspace, XZ section".to_string(), "x, m", "z, m"),
+        SectionPlane::XY { z } => (format!("Workspace, XY section at z = {:.3} m", z), "x, m", "y, m"),
+    };
+
+    let root = SVGBackend::new(path.as_ref(), (800, 800)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(caption, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(-extent..extent, -extent..extent)?;
+    chart.configure_mesh().x_desc(x_label).y_desc(y_label).draw()?;
+    chart.draw_series(points.iter().map(|&p| Circle::new(p, 1, BLUE.filled())))?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+    use crate::kinematics_impl::OPWKinematics;
+    use crate::parameters::opw_kinematics::Parameters;
+
+    #[test]
+    fn test_xz_section_within_reach() {
+        let parameters = Parameters::irb2400_10();
+        let robot = OPWKinematics::new(parameters.clone());
+        let settings = SamplingSettings {
+            steps: [1, 30, 30, 1, 5, 1],
+            ..SamplingSettings::default()
+        };
+        let points = sample_cross_section(&robot, SectionPlane::XZ, &settings);
+        assert!(!points.is_empty());
+
+        let reach = parameters.a1 + parameters.c2 +
+            (parameters.a2 * parameters.a2 + parameters.c3 * parameters.c3).sqrt() + parameters.c4;
+        for (x, z) in points {
+            let r = (x * x + (z - parameters.c1).powi(2)).sqrt();
+            assert!(r <= reach + 1E-9, "Point ({}, {}) is beyond the reach", x, z);
+        }
+    }
+
+    #[test]
+    fn test_reachability_partition() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let orientations = sample_orientations(40, 4);
+        assert_eq!(orientations.len(), 160);
+        let position = Vector3::new(1.35, 0.0, 0.8);
+        let partition = ReachabilityPartition::new(&robot, &position, orientations);
+
+        let front_up = ArmConfiguration { front: true, elbow_up: true };
+        let back_up = ArmConfiguration { front: false, elbow_up: true };
+        assert!(partition.coverage(&front_up) > 0.5);
+        // Close to the full reach, it is not possible to reach back over the head
+        assert!(partition.coverage(&back_up) < partition.coverage(&front_up));
+        assert_eq!(partition.best_configuration(), Some(front_up));
+    }
+
+    #[test]
+    fn test_solution_count_statistics() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let volume = TaskVolume {
+            min: Vector3::new(0.6, -0.3, 0.4),
+            max: Vector3::new(3.0, 0.3, 1.2),
+            steps: [5, 3, 3],
+        };
+        assert_eq!(volume.positions().len(), 45);
+        let statistics = SolutionCountStatistics::new(&robot, &volume, &sample_orientations(8, 2));
+        assert_eq!(statistics.samples, 45 * 16);
+        assert_eq!(statistics.counts.iter().sum::<usize>(), statistics.samples);
+        // The far end of the box (x = 3 m) is out of reach, the near end is reachable
+        assert!(statistics.fraction(0) > 0.1 && statistics.reachable_fraction() > 0.3);
+        assert!(statistics.mean_solutions() > 1.0);
+        assert!(statistics.to_string().starts_with("720 poses, 0 solutions: "));
+    }
+
+    #[test]
+    fn test_configuration_classification() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        // Typical working posture of ABB robot: front, elbow up, wrist not flipped
+        let configuration = robot.configuration(&[0.0, 0.3, 0.2, 0.0, 0.8, 0.0]);
+        assert!(configuration.arm.front);
+        assert!(configuration.arm.elbow_up);
+        assert!(!configuration.wrist_flipped);
+
+        // All 8 solutions of the generic pose have different configurations
+        let pose = robot.forward(&[0.2, 0.1, 0.1, 0.3, 0.5, 0.2]);
+        let solutions = robot.inverse(&pose);
+        let configurations: HashSet<_> = solutions.iter().map(|s| robot.configuration(s)).collect();
+        assert_eq!(solutions.len(), 8);
+        assert_eq!(configurations.len(), solutions.len());
+    }
+
+    #[test]
+    fn test_default_samples_j4() {
+        // With J5 bent, J4 swings the flange around the wrist center: the sampled flange
+        // positions of one arm posture differ with J4 and not with J6
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let steps = SamplingSettings::default().steps;
+        assert!(steps[3] > 1 && steps[5] == 1);
+        let ranges = [(0.1, 0.1), (0.2, 0.2), (0.3, 0.3), (-PI, PI), (0.8, 0.8), (-PI, PI)];
+        let mut positions: Vec<Vector3<f64>> = Vec::new();
+        for_each_sample(&ranges, &[1, 1, 1, steps[3], 1, 1], |joints| {
+            positions.push(robot.forward(joints).translation.vector);
+        });
+        assert!((positions[0] - positions[1]).norm() > 0.01);
+        let mut spun = Vec::new();
+        for_each_sample(&ranges, &[1, 1, 1, 1, 1, 8], |joints| {
+            spun.push(robot.forward(joints).translation.vector);
+        });
+        assert!(spun.iter().all(|p| (p - spun[0]).norm() < 1E-12));
+    }
+
+    #[test]
+    fn test_xy_section_at_height() {
+        let robot = OPWKinematics::new(Parameters::irb2400_10());
+        let settings = SamplingSettings {
+            steps: [24, 30, 30, 1, 5, 1],
+            slab: 0.02,
+            ..SamplingSettings::default()
+        };
+        let points = sample_cross_section(&robot, SectionPlane::XY { z: 0.8 }, &settings);
+        assert!(!points.is_empty());
+    }
+}